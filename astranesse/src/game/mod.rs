@@ -1,35 +1,47 @@
 pub mod object;
+pub mod placement;
 pub mod planet;
 pub mod entity;
 pub mod galaxy;
+pub mod save;
 pub mod shading;
+pub mod simulation;
 
-use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::game::entity::Entity;
 use crate::game::galaxy::Galaxy;
-use crate::game::object::Interrupt;
-use crate::game::object::ObjectLoader;
+use crate::game::object::{Interrupt, Object, cell};
+use crate::game::placement::{Ghost, HOTBAR_SLOTS, Hotbar};
 use crate::game::object::computer::BlockProperties;
-use crate::game::object::loader::ShipLoader;
+use crate::game::save::{Autosave, SaveSlots, WorldSave};
 use crate::game::shading::PostInfo;
+use crate::game::simulation::Simulation;
 use crate::graphics::*;
 use crate::physics::*;
 use crate::util::RcCell;
-use crate::util::my_fmod;
-use cgmath::InnerSpace;
 use cgmath::Vector3;
-use object::Object;
-use planet::{Planet, PlanetInit};
 use cgmath::Rotation;
 use rustc_hash::FxHashSet;
 use winit::{
     dpi::{PhysicalPosition, PhysicalSize},
-    event::{KeyEvent, WindowEvent}, keyboard::{KeyCode, PhysicalKey},
+    event::{KeyEvent, MouseScrollDelta, WindowEvent}, keyboard::{KeyCode, PhysicalKey},
 };
 
 const LOOK_DIST: f64 = 5.;
+/// Keys picking the slots of the hotbar
+const DIGIT_KEYS: [KeyCode; HOTBAR_SLOTS] = [
+    KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4, KeyCode::Digit5,
+    KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+];
+/// Directory of the save slots
+pub const SAVE_DIR: &str = "saves";
+/// Slot the game is loaded from and saved to
+pub const SAVE_SLOT: &str = "world";
+/// Seconds between autosaves
+const AUTOSAVE_INTERVAL: f64 = 60.;
+/// Environment variable naming a directory of scripts to interpret instead of compiling the built-in ones, so that
+/// they can be edited while the game runs and reloaded with F5
+const SCRIPT_DIR_VAR: &str = "ASTRANESSE_SCRIPTS";
 
 struct KeyState {
     down_set: FxHashSet<KeyCode>
@@ -67,23 +79,21 @@ pub struct Game {
     lighting: Lighting,
     font: Font,
     post_info: PostInfo,
-    
-    objects: Vec<RcCell<Object>>,
-    entities: Vec<Entity>,
-    planets: Vec<Planet>,
+    hotbar: Hotbar,
+    ghost: Ghost,
     
     key_state: KeyState,
     fps_counter: FpsCounter,
     mouse_motion: (f32, f32),
     
     galaxy: Galaxy,
-    block_properties: BlockProperties, // Needs to be last
-    physics: Box<Physics>, // Needs to be last
+    save_slots: SaveSlots,
+    autosave: Autosave,
+    sim: Simulation, // Needs to be last
 }
 
 impl Game {
     pub fn new(mut graphics: Graphics) -> Self {
-        let mut physics = Box::new(Physics::new());
         let key_state = KeyState::new();
         let block_shader = Shader::new::<BlockVertex>(&mut graphics, include_str!("../shaders/block.wgsl"), vec![
             ResourceType::Camera,
@@ -110,20 +120,11 @@ impl Game {
         let lighting = Lighting::new(&graphics);
         let post_info = PostInfo::new(&graphics, &camera);
 
-        let mut planets: Vec<Planet> = vec![
-            // Planet::new(PlanetInit::default()),
-        ];
-        let mut objects = vec![
-            Rc::new(RefCell::new(Object::new(&graphics, &mut physics, ObjectLoader::OneShot(ShipLoader{ pos: Vector3::new(12., 7., 42.), vel: Vector3::new(0., 0., 0.) })))),
-        ];
-        for planet in &mut planets {
-            let obj = Rc::new(RefCell::new(Object::new(&graphics, &mut physics, planet.loader())));
-            planet.object = Some(obj.clone());
-            objects.push(obj);
-        }
-        let entities = vec![
-            Entity::new(&mut physics, RigidBodyInit {pos: Vector3::new(0., 0., 50.), vel: Vector3::new(0., 0., 0.), ..Default::default()}),
-        ];
+        let save_slots = SaveSlots::new(SAVE_DIR);
+        let world = save_slots.read(SAVE_SLOT).unwrap_or_else(|e| panic!("{}", e)).unwrap_or_else(WorldSave::new_game);
+        let sim = Simulation::new(world, load_block_properties());
+        let hotbar = Hotbar::new(&sim.block_properties);
+        let ghost = Ghost::new(&graphics);
 
         
         // Set cursor to center of screen
@@ -142,32 +143,33 @@ impl Game {
         let mut galaxy = Galaxy::new(&graphics);
         galaxy.update_skybox(&graphics, &camera, Vector3::new(-1e7, 0., 0.));
 
-        // Load block properties
-        let mut block_properties = BlockProperties::new();
-        block_properties.preload_script(include_str!("../../assets/scripts/chair.txt"), "chair");
-        block_properties.preload_script(include_str!("../../assets/scripts/engine.txt"), "engine");
-        block_properties.load_manifest(include_str!("../../assets/blocks.txt"));
-
         Self {
             graphics,
-            block_properties,
             key_state,
             camera,
-            objects,
             mouse_motion: (0., 0.),
             lighting,
             texture,
             block_shader,
             flat_shader,
-            physics,
             font,
             fps_counter: FpsCounter::new(),
-            entities,
             galaxy,
+            save_slots,
+            autosave: Autosave::new(AUTOSAVE_INTERVAL),
             shadow_shader,
             post_shader,
             post_info,
-            planets,
+            hotbar,
+            ghost,
+            sim,
+        }
+    }
+
+    /// Write the world to its save slot
+    pub fn save(&self) {
+        if let Err(e) = self.save_slots.write(SAVE_SLOT, &self.sim.world_save()) {
+            println!("{}", e);
         }
     }
 
@@ -177,48 +179,17 @@ impl Game {
     
     fn mouse_clicked(&mut self, button: winit::event::MouseButton) {
         match button {
-            winit::event::MouseButton::Left => {
-                // Replace the player's collider with its look ray temporarily
-                let player = &mut self.entities[0];
-                let body_collider = player.body.collider.take();
-                let forward = self.camera.get_forward().cast().unwrap();
-                player.body.collider = Some(Collider::new_ray(self.camera.pos.cast().unwrap(), forward*LOOK_DIST));
-
-                let mut report = CollisionReport::None;
-                let mut collided_object = None;
-
-                for object in &mut self.objects {
-                    // The collision function should always pick some over None, but choose the one with the smallest distance to the target otherwise.
-                    let new_report = Collider::check_collision(&player.body, &object. borrow().body);
-
-                    if new_report > report {
-                        report = new_report;
-                        collided_object = Some(object);
-                    }
-                }
-
-                if let Some(o) = collided_object {
-                    let place_pos = match report {
-                        CollisionReport::Some { p2, .. } => {
-                            let offset = o.borrow().body.ori.invert() * forward;
-                            p2 - offset*0.001
-                        }
-                        CollisionReport::None => unreachable!(),
-                    };
-                    o.borrow_mut().insert_block(&self.graphics, &self.block_properties, 1, place_pos);
-                }
-
-                // Put the collider back
-                player.body.collider = body_collider;
-            },
+            winit::event::MouseButton::Left => self.place_block(),
+            winit::event::MouseButton::Middle => self.remove_block(),
             winit::event::MouseButton::Right => self.interact(),
             _ => (),
         }
     }
 
-    fn interact(&mut self) {
+    /// The object the player is looking at, the point looked at and the look direction, both in its body coordinates
+    fn look_at(&mut self) -> Option<(RcCell<Object>, Vector3<f64>, Vector3<f64>)> {
         // Replace the player's collider with its look ray temporarily
-        let player = &mut self.entities[0];
+        let player = &mut self.sim.entities[0];
         let body_collider = player.body.collider.take();
         let forward = self.camera.get_forward().cast().unwrap();
         player.body.collider = Some(Collider::new_ray(self.camera.pos.cast().unwrap(), forward*LOOK_DIST));
@@ -226,9 +197,9 @@ impl Game {
         let mut report = CollisionReport::None;
         let mut collided_object = None;
 
-        for object in &mut self.objects {
+        for object in &self.sim.objects {
             // The collision function should always pick some over None, but choose the one with the smallest distance to the target otherwise.
-            let new_report = Collider::check_collision(&player.body, &object. borrow().body);
+            let new_report = Collider::check_collision(&player.body, &object.borrow().body);
 
             if new_report > report {
                 report = new_report;
@@ -236,70 +207,70 @@ impl Game {
             }
         }
 
-        if let Some(o) = collided_object {
-            // Interacted with this block
-            let place_pos = match report {
-                CollisionReport::Some { p2, .. } => {
-                    let offset = o.borrow().body.ori.invert() * forward;
-                    p2 + offset*0.001
-                }
-                CollisionReport::None => unreachable!(),
-            };
-            let interacted_block = o.borrow().get_block(place_pos);
-            if !interacted_block.is_null() {
-                let updated_chunk = (
-                    (place_pos.x/CHUNK_SIZE as f64).floor() as i32,
-                    (place_pos.y/CHUNK_SIZE as f64).floor() as i32,
-                    (place_pos.z/CHUNK_SIZE as f64).floor() as i32,
-                );
-                let updated_block = (
-                    my_fmod(place_pos.x, CHUNK_SIZE as f64) as u32,
-                    my_fmod(place_pos.y, CHUNK_SIZE as f64) as u32,
-                    my_fmod(place_pos.z, CHUNK_SIZE as f64) as u32,
-                );
-                let block_key = (updated_chunk, updated_block);
-                o.borrow_mut().interrupt(block_key, Interrupt::Interact);
-                if self.block_properties.chair_blocks.contains(&interacted_block.id) {
-                    player.set_chair(o, block_key);
-                }
-            }
-        }
-
         // Put the collider back
         player.body.collider = body_collider;
+
+        let o = collided_object?;
+        match report {
+            CollisionReport::Some { p2, .. } => {
+                let dir = o.borrow().body.ori.invert() * forward;
+                Some((o.clone(), p2, dir))
+            },
+            CollisionReport::None => unreachable!(),
+        }
     }
 
-    pub fn update(&mut self, delta_t: f64) {
-        self.fps_counter.update(delta_t);
-        for object in &mut self.objects {
-            object.borrow_mut().update_graphics(&self.graphics, &self.block_properties, self.camera.pos.cast().unwrap());
+    /// Place the block selected in the hotbar in front of the block looked at
+    fn place_block(&mut self) {
+        let (Some((o, p2, dir)), Some(block)) = (self.look_at(), self.hotbar.block()) else { return; };
+        o.borrow_mut().insert_block(&self.sim.block_properties, block, p2 - dir*0.001);
+    }
+
+    /// Remove the block looked at, getting up from it if it is the player's chair
+    fn remove_block(&mut self) {
+        let Some((o, p2, dir)) = self.look_at() else { return; };
+        let pos = p2 + dir*0.001;
+        let removed = o.borrow_mut().remove_block(&self.sim.block_properties, pos);
+        let player = self.sim.player_mut();
+        if !removed.is_null() && player.chair.as_ref().is_some_and(|(c, block_key, _)| Rc::ptr_eq(c, &o) && *block_key == cell(pos)) {
+            player.chair = None;
         }
+    }
 
-        let my_planet = if !self.planets.is_empty() {
-            let mut min_dist = f64::INFINITY;
-            let mut min_index = 0;
-            for (i, planet) in self.planets.iter().enumerate() {
-                if let Some(o) = &planet.object {
-                    let o = o.borrow().body.clone();
-                    let dist = (o.pos - self.camera.pos.cast().unwrap()).magnitude();
-                    if dist < min_dist {
-                        min_index = i;
-                        min_dist = dist;
-                    }
-                }
+    fn interact(&mut self) {
+        let Some((o, p2, dir)) = self.look_at() else { return; };
+        // Interacted with this block
+        let place_pos = p2 + dir*0.001;
+        let interacted_block = o.borrow().get_block(place_pos);
+        if !interacted_block.is_null() {
+            let block_key = cell(place_pos);
+            o.borrow_mut().interrupt(block_key, Interrupt::Interact);
+            if self.sim.block_properties.chair_blocks.contains(&interacted_block.id) {
+                self.sim.player_mut().set_chair(&o, block_key);
             }
-            Some(&self.planets[min_index])
-        } else {
-            None
-        };
-        self.post_info.update_buffer(&self.graphics, &self.camera, my_planet);
+        }
+    }
+
+    /// Show where the selected block would go
+    fn update_ghost(&mut self) {
+        match (self.look_at(), self.hotbar.block()) {
+            (Some((o, p2, dir)), Some(block)) => {
+                let pos = p2 - dir*0.001;
+                let cell = pos.map(f64::floor);
+                self.ghost.show(&self.graphics, &self.sim.block_properties, block, &o.borrow().body, cell, &self.camera);
+            },
+            _ => self.ghost.hide(),
+        }
+    }
 
-        for object in &mut self.objects {
-            object.borrow_mut().update_internals(delta_t);
+    pub fn update(&mut self, delta_t: f64) {
+        self.fps_counter.update(delta_t);
+        if self.autosave.update(delta_t) {
+            self.save();
         }
 
         {
-            let player = &mut self.entities[0];
+            let player = &mut self.sim.entities[0];
             match &player.chair {
                 None => {
                     // Move camera pos
@@ -348,15 +319,17 @@ impl Game {
                 }
             }
         }
-        
-        self.physics.update(delta_t);
-        for entity in &mut self.entities {
-            entity.update(delta_t);
+
+        self.sim.step(delta_t);
+        for object in &mut self.sim.objects {
+            object.borrow_mut().update_models(&self.graphics, &self.sim.block_properties);
         }
+        let my_planet = self.sim.nearest_planet(self.camera.pos.cast().unwrap());
+        self.post_info.update_buffer(&self.graphics, &self.camera, my_planet);
 
         {
             // Move camera look
-            let player = &self.entities[0];
+            let player = self.sim.player();
             const SPEED: f64 = 0.2;
             self.camera.pos = player.body.pos;
             self.camera.theta += (SPEED*delta_t) as f32 *self.mouse_motion.1;
@@ -371,19 +344,25 @@ impl Game {
         // OPTIMIZE avoid all calls of queue.write_buffer.
         self.camera.update_buffer(&self.graphics, &self.lighting, &self.camera);
         self.lighting.update_buffer(&self.graphics, &self.camera);
-        for object in &mut self.objects {
+        self.update_ghost();
+        for object in &mut self.sim.objects {
             object.borrow_mut().update_buffer(&self.graphics, &self.camera)
         }
         self.font.text(&format!("FPS {}", self.fps_counter.get()), 0., 0.12);
-        if !self.planets.is_empty() {
-            self.font.text(&self.planets[0].dbg_text(self.camera.pos.cast().unwrap()), 0.0, 0.2);
+        if !self.sim.planets.is_empty() {
+            self.font.text(&self.sim.planets[0].dbg_text(self.camera.pos.cast().unwrap()), 0.0, 0.2);
+        }
+        let logs: String = self.sim.objects.iter().map(|o| o.borrow().log_text()).collect();
+        if !logs.is_empty() {
+            self.font.text(&logs, 0.0, 0.4);
         }
+        self.font.text(&self.hotbar.text(&self.sim.block_properties), 0.0, 1.8);
         self.font.update(&self.graphics);
         
         self.graphics.draw(
             |mut renderer| {
                 // Update buffers
-                for object in &self.objects {
+                for object in &self.sim.objects {
                     object.borrow().copy_buffers(&mut renderer);
                 }
                 self.font.copy_buffers(&mut renderer);
@@ -392,7 +371,7 @@ impl Game {
                 renderer.start_shadow(&mut self.camera);
                 self.shadow_shader.bind(&mut renderer);
                 self.camera.bind(&mut renderer);
-                for object in &self.objects {
+                for object in &self.sim.objects {
                     object.borrow().draw_shadow(&mut renderer);
                 }
 
@@ -407,9 +386,10 @@ impl Game {
                 self.block_shader.bind(&mut renderer);
                 self.camera.bind(&mut renderer);
                 self.lighting.bind(&mut renderer);
-                for object in &self.objects {
+                for object in &self.sim.objects {
                     object.borrow().draw(&mut renderer, &self.texture)
                 }
+                self.ghost.draw(&mut renderer, &self.texture);
                 
                 // Draw text
                 self.font.render(&mut renderer, &self.camera, &self.lighting);
@@ -434,12 +414,30 @@ impl Game {
     pub fn window_event(&mut self, event: WindowEvent) -> bool {
         match event {
             WindowEvent::Resized(size) => self.resized(size),
-            WindowEvent::CloseRequested => return true,
+            WindowEvent::CloseRequested => {
+                self.save();
+                return true;
+            },
             WindowEvent::KeyboardInput { event, .. } => {
                 self.key_state.update(&event);
                 if let PhysicalKey::Code(code) = &event.physical_key {
                     match code {
-                        KeyCode::Escape => return true,
+                        KeyCode::Escape => {
+                            self.save();
+                            return true;
+                        },
+                        KeyCode::KeyR if event.state.is_pressed() => self.hotbar.turn(),
+                        KeyCode::KeyF if event.state.is_pressed() => self.hotbar.face_next(),
+                        KeyCode::F5 if event.state.is_pressed() => {
+                            if let Err(e) = self.sim.reload_scripts() {
+                                println!("{}", e);
+                            }
+                        },
+                        _ if event.state.is_pressed() => {
+                            if let Some(slot) = DIGIT_KEYS.iter().position(|k| k == code) {
+                                self.hotbar.select(slot);
+                            }
+                        },
                         _ => ()
                     };
                 }
@@ -455,6 +453,15 @@ impl Game {
 
                 self.mouse_moved(difference)
             },
+            WindowEvent::MouseWheel { delta, .. } => {
+                let y = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y as f64,
+                    MouseScrollDelta::PixelDelta(p) => p.y,
+                };
+                if y != 0. {
+                    self.hotbar.scroll(if y < 0. { 1 } else { -1 });
+                }
+            },
             WindowEvent::MouseInput { state, button, .. } => {
                 match state {
                     winit::event::ElementState::Pressed => self.mouse_clicked(button),
@@ -467,6 +474,18 @@ impl Game {
     }
 }
 
+/// Block properties and the scripts they use
+pub fn load_block_properties() -> BlockProperties {
+    let mut block_properties = BlockProperties::new();
+    block_properties.preload_script(include_str!("../../assets/scripts/chair.txt"), "chair");
+    block_properties.preload_script(include_str!("../../assets/scripts/engine.txt"), "engine");
+    if let Some(dir) = std::env::var_os(SCRIPT_DIR_VAR) {
+        block_properties.interpret_scripts_from(dir);
+    }
+    block_properties.load_registry(include_str!("../../assets/blocks.toml"), "blocks.toml").unwrap_or_else(|e| panic!("{}", e));
+    block_properties
+}
+
 struct FpsCounter {
     data: [f64; 64],
     cursor: usize,
@@ -1,7 +1,7 @@
 use cgmath::{Matrix3, Vector3, Zero};
 use rustc_hash::FxHashMap;
 
-use crate::{graphics::{CHUNK_SIZE, Camera, CubeGrid, Graphics, ModelUniform, Renderer, StorageBuffer}, physics::RigidBody};
+use crate::{game::object::computer::BlockProperties, graphics::{CHUNK_SIZE, Camera, CubeGrid, Graphics, GridModel, ModelUniform, Renderer, StorageBuffer}, physics::RigidBody};
 
 pub struct Chunk {
     pub(super) grid: CubeGrid,
@@ -14,11 +14,15 @@ pub struct Chunk {
     pub exposed: u8,
     /// 0_0_z-_z+_y-_y+_x-_x+. One means hidden.
     pub detail: usize,
+    /// Made when the chunk is first drawn, so that chunks can be simulated without a window
+    model: Option<GridModel>,
+    /// Whether the model no longer matches the blocks, exposure or detail
+    pub model_stale: bool,
 }
 
 impl Chunk {
-    pub fn empty(graphics: &Graphics, pos: Vector3<f64>) -> Self {
-        let mut grid = CubeGrid::new(graphics);
+    pub fn empty(pos: Vector3<f64>) -> Self {
+        let mut grid = CubeGrid::new();
         grid.global_pos = pos;
         Self {
             grid,
@@ -28,27 +32,32 @@ impl Chunk {
             flush: 0,
             detail: 1,
             exposed: 0xff, // Ensures that the chunk model will be updated next frame
+            model: None,
+            model_stale: true,
         }
     }
 
-    // Update the rigid body collider to have the current block layout.
-    pub fn update_metadata(&mut self, blocks: &mut [u16; (CHUNK_SIZE*CHUNK_SIZE) as usize]) {
+    // Update the rigid body collider and mass moments to have the current block layout. Moments are in body coordinates.
+    pub fn update_metadata(&mut self, properties: &BlockProperties, blocks: &mut [u16; (CHUNK_SIZE*CHUNK_SIZE) as usize]) {
         // Update the collider
         self.mass_m0 = 0.;
         self.mass_m1 = Vector3::zero();
         self.mass_m2 = Matrix3::zero();
 
         for z in 0..CHUNK_SIZE {
-            let zf = z as f64 + 0.5;
+            let zf = self.grid.global_pos.z + z as f64 + 0.5;
             for y in 0..CHUNK_SIZE {
-                let yf = y as f64 + 0.5;
+                let yf = self.grid.global_pos.y + y as f64 + 0.5;
                 let mut block: u16 = 0;
                 for x in 0..CHUNK_SIZE {
-                    let xf = x as f64 + 0.5;
-                    if !self.grid[(x,y,z)].is_null() {
-                        block |= 1 << x;
+                    let xf = self.grid.global_pos.x + x as f64 + 0.5;
+                    let id = self.grid[(x,y,z)].id;
+                    if id != 0 {
+                        if properties.is_solid(id) {
+                            block |= 1 << x;
+                        }
 
-                        let block_mass = 1.;
+                        let block_mass = properties.mass(id);
                         self.mass_m0 += block_mass;
                         self.mass_m1.x += xf * block_mass;
                         self.mass_m1.y += yf * block_mass;
@@ -169,9 +178,16 @@ impl Chunk {
         exposed.insert(my_coord, new_exposed);
     }
     
-    /// Update the model buffer of the grid
-    pub(crate) fn update_model(&mut self, graphics: &Graphics) {
-        self.grid.update_model(graphics, self.detail, self.exposed);
+    /// Update the model buffer of the grid, making it if the chunk has not been drawn before
+    pub(crate) fn update_model(&mut self, graphics: &Graphics, properties: &BlockProperties) {
+        let model = self.model.get_or_insert_with(|| GridModel::new(graphics));
+        model.update(graphics, &self.grid, properties.texture_columns(), self.detail, self.exposed);
+        self.model_stale = false;
+    }
+
+    /// Whether the chunk has a model and some of it can be seen
+    pub fn is_drawn(&self) -> bool {
+        self.exposed != 63 && self.model.is_some()
     }
 
     /// Update the graphics buffer in the grid from the Rigid body.
@@ -184,10 +200,14 @@ impl Chunk {
     }
 
     pub fn draw(&self, renderer: &mut Renderer) {
-        self.grid.draw(renderer);
+        if let Some(model) = &self.model {
+            model.draw(renderer);
+        }
     }
     
     pub(crate) fn copy_buffer(&self, renderer: &mut Renderer, buffer: &StorageBuffer, index:u32) {
-        self.grid.buffer.copy_from_storage_buffer(renderer, buffer, index as u64 *std::mem::size_of::<ModelUniform>() as u64);
+        if let Some(model) = &self.model {
+            model.buffer.copy_from_storage_buffer(renderer, buffer, index as u64 *std::mem::size_of::<ModelUniform>() as u64);
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,204 @@
+// Signals sent between the command blocks of a circuit, on named channels
+use std::collections::VecDeque;
+
+use rustc_hash::FxHashMap;
+
+use crate::{game::object::BlockKey, graphics::CHUNK_SIZE};
+
+/// Signals a block can hold before more are dropped, and can send in one tick
+pub const QUEUE_LENGTH: usize = 64;
+
+/// Where a block is on its circuit. Addresses come from the block's position, so they stay the same as other blocks
+/// change and across saves. They start at 1, so 0 means no block.
+pub fn address((chunk, block): BlockKey) -> u64 {
+    // 17 bits an axis, which fits a float exactly
+    let axis = |c: i32, b: u32| (c * CHUNK_SIZE as i32 + b as i32 + (1 << 16)) as u64 & 0x1ffff;
+    (axis(chunk.0, block.0) << 34 | axis(chunk.1, block.1) << 17 | axis(chunk.2, block.2)) + 1
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signal {
+    pub from: u64,
+    pub channel: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Default)]
+struct Inbox {
+    signals: VecDeque<Signal>,
+    /// Signals the block sent this tick
+    sent: usize,
+    /// Signals lost because the inbox was full, since last taken
+    dropped: usize,
+}
+
+/// The command blocks of a circuit and the signals between them. Signals sent during a tick are delivered in the
+/// order sent when the tick ends, so they can be read from the next tick on. They wait in the inbox until read, and
+/// are only read once.
+#[derive(Debug)]
+pub struct CircuitData {
+    inboxes: FxHashMap<u64, Inbox>,
+    /// Signals sent this tick, with their address or None for broadcasts
+    outbox: Vec<(Option<u64>, Signal)>,
+}
+
+impl CircuitData {
+    pub fn new() -> Self {
+        Self {
+            inboxes: FxHashMap::default(),
+            outbox: Vec::new(),
+        }
+    }
+
+    /// Set the blocks on the circuit. Blocks which leave lose the signals waiting for them.
+    pub fn set_members(&mut self, addresses: impl IntoIterator<Item = u64>) {
+        let mut inboxes = FxHashMap::default();
+        for address in addresses {
+            inboxes.insert(address, self.inboxes.remove(&address).unwrap_or_default());
+        }
+        self.inboxes = inboxes;
+    }
+
+    /// Send a signal to one block, or to every other block for None. Returns false, sending nothing, if the sender
+    /// is not on the circuit or has already sent as many signals as it may this tick.
+    pub fn send(&mut self, to: Option<u64>, signal: Signal) -> bool {
+        let Some(inbox) = self.inboxes.get_mut(&signal.from) else { return false; };
+        if inbox.sent >= QUEUE_LENGTH { return false; }
+        inbox.sent += 1;
+        self.outbox.push((to, signal));
+        true
+    }
+
+    /// Number of signals waiting for a block on a channel
+    pub fn pending(&self, address: u64, channel: &str) -> usize {
+        self.inboxes.get(&address).map_or(0, |i| i.signals.iter().filter(|s| s.channel == channel).count())
+    }
+
+    /// Take the oldest signal waiting for a block on a channel
+    pub fn recv(&mut self, address: u64, channel: &str) -> Option<Signal> {
+        let signals = &mut self.inboxes.get_mut(&address)?.signals;
+        let index = signals.iter().position(|s| s.channel == channel)?;
+        signals.remove(index)
+    }
+
+    /// Number of signals a block has lost to a full inbox since this was last called for it
+    pub fn take_dropped(&mut self, address: u64) -> usize {
+        self.inboxes.get_mut(&address).map_or(0, |i| std::mem::take(&mut i.dropped))
+    }
+
+    /// Deliver the signals sent this tick. Signals to addresses not on the circuit are lost.
+    pub fn tick(&mut self) {
+        for (to, signal) in self.outbox.drain(..) {
+            for (address, inbox) in &mut self.inboxes {
+                let addressed = match to {
+                    Some(to) => *address == to,
+                    None => *address != signal.from,
+                };
+                if !addressed { continue; }
+                if inbox.signals.len() >= QUEUE_LENGTH {
+                    inbox.dropped += 1;
+                } else {
+                    inbox.signals.push_back(signal.clone());
+                }
+            }
+        }
+        for inbox in self.inboxes.values_mut() {
+            inbox.sent = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signal(from: u64, channel: &str, value: f64) -> Signal {
+        Signal { from, channel: channel.to_owned(), value }
+    }
+
+    fn circuit() -> CircuitData {
+        let mut circuit = CircuitData::new();
+        circuit.set_members([1, 2, 3]);
+        circuit
+    }
+
+    #[test]
+    fn addresses_are_unique() {
+        let a = address(((0, 0, 0), (1, 2, 3)));
+        assert_ne!(a, address(((0, 0, 0), (1, 3, 2))));
+        assert_ne!(a, address(((1, 0, 0), (1, 2, 3))));
+        assert_ne!(address(((-1, 0, 0), (15, 0, 0))), address(((0, 0, 0), (0, 0, 0))));
+        assert!(a > 0);
+        // Addresses pass through scripts as floats
+        let far = address(((-4000, 4000, -4000), (0, 15, 0)));
+        assert_eq!(far as f64 as u64, far);
+    }
+
+    #[test]
+    fn signals_arrive_next_tick_in_order() {
+        let mut circuit = circuit();
+        assert!(circuit.send(Some(2), signal(1, "a", 1.)));
+        assert!(circuit.send(Some(2), signal(3, "a", 2.)));
+        assert!(circuit.send(Some(2), signal(1, "b", 3.)));
+        assert_eq!(circuit.pending(2, "a"), 0);
+        circuit.tick();
+        assert_eq!(circuit.pending(2, "a"), 2);
+        assert_eq!(circuit.pending(1, "a"), 0);
+        // Channels are read separately, each in the order sent
+        assert_eq!(circuit.recv(2, "b"), Some(signal(1, "b", 3.)));
+        assert_eq!(circuit.recv(2, "a"), Some(signal(1, "a", 1.)));
+        assert_eq!(circuit.recv(2, "a"), Some(signal(3, "a", 2.)));
+        assert_eq!(circuit.recv(2, "a"), None);
+        // Unread signals wait for later ticks
+        circuit.send(Some(2), signal(1, "a", 4.));
+        circuit.tick();
+        circuit.tick();
+        assert_eq!(circuit.recv(2, "a").map(|s| s.value), Some(4.));
+    }
+
+    #[test]
+    fn broadcasts_reach_every_other_block() {
+        let mut circuit = circuit();
+        circuit.send(None, signal(1, "a", 5.));
+        // Signals to blocks not on the circuit are lost
+        circuit.send(Some(9), signal(1, "a", 6.));
+        circuit.tick();
+        assert_eq!(circuit.pending(1, "a"), 0);
+        assert_eq!(circuit.pending(2, "a"), 1);
+        assert_eq!(circuit.pending(3, "a"), 1);
+        // Blocks not on the circuit cannot send
+        assert!(!circuit.send(None, signal(9, "a", 1.)));
+    }
+
+    #[test]
+    fn queues_are_bounded() {
+        let mut circuit = circuit();
+        for i in 0..QUEUE_LENGTH {
+            assert!(circuit.send(Some(2), signal(1, "a", i as f64)));
+        }
+        // A block sends no more than a queue's worth each tick
+        assert!(!circuit.send(Some(2), signal(1, "a", -1.)));
+        for i in 0..3 {
+            assert!(circuit.send(Some(2), signal(3, "a", i as f64)));
+        }
+        circuit.tick();
+        // The inbox keeps the oldest and counts the rest
+        assert_eq!(circuit.pending(2, "a"), QUEUE_LENGTH);
+        assert_eq!(circuit.take_dropped(2), 3);
+        assert_eq!(circuit.take_dropped(2), 0);
+        assert_eq!(circuit.recv(2, "a").map(|s| s.value), Some(0.));
+        assert!(circuit.send(Some(2), signal(1, "a", 0.)));
+    }
+
+    #[test]
+    fn leaving_blocks_lose_their_signals() {
+        let mut circuit = circuit();
+        circuit.send(None, signal(1, "a", 5.));
+        circuit.tick();
+        circuit.set_members([1, 2]);
+        assert_eq!(circuit.pending(2, "a"), 1);
+        assert_eq!(circuit.pending(3, "a"), 0);
+        circuit.set_members([1, 2, 3]);
+        assert_eq!(circuit.pending(3, "a"), 0);
+    }
+}
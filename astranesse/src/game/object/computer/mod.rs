@@ -1,16 +1,38 @@
-use rustc_hash::FxHashMap;
+mod registry;
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use rustc_hash::{FxHashMap, FxHashSet};
 use sorted_vec::SortedSet;
-use crate::{game::object::computer::compiler::compile, util::{Tagged, Vendor}};
+use biscuit::{CompileCache, Instructions, Interpreter, machine::InstructionData, util::Vendor};
 
+use crate::game::object::internals::INSTRUCTIONS_PER_FRAME;
+
+pub use registry::{BlockType, Collision, Role};
+
+/// The script run by the command blocks of a type
+pub enum Script {
+    Compiled(Instructions),
+    /// Read from the script directory, so that it can be edited and reloaded while the game runs. Each command block
+    /// runs a copy.
+    Interpreted(Box<Interpreter>),
+}
 
 pub struct BlockProperties {
     pub command_blocks: SortedSet<u8>,
     pub conductor_blocks: SortedSet<u8>,
     pub pipe_blocks: SortedSet<u8>,
-    pub command_block_scripts: FxHashMap<u8, Instructions>,
+    pub command_block_scripts: FxHashMap<u8, Script>,
     pub chair_blocks: SortedSet<u8>,
+    types: BTreeMap<u8, BlockType>,
+    /// Texture atlas column of each id, for meshing
+    texture_columns: [u8; 256],
     preloaded_scripts: FxHashMap<String, &'static str>,
+    /// Directory of the scripts to interpret instead of compiling the preloaded ones
+    script_dir: Option<PathBuf>,
     script_vendor: Vendor<InstructionData>,
+    /// Scripts share library functions, which are compiled once
+    script_cache: CompileCache,
 }
 
 impl BlockProperties {
@@ -20,9 +42,13 @@ impl BlockProperties {
             conductor_blocks: SortedSet::new(),
             pipe_blocks: SortedSet::new(),
             chair_blocks: SortedSet::new(),
+            types: BTreeMap::new(),
+            texture_columns: std::array::from_fn(|id| id as u8),
             preloaded_scripts: FxHashMap::default(),
+            script_dir: None,
             command_block_scripts: FxHashMap::default(),
             script_vendor: Vendor::new(),
+            script_cache: CompileCache::new(),
         }
     }
 
@@ -30,101 +56,96 @@ impl BlockProperties {
         self.preloaded_scripts.insert(name.to_owned(), script);
     }
 
-    pub fn load_manifest(&mut self, text: &str) {
-        for line in text.split('\n') {
-            if line.starts_with('#') { continue; }
-            if line.is_empty() { continue; }
-            let mut arguments = line.split_whitespace();
-            let id: u8 = arguments.next().unwrap().parse().unwrap();
-            let name = arguments.next().unwrap().to_owned();
-            let is_conductor = arguments.next().unwrap() == "T";
-            let is_pipe = arguments.next().unwrap() == "T";
-            let is_command = arguments.next().unwrap() == "T";
-            let is_chair = arguments.next().unwrap() == "T";
-
-            if is_command {
-                let data = InstructionData::new(name, &self.preloaded_scripts);
-                let instructions = self.script_vendor.insert(data);
-                self.command_block_scripts.insert(id, instructions);
+    /// Interpret the `.txt` scripts in a directory instead of compiling the preloaded ones, so that they can be edited
+    /// while the game runs and read again with `reload_scripts`. For development. Must be set before the registry is
+    /// loaded.
+    pub fn interpret_scripts_from(&mut self, dir: impl Into<PathBuf>) {
+        self.script_dir = Some(dir.into());
+    }
+
+    /// Add the block types of a registry file, compiling the scripts of command blocks. Scripts must be preloaded,
+    /// or be in the script directory if they are interpreted.
+    pub fn load_registry(&mut self, text: &str, filename: &str) -> Result<(), String> {
+        let names = self.script_names()?;
+        let scripts: FxHashSet<&str> = names.iter().map(String::as_str).collect();
+        let types = registry::parse_registry(text, filename, &scripts)?;
+        for typ in types {
+            if let Some(script) = &typ.script {
+                let script = self.load_script(script)?;
+                self.command_block_scripts.insert(typ.id, script);
             }
 
-            if is_conductor { self.conductor_blocks.push(id); }
-            if is_pipe { self.pipe_blocks.push(id); }
-            if is_command { self.command_blocks.push(id); }
-            if is_chair { self.chair_blocks.push(id); }
+            if typ.has_role(Role::Conductor) { self.conductor_blocks.push(typ.id); }
+            if typ.has_role(Role::Pipe) { self.pipe_blocks.push(typ.id); }
+            if typ.has_role(Role::Command) { self.command_blocks.push(typ.id); }
+            if typ.has_role(Role::Chair) { self.chair_blocks.push(typ.id); }
+            self.texture_columns[typ.id as usize] = typ.texture;
+            self.types.insert(typ.id, typ);
         }
+        Ok(())
     }
-}
 
-/*
-
-
-    pub fn tick(&mut self, block: &mut CommandBlockInfo) -> Result<(), MachineError> {
-        let mut message_index = 0;
-        while let Some(function) = self.run_to_call()? {
-            match function {
-                0 => {// Print
-                    let arg = self.stack.pop().ok_or(MachineError::Stack)?;
-                    println!("Breakpoint {}", arg);
-                },
-                1 => {// Add force
-                    let mut force = Vector3::new(
-                        self.stack.pop().ok_or(MachineError::Stack)?,
-                        self.stack.pop().ok_or(MachineError::Stack)?,
-                        self.stack.pop().ok_or(MachineError::Stack)?
-                    );
-                    force = block.quat.rotate_vector(force);
-                    let torque = block.pos.cross(force);
-                    let force = block.body.ori.rotate_vector(force);
-                    block.body.add_force(force);
-                    block.body.add_torque(torque);
-                },
-                2 => {// Add torque
-                    let torque = Vector3::new(self.stack.pop().ok_or(MachineError::Stack)?, self.stack.pop().ok_or(MachineError::Stack)?, self.stack.pop().ok_or(MachineError::Stack)?);
-                    block.body.add_torque(torque);
-                },
-                3 => {// Emit signal
-                    let recv_block = self.stack.pop().ok_or(MachineError::Stack)?.round() as u8;
-                    let n_send = self.stack.pop().ok_or(MachineError::Stack)?.round() as usize;
-                    
-                    let mut data = Vec::with_capacity(n_send);
-                    for _ in 0..n_send {
-                        data.push(self.stack.pop().ok_or(MachineError::Stack)?);
-                    }
-                    
-                    match &mut block.circuit {
-                        Some(c) => {
-                            c.send(recv_block, data)
-                        },
-                        None => (),
-                    }
-                },
-                4 => {// Receive signal
-                    let arg = self.stack.pop().ok_or(MachineError::Stack)?;
-                    let jtrue = u64::from_le_bytes(self.stack.pop().ok_or(MachineError::Stack)?.to_le_bytes()) as usize;
-                    let jfalse = u64::from_le_bytes(self.stack.pop().ok_or(MachineError::Stack)?.to_le_bytes()) as usize;
-                    match &block.circuit {
-                        Some(c) => {
-                            let (data_option, new_message_index) = c.recv(block.id, message_index);
-                            match data_option {
-                                Some(data) => {
-                                    for d in data {
-                                        self.stack.push(*d);
-                                    }
-                                    self.stack.push(data.len() as f64);
-                                    self.ip = jtrue;
-                                },
-                                None => {
-                                    self.ip = jfalse;
-                                }
-                            }
-                            message_index = new_message_index;
-                        },
-                        None => self.ip = jfalse,
-                    }
-                },
-                _ => return Err(MachineError::Func)
-            }
+    /// Read the interpreted scripts again. Command blocks already running carry on with the old scripts until they
+    /// are restarted. If any script cannot be read or parsed, all the old ones are kept.
+    pub fn reload_scripts(&mut self) -> Result<(), String> {
+        if self.script_dir.is_none() {
+            return Err("Scripts are compiled, so cannot be reloaded".to_owned());
+        }
+        let names: Vec<(u8, String)> = self.types.values().filter_map(|t| Some((t.id, t.script.clone()?))).collect();
+        let mut scripts = FxHashMap::default();
+        for (id, name) in names {
+            scripts.insert(id, self.load_script(&name)?);
         }
+        self.command_block_scripts = scripts;
         Ok(())
-    } */
\ No newline at end of file
+    }
+
+    /// Names of the scripts command blocks may run
+    fn script_names(&self) -> Result<Vec<String>, String> {
+        let Some(dir) = &self.script_dir else {
+            return Ok(self.preloaded_scripts.keys().cloned().collect());
+        };
+        let entries = std::fs::read_dir(dir).map_err(|e| format!("Could not read {}: {}", dir.display(), e))?;
+        Ok(entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "txt"))
+            .filter_map(|p| Some(p.file_stem()?.to_str()?.to_owned()))
+            .collect())
+    }
+
+    /// Compile a preloaded script, or parse one from the script directory
+    fn load_script(&mut self, name: &str) -> Result<Script, String> {
+        let Some(dir) = &self.script_dir else {
+            let binary = self.script_cache.compile_str(self.preloaded_scripts[name], name)?;
+            let data = InstructionData::from_compiled(&binary);
+            return Ok(Script::Compiled(self.script_vendor.insert(data)));
+        };
+        let path = dir.join(format!("{}.txt", name));
+        let source = std::fs::read_to_string(&path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        let interpreter = Interpreter::new(&source, &path.to_string_lossy(), INSTRUCTIONS_PER_FRAME as usize)?;
+        Ok(Script::Interpreted(Box::new(interpreter)))
+    }
+
+    pub fn get(&self, id: u8) -> Option<&BlockType> {
+        self.types.get(&id)
+    }
+
+    /// Block types by id
+    pub fn types(&self) -> impl Iterator<Item = &BlockType> {
+        self.types.values()
+    }
+
+    /// Mass of a block. Blocks missing from the registry weigh one.
+    pub fn mass(&self, id: u8) -> f64 {
+        self.get(id).map_or(1., |t| t.mass)
+    }
+
+    /// Whether a block is part of its object's collider
+    pub fn is_solid(&self, id: u8) -> bool {
+        id != 0 && self.get(id).is_none_or(|t| t.collision == Collision::Cube)
+    }
+
+    pub fn texture_columns(&self) -> &[u8; 256] {
+        &self.texture_columns
+    }
+}
@@ -0,0 +1,232 @@
+// Block types, read from a TOML file so that blocks can be added without touching Rust
+use std::ops::Range;
+
+use rustc_hash::FxHashSet;
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::graphics::TEXTURE_COLUMNS;
+
+/// What a block does besides taking up space
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Carries circuit signals between command blocks
+    Conductor,
+    /// Carries fluid between tanks and command blocks
+    Pipe,
+    /// Runs a script
+    Command,
+    /// Can be sat in, passing the controls of the player to its script
+    Chair,
+}
+
+/// How a block collides with other bodies
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Collision {
+    #[default]
+    Cube,
+    None,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockType {
+    /// Stored in chunks and saves, so it must not change once blocks of the type have been placed
+    pub id: u8,
+    pub name: String,
+    pub mass: f64,
+    /// Column of the block in the texture atlas. Each row of the column is a face.
+    pub texture: u8,
+    pub collision: Collision,
+    pub roles: Vec<Role>,
+    /// Name of the script run by command blocks
+    pub script: Option<String>,
+    pub tooltip: String,
+    /// Fuel held by tanks
+    pub capacity: f64,
+    /// Force of engines at full thrust
+    pub thrust: f64,
+    /// Fuel burnt each second by engines at full thrust
+    pub burn: f64,
+}
+
+impl BlockType {
+    pub fn has_role(&self, role: Role) -> bool {
+        self.roles.contains(&role)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RegistryFile {
+    #[serde(default)]
+    block: Vec<BlockEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockEntry {
+    id: Spanned<u8>,
+    name: Spanned<String>,
+    mass: Spanned<f64>,
+    texture: Spanned<u8>,
+    #[serde(default)]
+    collision: Collision,
+    roles: Option<Spanned<Vec<Role>>>,
+    script: Option<Spanned<String>>,
+    #[serde(default)]
+    tooltip: String,
+    capacity: Option<Spanned<f64>>,
+    thrust: Option<Spanned<f64>>,
+    burn: Option<Spanned<f64>>,
+}
+
+/// Read the block types of a registry file. `scripts` are the names of the scripts command blocks may run.
+/// Errors point to the line and column of the problem.
+pub fn parse_registry(text: &str, filename: &str, scripts: &FxHashSet<&str>) -> Result<Vec<BlockType>, String> {
+    let raise = |span: Range<usize>, message: &str| {
+        let before = &text[..span.start.min(text.len())];
+        let line = before.matches('\n').count() + 1;
+        let col = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        format!("{}:{}:{}\n{}", filename, line, col, message)
+    };
+    let file: RegistryFile = toml::from_str(text)
+        .map_err(|e| raise(e.span().unwrap_or(0..0), e.message()))?;
+
+    let mut ids = FxHashSet::default();
+    let mut names = FxHashSet::default();
+    let mut out = Vec::with_capacity(file.block.len());
+    for entry in file.block {
+        let id = *entry.id.get_ref();
+        if id == 0 {
+            return Err(raise(entry.id.span(), "Block id 0 is reserved for empty space"));
+        }
+        if !ids.insert(id) {
+            return Err(raise(entry.id.span(), &format!("Block id {} is used twice", id)));
+        }
+        if !names.insert(entry.name.get_ref().clone()) {
+            return Err(raise(entry.name.span(), &format!("Block name {} is used twice", entry.name.get_ref())));
+        }
+        let mass = *entry.mass.get_ref();
+        if !mass.is_finite() || mass <= 0. {
+            return Err(raise(entry.mass.span(), "Mass must be positive"));
+        }
+        if *entry.texture.get_ref() >= TEXTURE_COLUMNS {
+            return Err(raise(entry.texture.span(), &format!("The texture atlas has {} columns", TEXTURE_COLUMNS)));
+        }
+        let roles = entry.roles.as_ref().map_or(&[][..], |r| r.get_ref());
+        match &entry.script {
+            Some(script) if !roles.contains(&Role::Command) => {
+                return Err(raise(script.span(), "Only blocks with the command role run scripts"));
+            },
+            Some(script) if !scripts.contains(script.get_ref().as_str()) => {
+                return Err(raise(script.span(), &format!("Unknown script {}", script.get_ref())));
+            },
+            None if roles.contains(&Role::Command) => {
+                return Err(raise(entry.roles.unwrap().span(), "Command blocks need a script"));
+            },
+            _ => (),
+        }
+        for value in [&entry.capacity, &entry.thrust, &entry.burn].into_iter().flatten() {
+            if !value.get_ref().is_finite() || *value.get_ref() < 0. {
+                return Err(raise(value.span(), "Must not be negative"));
+            }
+        }
+        if let Some(capacity) = &entry.capacity && !roles.contains(&Role::Pipe) {
+            return Err(raise(capacity.span(), "Only blocks with the pipe role hold fuel"));
+        }
+        if let Some(thrust) = &entry.thrust && !roles.contains(&Role::Command) {
+            return Err(raise(thrust.span(), "Only blocks with the command role thrust"));
+        }
+
+        out.push(BlockType {
+            id,
+            name: entry.name.into_inner(),
+            mass,
+            texture: entry.texture.into_inner(),
+            collision: entry.collision,
+            roles: entry.roles.map(Spanned::into_inner).unwrap_or_default(),
+            script: entry.script.map(Spanned::into_inner),
+            tooltip: entry.tooltip,
+            capacity: entry.capacity.map_or(0., Spanned::into_inner),
+            thrust: entry.thrust.map_or(0., Spanned::into_inner),
+            burn: entry.burn.map_or(0., Spanned::into_inner),
+        });
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Vec<BlockType>, String> {
+        parse_registry(text, "blocks.toml", &FxHashSet::from_iter(["engine"]))
+    }
+
+    #[test]
+    fn shipped_registry_is_valid() {
+        let scripts = FxHashSet::from_iter(["chair", "engine"]);
+        let types = parse_registry(include_str!("../../../../../assets/blocks.toml"), "blocks.toml", &scripts).unwrap();
+        assert!(types.iter().any(|t| t.has_role(Role::Chair)));
+    }
+
+    #[test]
+    fn blocks_are_read() {
+        let types = parse("
+[[block]]
+id = 5
+name = \"engine\"
+mass = 2.5
+texture = 5
+roles = [\"conductor\", \"command\"]
+script = \"engine\"
+tooltip = \"Pushes\"
+thrust = 40
+burn = 0.5
+
+[[block]]
+id = 8
+name = \"glass\"
+mass = 1
+texture = 8
+collision = \"none\"
+").unwrap();
+        assert_eq!(types[0], BlockType {
+            id: 5,
+            name: "engine".to_owned(),
+            mass: 2.5,
+            texture: 5,
+            collision: Collision::Cube,
+            roles: vec![Role::Conductor, Role::Command],
+            script: Some("engine".to_owned()),
+            tooltip: "Pushes".to_owned(),
+            capacity: 0.,
+            thrust: 40.,
+            burn: 0.5,
+        });
+        assert_eq!(types[1].collision, Collision::None);
+        assert!(types[1].roles.is_empty());
+    }
+
+    #[test]
+    fn errors_point_to_the_line() {
+        const BLOCK: &str = "[[block]]\nid = 1\nname = \"a\"\nmass = 1.0\ntexture = 1\n";
+        let error = |text: String| parse(&text).err().unwrap();
+
+        assert!(error(BLOCK.replace("mass = 1.0", "mass = -1.0")).starts_with("blocks.toml:4:8\nMass must be positive"));
+        assert!(error(BLOCK.replace("id = 1", "id = 0")).starts_with("blocks.toml:2:6\nBlock id 0 is reserved"));
+        assert!(error(BLOCK.replace("id = 1", "id = 300")).starts_with("blocks.toml:2:6\n"));
+        assert!(error(BLOCK.replace("texture = 1", "texture = 15")).starts_with("blocks.toml:5:11\n"));
+        assert!(error(format!("{}{}", BLOCK, BLOCK.replace("\"a\"", "\"b\""))).starts_with("blocks.toml:7:6\nBlock id 1 is used twice"));
+        assert!(error(format!("{}colour = 3\n", BLOCK)).starts_with("blocks.toml:6:1\n"));
+        assert!(error(format!("{}roles = [\"engine\"]\n", BLOCK)).starts_with("blocks.toml:6:10\n"));
+        assert!(error(format!("{}roles = [\"command\"]\n", BLOCK)).starts_with("blocks.toml:6:9\nCommand blocks need a script"));
+        assert!(error(format!("{}script = \"engine\"\n", BLOCK)).starts_with("blocks.toml:6:10\nOnly blocks with the command role"));
+        assert!(error(format!("{}roles = [\"command\"]\nscript = \"missile\"\n", BLOCK)).starts_with("blocks.toml:7:10\nUnknown script missile"));
+        assert!(error(format!("{}capacity = 5\n", BLOCK)).starts_with("blocks.toml:6:12\nOnly blocks with the pipe role hold fuel"));
+        assert!(error(format!("{}roles = [\"pipe\"]\ncapacity = -5\n", BLOCK)).starts_with("blocks.toml:7:12\nMust not be negative"));
+        assert!(error(format!("{}thrust = 5\n", BLOCK)).starts_with("blocks.toml:6:10\nOnly blocks with the command role thrust"));
+    }
+}
@@ -1,151 +1,199 @@
-use std::{cell::RefCell, rc::Rc};
+use std::collections::BTreeMap;
 
-use cgmath::{Quaternion, Vector3};
+use biscuit::{GlobalFunction, InterruptKind, Machine, MachineError, MachineId, MachineState, Scheduler, bytecode::decode_str};
+use cgmath::{Matrix3, Quaternion, Rotation, Vector3};
 use rustc_hash::FxHashMap;
-use sorted_vec::SortedSet;
-use crate::{game::object::{Chunk, computer::{BlockProperties, Memory, machine::{Machine, MachineError}}}, graphics::CHUNK_SIZE, physics::RigidBody, util::{Tagged, Vendor}};
+use crate::{game::object::{Chunk, circuits::{CircuitData, Signal, address}, computer::{BlockProperties, Script}, network::{Networks, chunk_blocks}, pipes::Tanks}, graphics::{Block, CHUNK_SIZE}, physics::RigidBody, util::{Tagged, Vendor}};
 
 type Pipe = Tagged<PipeData>;
 type Circuit = Tagged<CircuitData>;
 pub type BlockKey = ((i32, i32, i32), (u32, u32, u32));
 
-fn bucket_fill(chunks: &FxHashMap<(i32, i32, i32), Chunk>, targets: &SortedSet<u8>, commands: &SortedSet<u8>) -> FxHashMap<BlockKey, u32>{
-    let mut attached = FxHashMap::default();
-    let mut all_blocks = SortedSet::new();
-
-    for (chunk_pos, chunk) in chunks {
-        for i in 0..CHUNK_SIZE {
-            for j in 0..CHUNK_SIZE {
-                for k in 0..CHUNK_SIZE {
-                    let block = (*chunk_pos, (i,j,k));
-                    let block_type = chunks[&block.0].grid[block.1].id;
-                    if targets.contains(&block_type) { all_blocks.push(block); }
-                }
-            }
-        }
-    }
-
-    let mut index = 0;
-    while !all_blocks.is_empty() {
-        let mut queue = vec![*all_blocks.first().unwrap()];
-        while !queue.is_empty() {
-            let block = queue.pop().unwrap();
-            
-            if commands.contains(&chunks[&block.0].grid[block.1].id) {
-                attached.insert(block, index);
-            }
-            if let None = all_blocks.remove_item(&block) { continue; } // Already visited, or not a target.
-            
-            let new_coords = [
-                if block.1.0 != 0 { (block.0, (block.1.0-1, block.1.1, block.1.2)) }
-                else { ((block.0.0-1, block.0.1, block.0.2), (CHUNK_SIZE-1, block.1.1, block.1.2)) },
-
-                if block.1.0 != CHUNK_SIZE-1 { (block.0, (block.1.0+1, block.1.1, block.1.2)) }
-                else { ((block.0.0+1, block.0.1, block.0.2), (0, block.1.1, block.1.2)) },
-
-                if block.1.1 != 0 { (block.0, (block.1.0, block.1.1-1, block.1.2)) }
-                else { ((block.0.0, block.0.1-1, block.0.2), (block.1.0, CHUNK_SIZE-1, block.1.2)) },
-
-                if block.1.1 != CHUNK_SIZE-1 { (block.0, (block.1.0, block.1.1+1, block.1.2)) }
-                else { ((block.0.0, block.0.1+1, block.0.2), (block.1.0, 0, block.1.2)) },
-
-                if block.1.2 != 0 { (block.0, (block.1.0, block.1.1, block.1.2-1)) }
-                else { ((block.0.0, block.0.1, block.0.2-1), (block.1.0, block.1.1, CHUNK_SIZE-1)) },
-
-                if block.1.2 != CHUNK_SIZE-1 { (block.0, (block.1.0, block.1.1, block.1.2+1)) }
-                else { ((block.0.0, block.0.1, block.0.2+1), (block.1.0, block.1.1, 0)) },
-            ];
-            for c in new_coords {
-                if chunks[&c.0].grid[c.1].id == 0 {continue;}
-                queue.push(c);
-            }
-        }
-        index += 1;
-    }
-    attached
-}
+/// Instructions shared between all the command blocks of an object each frame
+pub(super) const INSTRUCTIONS_PER_FRAME: u64 = 10_000;
+/// Lines of each command block's log shown on the HUD
+const HUD_LOG_LINES: usize = 4;
 
 pub struct Internals {
     blocks: FxHashMap<BlockKey, CommandBlock>,
+    conductors: Networks,
+    pipe_blocks: Networks,
+    /// Circuit of each network of conductors with command blocks on it
+    network_circuits: FxHashMap<u32, Circuit>,
+    /// Pipe of each network of pipe blocks with command blocks on it
+    network_pipes: FxHashMap<u32, Pipe>,
     circuits: Vendor<CircuitData>,
     pipes: Vendor<PipeData>,
-    memory: Rc<RefCell<Memory>>,
+    tanks: Tanks,
+    scheduler: Scheduler,
+    /// Block running each scheduled machine
+    machine_blocks: FxHashMap<MachineId, BlockKey>,
 }
 impl Internals {
     pub fn new() -> Self {
         Self {
             circuits: Vendor::new(),
             pipes: Vendor::new(),
+            tanks: Tanks::new(),
             blocks: FxHashMap::default(),
-            memory: Rc::new(RefCell::new(Memory::new())),
+            conductors: Networks::new(),
+            pipe_blocks: Networks::new(),
+            network_circuits: FxHashMap::default(),
+            network_pipes: FxHashMap::default(),
+            scheduler: Scheduler::new(INSTRUCTIONS_PER_FRAME),
+            machine_blocks: FxHashMap::default(),
         }
     }
 
-    pub fn update_info(&mut self, properties: &BlockProperties, chunks: &FxHashMap<(i32, i32, i32), Chunk>, body: RigidBody) {
-        let attached_circuits = bucket_fill(chunks, &properties.conductor_blocks, &properties.command_blocks);
-        let attached_pipes = bucket_fill(chunks, &properties.pipe_blocks, &properties.command_blocks);
-
-        let mut circuit_structs: FxHashMap<u32, Circuit> = FxHashMap::default();
-        let mut pipe_structs: FxHashMap<u32, Pipe>  = FxHashMap::default();
-        for (chunk_pos, chunk) in chunks {
-            for i in 0..CHUNK_SIZE {
-                for j in 0..CHUNK_SIZE {
-                    for k in 0..CHUNK_SIZE {
-                        let block = (*chunk_pos, (i,j,k));
-                        if !properties.command_blocks.contains(&chunks[&block.0].grid[block.1].id) { continue; }
-
-                        let circuit = match attached_circuits.get(&block) {
-                            Some(index) => match circuit_structs.get(index) {
-                                Some(circuit_struct) => Some(circuit_struct.clone()),
-                                None => {
-                                    let circuit = self.circuits.insert(CircuitData::new());
-                                    circuit_structs.insert(*index, circuit.clone());
-                                    Some(circuit)
-                                }
-                            },
-                            None => None
-                        };
-                        let pipe = match attached_pipes.get(&block) {
-                            Some(index) => match pipe_structs.get(index) {
-                                Some(pipe_struct) => Some(pipe_struct.clone()),
-                                None => {
-                                    let pipe = self.pipes.insert(PipeData::new());
-                                    pipe_structs.insert(*index, pipe.clone());
-                                    Some(pipe)
-                                }
-                            },
-                            None => None
-                        };
-
-                        self.blocks.insert(block, CommandBlock::new(block, chunks, properties, circuit, pipe, body.clone(), self.memory.clone()));
-                    }
-                }
+    /// Find the circuits, pipes and command blocks of every block, replacing any machines running
+    pub fn rebuild(&mut self, properties: &BlockProperties, chunks: &FxHashMap<(i32, i32, i32), Chunk>, body: RigidBody) {
+        for (_, block) in self.blocks.drain() {
+            self.scheduler.remove(block.machine);
+        }
+        self.machine_blocks.clear();
+        self.conductors = Networks::new();
+        self.pipe_blocks = Networks::new();
+        self.network_circuits.clear();
+        self.network_pipes.clear();
+        let keys = chunks.keys().flat_map(|coord| chunk_blocks(*coord));
+        self.blocks_changed(properties, chunks, body, keys);
+    }
+
+    /// Update the circuits, pipes and command blocks for blocks which changed, or whose chunks were loaded or
+    /// unloaded. Only the networks touching the blocks are walked, and other command blocks keep their machines.
+    pub fn blocks_changed(&mut self, properties: &BlockProperties, chunks: &FxHashMap<(i32, i32, i32), Chunk>, body: RigidBody, keys: impl IntoIterator<Item = BlockKey>) {
+        let mut conductors = Vec::new();
+        let mut pipes = Vec::new();
+        for key in keys {
+            let block = chunks.get(&key.0).map_or(Block { id: 0, ori: 0 }, |c| c.grid[key.1]);
+            let id = block.id;
+            conductors.push((key, properties.conductor_blocks.contains(&id)));
+            pipes.push((key, properties.pipe_blocks.contains(&id)));
+            self.tanks.set(key, properties.get(id).map_or(0., |t| t.capacity));
+
+            // A command block replaced or turned loses its machine
+            if self.blocks.get(&key).is_some_and(|b| b.info.id != id || b.info.quat != block.quat()) {
+                let block = self.blocks.remove(&key).unwrap();
+                self.scheduler.remove(block.machine);
+                self.machine_blocks.remove(&block.machine);
             }
+            if properties.command_blocks.contains(&id) && !self.blocks.contains_key(&key) {
+                let command_block = CommandBlock::new(key, chunks, properties, body.clone(), &mut self.scheduler);
+                self.machine_blocks.insert(command_block.machine, key);
+                self.blocks.insert(key, command_block);
+            }
+        }
+        self.conductors.update(conductors);
+        self.pipe_blocks.update(pipes);
+
+        // Networks which merged or split may have new labels, so attach every command block again. Circuits and
+        // pipes of networks which kept their label carry on, and those with no command blocks left are dropped.
+        let mut circuits = FxHashMap::default();
+        let mut pipes = FxHashMap::default();
+        let mut members: FxHashMap<u32, Vec<u64>> = FxHashMap::default();
+        for (key, block) in &mut self.blocks {
+            block.info.circuit = self.conductors.attached(*key).map(|n| {
+                members.entry(n).or_default().push(block.info.address);
+                circuits.entry(n).or_insert_with(|| self.network_circuits.remove(&n).unwrap_or_else(|| self.circuits.insert(CircuitData::new()))).clone()
+            });
+            block.info.pipe = self.pipe_blocks.attached(*key).map(|n| {
+                pipes.entry(n).or_insert_with(|| self.network_pipes.remove(&n).unwrap_or_else(|| self.pipes.insert(PipeData::new()))).clone()
+            });
+        }
+        self.network_circuits = circuits;
+        self.network_pipes = pipes;
+        for (network, circuit) in &mut self.network_circuits {
+            circuit.set_members(members.remove(network).unwrap_or_default());
+        }
+        for (network, pipe) in &mut self.network_pipes {
+            pipe.tanks = self.pipe_blocks.members(*network).filter(|k| self.tanks.get(*k).is_some()).collect();
+            pipe.tanks.sort_unstable();
+        }
+    }
+
+    /// Start every command block again with the script its type has now, such as after the scripts were reloaded
+    pub fn restart_scripts(&mut self, properties: &BlockProperties) {
+        for (key, block) in &mut self.blocks {
+            self.scheduler.remove(block.machine);
+            self.machine_blocks.remove(&block.machine);
+            block.machine = start_script(properties, block.info.id, &mut self.scheduler);
+            self.machine_blocks.insert(block.machine, *key);
         }
     }
 
     pub fn update(&mut self, delta_t: f64) {
-        for block in self.blocks.values_mut() {
-            block.update(delta_t);
+        self.tanks.flow(&self.pipe_blocks, delta_t);
+        let faults = self.scheduler.run_frame(|id, func, args| {
+            match self.blocks.get_mut(&self.machine_blocks[&id]) {
+                Some(b) => b.call(func, args, &mut self.tanks, delta_t),
+                None => Err(MachineError::Func),
+            }
+        });
+        for (id, e) in faults {
+            if let Some(b) = self.blocks.get(&self.machine_blocks[&id]) {
+                b.report(e);
+            }
         }
         for mut circuit in &mut self.circuits.iter() {
             circuit.tick();
         }
     }
 
+    /// The latest output of each command block with any, for the HUD
+    pub fn log_text(&self) -> String {
+        let mut text = String::new();
+        for block in self.blocks.values() {
+            let Some(log) = self.scheduler.log(block.machine) else { continue; };
+            let lines: Vec<&str> = log.lines().rev().take(HUD_LOG_LINES).collect();
+            if lines.is_empty() { continue; }
+            text += &format!("Block (type {})\n", block.info.id);
+            for line in lines.into_iter().rev() {
+                text += &format!("  {}\n", line);
+            }
+        }
+        text
+    }
+
+    /// Whether fuel moved or was burnt since this was last called, changing the mass of the object
+    pub fn take_tanks_changed(&mut self) -> bool {
+        self.tanks.take_changed()
+    }
+
+    /// Zeroth, first and second moments of the mass of the fuel in the tanks, in body coordinates
+    pub fn fuel_mass_moments(&self) -> (f64, Vector3<f64>, Matrix3<f64>) {
+        self.tanks.mass_moments()
+    }
+
+    /// Fill of each tank, to save
+    pub fn tank_fills(&self) -> BTreeMap<BlockKey, f64> {
+        self.tanks.fills()
+    }
+
+    pub fn restore_tank_fills(&mut self, fills: &BTreeMap<BlockKey, f64>) {
+        self.tanks.restore_fills(fills);
+    }
+
+    /// Snapshot the machine of each command block, to save them
+    pub fn machine_states(&self) -> BTreeMap<BlockKey, MachineState> {
+        self.blocks.iter()
+            .filter_map(|(key, b)| Some((*key, self.scheduler.machine(b.machine)?.save_state()?)))
+            .collect()
+    }
+
+    /// Carry on running saved machines. States of blocks which are gone, or whose script has changed, are dropped.
+    pub fn restore_machine_states(&mut self, states: &BTreeMap<BlockKey, MachineState>) {
+        for (key, state) in states {
+            let Some(b) = self.blocks.get(key) else { continue; };
+            if let Some(machine) = self.scheduler.machine_mut(b.machine) {
+                let _ = machine.restore_state(state);
+            }
+        }
+    }
+
     pub fn interrupt(&mut self, block: BlockKey, interrupt: Interrupt) {
         if let Some(b) = self.blocks.get_mut(&block) {
             // Call an interrupt on block b
-            let irps = &mut b.machine.interrupts;
-            match interrupt {
-                Interrupt::Interact => irps.push(1.),
-                Interrupt::Forward(f) => {irps.push(f); irps.push(2.);},
-                Interrupt::Backward(f) => {irps.push(f); irps.push(3.);},
-                Interrupt::Left(f) => {irps.push(f); irps.push(4.);},
-                Interrupt::Right(f) => {irps.push(f); irps.push(5.);},
-                Interrupt::Up(f) => {irps.push(f); irps.push(6.);},
-                Interrupt::Down(f) => {irps.push(f); irps.push(7.);},
-            }
+            self.scheduler.interrupt(b.machine, interrupt.kind(), interrupt.arg());
         }
     }
 }
@@ -161,49 +209,39 @@ pub enum Interrupt {
     Down(f64),
 }
 
-pub struct CircuitData {
-    /// Messages are double buffered
-    messages: [Vec<(u8, Vec<f64>)>; 2],
-    up_index: usize,
-}
-
-impl CircuitData {
-    pub fn new() -> Self {
-        Self {
-            messages: [Vec::new(), Vec::new()],
-            up_index: 0,
+impl Interrupt {
+    /// The interrupt kind shared with the Biscuit compiler, used to find the `on <kind>` handler
+    pub fn kind(&self) -> InterruptKind {
+        match self {
+            Interrupt::Interact => InterruptKind::Interact,
+            Interrupt::Forward(_) => InterruptKind::Forward,
+            Interrupt::Backward(_) => InterruptKind::Backward,
+            Interrupt::Left(_) => InterruptKind::Left,
+            Interrupt::Right(_) => InterruptKind::Right,
+            Interrupt::Up(_) => InterruptKind::Up,
+            Interrupt::Down(_) => InterruptKind::Down,
         }
     }
-    /// Send a message for to_block 
-    pub fn send(&mut self, to_block: u8, data: Vec<f64>) {
-        let down_messages = &mut self.messages[(self.up_index + 1) % 2];
-        down_messages.push((to_block, data));
-    }
 
-    /// Receive a message for to_block. Returns the data if it exists, else None. Also return the index of the first unexplored message.
-    pub fn recv(&self, to_block: u8, start_message: usize) -> (Option<&[f64]>, usize) {
-        let up_messages = &self.messages[self.up_index];
-        for (i, (m_to_block, m_data)) in up_messages.iter().skip(start_message).enumerate() {
-            if *m_to_block != to_block {continue;}
-            return (Some(m_data), i + 1)
+    pub fn arg(&self) -> f64 {
+        match *self {
+            Interrupt::Interact => 0.,
+            Interrupt::Forward(f) | Interrupt::Backward(f) | Interrupt::Left(f)
+                | Interrupt::Right(f) | Interrupt::Up(f) | Interrupt::Down(f) => f,
         }
-        (None, self.messages.len())
-    }
-
-    pub fn tick(&mut self) {
-        self.messages[self.up_index].clear();
-        self.up_index = (self.up_index + 1) % 2;
     }
 }
 
+#[derive(Debug)]
 pub struct PipeData {
-
+    /// Tanks on the network, which the command blocks piped to it draw from
+    tanks: Vec<BlockKey>,
 }
 
 impl PipeData {
     fn new() -> Self {
         Self {
-
+            tanks: Vec::new(),
         }
     }
 }
@@ -214,51 +252,324 @@ pub struct CommandBlockInfo {
     pub(super) id: u8,
     pub(super) block: BlockKey,
     pub(super) body: RigidBody,
+    /// Center of the block in body coordinates, which unlike its offset from the center of mass does not change
+    /// when other blocks do
     pub(super) pos: Vector3<f64>,
     pub(super) quat: Quaternion<f64>,
+    /// Force along the front of the block at full thrust
+    pub(super) thrust: f64,
+    /// Fuel burnt each second at full thrust
+    pub(super) burn: f64,
+    /// Where the block is on its circuit
+    pub(super) address: u64,
+    /// The last signal the block took, which replies go back to
+    pub(super) last_signal: Option<Signal>,
 }
 pub struct CommandBlock {
     info: CommandBlockInfo,
-    machine: Machine,
+    machine: MachineId,
 }
 
 impl CommandBlock {
-    fn new(block_pos: BlockKey, chunks: &FxHashMap<(i32, i32, i32), Chunk>, properties: &BlockProperties, circuit: Option<Circuit>, pipe: Option<Pipe>, body: RigidBody, memory: Rc<RefCell<Memory>>) -> Self {
+    fn new(block_pos: BlockKey, chunks: &FxHashMap<(i32, i32, i32), Chunk>, properties: &BlockProperties, body: RigidBody, scheduler: &mut Scheduler) -> Self {
         let block = chunks[&block_pos.0].grid[block_pos.1];
         let mut pos: Vector3<f64> = Vector3::new(block_pos.0.0 * CHUNK_SIZE as i32, block_pos.0.1 * CHUNK_SIZE as i32, block_pos.0.2 * CHUNK_SIZE as i32).cast().unwrap();
         pos += Vector3::new(block_pos.1.0 as f64 + 0.5, block_pos.1.1 as f64 + 0.5, block_pos.1.2 as f64 + 0.5);
-        pos -= body.com_pos;
         let quat = block.quat();
-        let machine = Machine::new(
-            properties.command_block_scripts.get(&block.id).unwrap().clone(),
-            memory.clone(),
-        );
+        let typ = properties.get(block.id);
+        let machine = start_script(properties, block.id, scheduler);
         Self {
             info: CommandBlockInfo {
-                id: chunks[&block_pos.0].grid[block_pos.1].id,
+                id: block.id,
                 block: block_pos,
-                pipe,
-                circuit,
+                pipe: None,
+                circuit: None,
                 body,
                 pos,
                 quat,
+                thrust: typ.map_or(0., |t| t.thrust),
+                burn: typ.map_or(0., |t| t.burn),
+                address: address(block_pos),
+                last_signal: None,
             },
             machine,
         }
     }
 
-    fn update(&mut self, delta_t: f64) {
-        let tick = self.machine.tick(&mut self.info);
-        if let Err(e) = tick {
-            match e {
-                MachineError::Stack => println!("Segmentation fault in block (type {})", self.info.id),
-                MachineError::Memory => println!("Memory overflow"),
-                MachineError::Ip => println!("Program overflow in block (type {})", self.info.id),
-                MachineError::Func => println!("Invalid function call in block (type {})", self.info.id),
-                MachineError::OpCode => println!("Invalid opcode in block (type {})", self.info.id),
-            }
-            ;
-            self.machine.reset();
+    /// Run a host function called by the block's machine, returning its value, or 0 for functions without one.
+    /// Vectors are along the block's axes. `dbg`, `print` and the timing functions are handled by the scheduler.
+    fn call(&mut self, func: GlobalFunction, args: &[f64], tanks: &mut Tanks, delta_t: f64) -> Result<f64, MachineError> {
+        let arg = |i: usize| args.get(i).copied().ok_or(MachineError::Func);
+        let piped: &[BlockKey] = self.info.pipe.as_ref().map_or(&[], |p| &p.tanks);
+        match func {
+            GlobalFunction::Fuel => Ok(tanks.fuel(piped)),
+            GlobalFunction::FuelCapacity => Ok(tanks.capacity(piped)),
+            GlobalFunction::DrawFuel => Ok(tanks.draw(piped, *args.first().ok_or(MachineError::Func)?)),
+            GlobalFunction::Thrust => {
+                let throttle = args.first().ok_or(MachineError::Func)?.clamp(0., 1.);
+                if self.info.thrust <= 0. { return Ok(0.); }
+                // Thrust is cut back to what the fuel drawn can power
+                let needed = self.info.burn * throttle * delta_t;
+                let powered = if needed > 0. { tanks.draw(piped, needed) / needed } else { 1. };
+                self.push(Vector3::unit_x() * (self.info.thrust * throttle * powered));
+                Ok(powered)
+            },
+            GlobalFunction::AddForce => {
+                self.push(Vector3::new(arg(0)?, arg(1)?, arg(2)?));
+                Ok(0.)
+            },
+            GlobalFunction::AddTorque => {
+                let torque = self.info.quat * Vector3::new(arg(0)?, arg(1)?, arg(2)?);
+                self.info.body.add_torque(torque);
+                Ok(0.)
+            },
+            GlobalFunction::Send | GlobalFunction::Broadcast | GlobalFunction::Pending | GlobalFunction::Recv => {
+                let (channel, args) = decode_str(args).ok_or(MachineError::Func)?;
+                let from = self.info.address;
+                let Some(circuit) = &mut self.info.circuit else { return Ok(0.); };
+                match func {
+                    GlobalFunction::Send => {
+                        let to = *args.first().ok_or(MachineError::Func)?;
+                        let value = *args.get(1).ok_or(MachineError::Func)?;
+                        Ok(circuit.send(Some(to as u64), Signal { from, channel, value }) as u8 as f64)
+                    },
+                    GlobalFunction::Broadcast => {
+                        let value = *args.first().ok_or(MachineError::Func)?;
+                        Ok(circuit.send(None, Signal { from, channel, value }) as u8 as f64)
+                    },
+                    GlobalFunction::Pending => Ok(circuit.pending(from, &channel) as f64),
+                    _ => {
+                        let Some(signal) = circuit.recv(from, &channel) else { return Ok(0.); };
+                        let value = signal.value;
+                        self.info.last_signal = Some(signal);
+                        Ok(value)
+                    },
+                }
+            },
+            GlobalFunction::Reply => {
+                let value = arg(0)?;
+                let (Some(circuit), Some(last)) = (&mut self.info.circuit, &self.info.last_signal) else { return Ok(0.); };
+                let signal = Signal { from: self.info.address, channel: last.channel.clone(), value };
+                Ok(circuit.send(Some(last.from), signal) as u8 as f64)
+            },
+            GlobalFunction::Sender => Ok(self.info.last_signal.as_ref().map_or(0., |s| s.from as f64)),
+            GlobalFunction::Address => Ok(self.info.address as f64),
+            GlobalFunction::Dropped => Ok(self.info.circuit.as_mut().map_or(0, |c| c.take_dropped(self.info.address)) as f64),
+            GlobalFunction::Velocity => axis(self.to_block(self.info.body.vel), arg(0)?),
+            GlobalFunction::AngularVelocity => axis(self.to_block(self.info.body.ang_vel), arg(0)?),
+            GlobalFunction::Mass => Ok(self.info.body.mass),
+            _ => Err(MachineError::Func),
+        }
+    }
+
+    /// Push the body at the block with a force along the block's axes
+    fn push(&mut self, force: Vector3<f64>) {
+        let force = self.info.quat * force;
+        let body = &mut self.info.body;
+        let torque = (self.info.pos - body.com_pos).cross(force);
+        let global = body.ori * force;
+        body.add_force(global);
+        body.add_torque(torque);
+    }
+
+    /// A global vector along the block's axes
+    fn to_block(&self, v: Vector3<f64>) -> Vector3<f64> {
+        (self.info.body.ori * self.info.quat).invert().rotate_vector(v)
+    }
+
+    /// Print a fault of the block's machine. The scheduler has already reset it.
+    fn report(&self, e: MachineError) {
+        match e {
+            MachineError::Stack => println!("Segmentation fault in block (type {})", self.info.id),
+            MachineError::Memory => println!("Memory overflow"),
+            MachineError::Ip => println!("Program overflow in block (type {})", self.info.id),
+            MachineError::Func => println!("Invalid function call in block (type {})", self.info.id),
+            MachineError::OpCode => println!("Invalid opcode in block (type {})", self.info.id),
+            MachineError::CallDepth => println!("Calls nested too deeply in block (type {})", self.info.id),
+            MachineError::Unsupported => println!("Unsupported operation in block (type {})", self.info.id),
+        }
+    }
+}
+/// Schedule a machine running the script of a command block type
+fn start_script(properties: &BlockProperties, id: u8, scheduler: &mut Scheduler) -> MachineId {
+    match &properties.command_block_scripts[&id] {
+        Script::Compiled(instructions) => scheduler.insert(Machine::new(instructions.clone(), INSTRUCTIONS_PER_FRAME as usize)),
+        Script::Interpreted(interpreter) => scheduler.insert(interpreter.as_ref().clone()),
+    }
+}
+
+/// Component of a vector along axis 0, 1 or 2
+fn axis(v: Vector3<f64>, axis: f64) -> Result<f64, MachineError> {
+    match axis.round() as i64 {
+        0 => Ok(v.x),
+        1 => Ok(v.y),
+        2 => Ok(v.z),
+        _ => Err(MachineError::Func),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::InnerSpace;
+
+    use crate::{game::{load_block_properties, object::{loader::ShipLoader, save::ShipSave}}, physics::{Physics, RigidBodyInit}};
+
+    use super::*;
+
+    fn circuit(internals: &Internals, block: BlockKey) -> Option<&Circuit> {
+        internals.blocks[&block].info.circuit.as_ref()
+    }
+
+    fn pipe(internals: &Internals, block: BlockKey) -> Option<&Pipe> {
+        internals.blocks[&block].info.pipe.as_ref()
+    }
+
+    const IDLE: &str = "fn main() {\n    tick();\n}\n";
+
+    /// The shipped block types, with test scripts for the chair and engines
+    fn properties(chair: &'static str, engine: &'static str) -> BlockProperties {
+        let mut properties = BlockProperties::new();
+        properties.preload_script(chair, "chair");
+        properties.preload_script(engine, "engine");
+        properties.load_registry(include_str!("../../../../assets/blocks.toml"), "blocks.toml").unwrap();
+        properties
+    }
+
+    /// The demo ship's internals running on a body, with its center of mass between the engines
+    fn demo(properties: &BlockProperties) -> (Physics, RigidBody, Internals) {
+        let mut physics = Physics::new();
+        let mut body = RigidBody::new(&mut physics.rb_vendor, RigidBodyInit::default());
+        body.com_pos = Vector3::new(8.5, 7.5, 7.5);
+        let chunks = ShipLoader::new(ShipSave::demo(Vector3::new(0., 0., 0.), Vector3::new(0., 0., 0.))).load_all();
+        let mut internals = Internals::new();
+        internals.rebuild(properties, &chunks, body.clone());
+        (physics, body, internals)
+    }
+
+    /// Block turns are only stored to a few digits
+    fn close(a: Vector3<f64>, b: Vector3<f64>) -> bool {
+        (a - b).magnitude() < 1e-5
+    }
+
+    #[test]
+    fn scripts_push_along_the_block() {
+        let engine = "fn main() {\n    loop {\n        add_force(2, 0, 0);\n        tick();\n    }\n}\n";
+        let properties = properties(IDLE, engine);
+        let (mut physics, body, mut internals) = demo(&properties);
+        internals.update(0.5);
+        physics.update(0.5);
+        // Both engines face the same way, and their turning cancels out about the center of mass between them
+        let front = body.ori * Block { id: 5, ori: 3 }.quat() * Vector3::unit_x();
+        assert!(close(body.vel, front * 2.), "{:?}", body.vel);
+        assert!(close(body.ang_vel, Vector3::new(0., 0., 0.)), "{:?}", body.ang_vel);
+    }
+
+    #[test]
+    fn scripts_turn_about_the_block() {
+        let engine = "fn main() {\n    loop {\n        add_torque(0, 0, 1);\n        tick();\n    }\n}\n";
+        let properties = properties(IDLE, engine);
+        let (mut physics, body, mut internals) = demo(&properties);
+        internals.update(0.5);
+        physics.update(0.5);
+        let axis = body.ori * Block { id: 5, ori: 3 }.quat() * Vector3::unit_z();
+        assert!(close(body.ang_vel, axis), "{:?}", body.ang_vel);
+        assert!(close(body.vel, Vector3::new(0., 0., 0.)));
+    }
+
+    #[test]
+    fn scripts_signal_and_sense() {
+        let chair = "fn main() {\n    loop {\n        broadcast(\"a\", 3);\n        broadcast(\"b\", 7);\n        broadcast(\"a\", 4);\n        tick();\n    }\n}\n";
+        let engine = "fn main() {\n    loop {\n        n = pending(\"a\");\n        a = recv(\"a\");\n        b = recv(\"a\");\n        c = recv(\"a\");\n        v = velocity(0);\n        w = angular_velocity(2);\n        m = mass();\n        dbg(n, a, b, c, v, w, m);\n        tick();\n    }\n}\n";
+        let properties = properties(chair, engine);
+        let (_physics, mut body, mut internals) = demo(&properties);
+        let quat = body.ori * Block { id: 5, ori: 3 }.quat();
+        body.vel = quat * Vector3::new(1.5, 0., 0.);
+        body.ang_vel = quat * Vector3::new(0., 0., 0.25);
+        body.mass = 3.;
+        let engine = ((0, 0, 0), (8, 8, 7));
+        let values = |internals: &Internals| -> Vec<f64> {
+            let log = internals.scheduler.log(internals.blocks[&engine].machine).unwrap();
+            log.lines().last().unwrap().split(", ").map(|v| v.parse().unwrap()).collect()
+        };
+
+        // Signals arrive the tick after they are sent, and are read a channel at a time
+        internals.update(0.1);
+        assert_eq!(values(&internals)[..4], [0., 0., 0., 0.]);
+        internals.update(0.1);
+        let values = values(&internals);
+        assert_eq!(values[..4], [2., 3., 4., 0.]);
+        assert!((values[4] - 1.5).abs() < 1e-5);
+        assert!((values[5] - 0.25).abs() < 1e-5);
+        assert_eq!(values[6], 3.);
+    }
+
+    #[test]
+    fn blocks_are_addressed_separately() {
+        // The chair finds the engines by their replies, then signals only the first to answer
+        let chair = "fn main() {\n    broadcast(\"hello\", 1);\n    tick();\n    tick();\n    n = pending(\"hello\");\n    first = recv(\"hello\");\n    engine = sender();\n    sent = send(\"go\", engine, 7);\n    dbg(n, first, engine, sent);\n    loop {\n        tick();\n    }\n}\n";
+        let engine = "fn main() {\n    loop {\n        hello = pending(\"hello\");\n        if hello > 0 {\n            x = recv(\"hello\");\n            me = address();\n            reply(me);\n        }\n        go = pending(\"go\");\n        if go > 0 {\n            g = recv(\"go\");\n            dbg(g);\n        }\n        tick();\n    }\n}\n";
+        let properties = properties(chair, engine);
+        let (_physics, _body, mut internals) = demo(&properties);
+        for _ in 0..6 {
+            internals.update(0.1);
         }
+        let log = |block: BlockKey| internals.scheduler.log(internals.blocks[&block].machine).map_or(Vec::new(), |l| l.lines().map(str::to_owned).collect());
+        let chair = log(((0, 0, 0), (7, 7, 8)));
+        let values: Vec<f64> = chair[0].split(", ").map(|v| v.parse().unwrap()).collect();
+        assert_eq!((values[0], values[3]), (2., 1.));
+        assert_eq!(values[1], values[2]);
+        let engines = [((0, 0, 0), (8, 8, 7)), ((0, 0, 0), (8, 6, 7))];
+        let (first, second) = if values[2] == address(engines[0]) as f64 { (engines[0], engines[1]) } else { (engines[1], engines[0]) };
+        assert_eq!(values[2], address(first) as f64);
+        assert_eq!(log(first), ["7"]);
+        assert!(log(second).is_empty());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn placing_blocks_updates_circuits_and_pipes() {
+        let properties = load_block_properties();
+        let mut physics = Physics::new();
+        let body = RigidBody::new(&mut physics.rb_vendor, RigidBodyInit::default());
+        let mut chunks = ShipLoader::new(ShipSave::demo(Vector3::new(0., 0., 0.), Vector3::new(0., 0., 0.))).load_all();
+        let mut internals = Internals::new();
+        internals.rebuild(&properties, &chunks, body.clone());
+
+        let chair = ((0, 0, 0), (7, 7, 8));
+        let engines = [((0, 0, 0), (8, 8, 7)), ((0, 0, 0), (8, 6, 7))];
+        let metal = ((0, 0, 0), (7, 7, 7));
+        assert_eq!(internals.blocks.len(), 3);
+        // Everything touches the metal block, so shares a circuit, but each engine has its own tanks
+        assert!(circuit(&internals, chair).is_some());
+        assert_eq!(circuit(&internals, engines[0]), circuit(&internals, chair));
+        assert_eq!(circuit(&internals, engines[1]), circuit(&internals, chair));
+        assert!(pipe(&internals, chair).is_none());
+        assert!(pipe(&internals, engines[0]).is_some());
+        assert_ne!(pipe(&internals, engines[0]), pipe(&internals, engines[1]));
+        let machines: Vec<MachineId> = engines.iter().map(|e| internals.blocks[e].machine).collect();
+
+        // Taking the metal block out cuts the circuit in three
+        chunks.get_mut(&metal.0).unwrap().grid[metal.1] = Block { id: 0, ori: 0 };
+        internals.blocks_changed(&properties, &chunks, body.clone(), [metal]);
+        assert_ne!(circuit(&internals, engines[0]), circuit(&internals, chair));
+        assert_ne!(circuit(&internals, engines[1]), circuit(&internals, chair));
+        assert_ne!(circuit(&internals, engines[0]), circuit(&internals, engines[1]));
+        // The engines carry on running
+        assert_eq!(engines.map(|e| internals.blocks[&e].machine).to_vec(), machines);
+
+        // A tank between the tank rows joins the pipes, and puts the circuit back together
+        chunks.get_mut(&metal.0).unwrap().grid[metal.1] = Block { id: 4, ori: 0 };
+        internals.blocks_changed(&properties, &chunks, body.clone(), [metal]);
+        assert_eq!(pipe(&internals, engines[0]), pipe(&internals, engines[1]));
+        assert_eq!(circuit(&internals, engines[0]), circuit(&internals, chair));
+        assert_eq!(internals.network_circuits.len(), 1);
+        assert_eq!(internals.network_pipes.len(), 1);
+
+        // Removing the chair stops its machine
+        let chair_machine = internals.blocks[&chair].machine;
+        chunks.get_mut(&chair.0).unwrap().grid[chair.1] = Block { id: 0, ori: 0 };
+        internals.blocks_changed(&properties, &chunks, body.clone(), [chair]);
+        assert!(!internals.blocks.contains_key(&chair));
+        assert!(!internals.machine_blocks.contains_key(&chair_machine));
+        assert_eq!(internals.blocks.len(), 2);
+    }
+}
@@ -1,40 +1,54 @@
+use std::path::PathBuf;
+
 use cgmath::Vector3;
 use rustc_hash::FxHashMap;
 
-use crate::{game::planet::{Atmosphere, Terrain}, graphics::{Block, CHUNK_SIZE, Graphics}};
+use crate::{game::planet::{Atmosphere, ChunkEdits, Terrain}, graphics::{Block, CHUNK_SIZE}};
 
-use super::Chunk;
+use super::{Chunk, save::ShipSave};
 
 pub struct ShipLoader {
-    pub pos: Vector3<f64>,
-    pub vel: Vector3<f64>,
+    save: ShipSave,
+    /// Where the ship is written when it is unloaded, if anywhere
+    path: Option<PathBuf>,
 }
 impl ShipLoader {
-    pub(super) fn load_all(&self, graphics: &crate::graphics::Graphics) -> FxHashMap<(i32, i32, i32), Chunk> {
-        let chunk_coord = (0, 0, 0);
-        let pos = Vector3::new(
-            chunk_coord.0 as f64 * CHUNK_SIZE as f64,
-            chunk_coord.1 as f64 * CHUNK_SIZE as f64,
-            chunk_coord.2 as f64 * CHUNK_SIZE as f64,
-        );
-        let mut chunk = Chunk::empty(graphics, pos);
-
-        chunk.grid[(7,7,7)] = Block{id: 7, ori: 0};// Metal
-        chunk.grid[(6,8,7)] = Block{id: 4, ori: 1};// Tank
-        chunk.grid[(7,8,7)] = Block{id: 4, ori: 1};// Tank
-        chunk.grid[(8,8,7)] = Block{id: 5, ori: 3};// Engine
-        chunk.grid[(6,6,7)] = Block{id: 4, ori: 1};// Tank
-        chunk.grid[(7,6,7)] = Block{id: 4, ori: 1};// Tank
-        chunk.grid[(8,6,7)] = Block{id: 5, ori: 3};// Engine
-        chunk.grid[(7,7,8)] = Block{id: 6, ori: 0};// Chair
+    pub fn new(save: ShipSave) -> Self {
+        Self { save, path: None }
+    }
+
+    /// Load a ship from a file, which is rewritten with any changes whenever the ship is unloaded
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        Ok(Self { save: ShipSave::read(&path)?, path: Some(path) })
+    }
+
+    pub fn save(&self) -> &ShipSave {
+        &self.save
+    }
 
+    pub(super) fn load_all(&self) -> FxHashMap<(i32, i32, i32), Chunk> {
         let mut out = FxHashMap::default();
-        out.insert(chunk_coord, chunk);
+        for (chunk_coord, data) in &self.save.chunks {
+            let pos = Vector3::new(
+                chunk_coord.0 as f64 * CHUNK_SIZE as f64,
+                chunk_coord.1 as f64 * CHUNK_SIZE as f64,
+                chunk_coord.2 as f64 * CHUNK_SIZE as f64,
+            );
+            let mut chunk = Chunk::empty(pos);
+            chunk.grid.set_data(**data);
+            out.insert(*chunk_coord, chunk);
+        }
         out
     }
     
-    pub(super) fn unload_all(&self) {
-        // TODO implement unloading ships so that they can be reloaded with changes saved
+    /// Keep the ship's blocks, motion and machines so that it can be reloaded with its changes, writing them to its file if it has one
+    pub(super) fn unload_all(&mut self, save: ShipSave) -> Result<(), String> {
+        self.save = save;
+        match &self.path {
+            Some(path) => self.save.write(path),
+            None => Ok(()),
+        }
     }
 }
 
@@ -42,17 +56,47 @@ pub struct PlanetLoader {
     terrain: Terrain,
     atmosphere: Atmosphere,
     halfwidth: f32,
+    /// Blocks changed since the terrain was generated
+    edits: ChunkEdits,
 }
 impl PlanetLoader {
-    pub fn new(halfwidth: u32, terrain: &Terrain, atmosphere: &Atmosphere) -> Self {
+    pub fn new(halfwidth: u32, terrain: &Terrain, atmosphere: &Atmosphere, edits: ChunkEdits) -> Self {
         Self {
             terrain: terrain.clone(),
             atmosphere: atmosphere.clone(),
             halfwidth: halfwidth as f32,
+            edits,
         }
     }
 
-    pub(super) fn load_chunk(&self, graphics: &Graphics, chunk_coord: (i32, i32, i32)) -> Option<Chunk> {
+    pub fn edits(&self) -> &ChunkEdits {
+        &self.edits
+    }
+
+    /// Remember a changed block, so that it survives the chunk being unloaded. A block changed back to what the
+    /// terrain has there is forgotten instead. Finding that out generates the chunk again, which costs as much as
+    /// loading it.
+    pub(super) fn edit(&mut self, chunk_coord: (i32, i32, i32), block: (u32, u32, u32), value: Block) {
+        let generated = self.generate(chunk_coord).unwrap_or_else(|| Chunk::empty(chunk_pos(chunk_coord))).grid[block];
+        match value == generated {
+            true => self.edits.remove(chunk_coord, block),
+            false => self.edits.set(chunk_coord, block, value),
+        }
+    }
+
+    pub(super) fn load_chunk(&self, chunk_coord: (i32, i32, i32)) -> Option<Chunk> {
+        let mut chunk = match self.generate(chunk_coord) {
+            Some(chunk) => chunk,
+            // Chunks above the terrain only exist if something was built in them
+            None if self.edits.contains(chunk_coord) => Chunk::empty(chunk_pos(chunk_coord)),
+            None => return None,
+        };
+        self.apply_edits(chunk_coord, &mut chunk);
+        Some(chunk)
+    }
+
+    /// The terrain of a chunk, without the edits. Returns None for chunks above the terrain.
+    fn generate(&self, chunk_coord: (i32, i32, i32)) -> Option<Chunk> {
         // OPTIMIZE clean up given by wind analysis
         let chunk_coord_f = Vector3::new(chunk_coord.0 as f32, chunk_coord.1 as f32, chunk_coord.2 as f32);
         let mut intersecting_faces = Vec::new();
@@ -74,11 +118,11 @@ impl PlanetLoader {
                 intersecting_faces.push((face_index, chunk_alt))
             }
         }
+        let pos = chunk_coord_f * CHUNK_SIZE as f32;
         if is_outside {
             return None;
         }
-        let pos = chunk_coord_f * CHUNK_SIZE as f32;
-        let mut chunk = Chunk::empty(graphics, pos.cast().unwrap());
+        let mut chunk = Chunk::empty(pos.cast().unwrap());
         if intersecting_faces.is_empty() {
             chunk.grid.demo();
         }
@@ -153,8 +197,75 @@ impl PlanetLoader {
 
         Some(chunk)
     }
-    
-    pub(super) fn unload_chunk(&self, chunk_coord: (i32, i32, i32), chunk: &Chunk) {
-        // TODO implement unloading chunk with changes saved
+
+    fn apply_edits(&self, chunk_coord: (i32, i32, i32), chunk: &mut Chunk) {
+        for (pos, block) in self.edits.blocks(chunk_coord) {
+            chunk.grid[pos] = block;
+        }
+    }
+}
+
+/// Position of a chunk in body coordinates
+fn chunk_pos(chunk_coord: (i32, i32, i32)) -> Vector3<f64> {
+    Vector3::new(chunk_coord.0 as f64, chunk_coord.1 as f64, chunk_coord.2 as f64) * CHUNK_SIZE as f64
+}
+#[cfg(test)]
+mod tests {
+    use crate::game::{object::ObjectLoader, planet::{Planet, PlanetInit}};
+
+    use super::*;
+
+    fn loader() -> PlanetLoader {
+        let planet = Planet::new(PlanetInit::default());
+        let ObjectLoader::MultiShot(loader) = planet.loader(ChunkEdits::new()) else { unreachable!() };
+        loader
+    }
+
+    /// A chunk of terrain, and the chunk above it which has none
+    fn surface(loader: &PlanetLoader) -> ((i32, i32, i32), (i32, i32, i32)) {
+        let x = (0..20).find(|x| loader.load_chunk((*x, 0, 0)).is_none()).unwrap();
+        assert!(x > 0);
+        ((x - 1, 0, 0), (x, 0, 0))
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn edits_survive_reloading() {
+        let mut loader = loader();
+        let (ground, _) = surface(&loader);
+        let generated = loader.load_chunk(ground).unwrap();
+        let block = Block { id: generated.grid[(1, 2, 3)].id.wrapping_add(1), ori: 5 };
+        loader.edit(ground, (1, 2, 3), block);
+
+        // The chunk is unloaded by dropping it, and generated again with the edit laid over it
+        let reloaded = loader.load_chunk(ground).unwrap();
+        assert_eq!(reloaded.grid[(1, 2, 3)], block);
+        for pos in [(0, 0, 0), (1, 2, 4), (15, 15, 15)] {
+            assert_eq!(reloaded.grid[pos], generated.grid[pos]);
+        }
+        assert_eq!(loader.edits().len(), 1);
+
+        // Changing the block back leaves nothing to remember
+        loader.edit(ground, (1, 2, 3), generated.grid[(1, 2, 3)]);
+        assert!(loader.edits().is_empty());
+        assert_eq!(loader.load_chunk(ground).unwrap().grid[(1, 2, 3)], generated.grid[(1, 2, 3)]);
+    }
+
+    #[test]
+    fn building_above_the_terrain_makes_a_chunk() {
+        let mut loader = loader();
+        let (_, sky) = surface(&loader);
+        let block = Block { id: 1, ori: 0 };
+        loader.edit(sky, (4, 0, 15), block);
+        let chunk = loader.load_chunk(sky).expect("The edited chunk is loaded");
+        assert_eq!(chunk.grid[(4, 0, 15)], block);
+        assert_eq!(chunk.grid[(4, 0, 14)], Chunk::empty(Vector3::new(0., 0., 0.)).grid[(4, 0, 14)]);
+        assert_eq!(chunk.grid.global_pos, Vector3::new(sky.0 as f64, 0., 0.) * CHUNK_SIZE as f64);
+        // Chunks next to it are still not generated
+        assert!(loader.load_chunk((sky.0 + 1, 0, 0)).is_none());
+
+        // Removing the block leaves an empty chunk, which is no longer loaded
+        loader.edit(sky, (4, 0, 15), chunk.grid[(4, 0, 14)]);
+        assert!(loader.edits().is_empty());
+        assert!(loader.load_chunk(sky).is_none());
+    }
+}
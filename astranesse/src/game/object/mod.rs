@@ -2,18 +2,25 @@
 pub mod chunk;
 pub mod loader;
 pub mod computer;
+pub mod save;
+mod circuits;
 mod internals;
+mod network;
+mod pipes;
 
-use cgmath::{InnerSpace, Matrix3, Rotation, Vector3, Zero};
+use cgmath::{InnerSpace, Matrix3, Rotation, Vector3};
 use loader::{PlanetLoader, ShipLoader};
 use rustc_hash::FxHashMap;
 use crate::game::object::computer::BlockProperties;
 pub use crate::game::object::internals::{Internals, Interrupt};
+use crate::game::planet::ChunkEdits;
 use crate::graphics::{Block, CHUNK_SIZE, Graphics, GridTexture, ModelUniform, Renderer, StorageBuffer};
 use crate::physics::{Collider, MoI, Physics, RigidBody, RigidBodyInit};
 use crate::util::my_fmod;
 use chunk::Chunk;
+use save::{BodyState, ShipSave};
 pub use internals::BlockKey;
+use network::chunk_blocks;
 
 
 const RENDER_DISTANCE: i32 = 12; // Units of chunks
@@ -30,11 +37,7 @@ impl ObjectLoader {
 
     fn get_initial_data(&self) -> RigidBodyInit {
         match self {
-            ObjectLoader::OneShot(ship_loader) => RigidBodyInit {
-                pos: ship_loader.pos,
-                vel: ship_loader.vel,
-                ..Default::default()
-            },
+            ObjectLoader::OneShot(ship_loader) => ship_loader.save().body.init(),
             ObjectLoader::MultiShot(planet_loader) => {
                 let mass = 1000.;
                 RigidBodyInit {
@@ -55,15 +58,14 @@ pub struct Object {
     pub body: RigidBody,
     
     loader: ObjectLoader,
-    storage_buffer: StorageBuffer,
+    /// Made when the object is first drawn
+    storage_buffer: Option<StorageBuffer>,
 }
 impl Object {
-    pub fn new(graphics: &Graphics, physics: &mut Physics, loader: ObjectLoader) -> Self {
+    pub fn new(physics: &mut Physics, loader: ObjectLoader) -> Self {
         let mut initial_data = loader.get_initial_data();
         initial_data.collider = Some(Collider::empty_object());
         let body = RigidBody::new(&mut physics.rb_vendor, initial_data);
-        let buffer_size = loader.estimate_max_rendered_chunks()*std::mem::size_of::<ModelUniform>();
-        let storage_buffer = StorageBuffer::new(graphics, buffer_size as usize);
         let internals = Internals::new();
         Self {
             chunks: FxHashMap::default(),
@@ -71,29 +73,21 @@ impl Object {
             internals,
             body,
             last_load: None,
-            storage_buffer,
+            storage_buffer: None,
         }
     }
 
-    /// Update the model buffers and rigid body. If only some chunks were changed, pass a vector of chunk positions. Otherwise, pass an empty vector. This will update the passed chunks, and neighbors if the neighbors now become visible.
-    pub fn update_chunk_info(&mut self, graphics: &Graphics, properties: &BlockProperties, coord_vec: Vec<(i32, i32, i32)>) {
-        let mut mass_m0 = 0.;
-        let mut mass_m1 = Vector3::zero();
-        let mut mass_m2 = Matrix3::zero();
+    /// Update the model buffers, collider and chunk mass moments, but not the internals or the body mass. If only some chunks were changed, pass a vector of chunk positions. Otherwise, pass an empty vector. This will update the passed chunks, and neighbors if the neighbors now become visible.
+    pub fn update_chunk_info(&mut self, properties: &BlockProperties, coord_vec: Vec<(i32, i32, i32)>) {
         let collider = self.body.get_object_collider_mut();
         if coord_vec.is_empty() {
             for (coord, chunk) in &mut self.chunks {
-                chunk.update_metadata(collider.chunks.get_mut(coord).unwrap());
-                mass_m0 += chunk.mass_m0;
-                mass_m1 += chunk.mass_m1;
-                mass_m2 += chunk.mass_m2;
+                chunk.update_metadata(properties, collider.chunks.get_mut(coord).unwrap());
             }
         } else {
+            // The other chunks keep their moments
             for coord in coord_vec.iter() {
-                self.chunks.get_mut(coord).unwrap().update_metadata(collider.chunks.get_mut(coord).unwrap());
-                mass_m0 += self.chunks[coord].mass_m0;
-                mass_m1 += self.chunks[coord].mass_m1;
-                mass_m2 += self.chunks[coord].mass_m2;
+                self.chunks.get_mut(coord).unwrap().update_metadata(properties, collider.chunks.get_mut(coord).unwrap());
             }
         }
 
@@ -112,12 +106,32 @@ impl Object {
             if let Some(new_exposed) = exposed_map.get(coord) {
                 if *new_exposed != chunk.exposed {
                     chunk.exposed = *new_exposed;
-                    chunk.update_model(graphics);
+                    chunk.model_stale = true;
                 }
             }
         }
 
-        // Set the rigid body data
+    }
+
+    /// Set the rigid body data from the moments of the blocks and the fuel in the tanks. An object with no blocks left
+    /// keeps its last center of mass.
+    fn update_mass(&mut self) {
+        let (mut mass_m0, mut mass_m1, mut mass_m2) = self.internals.fuel_mass_moments();
+        for chunk in self.chunks.values() {
+            mass_m0 += chunk.mass_m0;
+            mass_m1 += chunk.mass_m1;
+            mass_m2 += chunk.mass_m2;
+        }
+        if mass_m0 > 0. {
+            self.set_mass_moments(mass_m0, mass_m1, mass_m2);
+        }
+    }
+
+    fn set_mass_moments(&mut self, mass_m0: f64, mut mass_m1: Vector3<f64>, mut mass_m2: Matrix3<f64>) {
+        // Planets only load the chunks near the player, so keep the mass they were made with
+        if let ObjectLoader::OneShot(_) = self.loader {
+            self.body.mass = mass_m0;
+        }
         mass_m1 /= mass_m0;
         mass_m2 /= mass_m0;
         let new_com = mass_m1.cast().unwrap();
@@ -130,17 +144,69 @@ impl Object {
             mass_m1.z*mass_m1.x, mass_m1.z*mass_m1.y, mass_m1.z*mass_m1.z - 0.1666666666,
         )) * mass_m0);
         self.body.pos += delta_com_global;
+    }
+
+    /// Load the chunks near the character and unload those far from it
+    pub fn update_chunks(&mut self, properties: &BlockProperties, character_pos: Vector3<f64>) {
+        self.load_chunks(properties, character_pos);
+    }
 
-        // Update the internals
-        self.internals.update_info(properties, &self.chunks, self.body.clone());// TODO implement partial updates
+    /// Rebuild the models of the chunks whose blocks or visibility changed
+    pub fn update_models(&mut self, graphics: &Graphics, properties: &BlockProperties) {
+        for chunk in self.chunks.values_mut() {
+            if chunk.model_stale {
+                chunk.update_model(graphics, properties);
+            }
+        }
     }
 
-    pub fn update_graphics(&mut self, graphics: &Graphics, properties: &BlockProperties, character_pos: Vector3<f64>) {
-        self.load_chunks(graphics, properties, character_pos);
+    /// Number of chunks loaded
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
     }
 
+    /// Run the command blocks and move fuel, updating the mass as fuel is burnt
     pub fn update_internals(&mut self, delta_t: f64) {
         self.internals.update(delta_t);
+        if self.internals.take_tanks_changed() {
+            self.update_mass();
+        }
+    }
+
+    /// Start the command blocks again with the scripts their types have now
+    pub fn restart_scripts(&mut self, properties: &BlockProperties) {
+        self.internals.restart_scripts(properties);
+    }
+
+    /// The latest output of the object's command blocks
+    pub fn log_text(&self) -> String {
+        self.internals.log_text()
+    }
+
+    /// The ship as it is now, or None for planets
+    pub fn ship_save(&self) -> Option<ShipSave> {
+        let ObjectLoader::OneShot(l) = &self.loader else { return None; };
+        if self.chunks.is_empty() {
+            // Unloaded ships keep moving
+            return Some(ShipSave { body: BodyState::of(&self.body), ..l.save().clone() });
+        }
+        Some(ShipSave::capture(&self.chunks, &self.body, self.internals.machine_states(), self.internals.tank_fills()))
+    }
+
+    /// Blocks changed on a planet, or None for ships
+    pub fn planet_edits(&self) -> Option<&ChunkEdits> {
+        match &self.loader {
+            ObjectLoader::MultiShot(l) => Some(l.edits()),
+            ObjectLoader::OneShot(_) => None,
+        }
+    }
+
+    /// Move the body to a saved state
+    pub fn set_motion(&mut self, state: &BodyState) {
+        self.body.pos = state.pos + state.ori * self.body.com_pos;
+        self.body.vel = state.vel;
+        self.body.ori = state.ori;
+        self.body.ang_vel = state.ang_vel;
     }
 
     /// Interact with a block through right clicking
@@ -148,7 +214,7 @@ impl Object {
         self.internals.interrupt(block, interrupt);
     }
 
-    fn load_chunks(&mut self, graphics: &Graphics, properties: &BlockProperties, character_pos: Vector3<f64>) {
+    fn load_chunks(&mut self, properties: &BlockProperties, character_pos: Vector3<f64>) {
         if let Some(l) = self.last_load {
             if l.elapsed().as_millis() < LOAD_TIME {
                 return;
@@ -163,17 +229,24 @@ impl Object {
             pos_body.z as i32 / CHUNK_SIZE as i32
         );
 
-        match &self.loader {
+        match &mut self.loader {
             ObjectLoader::OneShot(l) => {
                 if self.chunks.is_empty() {
                     let dist = (character_pos - self.body.pos).magnitude();
                     let collider = self.body.get_object_collider_mut();
                     if dist < (RENDER_DISTANCE * CHUNK_SIZE as i32) as f64 * 1.5 {
-                        self.chunks = l.load_all(graphics);
+                        self.chunks = l.load_all();
                         for coord in self.chunks.keys() {
                             collider.chunks.insert(*coord, [0; (CHUNK_SIZE*CHUNK_SIZE) as usize]);
                         }
-                        self.update_chunk_info(graphics, properties, Vec::new());
+                        self.update_chunk_info(properties, Vec::new());
+                        self.internals.rebuild(properties, &self.chunks, self.body.clone());
+                        if let ObjectLoader::OneShot(l) = &self.loader {
+                            self.internals.restore_machine_states(&l.save().machines);
+                            self.internals.restore_tank_fills(&l.save().tanks);
+                        }
+                        self.internals.take_tanks_changed();
+                        self.update_mass();
                     }
                 } else {
                     // Check if all chunks are outside render distance
@@ -187,7 +260,10 @@ impl Object {
                     }
         
                     // Unload everything
-                    l.unload_all();
+                    let save = ShipSave::capture(&self.chunks, &self.body, self.internals.machine_states(), self.internals.tank_fills());
+                    if let Err(e) = l.unload_all(save) {
+                        println!("{}", e);
+                    }
                     self.chunks.clear();
                     let collider = self.body.get_object_collider_mut();
                     collider.chunks.clear();
@@ -200,16 +276,16 @@ impl Object {
                 for (coord, chunk) in &mut self.chunks {
                     let detail = get_detail(coord.0 - character_chunk.0, coord.1 - character_chunk.1,coord.2 - character_chunk.2,);
                     if detail == 0 {
-                        l.unload_chunk(*coord, chunk);
+                        // Edits were kept by the loader as they were made
                         delete_coords.push(*coord);
                     } else if detail != chunk.detail {
                         chunk.detail = detail;
-                        chunk.update_model(graphics); // Update the model with the new detail
+                        chunk.model_stale = true; // Update the model with the new detail
                     }
                 }
-                for coord in delete_coords.into_iter().rev() {
-                    self.chunks.remove(&coord);
-                    collider.chunks.remove(&coord);
+                for coord in delete_coords.iter().rev() {
+                    self.chunks.remove(coord);
+                    collider.chunks.remove(coord);
                 }
 
                 // Load new chunks
@@ -219,7 +295,7 @@ impl Object {
                         for dz in (-RENDER_DISTANCE)..RENDER_DISTANCE {
                             let coord = (character_chunk.0 + dx, character_chunk.1 + dy, character_chunk.2 + dz);
                             if let None = self.chunks.get(&coord) {
-                                if let Some(mut chunk) = l.load_chunk(graphics, coord) {
+                                if let Some(mut chunk) = l.load_chunk(coord) {
                                     chunk.detail = get_detail(dx, dy, dz);
                                     self.chunks.insert(coord, chunk);
                                     collider.chunks.insert(coord, [0; (CHUNK_SIZE*CHUNK_SIZE) as usize]);
@@ -230,7 +306,13 @@ impl Object {
                     }
                 }
                 if !new_coords.is_empty() {
-                    self.update_chunk_info(graphics, properties, new_coords);
+                    self.update_chunk_info(properties, new_coords.clone());
+                }
+                if !delete_coords.is_empty() || !new_coords.is_empty() {
+                    let changed = delete_coords.into_iter().chain(new_coords).flat_map(chunk_blocks);
+                    self.internals.blocks_changed(properties, &self.chunks, self.body.clone(), changed);
+                    self.internals.take_tanks_changed();
+                    self.update_mass();
                 }
                 self.last_load = Some(std::time::Instant::now());
             },
@@ -242,7 +324,7 @@ impl Object {
         for detail in 1..=4 {
             texture.bind(renderer, detail);
             for chunk in self.chunks.values() {
-                if chunk.exposed != 63 && chunk.detail == detail {
+                if chunk.is_drawn() && chunk.detail == detail {
                     chunk.draw(renderer);
                 }
             }
@@ -253,7 +335,7 @@ impl Object {
     pub fn draw_shadow(&self, renderer: &mut Renderer) {
         for detail in 1..=4 {
             for chunk in self.chunks.values() {
-                if chunk.exposed != 63 && chunk.detail == detail {
+                if chunk.is_drawn() && chunk.detail == detail {
                     chunk.draw(renderer);
                 }
             }
@@ -263,67 +345,86 @@ impl Object {
     pub fn update_buffer(&mut self, graphics: &Graphics, camera: &crate::graphics::Camera) {
         let mut buffer = Vec::with_capacity(self.chunks.len());
         for chunk in self.chunks.values_mut() {
-            if chunk.exposed != 63  {
+            if chunk.is_drawn() {
                 buffer.push(chunk.get_uniform(&self.body, camera));
             }
         }
-        self.storage_buffer.write(graphics, &buffer);
+        let storage_buffer = self.storage_buffer.get_or_insert_with(|| {
+            let buffer_size = self.loader.estimate_max_rendered_chunks()*std::mem::size_of::<ModelUniform>();
+            StorageBuffer::new(graphics, buffer_size)
+        });
+        storage_buffer.write(graphics, &buffer);
     }
     
     pub(crate) fn copy_buffers(&self, renderer: &mut Renderer) {
+        let Some(storage_buffer) = &self.storage_buffer else { return; };
         let mut i = 0;
         for chunk in self.chunks.values() {
-            if chunk.exposed != 63  {
-                chunk.copy_buffer(renderer, &self.storage_buffer, i);
+            if chunk.is_drawn() {
+                chunk.copy_buffer(renderer, storage_buffer, i);
                 i += 1;
             }
         }
     }
 
     pub fn get_block(&self, pos: Vector3<f64>) -> Block {
-        let updated_chunk = (
-            (pos.x/CHUNK_SIZE as f64).floor() as i32,
-            (pos.y/CHUNK_SIZE as f64).floor() as i32,
-            (pos.z/CHUNK_SIZE as f64).floor() as i32,
-        );
-        let updated_block = (
-            my_fmod(pos.x, CHUNK_SIZE as f64) as u32,
-            my_fmod(pos.y, CHUNK_SIZE as f64) as u32,
-            my_fmod(pos.z, CHUNK_SIZE as f64) as u32,
-        );
-
-        let chunk = self.chunks.get(&updated_chunk).unwrap();
-        chunk.grid[updated_block]
+        let (chunk, block) = cell(pos);
+        self.chunks.get(&chunk).map_or(Block { id: 0, ori: 0 }, |c| c.grid[block])
     }
     
     /// Insert a block into the cell containg position pos. Pos is in body coordinates.
-    pub(crate) fn insert_block(&mut self, graphics: &Graphics, properties: &BlockProperties, typ: u8, pos: Vector3<f64>) {
-        let updated_chunk = (
-            (pos.x/CHUNK_SIZE as f64).floor() as i32,
-            (pos.y/CHUNK_SIZE as f64).floor() as i32,
-            (pos.z/CHUNK_SIZE as f64).floor() as i32,
-        );
-        let updated_block = (
-            my_fmod(pos.x, CHUNK_SIZE as f64) as u32,
-            my_fmod(pos.y, CHUNK_SIZE as f64) as u32,
-            my_fmod(pos.z, CHUNK_SIZE as f64) as u32,
-        );
+    pub(crate) fn insert_block(&mut self, properties: &BlockProperties, block: Block, pos: Vector3<f64>) {
+        self.set_block(properties, block, pos);
+    }
+
+    /// Empty the cell containing position pos, returning the block removed. Pos is in body coordinates.
+    pub(crate) fn remove_block(&mut self, properties: &BlockProperties, pos: Vector3<f64>) -> Block {
+        let block = self.get_block(pos);
+        if !block.is_null() {
+            self.set_block(properties, Block { id: 0, ori: 0 }, pos);
+        }
+        block
+    }
 
+    /// Set the cell containing pos, then update the mass, collider, models and internals
+    fn set_block(&mut self, properties: &BlockProperties, block: Block, pos: Vector3<f64>) {
+        let (updated_chunk, updated_block) = cell(pos);
         if let None = self.chunks.get(&updated_chunk) {
             // Make a new chunk
             let pos = Vector3::new(updated_chunk.0 as f64, updated_chunk.1 as f64, updated_chunk.2 as f64)*CHUNK_SIZE as f64;
-            let new_chunk = Chunk::empty(graphics, pos);
+            let new_chunk = Chunk::empty(pos);
             self.chunks.insert(updated_chunk, new_chunk);
             self.body.get_object_collider_mut().chunks.insert(updated_chunk, [0; (CHUNK_SIZE*CHUNK_SIZE) as usize]);
         }
-        // Set the block
         let chunk = self.chunks.get_mut(&updated_chunk).unwrap();
-        chunk.grid[updated_block] = Block{id: typ, ori: 0}; // TODO work out the orientation
-        chunk.update_model(graphics);
-        self.update_chunk_info(graphics, properties, vec![updated_chunk]);
+        chunk.grid[updated_block] = block;
+        if let ObjectLoader::MultiShot(l) = &mut self.loader {
+            l.edit(updated_chunk, updated_block, block);
+        }
+        chunk.model_stale = true;
+        self.update_chunk_info(properties, vec![updated_chunk]);
+        self.internals.blocks_changed(properties, &self.chunks, self.body.clone(), [(updated_chunk, updated_block)]);
+        self.internals.take_tanks_changed();
+        self.update_mass();
     }
 }
 
+/// The chunk and the block in it of a position in body coordinates
+pub fn cell(pos: Vector3<f64>) -> BlockKey {
+    (
+        (
+            (pos.x/CHUNK_SIZE as f64).floor() as i32,
+            (pos.y/CHUNK_SIZE as f64).floor() as i32,
+            (pos.z/CHUNK_SIZE as f64).floor() as i32,
+        ),
+        (
+            my_fmod(pos.x, CHUNK_SIZE as f64) as u32,
+            my_fmod(pos.y, CHUNK_SIZE as f64) as u32,
+            my_fmod(pos.z, CHUNK_SIZE as f64) as u32,
+        ),
+    )
+}
+
 /// Return the detail of the cube given how far away it is
 fn get_detail(dx: i32, dy: i32, dz: i32) -> usize {
     let dist = dx.abs().max(dy.abs().max(dz.abs()));
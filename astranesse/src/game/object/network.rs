@@ -0,0 +1,267 @@
+// Connected groups of blocks sharing a role, such as circuits of conductors, kept up to date as blocks change
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{game::object::BlockKey, graphics::CHUNK_SIZE};
+
+/// Blocks of one role split into networks of touching blocks. Changes only visit the networks they touch, so
+/// placing a block never scans the whole object.
+pub struct Networks {
+    /// Network of each block with the role
+    labels: FxHashMap<BlockKey, u32>,
+    members: FxHashMap<u32, FxHashSet<BlockKey>>,
+    next_label: u32,
+}
+
+impl Networks {
+    pub fn new() -> Self {
+        Self { labels: FxHashMap::default(), members: FxHashMap::default(), next_label: 0 }
+    }
+
+    /// Network of a block with the role, or of one beside it for other blocks. Blocks beside several networks join
+    /// the first found.
+    pub fn attached(&self, block: BlockKey) -> Option<u32> {
+        self.labels.get(&block).or_else(|| neighbours(block).iter().find_map(|n| self.labels.get(n))).copied()
+    }
+
+    /// Blocks with the role on a network
+    pub fn members(&self, network: u32) -> impl Iterator<Item = BlockKey> + '_ {
+        self.members.get(&network).into_iter().flatten().copied()
+    }
+
+    /// Number of networks
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Add and remove blocks with the role. Each change is a block and whether it has the role now. A block may be
+    /// listed more than once, and its last change wins.
+    pub fn update(&mut self, changes: impl IntoIterator<Item = (BlockKey, bool)>) {
+        let changes: Vec<(BlockKey, bool)> = changes.into_iter().collect();
+        let last: FxHashMap<BlockKey, usize> = changes.iter().enumerate().map(|(i, (block, _))| (*block, i)).collect();
+        // Removals first, so that each network they split is only walked once
+        let mut added = Vec::new();
+        let mut split = FxHashSet::default();
+        for (i, (block, member)) in changes.into_iter().enumerate() {
+            if last[&block] != i { continue; }
+            match (self.labels.get(&block).copied(), member) {
+                (Some(label), false) => {
+                    self.labels.remove(&block);
+                    let members = self.members.get_mut(&label).unwrap();
+                    members.remove(&block);
+                    if members.is_empty() {
+                        self.members.remove(&label);
+                        split.remove(&label);
+                    } else {
+                        split.insert(label);
+                    }
+                },
+                (None, true) => added.push(block),
+                _ => (),
+            }
+        }
+        for label in split {
+            self.split(label);
+        }
+        for block in added {
+            self.add(block);
+        }
+    }
+
+    /// Join a block to the networks beside it, merging them if there are several
+    fn add(&mut self, block: BlockKey) {
+        if self.labels.contains_key(&block) { return; }
+        let mut touching: Vec<u32> = neighbours(block).iter().filter_map(|n| self.labels.get(n).copied()).collect();
+        touching.sort_unstable();
+        touching.dedup();
+        // The largest network keeps its label, so the fewest blocks are relabelled
+        let label = match touching.iter().max_by_key(|l| self.members[l].len()) {
+            Some(label) => *label,
+            None => {
+                self.next_label += 1;
+                self.members.insert(self.next_label, FxHashSet::default());
+                self.next_label
+            },
+        };
+        for other in touching {
+            if other == label { continue; }
+            let moved = self.members.remove(&other).unwrap();
+            for b in &moved {
+                self.labels.insert(*b, label);
+            }
+            self.members.get_mut(&label).unwrap().extend(moved);
+        }
+        self.labels.insert(block, label);
+        self.members.get_mut(&label).unwrap().insert(block);
+    }
+
+    /// Break a network which lost blocks into the pieces still touching. The largest piece keeps the label.
+    /// This walks every block left on the network, so a removal costs as much as the network is large, however
+    /// few blocks it cut off. Removals from one network in the same update share a walk.
+    fn split(&mut self, label: u32) {
+        let mut unvisited = self.members.remove(&label).unwrap();
+        let mut pieces = Vec::new();
+        while let Some(start) = unvisited.iter().next().copied() {
+            unvisited.remove(&start);
+            let mut piece = FxHashSet::default();
+            let mut queue = vec![start];
+            while let Some(block) = queue.pop() {
+                piece.insert(block);
+                for n in neighbours(block) {
+                    if unvisited.remove(&n) {
+                        queue.push(n);
+                    }
+                }
+            }
+            pieces.push(piece);
+        }
+        let Some(largest) = (0..pieces.len()).max_by_key(|i| pieces[*i].len()) else { return; };
+        self.members.insert(label, pieces.swap_remove(largest));
+        for piece in pieces {
+            self.next_label += 1;
+            for b in &piece {
+                self.labels.insert(*b, self.next_label);
+            }
+            self.members.insert(self.next_label, piece);
+        }
+    }
+}
+
+/// The six blocks sharing a face with a block, crossing into neighbouring chunks at the edges
+pub fn neighbours((chunk, (i, j, k)): BlockKey) -> [BlockKey; 6] {
+    let step = |c: i32, b: u32, d: i32| {
+        let b = b as i32 + d;
+        if b < 0 { (c - 1, CHUNK_SIZE - 1) }
+        else if b >= CHUNK_SIZE as i32 { (c + 1, 0) }
+        else { (c, b as u32) }
+    };
+    let x = |d| { let (c, b) = step(chunk.0, i, d); ((c, chunk.1, chunk.2), (b, j, k)) };
+    let y = |d| { let (c, b) = step(chunk.1, j, d); ((chunk.0, c, chunk.2), (i, b, k)) };
+    let z = |d| { let (c, b) = step(chunk.2, k, d); ((chunk.0, chunk.1, c), (i, j, b)) };
+    [x(-1), x(1), y(-1), y(1), z(-1), z(1)]
+}
+
+/// Every block of a chunk
+pub fn chunk_blocks(coord: (i32, i32, i32)) -> impl Iterator<Item = BlockKey> {
+    (0..CHUNK_SIZE).flat_map(move |i| (0..CHUNK_SIZE).flat_map(move |j| (0..CHUNK_SIZE).map(move |k| (coord, (i, j, k)))))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use cgmath::Vector3;
+
+    use crate::{game::object::{Chunk, cell}, graphics::Block};
+
+    use super::*;
+
+    type Chunks = FxHashMap<(i32, i32, i32), Chunk>;
+
+    /// Set a block of a chunk map, making its chunk if needed
+    fn set(chunks: &mut Chunks, pos: (i32, i32, i32), id: u8) -> BlockKey {
+        let key = cell(Vector3::new(pos.0 as f64, pos.1 as f64, pos.2 as f64) + Vector3::new(0.5, 0.5, 0.5));
+        chunks.entry(key.0).or_insert_with(|| Chunk::empty(Vector3::new(0., 0., 0.))).grid[key.1] = Block { id, ori: 0 };
+        key
+    }
+
+    fn id(chunks: &Chunks, key: BlockKey) -> u8 {
+        chunks.get(&key.0).map_or(0, |c| c.grid[key.1].id)
+    }
+
+    /// The networks as sets of blocks, to compare without labels
+    fn partition(networks: &Networks) -> BTreeSet<BTreeSet<BlockKey>> {
+        networks.members.values().map(|m| m.iter().copied().collect()).collect()
+    }
+
+    /// Networks found by flood filling every block of a chunk map, as a check on the incremental ones
+    fn scan(chunks: &Chunks) -> BTreeSet<BTreeSet<BlockKey>> {
+        let mut unvisited: BTreeSet<BlockKey> = chunks.keys().flat_map(|c| chunk_blocks(*c)).filter(|k| id(chunks, *k) == 1).collect();
+        let mut out = BTreeSet::new();
+        while let Some(start) = unvisited.pop_first() {
+            let mut piece = BTreeSet::new();
+            let mut queue = vec![start];
+            while let Some(block) = queue.pop() {
+                piece.insert(block);
+                queue.extend(neighbours(block).into_iter().filter(|n| unvisited.remove(n)));
+            }
+            out.insert(piece);
+        }
+        out
+    }
+
+    #[test]
+    fn neighbours_cross_chunks() {
+        let n = neighbours(((0, 0, 0), (0, 5, CHUNK_SIZE - 1)));
+        assert!(n.contains(&((-1, 0, 0), (CHUNK_SIZE - 1, 5, CHUNK_SIZE - 1))));
+        assert!(n.contains(&((0, 0, 1), (0, 5, 0))));
+        assert!(n.contains(&((0, 0, 0), (0, 4, CHUNK_SIZE - 1))));
+    }
+
+    #[test]
+    fn lines_join_and_split() {
+        let mut chunks = Chunks::default();
+        let mut networks = Networks::new();
+        // A line of conductors across a chunk edge
+        let line: Vec<BlockKey> = (-3..4).map(|x| set(&mut chunks, (x, 2, 2), 1)).collect();
+        networks.update(line.iter().map(|k| (*k, true)));
+        assert_eq!(networks.len(), 1);
+        // A block beside the line joins it without the role
+        let beside = set(&mut chunks, (0, 3, 2), 2);
+        assert_eq!(networks.attached(beside), networks.attached(line[0]));
+        assert_eq!(networks.attached(set(&mut chunks, (0, 5, 2), 2)), None);
+
+        // Cutting the middle leaves two networks, and the larger keeps its label
+        let label = networks.attached(line[5]);
+        networks.update([(line[2], false)]);
+        assert_eq!(networks.len(), 2);
+        assert_eq!(networks.attached(line[5]), label);
+        assert_ne!(networks.attached(line[0]), label);
+        assert_eq!(networks.attached(line[1]), networks.attached(line[0]));
+
+        // Filling the gap merges them again
+        networks.update([(line[2], true)]);
+        assert_eq!(networks.len(), 1);
+        assert_eq!(networks.attached(line[0]), label);
+
+        // Only the last change to a block in an update counts
+        let extra = set(&mut chunks, (0, 2, 3), 1);
+        networks.update([(extra, true), (extra, false)]);
+        assert!(!networks.members(label.unwrap()).any(|b| b == extra));
+        assert_eq!(networks.len(), 1);
+        networks.update([(line[2], false), (line[2], true)]);
+        assert_eq!(networks.len(), 1);
+        assert!(networks.members(label.unwrap()).any(|b| b == line[2]));
+
+        // Removing everything at once leaves nothing
+        networks.update(line.iter().map(|k| (*k, false)));
+        assert_eq!(networks.len(), 0);
+        assert_eq!(networks.attached(beside), None);
+    }
+
+    #[test]
+    fn edits_match_a_full_scan() {
+        let mut chunks = Chunks::default();
+        let mut networks = Networks::new();
+        // A fixed pseudo-random walk of edits in a box spanning eight chunks
+        let mut seed = 12345u64;
+        let mut next = |n: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % n
+        };
+        for step in 0..3000 {
+            let pos = (next(8) as i32 - 4, next(8) as i32 - 4, next(4) as i32 - 2);
+            let key = set(&mut chunks, pos, next(3) as u8);
+            networks.update([(key, id(&chunks, key) == 1)]);
+            if step % 500 == 0 {
+                assert_eq!(partition(&networks), scan(&chunks));
+            }
+        }
+        assert_eq!(partition(&networks), scan(&chunks));
+
+        // Clearing a whole chunk at once, as when a planet chunk is unloaded
+        let coord = (-1, -1, -1);
+        chunks.remove(&coord);
+        networks.update(chunk_blocks(coord).map(|k| (k, false)));
+        assert_eq!(partition(&networks), scan(&chunks));
+    }
+}
@@ -0,0 +1,220 @@
+// Fuel held in tanks, flowing between the tanks of each pipe network and drawn by the command blocks piped to them
+use std::collections::BTreeMap;
+
+use cgmath::{Matrix3, Vector3, Zero};
+use rustc_hash::FxHashMap;
+
+use crate::{game::object::{BlockKey, network::Networks}, graphics::CHUNK_SIZE};
+
+/// Mass of one unit of fuel
+pub const FUEL_DENSITY: f64 = 0.01;
+/// Fuel a tank can take in or give out each second, to even out the tanks of a network
+const FLOW_RATE: f64 = 20.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tank {
+    pub capacity: f64,
+    pub fill: f64,
+}
+
+/// Every tank of an object, by block
+pub struct Tanks {
+    tanks: FxHashMap<BlockKey, Tank>,
+    /// Whether any fill changed since the mass was last taken
+    changed: bool,
+}
+
+impl Tanks {
+    pub fn new() -> Self {
+        Self { tanks: FxHashMap::default(), changed: false }
+    }
+
+    /// Add or remove the tank of a block after it changes. New tanks start full, since nothing fills them yet.
+    pub fn set(&mut self, block: BlockKey, capacity: f64) {
+        if capacity <= 0. {
+            self.changed |= self.tanks.remove(&block).is_some_and(|t| t.fill > 0.);
+            return;
+        }
+        match self.tanks.get_mut(&block) {
+            Some(tank) if tank.capacity == capacity => return,
+            Some(tank) => *tank = Tank { capacity, fill: tank.fill.min(capacity) },
+            None => { self.tanks.insert(block, Tank { capacity, fill: capacity }); },
+        }
+        self.changed = true;
+    }
+
+    pub fn get(&self, block: BlockKey) -> Option<&Tank> {
+        self.tanks.get(&block)
+    }
+
+    /// Fuel in some tanks
+    pub fn fuel(&self, tanks: &[BlockKey]) -> f64 {
+        tanks.iter().filter_map(|t| self.tanks.get(t)).map(|t| t.fill).sum()
+    }
+
+    /// Fuel some tanks can hold
+    pub fn capacity(&self, tanks: &[BlockKey]) -> f64 {
+        tanks.iter().filter_map(|t| self.tanks.get(t)).map(|t| t.capacity).sum()
+    }
+
+    /// Take up to `amount` fuel from some tanks, in proportion to their fill. Returns the amount taken.
+    pub fn draw(&mut self, tanks: &[BlockKey], amount: f64) -> f64 {
+        let available = self.fuel(tanks);
+        let taken = amount.clamp(0., available);
+        if taken > 0. {
+            for key in tanks {
+                if let Some(tank) = self.tanks.get_mut(key) {
+                    tank.fill = (tank.fill - tank.fill * taken / available).max(0.);
+                }
+            }
+            self.changed = true;
+        }
+        taken
+    }
+
+    /// Move fuel between the tanks of each pipe network towards the same fraction full, at no more than the flow
+    /// rate of each tank
+    pub fn flow(&mut self, pipes: &Networks, delta_t: f64) {
+        let mut networks: BTreeMap<u32, Vec<BlockKey>> = BTreeMap::new();
+        for key in self.tanks.keys() {
+            if let Some(network) = pipes.attached(*key) {
+                networks.entry(network).or_default().push(*key);
+            }
+        }
+        let most = FLOW_RATE * delta_t;
+        for mut keys in networks.into_values() {
+            if keys.len() < 2 { continue; }
+            keys.sort_unstable();
+            let fraction = self.fuel(&keys) / self.capacity(&keys);
+            let deltas: Vec<f64> = keys.iter().map(|k| {
+                let tank = self.tanks[k];
+                (tank.capacity * fraction - tank.fill).clamp(-most, most)
+            }).collect();
+            // Only as much can flow in as flows out
+            let inflow: f64 = deltas.iter().filter(|d| **d > 0.).sum();
+            let outflow: f64 = -deltas.iter().filter(|d| **d < 0.).sum::<f64>();
+            let moved = inflow.min(outflow);
+            if moved <= 0. { continue; }
+            for (key, delta) in keys.iter().zip(deltas) {
+                let scale = if delta > 0. { moved / inflow } else { moved / outflow };
+                let tank = self.tanks.get_mut(key).unwrap();
+                tank.fill = (tank.fill + delta * scale).clamp(0., tank.capacity);
+            }
+            self.changed = true;
+        }
+    }
+
+    /// Whether any fill changed since this was last called
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// Zeroth, first and second moments of the mass of the fuel, in body coordinates
+    pub fn mass_moments(&self) -> (f64, Vector3<f64>, Matrix3<f64>) {
+        let mut m0 = 0.;
+        let mut m1 = Vector3::zero();
+        let mut m2 = Matrix3::zero();
+        for (key, tank) in &self.tanks {
+            let mass = tank.fill * FUEL_DENSITY;
+            let pos = center(*key);
+            m0 += mass;
+            m1 += pos * mass;
+            m2 += Matrix3::from_cols(pos * pos.x, pos * pos.y, pos * pos.z) * mass;
+        }
+        (m0, m1, m2)
+    }
+
+    /// Fill of each tank, to save
+    pub fn fills(&self) -> BTreeMap<BlockKey, f64> {
+        self.tanks.iter().map(|(k, t)| (*k, t.fill)).collect()
+    }
+
+    /// Set the fill of saved tanks. Tanks which are gone are skipped.
+    pub fn restore_fills(&mut self, fills: &BTreeMap<BlockKey, f64>) {
+        for (key, fill) in fills {
+            if let Some(tank) = self.tanks.get_mut(key) {
+                tank.fill = fill.clamp(0., tank.capacity);
+                self.changed = true;
+            }
+        }
+    }
+}
+
+/// Center of a block in body coordinates
+pub fn center((chunk, block): BlockKey) -> Vector3<f64> {
+    Vector3::new(
+        (chunk.0 * CHUNK_SIZE as i32) as f64 + block.0 as f64 + 0.5,
+        (chunk.1 * CHUNK_SIZE as i32) as f64 + block.1 as f64 + 0.5,
+        (chunk.2 * CHUNK_SIZE as i32) as f64 + block.2 as f64 + 0.5,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(x: u32) -> BlockKey {
+        ((0, 0, 0), (x, 0, 0))
+    }
+
+    /// A row of tanks all on one network
+    fn row(fills: &[f64]) -> (Tanks, Networks) {
+        let mut tanks = Tanks::new();
+        let mut pipes = Networks::new();
+        for (i, fill) in fills.iter().enumerate() {
+            tanks.set(key(i as u32), 10.);
+            tanks.restore_fills(&BTreeMap::from([(key(i as u32), *fill)]));
+            pipes.update([(key(i as u32), true)]);
+        }
+        (tanks, pipes)
+    }
+
+    #[test]
+    fn flow_evens_out_tanks() {
+        let (mut tanks, pipes) = row(&[10., 0., 2.]);
+        let keys = [key(0), key(1), key(2)];
+        tanks.flow(&pipes, 0.1);
+        // Fuel is kept, and no tank moves more than the flow rate allows
+        assert!((tanks.fuel(&keys) - 12.).abs() < 1e-9);
+        assert_eq!(tanks.get(key(0)).unwrap().fill, 8.);
+        for _ in 0..10 {
+            tanks.flow(&pipes, 0.1);
+        }
+        for k in keys {
+            assert!((tanks.get(k).unwrap().fill - 4.).abs() < 1e-9);
+        }
+
+        // Tanks on other networks are left alone
+        let (mut tanks, _) = row(&[10., 0.]);
+        tanks.flow(&Networks::new(), 1.);
+        assert_eq!(tanks.get(key(1)).unwrap().fill, 0.);
+    }
+
+    #[test]
+    fn drawing_empties_tanks_evenly() {
+        let (mut tanks, _) = row(&[6., 2.]);
+        let keys = [key(0), key(1)];
+        assert_eq!(tanks.draw(&keys, 4.), 4.);
+        assert_eq!(tanks.get(key(0)).unwrap().fill, 3.);
+        assert_eq!(tanks.get(key(1)).unwrap().fill, 1.);
+        assert_eq!(tanks.draw(&keys, 100.), 4.);
+        assert_eq!(tanks.draw(&keys, 1.), 0.);
+        assert_eq!(tanks.draw(&keys, -1.), 0.);
+        assert_eq!(tanks.capacity(&keys), 20.);
+    }
+
+    #[test]
+    fn fuel_has_mass() {
+        let (mut tanks, _) = row(&[10., 0.]);
+        assert!(tanks.take_changed());
+        assert!(!tanks.take_changed());
+        let (m0, m1, _) = tanks.mass_moments();
+        assert!((m0 - 10. * FUEL_DENSITY).abs() < 1e-12);
+        assert!((m1 / m0 - center(key(0))).x.abs() < 1e-12);
+
+        // Removing a full tank takes its fuel with it
+        tanks.set(key(0), 0.);
+        assert!(tanks.take_changed());
+        assert_eq!(tanks.mass_moments().0, 0.);
+    }
+}
@@ -0,0 +1,281 @@
+use std::{collections::BTreeMap, path::Path};
+
+use biscuit::{MachineState, util::bytes::{Reader, write_f64, write_len}};
+use cgmath::{Quaternion, Rotation, Vector3};
+use rustc_hash::FxHashMap;
+
+use crate::{game::{object::{BlockKey, Chunk}, save::{read_block_key, read_body, read_coord, read_file, sections, write_block_key, write_body, write_coord, write_file, write_section}}, graphics::{Block, CHUNK_SIZE}, physics::{RigidBody, RigidBodyInit}};
+
+const MAGIC: &[u8; 4] = b"ASHP";
+/// Bumped when a section changes layout. New sections do not need a new version, since unknown sections are skipped.
+const VERSION: u8 = 1;
+
+const BODY: [u8; 4] = *b"BODY";
+const CHUNK: [u8; 4] = *b"CHNK";
+const MACHINE: [u8; 4] = *b"MACH";
+const TANK: [u8; 4] = *b"TANK";
+
+pub type ChunkData = [Block; (CHUNK_SIZE*CHUNK_SIZE*CHUNK_SIZE) as usize];
+
+/// Motion of a ship's body origin, which unlike its center of mass does not move when blocks change
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BodyState {
+    pub pos: Vector3<f64>,
+    pub vel: Vector3<f64>,
+    /// Rotates from the body to global
+    pub ori: Quaternion<f64>,
+    pub ang_vel: Vector3<f64>,
+}
+
+impl BodyState {
+    pub fn at_rest(pos: Vector3<f64>, vel: Vector3<f64>) -> Self {
+        let init = RigidBodyInit::default();
+        Self { pos, vel, ori: init.ori, ang_vel: init.ang_vel }
+    }
+
+    pub fn of(body: &RigidBody) -> Self {
+        Self {
+            pos: body.pos - body.ori.rotate_vector(body.com_pos),
+            vel: body.vel,
+            ori: body.ori,
+            ang_vel: body.ang_vel,
+        }
+    }
+
+    pub fn init(&self) -> RigidBodyInit {
+        RigidBodyInit {
+            pos: self.pos,
+            vel: self.vel,
+            ori: self.ori,
+            ang_vel: self.ang_vel,
+            ..Default::default()
+        }
+    }
+}
+
+/// Everything needed to rebuild a ship: its blocks, its motion, the machines of its command blocks and the fuel in
+/// its tanks
+#[derive(Clone)]
+pub struct ShipSave {
+    pub body: BodyState,
+    pub chunks: BTreeMap<(i32, i32, i32), Box<ChunkData>>,
+    pub machines: BTreeMap<BlockKey, MachineState>,
+    /// Fill of each tank. Tanks missing start full.
+    pub tanks: BTreeMap<BlockKey, f64>,
+}
+
+impl ShipSave {
+    /// The demo ship: two engines fed by four tanks, and a chair
+    pub fn demo(pos: Vector3<f64>, vel: Vector3<f64>) -> Self {
+        let mut data = Box::new([Block{id: 0, ori: 0}; (CHUNK_SIZE*CHUNK_SIZE*CHUNK_SIZE) as usize]);
+        let mut set = |(x, y, z): (u32, u32, u32), block| data[(x + y*CHUNK_SIZE + z*CHUNK_SIZE*CHUNK_SIZE) as usize] = block;
+        set((7,7,7), Block{id: 7, ori: 0});// Metal
+        set((6,8,7), Block{id: 4, ori: 1});// Tank
+        set((7,8,7), Block{id: 4, ori: 1});// Tank
+        set((8,8,7), Block{id: 5, ori: 3});// Engine
+        set((6,6,7), Block{id: 4, ori: 1});// Tank
+        set((7,6,7), Block{id: 4, ori: 1});// Tank
+        set((8,6,7), Block{id: 5, ori: 3});// Engine
+        set((7,7,8), Block{id: 6, ori: 0});// Chair
+
+        Self {
+            body: BodyState::at_rest(pos, vel),
+            chunks: BTreeMap::from([((0, 0, 0), data)]),
+            machines: BTreeMap::new(),
+            tanks: BTreeMap::new(),
+        }
+    }
+
+    /// Save a loaded ship
+    pub fn capture(chunks: &FxHashMap<(i32, i32, i32), Chunk>, body: &RigidBody, machines: BTreeMap<BlockKey, MachineState>, tanks: BTreeMap<BlockKey, f64>) -> Self {
+        Self {
+            body: BodyState::of(body),
+            chunks: chunks.iter().map(|(coord, chunk)| (*coord, Box::new(*chunk.grid.data()))).collect(),
+            machines,
+            tanks,
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        Self::from_bytes(&read_file(path)?).map_err(|e| format!("Could not read {}: {}", path.display(), e))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        write_file(path, &self.to_bytes())
+    }
+
+    /// The magic and version, then tagged sections. Chunks are run length encoded, since ships are mostly air.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(MAGIC);
+        out.push(VERSION);
+
+        let mut body = Vec::new();
+        write_body(&mut body, &self.body);
+        write_section(&mut out, &BODY, &body);
+
+        for (coord, data) in &self.chunks {
+            let mut chunk = Vec::new();
+            write_coord(&mut chunk, *coord);
+            let runs = data.chunk_by(|a, b| a.id == b.id && a.ori == b.ori).collect::<Vec<_>>();
+            write_len(&mut chunk, runs.len());
+            for run in runs {
+                chunk.extend((run.len() as u16).to_le_bytes());
+                chunk.push(run[0].id);
+                chunk.push(run[0].ori);
+            }
+            write_section(&mut out, &CHUNK, &chunk);
+        }
+
+        for ((coord, block), state) in &self.machines {
+            let mut machine = Vec::new();
+            write_block_key(&mut machine, (*coord, *block));
+            machine.extend(state.to_bytes());
+            write_section(&mut out, &MACHINE, &machine);
+        }
+
+        for ((coord, block), fill) in &self.tanks {
+            let mut tank = Vec::new();
+            write_block_key(&mut tank, (*coord, *block));
+            write_f64(&mut tank, *fill);
+            write_section(&mut out, &TANK, &tank);
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let invalid = || "ship is corrupt".to_owned();
+        let mut body = None;
+        let mut chunks = BTreeMap::new();
+        let mut machines = BTreeMap::new();
+        let mut tanks = BTreeMap::new();
+        let (_, sections) = sections(bytes, MAGIC, VERSION, "ship")?;
+        for (tag, mut section) in sections {
+            match tag {
+                BODY => body = Some(read_body(&mut section).ok_or_else(invalid)?),
+                CHUNK => {
+                    let (coord, data) = read_chunk(&mut section).ok_or_else(invalid)?;
+                    chunks.insert(coord, data);
+                },
+                MACHINE => {
+                    let block = read_block_key(&mut section).ok_or_else(invalid)?;
+                    machines.insert(block, MachineState::from_bytes(section.rest())?);
+                },
+                TANK => {
+                    let block = read_block_key(&mut section).ok_or_else(invalid)?;
+                    tanks.insert(block, section.f64().ok_or_else(invalid)?);
+                },
+                // Written by a newer version which added a section
+                _ => continue,
+            }
+            if !section.is_finished() {
+                return Err(invalid());
+            }
+        }
+        Ok(Self { body: body.ok_or_else(invalid)?, chunks, machines, tanks })
+    }
+}
+
+fn read_chunk(reader: &mut Reader) -> Option<((i32, i32, i32), Box<ChunkData>)> {
+    let coord = read_coord(reader)?;
+    let mut data = Box::new([Block{id: 0, ori: 0}; (CHUNK_SIZE*CHUNK_SIZE*CHUNK_SIZE) as usize]);
+    let mut index = 0;
+    for _ in 0..reader.length()? {
+        let count = u16::from_le_bytes(reader.take(2)?.try_into().ok()?) as usize;
+        let block = Block { id: reader.u8()?, ori: reader.u8()? };
+        data.get_mut(index..index + count)?.fill(block);
+        index += count;
+    }
+    // Every block of the chunk must be written
+    (index == data.len()).then_some((coord, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use biscuit::{Machine, compile_str, machine::InstructionData, util::Vendor};
+
+    use super::*;
+
+    fn ship() -> ShipSave {
+        let mut save = ShipSave::demo(Vector3::new(12., 7., 42.), Vector3::new(0.5, 0., -1.));
+        save.body.ori = Quaternion::new(0.5, 0.5, -0.5, 0.5);
+        save.body.ang_vel = Vector3::new(0., 0.25, 0.);
+        let mut far = Box::new([Block{id: 0, ori: 0}; (CHUNK_SIZE*CHUNK_SIZE*CHUNK_SIZE) as usize]);
+        far[4095] = Block{id: 7, ori: 23};
+        save.chunks.insert((-1, 2, -300), far);
+        save
+    }
+
+    fn assert_same(a: &ShipSave, b: &ShipSave) {
+        assert_eq!(a.body, b.body);
+        assert_eq!(a.chunks, b.chunks);
+        let states = |s: &ShipSave| s.machines.iter().map(|(k, m)| (*k, m.to_bytes())).collect::<Vec<_>>();
+        assert_eq!(states(a), states(b));
+        assert_eq!(a.tanks, b.tanks);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut save = ship();
+        // A machine part way through its loop, with a record in memory
+        let source = "struct P { a, b }\nfn main() {\n    p = P(1, 2);\n    loop {\n        p.a += p.b;\n        dbg(p.a);\n        tick();\n    }\n}\n";
+        let mut vendor = Vendor::new();
+        let mut machine = Machine::new(vendor.insert(InstructionData::from_compiled(&compile_str(source, "test").unwrap())), 1000);
+        for _ in 0..10 {
+            machine.run_for(5).unwrap();
+        }
+        save.machines.insert(((0, 0, 0), (7, 7, 8)), machine.save_state());
+        save.tanks = BTreeMap::from([(((0, 0, 0), (6, 8, 7)), 12.5), (((0, 0, 0), (7, 6, 7)), 0.)]);
+
+        let bytes = save.to_bytes();
+        let loaded = ShipSave::from_bytes(&bytes).unwrap();
+        assert_same(&save, &loaded);
+        // Writing is deterministic
+        assert_eq!(loaded.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn chunks_are_compressed() {
+        let save = ShipSave::demo(Vector3::new(0., 0., 0.), Vector3::new(0., 0., 0.));
+        assert!(save.to_bytes().len() < 200, "{}", save.to_bytes().len());
+    }
+
+    #[test]
+    fn unknown_sections_are_skipped() {
+        let save = ship();
+        let mut bytes = save.to_bytes();
+        write_section(&mut bytes, b"NEW!", &[1, 2, 3]);
+        assert_same(&save, &ShipSave::from_bytes(&bytes).unwrap());
+    }
+
+    #[test]
+    fn bad_saves_are_refused() {
+        let bytes = ship().to_bytes();
+        let mut newer = bytes.clone();
+        newer[4] = VERSION + 1;
+        assert!(ShipSave::from_bytes(&newer).err().unwrap().contains("newer version"));
+        assert!(ShipSave::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(ShipSave::from_bytes(b"PNG").is_err());
+        // A chunk with blocks missing
+        let mut short = bytes[..5].to_vec();
+        let mut body = Vec::new();
+        write_body(&mut body, &ship().body);
+        write_section(&mut short, &BODY, &body);
+        assert!(ShipSave::from_bytes(&short).is_ok());
+        let mut chunk = Vec::new();
+        write_coord(&mut chunk, (0, 0, 0));
+        write_len(&mut chunk, 1);
+        chunk.extend([1, 0, 7, 0]);
+        write_section(&mut short, &CHUNK, &chunk);
+        assert!(ShipSave::from_bytes(&short).is_err());
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let path = std::env::temp_dir().join(format!("astranesse-ship-{}", std::process::id())).join("ship.ship");
+        let save = ship();
+        save.write(&path).unwrap();
+        assert_same(&save, &ShipSave::read(&path).unwrap());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,163 @@
+use cgmath::{Matrix4, Vector3};
+
+use crate::{game::object::computer::BlockProperties, graphics::{Block, Camera, CubeGrid, Graphics, GridModel, GridTexture, ModelUniform, Renderer}, physics::RigidBody};
+
+/// Size of the ghost block, relative to a block
+const GHOST_SCALE: f64 = 0.6;
+/// Slots shown at once, which the number keys pick from
+pub const HOTBAR_SLOTS: usize = 9;
+
+/// The block type the player builds with and how it is turned
+pub struct Hotbar {
+    /// Every registered block type, in order of id
+    ids: Vec<u8>,
+    selected: usize,
+    /// Which way the front of the block faces. See `Block::ori`.
+    facing: u8,
+    /// Quarter turns about the front
+    turn: u8,
+}
+
+impl Hotbar {
+    pub fn new(properties: &BlockProperties) -> Self {
+        // Start unrotated
+        Self { ids: properties.types().map(|t| t.id).collect(), selected: 0, facing: 5, turn: 2 }
+    }
+
+    /// Pick a slot of the page shown, counting from zero. Slots past the end are ignored.
+    pub fn select(&mut self, slot: usize) {
+        let index = self.page_start() + slot;
+        if slot < HOTBAR_SLOTS && index < self.ids.len() {
+            self.selected = index;
+        }
+    }
+
+    fn page_start(&self) -> usize {
+        self.selected / HOTBAR_SLOTS * HOTBAR_SLOTS
+    }
+
+    /// Move the selection along, wrapping around the ends
+    pub fn scroll(&mut self, steps: i32) {
+        if !self.ids.is_empty() {
+            self.selected = (self.selected as i32 + steps).rem_euclid(self.ids.len() as i32) as usize;
+        }
+    }
+
+    /// Point the front of the block the next way, keeping the turn
+    pub fn face_next(&mut self) {
+        self.facing = (self.facing + 1) % 6;
+    }
+
+    /// Turn the block a quarter about its front
+    pub fn turn(&mut self) {
+        self.turn = (self.turn + 1) % 4;
+    }
+
+    /// The block to place, or None if no block types are registered
+    pub fn block(&self) -> Option<Block> {
+        Some(Block { id: *self.ids.get(self.selected)?, ori: Block::ori(self.facing, self.turn) })
+    }
+
+    /// The hotbar with the selection in brackets, then what the selected block is
+    pub fn text(&self, properties: &BlockProperties) -> String {
+        let Some(block) = self.block() else { return String::new(); };
+        let start = self.page_start();
+        let slots: Vec<String> = self.ids.iter().enumerate().skip(start).take(HOTBAR_SLOTS).map(|(i, id)| {
+            let name = properties.get(*id).map_or("?", |t| &t.name);
+            if i == self.selected { format!("[{} {}]", i - start + 1, name) } else { format!("{} {}", i - start + 1, name) }
+        }).collect();
+        let front = block.front();
+        let tooltip = properties.get(block.id).map_or("", |t| &t.tooltip);
+        format!("{}\nFacing ({}, {}, {}), turned {} (F, R). {}", slots.join("  "), front.x, front.y, front.z, self.turn, tooltip)
+    }
+}
+
+/// A small block drawn where the selected block would be placed
+pub struct Ghost {
+    grid: CubeGrid,
+    model: GridModel,
+    shown: bool,
+}
+
+impl Ghost {
+    pub fn new(graphics: &Graphics) -> Self {
+        Self { grid: CubeGrid::new(), model: GridModel::new(graphics), shown: false }
+    }
+
+    /// Show a block in the cell at `cell`, in the body coordinates of an object
+    pub fn show(&mut self, graphics: &Graphics, properties: &BlockProperties, block: Block, body: &RigidBody, cell: Vector3<f64>, camera: &Camera) {
+        if self.grid[(0, 0, 0)] != block || !self.shown {
+            self.grid[(0, 0, 0)] = block;
+            self.model.update(graphics, &self.grid, properties.texture_columns(), 1, 0);
+        }
+        let center = body.pos + body.ori * (cell + Vector3::new(0.5, 0.5, 0.5) - body.com_pos);
+        let model = Matrix4::from_translation(center - camera.pos)
+            * Matrix4::from(body.ori)
+            * Matrix4::from_scale(GHOST_SCALE)
+            * Matrix4::from_translation(Vector3::new(-0.5, -0.5, -0.5));
+        self.model.buffer.write(graphics, ModelUniform::new(model.cast().unwrap()));
+        self.shown = true;
+    }
+
+    pub fn hide(&mut self) {
+        self.shown = false;
+    }
+
+    pub fn draw(&self, renderer: &mut Renderer, texture: &GridTexture) {
+        if self.shown {
+            texture.bind(renderer, 1);
+            self.model.draw(renderer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::{game::load_block_properties, graphics::block::ORIENTATIONS};
+
+    use super::*;
+
+    #[test]
+    fn hotbar_selects_every_block_type() {
+        let properties = load_block_properties();
+        let mut hotbar = Hotbar::new(&properties);
+        let count = properties.types().count();
+        let mut seen = BTreeSet::new();
+        for _ in 0..count {
+            seen.insert(hotbar.block().unwrap().id);
+            hotbar.scroll(1);
+        }
+        assert_eq!(seen.len(), count);
+        assert_eq!(hotbar.block().unwrap().id, *seen.first().unwrap());
+        hotbar.scroll(-1);
+        assert_eq!(hotbar.block().unwrap().id, *seen.last().unwrap());
+
+        hotbar.select(1);
+        assert_eq!(hotbar.block().unwrap().id, *seen.iter().nth(1).unwrap());
+        hotbar.select(100);
+        assert_eq!(hotbar.block().unwrap().id, *seen.iter().nth(1).unwrap());
+    }
+
+    #[test]
+    fn hotbar_turns_blocks_every_way() {
+        let mut hotbar = Hotbar::new(&load_block_properties());
+        let axes = |block: Block| [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()].map(|v| (block.quat() * v).map(|c| c.round() as i32));
+        assert_eq!(axes(hotbar.block().unwrap()), [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()]);
+        let mut rotations = Vec::new();
+        for _ in 0..6 {
+            let front = hotbar.block().unwrap().front();
+            for _ in 0..4 {
+                let block = hotbar.block().unwrap();
+                // Turning keeps the front where it is
+                assert_eq!(block.front(), front);
+                assert!(!rotations.contains(&axes(block)));
+                rotations.push(axes(block));
+                hotbar.turn();
+            }
+            hotbar.face_next();
+        }
+        assert_eq!(rotations.len(), ORIENTATIONS as usize);
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+
+use biscuit::util::bytes::{Reader, write_len};
+
+use crate::{game::save::{read_coord, write_coord}, graphics::{Block, CHUNK_SIZE}};
+
+/// Changed blocks of a chunk, by position in the chunk
+type ChunkBlocks = BTreeMap<(u32, u32, u32), Block>;
+
+/// Blocks changed on a planet since it was generated, kept by chunk so that they can be laid over the generated
+/// terrain whenever the chunk is loaded again
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChunkEdits {
+    chunks: BTreeMap<(i32, i32, i32), ChunkBlocks>,
+}
+
+impl ChunkEdits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, chunk: (i32, i32, i32), block: (u32, u32, u32), value: Block) {
+        self.chunks.entry(chunk).or_default().insert(block, value);
+    }
+
+    /// Forget a changed block, and the chunk once none of its blocks are changed
+    pub fn remove(&mut self, chunk: (i32, i32, i32), block: (u32, u32, u32)) {
+        if let Some(blocks) = self.chunks.get_mut(&chunk) {
+            blocks.remove(&block);
+            if blocks.is_empty() {
+                self.chunks.remove(&chunk);
+            }
+        }
+    }
+
+    pub fn get(&self, chunk: (i32, i32, i32), block: (u32, u32, u32)) -> Option<Block> {
+        self.chunks.get(&chunk)?.get(&block).copied()
+    }
+
+    /// Whether any block of the chunk was changed
+    pub fn contains(&self, chunk: (i32, i32, i32)) -> bool {
+        self.chunks.contains_key(&chunk)
+    }
+
+    /// The changed blocks of a chunk
+    pub fn blocks(&self, chunk: (i32, i32, i32)) -> impl Iterator<Item = ((u32, u32, u32), Block)> + '_ {
+        self.chunks.get(&chunk).into_iter().flatten().map(|(pos, block)| (*pos, *block))
+    }
+
+    /// Number of changed blocks
+    pub fn len(&self) -> usize {
+        self.chunks.values().map(|c| c.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        write_len(out, self.chunks.len());
+        for (coord, blocks) in &self.chunks {
+            write_coord(out, *coord);
+            write_len(out, blocks.len());
+            for ((x, y, z), block) in blocks {
+                out.extend([*x as u8, *y as u8, *z as u8, block.id, block.ori]);
+            }
+        }
+    }
+
+    pub fn read(reader: &mut Reader) -> Option<Self> {
+        let mut edits = Self::new();
+        for _ in 0..reader.length()? {
+            let coord = read_coord(reader)?;
+            let len = reader.length()?;
+            if len == 0 { return None; }
+            for _ in 0..len {
+                let mut c = || reader.u8().map(|c| c as u32).filter(|c| *c < CHUNK_SIZE);
+                let pos = (c()?, c()?, c()?);
+                edits.set(coord, pos, Block { id: reader.u8()?, ori: reader.u8()? });
+            }
+        }
+        Some(edits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_edits_replace_earlier_ones() {
+        let mut edits = ChunkEdits::new();
+        assert!(edits.is_empty());
+        edits.set((0, -1, 2), (3, 4, 5), Block { id: 7, ori: 0 });
+        edits.set((0, -1, 2), (3, 4, 5), Block { id: 0, ori: 0 });
+        edits.set((0, -1, 2), (0, 0, 15), Block { id: 4, ori: 9 });
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits.get((0, -1, 2), (3, 4, 5)), Some(Block { id: 0, ori: 0 }));
+        assert_eq!(edits.get((0, -1, 3), (3, 4, 5)), None);
+        assert!(!edits.contains((1, 1, 1)));
+        assert_eq!(edits.blocks((0, -1, 2)).count(), 2);
+        assert_eq!(edits.blocks((1, 1, 1)).count(), 0);
+
+        edits.remove((0, -1, 2), (3, 4, 5));
+        assert_eq!(edits.get((0, -1, 2), (3, 4, 5)), None);
+        assert!(edits.contains((0, -1, 2)));
+        edits.remove((0, -1, 2), (0, 0, 15));
+        assert!(!edits.contains((0, -1, 2)));
+        assert!(edits.is_empty());
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut edits = ChunkEdits::new();
+        edits.set((-4, 0, 9), (15, 0, 1), Block { id: 1, ori: 2 });
+        edits.set((5, 5, 5), (2, 2, 2), Block { id: 0, ori: 0 });
+        let mut bytes = Vec::new();
+        edits.write(&mut bytes);
+        let mut reader = Reader::new(&bytes);
+        assert_eq!(ChunkEdits::read(&mut reader), Some(edits));
+        assert!(reader.is_finished());
+        // Blocks outside the chunk
+        bytes[20] = 16;
+        assert_eq!(ChunkEdits::read(&mut Reader::new(&bytes)), None);
+    }
+}
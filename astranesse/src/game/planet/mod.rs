@@ -1,21 +1,24 @@
 mod atmosphere;
+mod edits;
 mod ocean;
 mod terrain;
 
 pub use terrain::Terrain;
 pub use atmosphere::Atmosphere;
 pub use ocean::Ocean;
+pub use edits::ChunkEdits;
 
 use cgmath::Vector3;
 
 use crate::{game::object::{Object, ObjectLoader, loader::PlanetLoader}, util::RcCell};
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct PlanetInit {
-    width: u32,
-    seed: u32,
-    mass: f32,
-    to_sun: Vector3<f32>,
-    spin_local: Vector3<f32>,
+    pub width: u32,
+    pub seed: u32,
+    pub mass: f32,
+    pub to_sun: Vector3<f32>,
+    pub spin_local: Vector3<f32>,
 }
 impl Default for PlanetInit {
     fn default() -> Self {
@@ -31,6 +34,8 @@ impl Default for PlanetInit {
 
 pub struct Planet {
     pub width: u32,
+    /// What the planet was generated from, to save it
+    pub init: PlanetInit,
     terrain: Terrain,
     atmosphere: Atmosphere,
     pub object: Option<RcCell<Object>>,
@@ -46,13 +51,15 @@ impl Planet {
 
         Self {
             width: data.width,
+            init: data,
             terrain,
             atmosphere,
             object: None,
         }
     }
-    pub fn loader(&self) -> ObjectLoader {
-        ObjectLoader::MultiShot(PlanetLoader::new(self.width/2, &self.terrain, &self.atmosphere))
+    /// A loader generating the planet's terrain, with the edits laid over it
+    pub fn loader(&self, edits: ChunkEdits) -> ObjectLoader {
+        ObjectLoader::MultiShot(PlanetLoader::new(self.width/2, &self.terrain, &self.atmosphere, edits))
     }
     
     pub fn dbg_text(&self, pos: Vector3<f32>) -> String {
@@ -0,0 +1,396 @@
+use std::path::{Path, PathBuf};
+
+use biscuit::util::bytes::{Reader, write_f64, write_len};
+use cgmath::{Quaternion, Vector3};
+
+use crate::{game::{object::{BlockKey, save::{BodyState, ShipSave}}, planet::{ChunkEdits, PlanetInit}}, graphics::CHUNK_SIZE};
+
+const MAGIC: &[u8; 4] = b"AWLD";
+/// Bumped when a section changes layout. New sections do not need a new version, since unknown sections are skipped.
+/// Version 2 added planet edits.
+const VERSION: u8 = 2;
+
+const SHIP: [u8; 4] = *b"SHIP";
+const PLANET: [u8; 4] = *b"PLNT";
+const ENTITY: [u8; 4] = *b"ENTY";
+
+/// Extension of the files in a save slot directory
+const EXTENSION: &str = "world";
+
+/// A saved object, in the order of the game's objects
+#[derive(Clone)]
+pub enum ObjectSave {
+    Ship(ShipSave),
+    Planet(PlanetSave),
+}
+
+/// Planets are generated from their seed, so only it, their motion and the blocks changed since are kept
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlanetSave {
+    pub init: PlanetInit,
+    pub body: BodyState,
+    pub edits: ChunkEdits,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntitySave {
+    pub body: BodyState,
+    /// Index of the object the entity is sitting in, and the chair block
+    pub chair: Option<(usize, BlockKey)>,
+}
+
+/// Everything needed to rebuild the game's world. It holds no graphics, so it can be made and read without a window.
+#[derive(Clone)]
+pub struct WorldSave {
+    pub objects: Vec<ObjectSave>,
+    /// The first entity is the player
+    pub entities: Vec<EntitySave>,
+}
+
+impl WorldSave {
+    /// The world of a new game: the demo ship, and the player floating near it
+    pub fn new_game() -> Self {
+        Self {
+            objects: vec![
+                ObjectSave::Ship(ShipSave::demo(Vector3::new(12., 7., 42.), Vector3::new(0., 0., 0.))),
+            ],
+            entities: vec![
+                EntitySave { body: BodyState::at_rest(Vector3::new(0., 0., 50.), Vector3::new(0., 0., 0.)), chair: None },
+            ],
+        }
+    }
+
+    /// The magic and version, then a section per object in order, then a section per entity
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(MAGIC);
+        out.push(VERSION);
+        for object in &self.objects {
+            match object {
+                ObjectSave::Ship(ship) => write_section(&mut out, &SHIP, &ship.to_bytes()),
+                ObjectSave::Planet(planet) => {
+                    let mut section = Vec::new();
+                    write_len(&mut section, planet.init.width as usize);
+                    write_len(&mut section, planet.init.seed as usize);
+                    for value in [planet.init.mass, planet.init.to_sun.x, planet.init.to_sun.y, planet.init.to_sun.z,
+                        planet.init.spin_local.x, planet.init.spin_local.y, planet.init.spin_local.z] {
+                        write_f64(&mut section, value as f64);
+                    }
+                    write_body(&mut section, &planet.body);
+                    planet.edits.write(&mut section);
+                    write_section(&mut out, &PLANET, &section);
+                },
+            }
+        }
+        for entity in &self.entities {
+            let mut section = Vec::new();
+            write_body(&mut section, &entity.body);
+            match entity.chair {
+                Some((object, block)) => {
+                    section.push(1);
+                    write_len(&mut section, object);
+                    write_block_key(&mut section, block);
+                },
+                None => section.push(0),
+            }
+            write_section(&mut out, &ENTITY, &section);
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let invalid = || "world is corrupt".to_owned();
+        let mut objects = Vec::new();
+        let mut entities = Vec::new();
+        let (version, sections) = sections(bytes, MAGIC, VERSION, "world")?;
+        for (tag, mut section) in sections {
+            match tag {
+                SHIP => objects.push(ObjectSave::Ship(ShipSave::from_bytes(section.rest())?)),
+                PLANET => objects.push(ObjectSave::Planet(read_planet(&mut section, version).ok_or_else(invalid)?)),
+                ENTITY => entities.push(read_entity(&mut section).ok_or_else(invalid)?),
+                _ => continue,
+            }
+            if !section.is_finished() {
+                return Err(invalid());
+            }
+        }
+        if entities.is_empty() || entities.iter().any(|e| e.chair.is_some_and(|(o, _)| !matches!(objects.get(o), Some(ObjectSave::Ship(_))))) {
+            return Err(invalid());
+        }
+        Ok(Self { objects, entities })
+    }
+}
+
+fn read_planet(reader: &mut Reader, version: u8) -> Option<PlanetSave> {
+    let width = reader.length()? as u32;
+    let seed = reader.length()? as u32;
+    let mut f = || reader.f64().map(|f| f as f32);
+    let init = PlanetInit {
+        width,
+        seed,
+        mass: f()?,
+        to_sun: Vector3::new(f()?, f()?, f()?),
+        spin_local: Vector3::new(f()?, f()?, f()?),
+    };
+    let body = read_body(reader)?;
+    let edits = match version {
+        1 => ChunkEdits::new(),
+        _ => ChunkEdits::read(reader)?,
+    };
+    Some(PlanetSave { init, body, edits })
+}
+
+fn read_entity(reader: &mut Reader) -> Option<EntitySave> {
+    let body = read_body(reader)?;
+    let chair = match reader.u8()? {
+        0 => None,
+        1 => Some((reader.length()?, read_block_key(reader)?)),
+        _ => return None,
+    };
+    Some(EntitySave { body, chair })
+}
+
+/// A directory of saved worlds, each in a file named after its slot
+pub struct SaveSlots {
+    dir: PathBuf,
+}
+
+impl SaveSlots {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn path(&self, slot: &str) -> PathBuf {
+        self.dir.join(slot).with_extension(EXTENSION)
+    }
+
+    /// Names of the saved slots, in order
+    pub fn list(&self) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else { return Vec::new(); };
+        let mut slots: Vec<String> = entries.filter_map(|e| {
+            let path = e.ok()?.path();
+            if path.extension()? != EXTENSION { return None; }
+            Some(path.file_stem()?.to_str()?.to_owned())
+        }).collect();
+        slots.sort();
+        slots
+    }
+
+    /// Read a slot, or None if it was never saved
+    pub fn read(&self, slot: &str) -> Result<Option<WorldSave>, String> {
+        let path = self.path(slot);
+        if !path.exists() {
+            return Ok(None);
+        }
+        WorldSave::from_bytes(&read_file(&path)?).map(Some).map_err(|e| format!("Could not read {}: {}", path.display(), e))
+    }
+
+    pub fn write(&self, slot: &str, world: &WorldSave) -> Result<(), String> {
+        write_file(&self.path(slot), &world.to_bytes())
+    }
+}
+
+/// Counts down to the next autosave
+pub struct Autosave {
+    /// Seconds between saves
+    interval: f64,
+    elapsed: f64,
+}
+
+impl Autosave {
+    pub fn new(interval: f64) -> Self {
+        Self { interval, elapsed: 0. }
+    }
+
+    /// Advance the clock, returning whether it is time to save
+    pub fn update(&mut self, delta_t: f64) -> bool {
+        self.elapsed += delta_t;
+        if self.elapsed < self.interval {
+            return false;
+        }
+        self.elapsed = 0.;
+        true
+    }
+}
+
+pub(crate) fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))
+}
+
+/// Write a file through a temporary one, so that a crash while saving does not lose the last save
+pub(crate) fn write_file(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let error = |e: std::io::Error| format!("Could not write {}: {}", path.display(), e);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(error)?;
+    }
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, bytes).map_err(error)?;
+    std::fs::rename(&temporary, path).map_err(error)
+}
+
+/// A tagged part of a save
+pub(crate) type Section<'a> = ([u8; 4], Reader<'a>);
+
+/// Check the magic and version of a save, then split it into its tagged sections. Saves of older versions are
+/// accepted, and their version returned so that their sections can be read as they were written.
+pub(crate) fn sections<'a>(bytes: &'a [u8], magic: &[u8; 4], version: u8, what: &str) -> Result<(u8, Vec<Section<'a>>), String> {
+    let invalid = || format!("{} is corrupt", what);
+    let mut reader = Reader::new(bytes);
+    if reader.take(4) != Some(magic) {
+        return Err(format!("not a {}", what));
+    }
+    let saved = match reader.u8() {
+        Some(v) if v > version => return Err(format!("{} was saved by a newer version of the game (version {})", what, v)),
+        Some(v) if v > 0 => v,
+        _ => return Err(invalid()),
+    };
+    let mut sections = Vec::new();
+    while !reader.is_finished() {
+        let tag = reader.take(4).ok_or_else(invalid)?.try_into().unwrap();
+        let len = reader.length().ok_or_else(invalid)?;
+        sections.push((tag, Reader::new(reader.take(len).ok_or_else(invalid)?)));
+    }
+    Ok((saved, sections))
+}
+
+pub(crate) fn write_section(out: &mut Vec<u8>, tag: &[u8; 4], section: &[u8]) {
+    out.extend(tag);
+    write_len(out, section.len());
+    out.extend(section);
+}
+
+pub(crate) fn write_coord(out: &mut Vec<u8>, coord: (i32, i32, i32)) {
+    for c in [coord.0, coord.1, coord.2] {
+        out.extend(c.to_le_bytes());
+    }
+}
+
+pub(crate) fn read_coord(reader: &mut Reader) -> Option<(i32, i32, i32)> {
+    let mut c = || Some(i32::from_le_bytes(reader.take(4)?.try_into().ok()?));
+    Some((c()?, c()?, c()?))
+}
+
+pub(crate) fn write_block_key(out: &mut Vec<u8>, (coord, block): BlockKey) {
+    write_coord(out, coord);
+    out.extend([block.0 as u8, block.1 as u8, block.2 as u8]);
+}
+
+pub(crate) fn read_block_key(reader: &mut Reader) -> Option<BlockKey> {
+    let coord = read_coord(reader)?;
+    let mut c = || reader.u8().map(|c| c as u32).filter(|c| *c < CHUNK_SIZE);
+    Some((coord, (c()?, c()?, c()?)))
+}
+
+pub(crate) fn write_body(out: &mut Vec<u8>, body: &BodyState) {
+    for value in [body.pos.x, body.pos.y, body.pos.z, body.vel.x, body.vel.y, body.vel.z,
+        body.ori.s, body.ori.v.x, body.ori.v.y, body.ori.v.z, body.ang_vel.x, body.ang_vel.y, body.ang_vel.z] {
+        write_f64(out, value);
+    }
+}
+
+pub(crate) fn read_body(reader: &mut Reader) -> Option<BodyState> {
+    let mut f = || reader.f64();
+    Some(BodyState {
+        pos: Vector3::new(f()?, f()?, f()?),
+        vel: Vector3::new(f()?, f()?, f()?),
+        ori: Quaternion::new(f()?, f()?, f()?, f()?),
+        ang_vel: Vector3::new(f()?, f()?, f()?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graphics::Block;
+
+    use super::*;
+
+    fn world() -> WorldSave {
+        let mut world = WorldSave::new_game();
+        let mut edits = ChunkEdits::new();
+        edits.set((1, -2, 0), (4, 0, 15), Block { id: 1, ori: 0 });
+        edits.set((1, -2, 0), (5, 0, 15), Block { id: 0, ori: 0 });
+        world.objects.push(ObjectSave::Planet(PlanetSave {
+            init: PlanetInit { seed: 12345, ..Default::default() },
+            body: BodyState::at_rest(Vector3::new(0., -500., 0.), Vector3::new(0., 0., 1.)),
+            edits,
+        }));
+        world.entities[0].chair = Some((0, ((0, 0, 0), (7, 7, 8))));
+        world.entities.push(EntitySave { body: BodyState::at_rest(Vector3::new(3., 2., 1.), Vector3::new(0., 0., 0.)), chair: None });
+        world
+    }
+
+    fn dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("astranesse-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn round_trip() {
+        let world = world();
+        let bytes = world.to_bytes();
+        let loaded = WorldSave::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.entities, world.entities);
+        assert!(matches!(&loaded.objects[..], [ObjectSave::Ship(_), ObjectSave::Planet(p)] if p.init.seed == 12345 && p.edits.len() == 2));
+        assert_eq!(loaded.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn version_1_planets_have_no_edits() {
+        let world = world();
+        let ObjectSave::Planet(planet) = &world.objects[1] else { unreachable!() };
+        // The layout of version 1: a planet section without edits
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(1);
+        let mut section = Vec::new();
+        write_len(&mut section, planet.init.width as usize);
+        write_len(&mut section, planet.init.seed as usize);
+        for value in [planet.init.mass, planet.init.to_sun.x, planet.init.to_sun.y, planet.init.to_sun.z,
+            planet.init.spin_local.x, planet.init.spin_local.y, planet.init.spin_local.z] {
+            write_f64(&mut section, value as f64);
+        }
+        write_body(&mut section, &planet.body);
+        write_section(&mut bytes, &PLANET, &section);
+        let mut section = Vec::new();
+        write_body(&mut section, &world.entities[0].body);
+        section.push(0);
+        write_section(&mut bytes, &ENTITY, &section);
+
+        let loaded = WorldSave::from_bytes(&bytes).unwrap();
+        let [ObjectSave::Planet(loaded)] = &loaded.objects[..] else { panic!("Expected a planet") };
+        assert_eq!(loaded.init, planet.init);
+        assert!(loaded.edits.is_empty());
+    }
+
+    #[test]
+    fn chairs_must_be_in_ships() {
+        let mut world = world();
+        world.entities[0].chair = Some((1, ((0, 0, 0), (7, 7, 8))));
+        assert!(WorldSave::from_bytes(&world.to_bytes()).is_err());
+        world.entities[0].chair = Some((5, ((0, 0, 0), (7, 7, 8))));
+        assert!(WorldSave::from_bytes(&world.to_bytes()).is_err());
+    }
+
+    #[test]
+    fn slots() {
+        let dir = dir("slots");
+        let slots = SaveSlots::new(&dir);
+        assert!(slots.list().is_empty());
+        assert!(slots.read("first").unwrap().is_none());
+        slots.write("second", &world()).unwrap();
+        slots.write("first", &WorldSave::new_game()).unwrap();
+        // Overwriting a slot leaves no temporary file behind
+        slots.write("first", &world()).unwrap();
+        assert_eq!(slots.list(), ["first", "second"]);
+        assert_eq!(slots.read("first").unwrap().unwrap().to_bytes(), world().to_bytes());
+
+        std::fs::write(slots.path("second"), b"AWLD").unwrap();
+        assert!(slots.read("second").is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn autosave() {
+        let mut autosave = Autosave::new(1.);
+        let saves = (0..25).filter(|_| autosave.update(0.1)).count();
+        assert_eq!(saves, 2);
+    }
+}
@@ -0,0 +1,334 @@
+use std::{cell::RefCell, rc::Rc};
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::{game::{SAVE_DIR, SAVE_SLOT, load_block_properties, entity::Entity, object::{Object, ObjectLoader, computer::BlockProperties, loader::ShipLoader, save::BodyState}, planet::Planet, save::{EntitySave, ObjectSave, PlanetSave, SaveSlots, WorldSave}}, physics::Physics, util::RcCell};
+
+/// The state of the world and the rules moving it on: objects with their chunks and command blocks, entities,
+/// planets and physics. It owns no rendering resources, so it can be stepped without a window, and the game draws
+/// it as a view.
+pub struct Simulation {
+    pub objects: Vec<RcCell<Object>>,
+    /// The first entity is the player
+    pub entities: Vec<Entity>,
+    pub planets: Vec<Planet>,
+    pub block_properties: BlockProperties, // Needs to be last
+    pub physics: Box<Physics>, // Needs to be last
+}
+
+impl Simulation {
+    /// Make the objects, planets and entities of a saved world
+    pub fn new(world: WorldSave, block_properties: BlockProperties) -> Self {
+        let mut physics = Box::new(Physics::new());
+        let mut objects = Vec::new();
+        let mut planets = Vec::new();
+        for object in world.objects {
+            match object {
+                ObjectSave::Ship(ship) => {
+                    objects.push(Rc::new(RefCell::new(Object::new(&mut physics, ObjectLoader::OneShot(ShipLoader::new(ship))))));
+                },
+                ObjectSave::Planet(save) => {
+                    let mut planet = Planet::new(save.init);
+                    let obj = Rc::new(RefCell::new(Object::new(&mut physics, planet.loader(save.edits))));
+                    obj.borrow_mut().set_motion(&save.body);
+                    planet.object = Some(obj.clone());
+                    objects.push(obj);
+                    planets.push(planet);
+                },
+            }
+        }
+        let entities = world.entities.iter().map(|save| {
+            let mut entity = Entity::new(&mut physics, save.body.init());
+            if let Some((object, block_key)) = save.chair {
+                entity.set_chair(&objects[object], block_key);
+            }
+            entity
+        }).collect();
+        Self { objects, entities, planets, block_properties, physics }
+    }
+
+    pub fn player(&self) -> &Entity {
+        &self.entities[0]
+    }
+
+    pub fn player_mut(&mut self) -> &mut Entity {
+        &mut self.entities[0]
+    }
+
+    /// Move the world on by one tick: load the chunks near the player, run the command blocks, then the physics
+    pub fn step(&mut self, delta_t: f64) {
+        let focus = self.player().body.pos;
+        for object in &mut self.objects {
+            object.borrow_mut().update_chunks(&self.block_properties, focus);
+        }
+        for object in &mut self.objects {
+            object.borrow_mut().update_internals(delta_t);
+        }
+        self.physics.update(delta_t);
+        for entity in &mut self.entities {
+            entity.update(delta_t);
+        }
+    }
+
+    /// Read the interpreted scripts again, and start every command block again with them
+    pub fn reload_scripts(&mut self) -> Result<(), String> {
+        self.block_properties.reload_scripts()?;
+        for object in &self.objects {
+            object.borrow_mut().restart_scripts(&self.block_properties);
+        }
+        Ok(())
+    }
+
+    /// The planet closest to a position
+    pub fn nearest_planet(&self, pos: Vector3<f64>) -> Option<&Planet> {
+        self.planets.iter()
+            .filter_map(|p| Some((p, (p.object.as_ref()?.borrow().body.pos - pos).magnitude())))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(p, _)| p)
+    }
+
+    /// Everything needed to rebuild the world as it is now
+    pub fn world_save(&self) -> WorldSave {
+        let objects = self.objects.iter().map(|o| {
+            match self.planets.iter().find(|p| p.object.as_ref().is_some_and(|p| Rc::ptr_eq(p, o))) {
+                Some(planet) => {
+                    let o = o.borrow();
+                    let edits = o.planet_edits().unwrap().clone();
+                    ObjectSave::Planet(PlanetSave { init: planet.init.clone(), body: BodyState::of(&o.body), edits })
+                },
+                None => ObjectSave::Ship(o.borrow().ship_save().unwrap()),
+            }
+        }).collect();
+        let entities = self.entities.iter().map(|e| EntitySave {
+            body: BodyState::of(&e.body),
+            chair: e.chair.as_ref().map(|(o, block_key, _)| (self.objects.iter().position(|p| Rc::ptr_eq(p, o)).unwrap(), *block_key)),
+        }).collect();
+        WorldSave { objects, entities }
+    }
+}
+
+const USAGE: &str = "Usage: astranesse sim --ticks N [--dt SECONDS] [--slot NAME] [--save]";
+
+/// Step a world without a window and print where everything ended up. The world comes from a save slot if one is
+/// given, or is a new game otherwise, and `--save` writes it back to the slot.
+pub fn command(args: &[String]) -> Result<(), String> {
+    let mut ticks = None;
+    let mut delta_t = 1. / 60.;
+    let mut slot = None;
+    let mut save = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ticks" => ticks = Some(value(&mut args, arg)?.parse::<u64>().map_err(|e| format!("Bad value for --ticks: {}", e))?),
+            "--dt" => delta_t = value(&mut args, arg)?.parse::<f64>().map_err(|e| format!("Bad value for --dt: {}", e))?,
+            "--slot" => slot = Some(value(&mut args, arg)?),
+            "--save" => save = true,
+            _ => return Err(format!("Unknown argument {}\n{}", arg, USAGE)),
+        }
+    }
+    let ticks = ticks.ok_or_else(|| USAGE.to_owned())?;
+
+    let slots = SaveSlots::new(SAVE_DIR);
+    let slot = slot.unwrap_or(SAVE_SLOT);
+    let world = slots.read(slot)?.unwrap_or_else(WorldSave::new_game);
+    let mut sim = Simulation::new(world, load_block_properties());
+    for _ in 0..ticks {
+        sim.step(delta_t);
+    }
+
+    for (i, object) in sim.objects.iter().enumerate() {
+        let o = object.borrow();
+        let kind = if o.planet_edits().is_some() { "planet" } else { "ship" };
+        println!("object {} {}: pos {:?} vel {:?} chunks {}", i, kind, o.body.pos, o.body.vel, o.chunk_count());
+    }
+    for (i, entity) in sim.entities.iter().enumerate() {
+        println!("entity {}: pos {:?} vel {:?}", i, entity.body.pos, entity.body.vel);
+    }
+    for object in &sim.objects {
+        print!("{}", object.borrow().log_text());
+    }
+    if save {
+        slots.write(slot, &sim.world_save())?;
+    }
+    Ok(())
+}
+
+fn value<'a>(args: &mut impl Iterator<Item = &'a String>, name: &str) -> Result<&'a str, String> {
+    args.next().map(String::as_str).ok_or_else(|| format!("Missing value for {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::object::{Interrupt, cell, save::ShipSave};
+
+    use super::*;
+
+    const DELTA_T: f64 = 1. / 60.;
+
+    fn run(world: WorldSave, ticks: usize) -> Simulation {
+        let mut sim = Simulation::new(world, load_block_properties());
+        for _ in 0..ticks {
+            sim.step(DELTA_T);
+        }
+        sim
+    }
+
+    fn ship(world: &WorldSave) -> &ShipSave {
+        let ObjectSave::Ship(ship) = &world.objects[0] else { panic!("Expected a ship") };
+        ship
+    }
+
+    #[test]
+    fn ships_load_near_the_player() {
+        let sim = run(WorldSave::new_game(), 1);
+        assert_eq!(sim.objects[0].borrow().chunk_count(), 1);
+
+        // A player far away does not load the ship
+        let mut world = WorldSave::new_game();
+        world.entities[0].body.pos = Vector3::new(1e5, 0., 0.);
+        let sim = run(world, 1);
+        assert_eq!(sim.objects[0].borrow().chunk_count(), 0);
+    }
+
+    #[test]
+    fn simulations_drop_after_collisions() {
+        // The player lands on the ship's deck a little after the first hundred ticks
+        let sim = run(WorldSave::new_game(), 150);
+        drop(sim);
+    }
+
+    #[test]
+    fn ships_move() {
+        let mut world = WorldSave::new_game();
+        let ObjectSave::Ship(save) = &mut world.objects[0] else { unreachable!() };
+        save.body.vel = Vector3::new(3., 0., 0.);
+        let start = save.body.pos;
+        let sim = run(world, 60);
+        let moved = ship(&sim.world_save()).body.pos - start;
+        assert!((moved - Vector3::new(3., 0., 0.)).magnitude() < 1e-6, "{:?}", moved);
+    }
+
+    #[test]
+    fn removing_blocks_updates_the_ship() {
+        let sim = run(WorldSave::new_game(), 1);
+        let mut ship = sim.objects[0].borrow_mut();
+        let before = ship.ship_save().unwrap();
+        let com = ship.body.com_pos;
+        let engine = Vector3::new(8.5, 8.5, 7.5);
+
+        let removed = ship.remove_block(&sim.block_properties, engine);
+        assert_eq!(removed.id, 5);
+        assert!(ship.get_block(engine).is_null());
+        assert!(ship.body.com_pos.x < com.x);
+        let machines = ship.ship_save().unwrap().machines;
+        assert!(!machines.contains_key(&cell(engine)));
+        assert_eq!(machines.len(), before.machines.len() - 1);
+
+        // Removing nothing changes nothing
+        assert!(ship.remove_block(&sim.block_properties, engine).is_null());
+
+        ship.insert_block(&sim.block_properties, removed, engine);
+        assert!((ship.body.com_pos - com).magnitude() < 1e-9);
+        assert_eq!(ship.ship_save().unwrap().chunks, before.chunks);
+    }
+
+    #[test]
+    fn engines_burn_fuel_until_the_tanks_are_empty() {
+        const ENGINE: &str = "fn main() {\n    loop {\n        thrust(1);\n        tick();\n    }\n}\n";
+        let mut properties = BlockProperties::new();
+        properties.preload_script(include_str!("../../../assets/scripts/chair.txt"), "chair");
+        properties.preload_script(ENGINE, "engine");
+        properties.load_registry(include_str!("../../../assets/blocks.toml"), "blocks.toml").unwrap();
+        // Enough fuel in each tank for a quarter of a second at full thrust
+        let mut world = WorldSave::new_game();
+        let ObjectSave::Ship(save) = &mut world.objects[0] else { unreachable!() };
+        let burn = properties.get(5).unwrap().burn;
+        save.tanks = [(6, 8, 7), (7, 8, 7), (6, 6, 7), (7, 6, 7)].into_iter().map(|b| (((0, 0, 0), b), burn / 8.)).collect();
+        let mut sim = Simulation::new(world, properties);
+        sim.step(DELTA_T);
+        let full = sim.objects[0].borrow().body.mass;
+
+        for _ in 0..10 {
+            sim.step(DELTA_T);
+        }
+        let speed = sim.objects[0].borrow().body.vel.magnitude();
+        assert!(speed > 0.);
+        // Burning fuel makes the ship lighter
+        assert!(sim.objects[0].borrow().body.mass < full);
+
+        for _ in 0..30 {
+            sim.step(DELTA_T);
+        }
+        let ship = sim.objects[0].borrow();
+        let saved = ship.ship_save().unwrap();
+        assert!(saved.tanks.values().all(|f| *f < 1e-9), "{:?}", saved.tanks);
+        // With the tanks empty the engines stop pushing
+        let vel = ship.body.vel;
+        drop(ship);
+        sim.step(DELTA_T);
+        assert!((sim.objects[0].borrow().body.vel - vel).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn the_chair_fires_the_engines() {
+        let mut sim = run(WorldSave::new_game(), 1);
+        let chair = cell(Vector3::new(7.5, 7.5, 8.5));
+        for _ in 0..5 {
+            sim.objects[0].borrow_mut().interrupt(chair, Interrupt::Forward(1.));
+            sim.step(DELTA_T);
+        }
+        let speed = sim.objects[0].borrow().body.vel.magnitude();
+        assert!(speed > 0.);
+        // Letting go stops them
+        sim.step(DELTA_T);
+        sim.step(DELTA_T);
+        let vel = sim.objects[0].borrow().body.vel;
+        sim.step(DELTA_T);
+        assert!((sim.objects[0].borrow().body.vel - vel).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn interpreted_scripts_are_reloaded_while_running() {
+        let dir = std::env::temp_dir().join(format!("astranesse-scripts-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let engine = |value: u32| format!("fn main() {{\n    loop {{\n        dbg({});\n        tick();\n    }}\n}}\n", value);
+        std::fs::write(dir.join("chair.txt"), include_str!("../../../assets/scripts/chair.txt")).unwrap();
+        std::fs::write(dir.join("engine.txt"), engine(1)).unwrap();
+        let mut properties = BlockProperties::new();
+        properties.interpret_scripts_from(&dir);
+        properties.load_registry(include_str!("../../../assets/blocks.toml"), "blocks.toml").unwrap();
+        let mut sim = Simulation::new(WorldSave::new_game(), properties);
+        let log = |sim: &Simulation| sim.objects[0].borrow().log_text();
+        sim.step(DELTA_T);
+        assert!(log(&sim).contains("  1\n"), "{}", log(&sim));
+
+        // The edited script runs from the start in every engine
+        std::fs::write(dir.join("engine.txt"), engine(2)).unwrap();
+        sim.reload_scripts().unwrap();
+        sim.step(DELTA_T);
+        assert_eq!(log(&sim).matches("  2\n").count(), 2, "{}", log(&sim));
+        assert!(!log(&sim).contains("  1\n"));
+
+        // A script which does not parse leaves the old one running
+        std::fs::write(dir.join("engine.txt"), "fn main( {\n").unwrap();
+        assert!(sim.reload_scripts().is_err());
+        sim.step(DELTA_T);
+        assert_eq!(log(&sim).matches("  2\n").count(), 4, "{}", log(&sim));
+        drop(sim);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn saved_simulations_carry_on() {
+        let sim = run(WorldSave::new_game(), 30);
+        let saved = sim.world_save();
+        let restored = Simulation::new(WorldSave::from_bytes(&saved.to_bytes()).unwrap(), load_block_properties());
+        let resaved = restored.world_save();
+        assert_eq!(ship(&resaved).chunks, ship(&saved).chunks);
+        assert!((ship(&resaved).body.pos - ship(&saved).body.pos).magnitude() < 1e-9);
+        let machines = |w: &WorldSave| ship(w).machines.iter().map(|(k, m)| (*k, m.to_bytes())).collect::<Vec<_>>();
+        assert_eq!(machines(&resaved), machines(&saved));
+        assert_eq!(ship(&resaved).tanks, ship(&saved).tanks);
+        assert_eq!(resaved.entities, saved.entities);
+    }
+}
@@ -24,10 +24,48 @@ impl ModelUniform {
 pub const CHUNK_SIZE: u32 = 16;
 const VERTEX_CAPACITY: usize = 0x10000;//TODO
 
+/// The blocks of a chunk. The model drawn from them is kept apart in a `GridModel`, so that grids exist without a window.
 pub struct CubeGrid {
     data: [Block; (CHUNK_SIZE*CHUNK_SIZE*CHUNK_SIZE) as usize],
     /// Offset of the chunk from the rigid body center (i.e. the center of mass)
     pub global_pos: Vector3<f64>,
+}
+
+impl CubeGrid {
+    pub fn new() -> Self {
+        Self {
+            data: [Block{id: 0, ori: 0}; (CHUNK_SIZE*CHUNK_SIZE*CHUNK_SIZE) as usize],
+            global_pos: Vector3::new(0., 0., 0.),
+        }
+    }
+
+    pub fn set_data(&mut self, data: [Block; (CHUNK_SIZE*CHUNK_SIZE*CHUNK_SIZE) as usize]) {
+        self.data = data
+    }
+
+    pub fn data(&self) -> &[Block; (CHUNK_SIZE*CHUNK_SIZE*CHUNK_SIZE) as usize] {
+        &self.data
+    }
+
+    pub fn demo(&mut self) {
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    self[(x,y,z)] = Block {id: 1, ori: 0};
+                }
+            }
+        }
+    }
+
+    pub fn get_uniform(&self, pos: Vector3<f64>, ori: Quaternion<f64>, camera: &Camera) -> ModelUniform {
+        let model = Matrix4::from_translation(pos + ori * self.global_pos - camera.pos)
+            * Matrix4::from(ori);
+        ModelUniform::new(model.cast().unwrap())
+    }
+}
+
+/// The buffers a chunk is drawn from
+pub struct GridModel {
     vertex_buffer: VertexBuffer<BlockVertex>,
     index_buffer: IndexBuffer,
     n_indices: u32,
@@ -36,17 +74,13 @@ pub struct CubeGrid {
     detail: usize,
 }
 
-impl CubeGrid {
+impl GridModel {
     pub fn new(graphics: &Graphics) -> Self {
-        let data = [Block{id: 0, ori: 0}; (CHUNK_SIZE*CHUNK_SIZE*CHUNK_SIZE) as usize];
-
         let vertex_buffer = VertexBuffer::new(graphics, VERTEX_CAPACITY);
         let index_buffer = IndexBuffer::new(graphics, VERTEX_CAPACITY/4*6);
         let buffer = UniformBuffer::new(graphics);
 
         Self {
-            data,
-            global_pos: Vector3::new(0., 0., 0.),
             vertex_buffer,
             index_buffer,
             n_indices: 0,
@@ -56,22 +90,8 @@ impl CubeGrid {
         }
     }
 
-    pub fn set_data(&mut self, data: [Block; (CHUNK_SIZE*CHUNK_SIZE*CHUNK_SIZE) as usize]) {
-        self.data = data
-    }
-
-    pub fn demo(&mut self) {
-        for x in 0..CHUNK_SIZE {
-            for y in 0..CHUNK_SIZE {
-                for z in 0..CHUNK_SIZE {
-                    self[(x,y,z)] = Block {id: 1, ori: 0};
-                }
-            }
-        }
-    }
-
     // Create vertex and index buffers from the block layout. face_mask should be set to one for hidden faces
-    pub fn update_model(&mut self, graphics: &Graphics, detail: usize, face_mask: u8) {
+    pub fn update(&mut self, graphics: &Graphics, grid: &CubeGrid, texture_columns: &[u8; 256], detail: usize, face_mask: u8) {
         // Right now I'm just making vertices for every block, including faces that don't face outwards.
         // OPTIMIZE implement face_mask
         let mut vertices = Vec::new();
@@ -85,8 +105,9 @@ impl CubeGrid {
         for x in (0..CHUNK_SIZE).step_by(detail_skip as usize) {
             for y in (0..CHUNK_SIZE).step_by(detail_skip as usize) {
                 for z in (0..CHUNK_SIZE).step_by(detail_skip as usize) {
-                    let block = self[(x,y,z)];
+                    let block = grid[(x,y,z)];
                     if block.is_null() {continue;}
+                    let column = texture_columns[block.id as usize] as u32;
 
                     let x0 = x;
                     let x1 = ((x+detail_skip) % 16) | (((x+detail_skip)/16) << 24);
@@ -95,9 +116,9 @@ impl CubeGrid {
                     let z0 = z << 8;
                     let z1 = (((z+detail_skip) % 16) << 8) | (((z+detail_skip)/16) << 26);
 
-                    if x == 0 || self[(x-detail_skip,y,z)].is_null() {
+                    if x == 0 || grid[(x-detail_skip,y,z)].is_null() {
                         let face = x0 | (1 << 12);
-                        let uv = block.get_uv_forward();
+                        let uv = block.get_uv_forward(column);
                         vertices.push(BlockVertex {data: uv.0|y0|z0|face});
                         vertices.push(BlockVertex {data: uv.1|y1|z0|face});
                         vertices.push(BlockVertex {data: uv.2|y1|z1|face});
@@ -110,9 +131,9 @@ impl CubeGrid {
                         indices.push(2+index_offset);
                         index_offset += 4;
                     }
-                    if y == 0 || self[(x,y-detail_skip,z)].is_null() {
+                    if y == 0 || grid[(x,y-detail_skip,z)].is_null() {
                         let face = y0 | (3 << 12);
-                        let uv = block.get_uv_left();
+                        let uv = block.get_uv_left(column);
                         vertices.push(BlockVertex {data: uv.0|x0|z0|face});
                         vertices.push(BlockVertex {data: uv.1|x1|z0|face});
                         vertices.push(BlockVertex {data: uv.2|x1|z1|face});
@@ -125,9 +146,9 @@ impl CubeGrid {
                         indices.push(3+index_offset);
                         index_offset += 4;
                     }
-                    if z == 0 || self[(x,y,z-detail_skip)].is_null() {
+                    if z == 0 || grid[(x,y,z-detail_skip)].is_null() {
                         let face = z0 | (5 << 12);
-                        let uv = block.get_uv_up();
+                        let uv = block.get_uv_up(column);
                         vertices.push(BlockVertex {data: uv.0|x0|y0|face});
                         vertices.push(BlockVertex {data: uv.1|x1|y0|face});
                         vertices.push(BlockVertex {data: uv.2|x1|y1|face});
@@ -140,9 +161,9 @@ impl CubeGrid {
                         indices.push(2+index_offset);
                         index_offset += 4;
                     }
-                    if x == last || self[(x+detail_skip,y,z)].is_null() {
+                    if x == last || grid[(x+detail_skip,y,z)].is_null() {
                         let face = x1; // Normal or constant face
-                        let uv = block.get_uv_backward();
+                        let uv = block.get_uv_backward(column);
                         vertices.push(BlockVertex {data: uv.0|y0|z0|face});
                         vertices.push(BlockVertex {data: uv.1|y1|z0|face});
                         vertices.push(BlockVertex {data: uv.2|y1|z1|face});
@@ -155,9 +176,9 @@ impl CubeGrid {
                         indices.push(3+index_offset);
                         index_offset += 4;
                     }
-                    if y == last || self[(x,y+detail_skip,z)].is_null() {
+                    if y == last || grid[(x,y+detail_skip,z)].is_null() {
                         let face = y1 | (2 << 12);
-                        let uv = block.get_uv_right();
+                        let uv = block.get_uv_right(column);
                         vertices.push(BlockVertex {data: uv.0|x0|z0|face});
                         vertices.push(BlockVertex {data: uv.1|x1|z0|face});
                         vertices.push(BlockVertex {data: uv.2|x1|z1|face});
@@ -170,9 +191,9 @@ impl CubeGrid {
                         indices.push(2+index_offset);
                         index_offset += 4;
                     }
-                    if z == last || self[(x,y,z+detail_skip)].is_null() {
+                    if z == last || grid[(x,y,z+detail_skip)].is_null() {
                         let face = z1 | (4 << 12);
-                        let uv = block.get_uv_down();
+                        let uv = block.get_uv_down(column);
                         vertices.push(BlockVertex {data: uv.0|x0|y0|face});
                         vertices.push(BlockVertex {data: uv.1|x1|y0|face});
                         vertices.push(BlockVertex {data: uv.2|x1|y1|face});
@@ -198,12 +219,6 @@ impl CubeGrid {
         self.detail = detail;
     }
 
-    pub fn get_uniform(&self, pos: Vector3<f64>, ori: Quaternion<f64>, camera: &Camera) -> ModelUniform {
-        let model = Matrix4::from_translation(pos + ori * self.global_pos - camera.pos)
-            * Matrix4::from(ori);
-        ModelUniform::new(model.cast().unwrap())
-    }
-
     pub fn draw(&self, renderer: &mut Renderer) {
         self.buffer.bind(renderer);
         self.vertex_buffer.bind(renderer);
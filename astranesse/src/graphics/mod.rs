@@ -5,16 +5,16 @@ mod camera;
 mod resource;
 mod lighting;
 mod font;
-mod block;
+pub(crate) mod block;
 
-pub use grid::{CubeGrid, GridTexture, CHUNK_SIZE, ModelUniform};
+pub use grid::{CubeGrid, GridModel, GridTexture, CHUNK_SIZE, ModelUniform};
 pub use camera::Camera;
 pub use lighting::Lighting;
 pub use shader::Shader;
 pub use vertex::*;
 pub use font::Font;
 pub use resource::*;
-pub use block::Block;
+pub use block::{Block, TEXTURE_COLUMNS};
 
 use std::sync::Arc;
 use winit::{dpi::PhysicalSize, event_loop::EventLoopProxy, window::Window};
@@ -66,6 +66,15 @@ impl ApplicationHandler<Graphics> for Initializer {
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|a| a == "sim") {
+        if let Err(e) = game::simulation::command(&args[1..]) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let event_loop = EventLoop::<Graphics>::with_user_event().build().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
 
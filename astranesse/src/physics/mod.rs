@@ -11,9 +11,10 @@ pub const NEWTON_G: f64 = 5.;
 const GRAVITY_THRESH: f64 = 10.;
 
 pub struct Physics {
-    pub rb_vendor: Vendor<RigidBodyData>,
     collision_pairs: Vec<(RigidBody, RigidBody)>,
     collision_normals: Vec<Vector3<f64>>,
+    // Declared last so the pairs above are dropped before the bodies they point into
+    pub rb_vendor: Vendor<RigidBodyData>,
 }
 
 impl Physics {
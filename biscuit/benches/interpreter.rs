@@ -0,0 +1,91 @@
+//! Compares the pre-decoded interpreter against decoding the bytecode as it runs, on the game's scripts in
+//! `assets/scripts/` and the samples in `tests/`. Run with
+//! `cargo bench -p biscuit --bench interpreter --features bench`.
+
+use std::time::{Duration, Instant};
+
+use std::path::PathBuf;
+
+use biscuit::{InterruptKind, Machine, MachineError, MachineOutput, bytecode::VariableType, compile_file, machine::InstructionData, util::Vendor};
+
+const INSTRUCTIONS: u64 = 2_000_000;
+const SLICE: usize = 1000;
+
+#[derive(Clone, Copy)]
+enum Interpreter {
+    Bytes,
+    Decoded,
+}
+
+/// Run a machine for `INSTRUCTIONS` instructions, and return the time taken
+fn run(machine: &mut Machine, interpreter: Interpreter) -> Result<Duration, MachineError> {
+    let start = Instant::now();
+    while machine.instructions_executed() < INSTRUCTIONS {
+        let before = machine.instructions_executed();
+        let output = match interpreter {
+            Interpreter::Bytes => machine.run_for_bytes(SLICE)?,
+            Interpreter::Decoded => machine.run_for(SLICE)?,
+        };
+        // Host calls do nothing, and those with a value return zero
+        if let MachineOutput::Call { func, .. } = output && func.return_type() != VariableType::Null {
+            machine.return_value(0.);
+        }
+        if machine.is_waiting() {
+            if machine.instructions_executed() == before {
+                // Nothing handles the interrupts, so start again
+                machine.reset();
+            }
+            // Keep scripts which react to interrupts busy
+            machine.interrupt(InterruptKind::Interact, 0.);
+            machine.interrupt(InterruptKind::Forward, 1.);
+        }
+    }
+    Ok(start.elapsed())
+}
+
+/// Scripts in a directory with the given extension, in order of name
+fn scripts(dir: &str, extension: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .expect("Could not read the scripts")
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == extension))
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn main() {
+    let mut paths = scripts(concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/scripts"), "txt");
+    paths.extend(scripts(concat!(env!("CARGO_MANIFEST_DIR"), "/tests"), "bisc"));
+
+    let mut vendor = Vendor::new();
+    println!("{:<20} {:>14} {:>14} {:>8}", "script", "bytes ns/op", "decoded ns/op", "speedup");
+    for path in paths {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let bytes = match compile_file(path.to_str().unwrap()) {
+            Ok(b) => b,
+            Err(e) => {
+                println!("{:<20} failed to compile: {}", name, e);
+                continue;
+            }
+        };
+        let instructions = vendor.insert(InstructionData::from_compiled(&bytes));
+
+        let mut results = Vec::new();
+        for interpreter in [Interpreter::Bytes, Interpreter::Decoded] {
+            let mut machine = Machine::new(instructions.clone(), SLICE);
+            match run(&mut machine, interpreter) {
+                Ok(r) => results.push(r),
+                Err(e) => {
+                    println!("{:<20} failed to run: {:?}", name, e);
+                    break;
+                }
+            }
+        }
+        let [bytes_time, decoded_time] = results[..] else { continue; };
+
+        let bytes_ns = bytes_time.as_nanos() as f64 / INSTRUCTIONS as f64;
+        let decoded_ns = decoded_time.as_nanos() as f64 / INSTRUCTIONS as f64;
+        println!("{:<20} {:>14.2} {:>14.2} {:>7.2}x", name, bytes_ns, decoded_ns, bytes_ns / decoded_ns);
+    }
+}
@@ -27,6 +27,13 @@ lazy_static! {
         }
         map
     };
+    static ref INTERRUPT_MAP_LOWER : FxHashMap<String, InterruptKind> = {
+        let mut map = FxHashMap::default();
+        for v in InterruptKind::iter() {
+            map.insert(v.to_string().to_lowercase(), v);
+        }
+        map
+    };
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -34,6 +41,10 @@ pub enum VariableType {
     Null,
     Float,
     List,
+    /// A record, by its index among the program's declared records
+    Record(u32),
+    /// A function, by the index of its signature
+    Function(u32),
 }
 
 #[repr(u8)]
@@ -78,6 +89,14 @@ pub enum Command {
     Div,        // Push T / N
     Neg,        // Push -T
     Pow,        // Push T**N
+
+    Hnd,        // Register address N as the handler for interrupt T
+    Wait,       // Yield until an interrupt is raised
+    Reti,       // Return from an interrupt handler
+
+    Jsr,        // Call A: jump to it, adding a frame to the call stack
+    Jsrp,       // Call T: pop it to IP, adding a frame to the call stack
+    Ret,        // Return from a call: pop T to IP, removing a frame from the call stack
 }
 
 #[repr(u8)]
@@ -86,6 +105,45 @@ pub enum GlobalFunction {
     Dbg,
     Tick,
     Interrupt,
+    Sleep,          // Yield for #1 ticks
+    #[strum(to_string = "wait_for_signal")]
+    WaitForSignal,  // Yield until the host signals the machine
+    Print,          // Log a formatted string. The format is passed first, see `encode_format`
+    Fuel,           // Return the fuel in the tanks piped to the block
+    #[strum(to_string = "fuel_capacity")]
+    FuelCapacity,   // Return how much fuel the tanks piped to the block can hold
+    #[strum(to_string = "draw_fuel")]
+    DrawFuel,       // Take up to #1 fuel from the tanks piped to the block, returning the amount taken
+    Thrust,         // Burn fuel to push at #1 of full thrust for a tick, returning the fraction of that fuel could power
+    #[strum(to_string = "add_force")]
+    AddForce,       // Push along the block's axes by #1, #2 and #3 for a tick
+    #[strum(to_string = "add_torque")]
+    AddTorque,      // Turn about the block's axes by #1, #2 and #3 for a tick
+    Send,           // Send #2 on a channel to the block at address #1 of the circuit, returning whether it was queued. The channel is passed first, see `encode_str`
+    Pending,        // Return the number of signals waiting for the block on a channel
+    Recv,           // Take the oldest signal waiting for the block on a channel, returning 0 if there are none
+    Velocity,       // Return the velocity along axis #1 of the block
+    #[strum(to_string = "angular_velocity")]
+    AngularVelocity, // Return the angular velocity about axis #1 of the block
+    Mass,           // Return the mass of the object the block is part of
+    Broadcast,      // Send #1 on a channel to every other block of the circuit, returning whether it was queued
+    Reply,          // Send #1 back to the sender of the last signal taken, on its channel, returning whether it was queued
+    Sender,         // Return the address of the sender of the last signal taken, or 0 if none has been
+    Address,        // Return the block's address on its circuit
+    Dropped,        // Return the number of signals lost since the last call because the block's inbox was full
+}
+
+/// Interrupts raised by the host. The discriminant is the value used in the bytecode.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, strum_macros::Display, strum_macros::EnumIter, TryFromPrimitive)]
+pub enum InterruptKind {
+    Interact = 1,
+    Forward,
+    Backward,
+    Left,
+    Right,
+    Up,
+    Down,
 }
 
 impl Command {
@@ -112,7 +170,7 @@ impl Command {
 
     pub fn takes_label_arg(&self) -> bool {
         match self {
-            Self::Jmp | Command::Jnz => true,
+            Self::Jmp | Command::Jnz | Command::Jsr => true,
             _ => false,
         }
     }
@@ -131,6 +189,113 @@ impl GlobalFunction {
             GlobalFunction::Dbg => VariableType::Null,
             GlobalFunction::Tick => VariableType::Null,
             GlobalFunction::Interrupt => VariableType::Null,
+            GlobalFunction::Sleep => VariableType::Null,
+            GlobalFunction::WaitForSignal => VariableType::Null,
+            GlobalFunction::Print => VariableType::Null,
+            GlobalFunction::Fuel => VariableType::Float,
+            GlobalFunction::FuelCapacity => VariableType::Float,
+            GlobalFunction::DrawFuel => VariableType::Float,
+            GlobalFunction::Thrust => VariableType::Float,
+            GlobalFunction::AddForce => VariableType::Null,
+            GlobalFunction::AddTorque => VariableType::Null,
+            GlobalFunction::Send => VariableType::Float,
+            GlobalFunction::Pending => VariableType::Float,
+            GlobalFunction::Recv => VariableType::Float,
+            GlobalFunction::Velocity => VariableType::Float,
+            GlobalFunction::AngularVelocity => VariableType::Float,
+            GlobalFunction::Mass => VariableType::Float,
+            GlobalFunction::Broadcast => VariableType::Float,
+            GlobalFunction::Reply => VariableType::Float,
+            GlobalFunction::Sender => VariableType::Float,
+            GlobalFunction::Address => VariableType::Float,
+            GlobalFunction::Dropped => VariableType::Float,
+        }
+    }
+
+    /// Argument types, or None if any number of floats may be passed. Data written by the compiler, such as a
+    /// format or channel, is not counted.
+    pub fn arguments(&self) -> Option<&'static [VariableType]> {
+        match self {
+            GlobalFunction::Dbg => None,
+            GlobalFunction::Tick => Some(&[]),
+            GlobalFunction::Interrupt => None,
+            GlobalFunction::Sleep => Some(&[VariableType::Float]),
+            GlobalFunction::WaitForSignal => Some(&[]),
+            GlobalFunction::Print => None,
+            GlobalFunction::Fuel => Some(&[]),
+            GlobalFunction::FuelCapacity => Some(&[]),
+            GlobalFunction::DrawFuel => Some(&[VariableType::Float]),
+            GlobalFunction::Thrust => Some(&[VariableType::Float]),
+            GlobalFunction::AddForce => Some(&[VariableType::Float, VariableType::Float, VariableType::Float]),
+            GlobalFunction::AddTorque => Some(&[VariableType::Float, VariableType::Float, VariableType::Float]),
+            GlobalFunction::Send => Some(&[VariableType::Float, VariableType::Float]),
+            GlobalFunction::Pending => Some(&[]),
+            GlobalFunction::Recv => Some(&[]),
+            GlobalFunction::Velocity => Some(&[VariableType::Float]),
+            GlobalFunction::AngularVelocity => Some(&[VariableType::Float]),
+            GlobalFunction::Mass => Some(&[]),
+            GlobalFunction::Broadcast => Some(&[VariableType::Float]),
+            GlobalFunction::Reply => Some(&[VariableType::Float]),
+            GlobalFunction::Sender => Some(&[]),
+            GlobalFunction::Address => Some(&[]),
+            GlobalFunction::Dropped => Some(&[]),
+        }
+    }
+}
+
+/// Encode the format string of a `print` call, which is passed before the values as its length and characters
+pub fn encode_format(format: &str) -> Vec<f64> {
+    encode_str(format)
+}
+
+/// Encode a string literal passed to a host function, such as the channel of `send`, as its length and characters
+pub fn encode_str(s: &str) -> Vec<f64> {
+    std::iter::once(s.len() as f64).chain(s.bytes().map(|b| b as f64)).collect()
+}
+
+/// Split a string encoded by `encode_str` from the values after it. Returns None if the arguments do not start
+/// with one.
+pub fn decode_str(args: &[f64]) -> Option<(String, &[f64])> {
+    let (len, rest) = args.split_first()?;
+    if !(0. ..=rest.len() as f64).contains(len) { return None; }
+    let (chars, values) = rest.split_at(*len as usize);
+    let mut s = String::with_capacity(chars.len());
+    for c in chars {
+        if !(0. ..128.).contains(c) { return None; }
+        s.push(*c as u8 as char);
+    }
+    Some((s, values))
+}
+
+/// Format the arguments of a `print` call, replacing each `{}` with the next value. Returns None if the
+/// arguments were not encoded by `encode_format`.
+pub fn format_print(args: &[f64]) -> Option<String> {
+    let (format, values) = decode_str(args)?;
+    let mut values = values.iter();
+    let mut out = String::with_capacity(format.len());
+    let mut pieces = format.split("{}");
+    out.push_str(pieces.next()?);
+    for piece in pieces {
+        out.push_str(&values.next()?.to_string());
+        out.push_str(piece);
+    }
+    Some(out)
+}
+impl InterruptKind {
+    pub fn from_string(s: &str) -> Option<Self> {
+        INTERRUPT_MAP_LOWER.get(s).copied()
+    }
+
+    /// Whether the handler receives a float argument (e.g. the throttle of a direction)
+    pub fn takes_arg(&self) -> bool {
+        !matches!(self, InterruptKind::Interact)
+    }
+
+    /// Higher priority interrupts are dispatched first, and may preempt lower priority handlers
+    pub fn default_priority(&self) -> u8 {
+        match self {
+            InterruptKind::Interact => 1,
+            _ => 0,
         }
     }
-}
\ No newline at end of file
+}
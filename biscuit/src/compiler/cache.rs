@@ -0,0 +1,141 @@
+// Reuses the code of functions which are unchanged since an earlier compilation
+use std::{collections::BTreeMap, hash::{DefaultHasher, Hash, Hasher}};
+
+use rustc_hash::FxHashMap;
+
+use crate::{bytecode::VariableType, compiler::{Compiler, implementer::FunctionCode, link}, parser::SyntaxNode};
+
+/// Compiled functions, by a hash of everything their code depends on: their source, and what the names in it
+/// refer to. Positions are not part of the hash, so moving a function or editing another one keeps it cached,
+/// and libraries shared by several scripts are compiled once.
+#[derive(Default)]
+pub struct CompileCache {
+    functions: FxHashMap<u64, FunctionCode>,
+    hits: u64,
+    misses: u64,
+}
+
+impl CompileCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compile a string of Biscuit code, reusing the functions found in the cache. The binary is the same as
+    /// the one `compile_str` gives.
+    pub fn compile_str(&mut self, s: &str, filename: &str) -> Result<Vec<u8>, String> {
+        let tokens = crate::parser::load_str(s, filename)?;
+        let tree = SyntaxNode::tree(tokens)?;
+        let compiler = Compiler::new(&tree)?;
+        compiler.roots(&tree)?;
+
+        // Every function is checked, as with a full compilation, but only the reachable ones are linked
+        let mut names: Vec<&String> = compiler.functions.iter()
+            .filter(|(name, function)| *name != "const" && !function.external)
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        let mut functions = BTreeMap::new();
+        for name in names {
+            let key = function_key(&compiler, name);
+            let code = match self.functions.get(&key) {
+                Some(code) => {
+                    self.hits += 1;
+                    code.clone()
+                },
+                None => {
+                    self.misses += 1;
+                    let ssa = compiler.functions[name].compile(&compiler.functions, &compiler.declarations)?;
+                    let code = compiler.write(name, &ssa);
+                    self.functions.insert(key, code.clone());
+                    code
+                },
+            };
+            functions.insert(name.to_owned(), code);
+        }
+        link(&[compiler.object(filename, functions)])
+    }
+
+    /// Functions found in the cache, over all compilations
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Functions compiled because they were not in the cache, over all compilations
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Number of functions cached
+    pub fn len(&self) -> usize {
+        self.functions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.functions.clear();
+    }
+}
+
+/// Hash a function with the declarations of everything it can refer to
+fn function_key(compiler: &Compiler, name: &str) -> u64 {
+    let function = &compiler.functions[name];
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    function.node.hash(&mut hasher);
+    for (argument, typ) in &function.arguments {
+        argument.hash(&mut hasher);
+        compiler.declarations.type_name(*typ).hash(&mut hasher);
+    }
+    compiler.declarations.type_name(function.return_value).hash(&mut hasher);
+
+    // Names used in the body may be other functions or records, and records may come in as arguments
+    let mut names = Vec::new();
+    identifiers(&function.node, &mut names);
+    for (_, typ) in &function.arguments {
+        record_names(compiler, *typ, &mut names);
+    }
+    names.sort();
+    names.dedup();
+    for name in names {
+        if let Some(other) = compiler.functions.get(name) && name != "const" {
+            name.hash(&mut hasher);
+            for (_, typ) in &other.arguments {
+                compiler.declarations.type_name(*typ).hash(&mut hasher);
+            }
+        } else if let Some(record) = compiler.declarations.record(name) {
+            name.hash(&mut hasher);
+            compiler.declarations.records[record as usize].fields.hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+/// Collect the words in a node, with `variable.field` counting as `variable`
+fn identifiers<'a>(node: &'a SyntaxNode, out: &mut Vec<&'a str>) {
+    match node {
+        SyntaxNode::Unclassified(token) => out.push(token.get_inner().split('.').next().unwrap()),
+        SyntaxNode::Number(_) | SyntaxNode::Str(_) => (),
+        SyntaxNode::Adjacent(nodes) | SyntaxNode::IfChain(nodes) => for node in nodes {
+            identifiers(node, out);
+        },
+        SyntaxNode::Parenthesis(_, n) | SyntaxNode::List(_, n) | SyntaxNode::Unop(_, n) => identifiers(n, out),
+        SyntaxNode::Binop(_, n1, n2) | SyntaxNode::Block(n1, n2) => {
+            identifiers(n1, out);
+            identifiers(n2, out);
+        },
+    }
+}
+
+/// Collect the records in a type, including those in the arguments of function types
+fn record_names<'a>(compiler: &'a Compiler, typ: VariableType, out: &mut Vec<&'a str>) {
+    match typ {
+        VariableType::Record(record) => out.push(&compiler.declarations.records[record as usize].name),
+        VariableType::Function(signature) => for typ in &compiler.declarations.signatures[signature as usize].arguments {
+            record_names(compiler, *typ, out);
+        },
+        VariableType::Null | VariableType::Float | VariableType::List => (),
+    }
+}
@@ -0,0 +1,37 @@
+// Debug info: where the code at each address of a binary came from
+
+/// The function and source line of each address in a binary, written by the linker. Binaries do not hold it,
+/// so it is kept next to them by whatever needs it, such as the profiler.
+#[derive(Clone, Debug, Default)]
+pub struct DebugInfo {
+    /// Address of each function, with its name and the module defining it, in order of address
+    pub(crate) functions: Vec<(usize, String, String)>,
+    /// Address at which the code of each source line starts, in order of address
+    pub(crate) lines: Vec<(usize, u32)>,
+}
+
+impl DebugInfo {
+    /// The function whose code holds the address. The handler registration before `main` is in none.
+    pub fn function(&self, ip: usize) -> Option<&str> {
+        self.function_entry(ip).map(|(_, name, _)| name.as_str())
+    }
+
+    /// The module defining the function whose code holds the address
+    pub fn module(&self, ip: usize) -> Option<&str> {
+        self.function_entry(ip).map(|(_, _, module)| module.as_str())
+    }
+
+    /// The source line the code at the address was written for, counting from 1. Functions linked from object
+    /// files have no lines.
+    pub fn line(&self, ip: usize) -> Option<u32> {
+        let (start, _, _) = self.function_entry(ip)?;
+        let index = self.lines.partition_point(|(pos, _)| *pos <= ip).checked_sub(1)?;
+        let (pos, line) = self.lines[index];
+        (pos >= *start).then_some(line)
+    }
+
+    fn function_entry(&self, ip: usize) -> Option<&(usize, String, String)> {
+        let index = self.functions.partition_point(|(pos, _, _)| *pos <= ip).checked_sub(1)?;
+        Some(&self.functions[index])
+    }
+}
@@ -0,0 +1,143 @@
+// Text dumps of each stage of compilation, for debugging the compiler
+use std::{collections::BTreeMap, fmt::Write};
+
+use rustc_hash::FxHashMap;
+
+use crate::{compiler::ssa::{Branch, Location, Ssa}, machine::decode::{Decoded, Op}};
+
+/// A stage of compilation which can be dumped as text
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum_macros::Display, strum_macros::EnumString, strum_macros::EnumIter)]
+#[strum(serialize_all = "lowercase")]
+pub enum Stage {
+    /// Tokens read from the source
+    Tokens,
+    /// The syntax tree
+    Ast,
+    /// The SSA of each function: its instructions with their types, then the order they are written in
+    Ssa,
+    /// The bytecode, with the SSA instruction and stack layout each part implements
+    Asm,
+}
+
+/// Collects the dumps of the requested stages
+pub(crate) struct Dumps {
+    stages: Vec<Stage>,
+    dumps: Vec<(Stage, String)>,
+}
+
+impl Dumps {
+    pub fn new(stages: &[Stage]) -> Self {
+        Self {
+            stages: stages.to_vec(),
+            dumps: Vec::new(),
+        }
+    }
+
+    /// Add a dump, which is only generated if the stage was requested
+    pub fn add(&mut self, stage: Stage, dump: impl FnOnce() -> String) {
+        if self.stages.contains(&stage) {
+            self.dumps.push((stage, dump()));
+        }
+    }
+
+    pub fn into_inner(self) -> Vec<(Stage, String)> {
+        self.dumps
+    }
+}
+
+/// Write every function's SSA, in order of name. The instructions, variables and branches are the unordered SSA,
+/// and the `order` and `last used` lines are what ordering adds to it.
+pub(crate) fn dump_ssa(functions: &BTreeMap<String, Ssa>) -> String {
+    let mut out = String::new();
+    for (name, ssa) in functions {
+        writeln!(out, "fn {}", name).unwrap();
+        write_ssa(ssa, 1, &mut out);
+    }
+    out
+}
+
+fn write_ssa(ssa: &Ssa, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+
+    let mut indices: Vec<u32> = ssa.instructions.keys().copied().collect();
+    indices.sort();
+    for index in indices {
+        let loc = Location::internal(index);
+        let typ = ssa.types.get(&loc).map_or("?".to_owned(), |t| format!("{:?}", t));
+        let position = ssa.spans.get(&index).map_or("-".to_owned(), |s| s.to_string());
+        writeln!(out, "{}{:?}: {} = {:?} @ {}", indent, loc, typ, ssa.instructions[&index], position).unwrap();
+    }
+
+    let mut variables: Vec<_> = ssa.declared_variables.iter().collect();
+    variables.sort_by_key(|(name, _)| *name);
+    let variables: Vec<String> = variables.iter().map(|(name, loc)| format!("{}={:?}", name, loc)).collect();
+    writeln!(out, "{}variables {}", indent, variables.join(" ")).unwrap();
+    if !ssa.return_variables.is_empty() {
+        writeln!(out, "{}returns {:?}", indent, ssa.return_variables).unwrap();
+    }
+    match ssa {
+        Ssa::Ordered { instruction_order, last_used, .. } => {
+            let order: Vec<String> = instruction_order.iter().map(|i| format!("{:04}", i)).collect();
+            writeln!(out, "{}order {}", indent, order.join(" ")).unwrap();
+            let mut last_used: Vec<_> = last_used.iter().collect();
+            last_used.sort();
+            let last_used: Vec<String> = last_used.iter().map(|(i, user)| match **user {
+                u32::MAX => format!("{:04}->return", i),
+                user => format!("{:04}->{:04}", i, user),
+            }).collect();
+            writeln!(out, "{}last used {}", indent, last_used.join(" ")).unwrap();
+        },
+        Ssa::Unordered { .. } => writeln!(out, "{}unordered", indent).unwrap(),
+    }
+
+    for (i, branch) in ssa.branches.iter().enumerate() {
+        match branch {
+            Branch::If(items, els) => {
+                writeln!(out, "{}branch {}: if", indent, i).unwrap();
+                for (j, (body, condition)) in items.iter().enumerate() {
+                    writeln!(out, "{}  condition {}", indent, j).unwrap();
+                    write_ssa(condition, depth + 2, out);
+                    writeln!(out, "{}  body {}", indent, j).unwrap();
+                    write_ssa(body, depth + 2, out);
+                }
+                if let Some(els) = els {
+                    writeln!(out, "{}  else", indent).unwrap();
+                    write_ssa(els, depth + 2, out);
+                }
+            },
+            Branch::Loop(body) => {
+                writeln!(out, "{}branch {}: loop", indent, i).unwrap();
+                write_ssa(body, depth + 1, out);
+            },
+        }
+    }
+}
+
+/// Write the bytecode one instruction per line, preceded by the labels and notes at its address
+pub(crate) fn dump_asm(code: &[u8], notes: &[(usize, String)]) -> String {
+    let mut notes_at: FxHashMap<usize, Vec<&str>> = FxHashMap::default();
+    for (pos, note) in notes {
+        notes_at.entry(*pos).or_default().push(note);
+    }
+
+    let mut out = String::new();
+    let mut ip = 0;
+    while ip < code.len() {
+        for note in notes_at.get(&ip).into_iter().flatten() {
+            writeln!(out, "; {}", note).unwrap();
+        }
+        let decoded = Decoded::decode(code, ip);
+        let text = match decoded.op {
+            Op::Bare(command) => command.to_string().to_lowercase(),
+            Op::Push(v) => format!("push {}", v),
+            Op::Jmp(target) => format!("jmp {:06}", target),
+            Op::Jnz(target) => format!("jnz {:06}", target),
+            Op::Jsr(target) => format!("jsr {:06}", target),
+            Op::Call(func) => format!("call {}", func.to_string().to_uppercase()),
+            _ => format!("invalid {:#04x}", code[ip]),
+        };
+        writeln!(out, "{:06} {}", ip, text).unwrap();
+        ip += decoded.len as usize;
+    }
+    out
+}
@@ -6,41 +6,143 @@
 use rustc_hash::FxHashMap;
 use sorted_vec::SortedSet;
 
-use crate::{Command, compiler::ssa::{Branch, Location}};
+use crate::{Command, bytecode::VariableType, compiler::ssa::{Branch, Location}};
 
 use super::ssa::{Ssa, Instruction};
 
+/// The code of one function, with the places the linker fills in once every function has an address
+#[derive(Clone, Debug)]
+pub(crate) struct FunctionCode {
+    pub code: Vec<u8>,
+    /// Positions of the targets of jumps to other functions
+    pub calls: Vec<(usize, String)>,
+    /// Positions of the addresses of functions pushed as values
+    pub function_values: Vec<(usize, String)>,
+    /// Positions of jump targets, which are relative to the start of the function
+    pub jumps: Vec<usize>,
+    /// Offset at which each SSA instruction is written, with a description and the stack after it
+    pub layout: Vec<(usize, String)>,
+    /// Offset at which the code of each source line starts, in order
+    pub lines: Vec<(usize, u32)>,
+}
+impl FunctionCode {
+    /// Functions which this one calls or uses as values
+    pub fn uses(&self) -> impl Iterator<Item = &String> {
+        self.calls.iter().chain(&self.function_values).map(|(_, name)| name)
+    }
+
+    /// Shift all the jump targets to account for this code being placed at `offset`
+    pub fn relocate(&mut self, offset: usize) {
+        for pos in &self.jumps {
+            offset_jump(&mut self.code, *pos, offset);
+        }
+    }
+
+    pub fn replace_calls(&mut self, locations: &FxHashMap<String, usize>) {
+        for (pos, name) in &self.calls {
+            self.code.splice(*pos..pos+8, (locations[name] as u64).to_le_bytes());
+        }
+        for (pos, name) in &self.function_values {
+            self.code.splice(*pos..pos+8, (locations[name] as f64).to_le_bytes());
+        }
+    }
+}
+
+fn offset_jump(code: &mut [u8], pos: usize, offset: usize) {
+    let mut target = [0; 8];
+    target.copy_from_slice(&code[pos..pos+8]);
+    let target = u64::from_le_bytes(target) + offset as u64;
+    code[pos..pos+8].copy_from_slice(&target.to_le_bytes());
+}
+
 #[derive(Debug)]
 pub(crate) struct Bytecode<'a> {
     ssa: &'a Ssa,
     bytecode: Vec<u8>,
     running_stack: Vec<Location>,
     functions: Vec<(usize, String)>,
+    /// Positions of the addresses of functions pushed as values
+    function_values: Vec<(usize, String)>,
+    /// Positions of jump targets, which are relative to the start of this bytecode
+    jumps: Vec<usize>,
     written_branches: SortedSet<usize>,
+    /// Offset at which each SSA instruction is written, with a description and the stack after it
+    layout: Vec<(usize, String)>,
+    /// Offset at which the code of each source line starts
+    lines: Vec<(usize, u32)>,
+    /// Whether the function returns with `ret`, so that a call it ends with may take over its frame
+    tail_calls: bool,
+    /// Lists from enclosing scopes, and the location that allocated each if the function must free it
+    outer_roots: FxHashMap<Location, Option<Location>>,
 }
 impl<'a> Bytecode<'a> {
-    pub fn new(ssa: &'a Ssa) -> Self {
-        dbg!(ssa);
+    pub fn new(ssa: &'a Ssa, tail_calls: bool) -> Self {
         let mut bytecode = Self {
             ssa,
             bytecode: Vec::new(),
             running_stack: Vec::new(),
             functions: Vec::new(),
+            function_values: Vec::new(),
+            jumps: Vec::new(),
             written_branches: SortedSet::new(),
+            layout: Vec::new(),
+            lines: Vec::new(),
+            tail_calls,
+            outer_roots: FxHashMap::default(),
         };
+        bytecode.write_all();
+        bytecode
+    }
+
+    fn write_all(&mut self) {
+        let ssa = self.ssa;
         for (instruction_index, (location, _)) in ssa.iter().enumerate() {
-            bytecode.pop_unused(instruction_index);
-            bytecode.write_bytecode(location);
+            let start = self.bytecode.len();
+            let entry = self.layout.len();
+            let line_entry = self.lines.len();
+            // Arguments are already on the stack, so nothing can be popped until all of them are accounted for
+            if !matches!(ssa.instructions[&location], Instruction::Argument) {
+                self.pop_unused(instruction_index);
+            }
+            self.write_bytecode(location);
+            self.record_layout(entry, start, location);
+            if let Some(span) = ssa.spans.get(&location) {
+                // Like the layout, branches come before their contents
+                self.lines.insert(line_entry, (start, span.line()));
+            }
         }
-        bytecode.dump_scope();
-        bytecode
+        self.finish_scope();
     }
 
-    pub fn len(&self) -> usize {
-        self.bytecode.len()
+    fn record_layout(&mut self, entry: usize, start: usize, op: u32) {
+        let stack = self.running_stack.iter().map(|l| format!("{:?}", l)).collect::<Vec<_>>().join(" ");
+        let text = format!("{:?} = {:?} [{}]", Location::internal(op), self.ssa.instructions[&op], stack);
+        // Branches are recorded after their contents, but are listed before them
+        self.layout.insert(entry, (start, text));
     }
-    pub fn code(&self) -> &[u8] {
-        &self.bytecode
+
+    /// Append a command which takes no arguments
+    pub fn push_command(&mut self, command: Command) {
+        self.bytecode.push(command as u8);
+    }
+
+    /// The finished code, which no longer needs the SSA
+    pub fn into_code(self) -> FunctionCode {
+        FunctionCode {
+            code: self.bytecode,
+            calls: self.functions,
+            function_values: self.function_values,
+            jumps: self.jumps,
+            layout: self.layout,
+            lines: self.lines,
+        }
+    }
+
+    /// Shift all the jump targets to account for this bytecode being placed at `offset`
+    fn relocate(&mut self, offset: usize) {
+        for pos in &self.jumps {
+            offset_jump(&mut self.bytecode, *pos, offset);
+        }
     }
 
     /// Pop all the unused items in the stack until a used item is at top
@@ -180,15 +282,15 @@ impl<'a> Bytecode<'a> {
             },
             Instruction::Neg(a) => {
                 self.set_state(&[*a]);
-                self.bytecode.push(Command::Not as u8);
+                self.bytecode.push(Command::Neg as u8);
                 self.running_stack.pop();
                 self.running_stack.push(Location::internal(op));
             },
             Instruction::Ld(adr, idx) => {
+                // The vector stays below the item
                 self.set_state(&[*idx, *adr]);
                 self.bytecode.push(Command::Ld as u8);
                 self.running_stack.pop();
-                self.running_stack.pop();
                 self.running_stack.push(Location::internal(op));
             },
             Instruction::St(adr, idx, val) => {
@@ -199,6 +301,12 @@ impl<'a> Bytecode<'a> {
                 self.running_stack.pop();
                 self.running_stack.push(Location::internal(op));
             },
+            Instruction::Record(_, adr) => {
+                // Only the type changes
+                self.set_state(&[*adr]);
+                self.running_stack.pop();
+                self.running_stack.push(Location::internal(op));
+            },
             Instruction::Stb(adr, val) => {
                 self.set_state(&[*val, *adr]);
                 self.bytecode.push(Command::Stb as u8);
@@ -211,82 +319,208 @@ impl<'a> Bytecode<'a> {
                 self.bytecode.push(Command::Call as u8);
                 self.bytecode.push(*func);
                 self.running_stack.pop();
+                // The host pushes the value returned, if any
+                if self.ssa.types[&Location::internal(op)] != crate::bytecode::VariableType::Null {
+                    self.running_stack.push(Location::internal(op));
+                }
+            },
+            Instruction::LiteralFunction(name) => {
+                self.bytecode.push(Command::Push as u8);
+                self.function_values.push((self.bytecode.len(), name.to_owned()));
+                self.bytecode.extend(0f64.to_le_bytes());
+                self.running_stack.push(Location::internal(op));
+            },
+            Instruction::DynamicCall(_, callee, args) if self.is_tail_call(op, args) => {
+                let mut target = vec![*callee];
+                target.extend(args.iter().rev());
+                self.write_tail_call(&target);
+                self.bytecode.push(Command::Jpop as u8);
+            },
+            Instruction::DynamicCall(_, callee, args) => {
+                // Called like a local function, but jumps to the address held by the callee
+                let height = self.running_stack.len();
+                let start = self.bytecode.len();
+                self.bytecode.push(Command::Pip as u8);
+                self.bytecode.push(Command::Push as u8);
+                self.bytecode.extend(0f64.to_le_bytes());
+                self.bytecode.push(Command::Add as u8);
+                self.running_stack.push(Location::internal(op));
+                let args: Vec<Location> = args.iter().rev().copied().collect();
+                self.set_state(&args);
+                self.set_state(&[*callee]);
+                self.bytecode.push(Command::Jsrp as u8);
+                let return_offset = (self.bytecode.len() - start) as f64;
+                self.bytecode.splice(start+2..start+10, return_offset.to_le_bytes());
+                self.running_stack.truncate(height);
+            },
+            Instruction::LocalCall(name, args) if self.is_tail_call(op, args) => {
+                let target: Vec<Location> = args.iter().rev().copied().collect();
+                self.write_tail_call(&target);
+                self.bytecode.push(Command::Jmp as u8);
+                self.functions.push((self.bytecode.len(), name.to_owned()));
+                self.bytecode.extend(0u64.to_le_bytes());
             },
             Instruction::LocalCall(name, args) => {
-                self.set_state(&[*args]);
+                // Push the return address, which is just after the jump, then copies of the arguments
+                let height = self.running_stack.len();
+                let start = self.bytecode.len();
+                self.bytecode.push(Command::Pip as u8);
+                self.bytecode.push(Command::Push as u8);
+                self.bytecode.extend(0f64.to_le_bytes());
+                self.bytecode.push(Command::Add as u8);
+                self.running_stack.push(Location::internal(op));
+                let args: Vec<Location> = args.iter().rev().copied().collect();
+                self.set_state(&args);
+
+                // The callee leaves nothing but the return address, which it returns to
+                self.bytecode.push(Command::Jsr as u8);
                 self.functions.push((self.bytecode.len(), name.to_owned()));
-                self.running_stack.pop();
+                self.bytecode.extend(0u64.to_le_bytes());
+                let return_offset = (self.bytecode.len() - start) as f64;
+                self.bytecode.splice(start+2..start+10, return_offset.to_le_bytes());
+                self.running_stack.truncate(height);
+            },
+            Instruction::Theta(branch, _) if matches!(self.ssa.branches[*branch], Branch::Loop(_)) => {
+                // Loop variables start with their value before the loop
+                self.set_state(&[self.ssa.theta_origins[&op]]);
+                *self.running_stack.last_mut().unwrap() = Location::internal(op);
             },
             Instruction::Theta(branch, _) | Instruction::Action(branch) => if !self.written_branches.contains(branch) {
                 match &self.ssa.branches[*branch] {
-                    Branch::If(items, ssa) => {
-                        // Determine the final layout
-                        let mut theta_names = Vec::new();
-                        for (_, instruction) in self.ssa.iter() {
-                            if let Instruction::Theta(b, n) = instruction {
-                                if b == branch {
-                                    theta_names.push(n.clone());
-                                }
-                            }
-                        }
-
-                        let mut terminal_locations = Vec::new();
-
-                        // Embed the branch code, with branching logic
-                        for (ssa, condition) in items {
-                            let branch = self.compile_branch(&condition);
-                            self.embed_branch(branch);
-
-                            self.bytecode.push(Command::Jnz as u8);
-                            let start = self.bytecode.len();
-                            self.bytecode.extend(0u64.to_le_bytes());
-                            
-                            // Roll to match scope
-                            let mut branch = self.compile_branch(&ssa);
-                            branch.roll_scope_names(&theta_names);
-                            self.embed_branch(branch);
-                            
-                            self.bytecode.push(Command::Jmp as u8);
-                            terminal_locations.push(self.bytecode.len());
-                            self.bytecode.extend(0u64.to_le_bytes());
-                            self.bytecode.splice(start..start+8, (self.bytecode.len() as u64).to_le_bytes());
-                        }
-                        if let Some(ssa) = ssa {
-                            // Else
-                            let mut branch = self.compile_branch(&ssa);
-                            branch.roll_scope_names(&theta_names);
-                            self.embed_branch(branch);
-                        }
-                        
-                        for loc in terminal_locations {
-                            self.bytecode.splice(loc..loc+8, (self.bytecode.len() as u64).to_le_bytes());
-                        }
-                    },
-                    Branch::Loop(ssa) => {
-                        // Embed the branch code, with branching logic
-                        let start = self.bytecode.len() as u64;
-                        let branch = self.compile_branch(&ssa);
-                        self.embed_branch(branch);
-                        self.bytecode.push(Command::Jmp as u8);
-                        self.bytecode.extend(start.to_le_bytes());
-                    },
+                    Branch::If(items, els) => self.write_if(*branch, items, els.as_ref()),
+                    Branch::Loop(body) => self.write_loop(*branch, body),
                 }
                 self.written_branches.push(*branch);
             },
         }
     }
 
-    /// Dump all scope not relevant for the return statement
-    fn dump_scope(&mut self) {
-        while self.running_stack.len() > self.ssa.return_variables.len() {
-            if self.ssa.return_variables.contains(self.running_stack.last().unwrap()) {
-                self.bytecode.push(Command::Push as u8);
-                self.bytecode.extend(&(self.running_stack.len() as f64).to_le_bytes());
-                self.bytecode.push(Command::Roll as u8);
-                self.running_stack.rotate_left(1);
-            } else {
-                self.pop()
-            }
+    /// Whether a call is the last thing its function does, and passes no lists, so that it can take over the
+    /// frame of the function. Lists are freed by the function which allocates them, which would have returned.
+    fn is_tail_call(&self, op: u32, args: &[Location]) -> bool {
+        self.tail_calls && self.ssa.tail_call == Some(op) && args.iter().all(|a| !is_vector(self.ssa.types[a]))
+    }
+
+    /// Replace everything the function has on the stack with the targets, leaving them just above the return
+    /// address, and free the lists it owns. The code after this is never run, so the stack is left as it was.
+    fn write_tail_call(&mut self, target: &[Location]) {
+        let stack = self.running_stack.clone();
+        let in_order: Vec<Location> = target.iter().rev().copied().collect();
+        if !self.running_stack.ends_with(&in_order) {
+            self.set_state(target);
+        }
+        while self.running_stack.len() > target.len() {
+            self.roll(target.len() + 1);
+            let item = self.running_stack.pop().unwrap();
+            let owned = match is_vector(self.ssa.types[&item]) {
+                true => self.owned_root(item),
+                false => None,
+            };
+            // Lists held in several places are freed with the last of them
+            let last = owned.is_some_and(|root| !self.running_stack.iter()
+                .any(|l| is_vector(self.ssa.types[l]) && self.owned_root(*l) == Some(root)));
+            self.bytecode.push(match last {
+                true => Command::Drop,
+                false => Command::Pop,
+            } as u8);
+        }
+        self.running_stack = stack;
+    }
+
+    /// The thetas of a branch, in order
+    fn thetas(&self, branch: usize) -> Vec<u32> {
+        let mut thetas: Vec<u32> = self.ssa.instructions.iter()
+            .filter(|(_, i)| matches!(i, Instruction::Theta(b, _) if *b == branch))
+            .map(|(index, _)| *index)
+            .collect();
+        thetas.sort();
+        thetas
+    }
+
+    /// Write each condition and body of an if statement. Every path leaves the values of the thetas on the stack.
+    fn write_if(&mut self, branch: usize, items: &'a [(Ssa, Ssa)], els: Option<&'a Ssa>) {
+        let thetas = self.thetas(branch);
+        let height = self.running_stack.len();
+        let mut ends = Vec::new();
+        for (body, condition) in items {
+            let condition = self.compile_branch(condition);
+            self.embed_branch(condition);
+            self.running_stack.truncate(height);
+            self.bytecode.push(Command::Not as u8);
+            self.bytecode.push(Command::Jnz as u8);
+            let next = self.bytecode.len();
+            self.jumps.push(next);
+            self.bytecode.extend(0u64.to_le_bytes());
+
+            let body = self.compile_branch(body);
+            self.embed_branch(body);
+            self.running_stack.truncate(height);
+            self.bytecode.push(Command::Jmp as u8);
+            ends.push(self.bytecode.len());
+            self.jumps.push(self.bytecode.len());
+            self.bytecode.extend(0u64.to_le_bytes());
+            self.bytecode.splice(next..next+8, (self.bytecode.len() as u64).to_le_bytes());
+        }
+        match els {
+            Some(els) => {
+                let els = self.compile_branch(els);
+                self.embed_branch(els);
+            },
+            // Without an else, the values are unchanged if no condition holds
+            None => {
+                let origins: Vec<Location> = thetas.iter().rev().map(|t| self.ssa.theta_origins[t]).collect();
+                self.set_state(&origins);
+            },
+        }
+        for end in ends {
+            self.bytecode.splice(end..end+8, (self.bytecode.len() as u64).to_le_bytes());
+        }
+        self.running_stack.truncate(height);
+        self.running_stack.extend(thetas.into_iter().map(Location::internal));
+    }
+
+    /// Write the body of a loop, which writes the new values of its thetas back into their places on the stack
+    fn write_loop(&mut self, branch: usize, body: &'a Ssa) {
+        let thetas = self.thetas(branch);
+        let height = self.running_stack.len();
+        let start = self.bytecode.len() as u64;
+        let body = self.compile_branch(body);
+        self.embed_branch(body);
+        self.running_stack.truncate(height);
+        self.running_stack.extend(thetas.iter().map(|t| Location::internal(*t)));
+
+        // Move each value into the place of its theta, starting from the top
+        for theta in thetas.iter().rev() {
+            let place = self.running_stack[..height].iter().position(|l| *l == Location::internal(*theta)).unwrap();
+            let depth = self.running_stack.len() - place - 1;
+            self.roll(depth + 1);
+            self.bytecode.push(Command::Pop as u8);
+            self.running_stack.pop();
+            self.rolr(depth);
+        }
+        self.bytecode.push(Command::Jmp as u8);
+        self.jumps.push(self.bytecode.len());
+        self.bytecode.extend(start.to_le_bytes());
+    }
+
+    /// Leave the values from the enclosing scope, followed by the return variables in order
+    fn finish_scope(&mut self) {
+        let outer = self.running_stack.iter().take_while(|l| l.tier > 0).count();
+        let returns = &self.ssa.return_variables;
+        while let Some(top) = self.running_stack.last()
+            && self.running_stack.len() > outer && !returns.contains(top) {
+            self.pop();
+        }
+
+        // Copy the return variables to the top unless they are already there, and remove everything between them
+        // and the enclosing scope
+        if !self.running_stack[outer..].ends_with(returns) {
+            let target: Vec<Location> = returns.iter().rev().copied().collect();
+            self.set_state(&target);
+        }
+        while self.running_stack.len() > outer + returns.len() {
+            self.roll(returns.len() + 1);
+            self.pop();
         }
     }
 
@@ -297,44 +531,39 @@ impl<'a> Bytecode<'a> {
             bytecode: Vec::new(),
             running_stack: self.running_stack.iter().map(|l| l.graduate()).collect(),
             functions: Vec::new(),
+            function_values: Vec::new(),
+            jumps: Vec::new(),
             written_branches: SortedSet::new(),
+            layout: Vec::new(),
+            lines: Vec::new(),
+            tail_calls: self.tail_calls,
+            outer_roots: self.running_stack.iter()
+                .filter(|l| is_vector(self.ssa.types[l]))
+                .map(|l| (l.graduate(), self.owned_root(*l).map(|r| r.graduate())))
+                .collect(),
         };
-        for (instruction_index, (location, _)) in ssa.iter().enumerate() {
-            bytecode.pop_unused(instruction_index);
-            bytecode.write_bytecode(location);
-        }
-        bytecode.dump_scope();
+        bytecode.write_all();
         bytecode
     }
 
-    fn roll_scope_names(&mut self, variable_names: &[String]) {
-        // Add code to set final state to the desired value
-        let mut final_state = Vec::new();
-        for name in variable_names {
-            match self.ssa.declared_variables.get(name) {
-                Some(loc) => final_state.push(*loc),
-                None => unreachable!()
-            }
-        };
-        self.roll_state(&final_state);
-    }
-
     fn embed_branch(&mut self, mut bytecode: Bytecode) {
         let offset = self.bytecode.len();
+        bytecode.relocate(offset);
         self.bytecode.append(&mut bytecode.bytecode);
         for (call_pos, name) in bytecode.functions {
             self.functions.push((call_pos + offset, name));
         }
-        self.running_stack.append(&mut bytecode.running_stack);
-    }
-
-    pub fn replace_calls(&mut self, locations: &FxHashMap<String, usize>) {
-        for (pos, name) in &self.functions {
-            let location_bytes = (locations[name] as u32).to_le_bytes();
-            self.bytecode[pos + 0] = location_bytes[0];
-            self.bytecode[pos + 1] = location_bytes[1];
-            self.bytecode[pos + 2] = location_bytes[2];
-            self.bytecode[pos + 3] = location_bytes[3];
+        for (pos, name) in bytecode.function_values {
+            self.function_values.push((pos + offset, name));
+        }
+        for jump_pos in bytecode.jumps {
+            self.jumps.push(jump_pos + offset);
+        }
+        for (pos, text) in bytecode.layout {
+            self.layout.push((pos + offset, format!("  {}", text)));
+        }
+        for (pos, line) in bytecode.lines {
+            self.lines.push((pos + offset, line));
         }
     }
 
@@ -356,52 +585,73 @@ impl<'a> Bytecode<'a> {
         // OPTIMIZE
     }
 
-    fn roll(&mut self, n: usize, shift: usize) {
+    /// Move the item `n - 1` down from the top to the top
+    fn roll(&mut self, n: usize) {
         let length = self.running_stack.len();
-        if shift == 0 {return;}
-        if n == 1 {return;}
-        if n == 2 {
-            self.bytecode.push(Command::Swp as u8);
-            self.running_stack.swap(length-1, length-2);
-            return;
-        }
-        if shift < n/2 {
-            for _ in 0..shift {
+        match n {
+            0 | 1 => return,
+            2 => self.bytecode.push(Command::Swp as u8),
+            _ => {
                 self.bytecode.push(Command::Push as u8);
                 self.bytecode.extend((n as f64).to_le_bytes());
                 self.bytecode.push(Command::Roll as u8);
-            }
-            self.running_stack[length-n..].rotate_right(shift);
-        } else {
-            for _ in 0..(n - shift) {
+            },
+        }
+        self.running_stack[length-n..].rotate_left(1);
+    }
+
+    /// Move the top item `n - 1` places down
+    fn rolr(&mut self, n: usize) {
+        let length = self.running_stack.len();
+        match n {
+            0 | 1 => return,
+            2 => self.bytecode.push(Command::Swp as u8),
+            _ => {
                 self.bytecode.push(Command::Push as u8);
                 self.bytecode.extend((n as f64).to_le_bytes());
                 self.bytecode.push(Command::Rolr as u8);
-            }
-            self.running_stack[length-n..].rotate_left(n - shift);
+            },
         }
+        self.running_stack[length-n..].rotate_right(1);
     }
 
     fn pop(&mut self) {
-        let op = self.running_stack.last().unwrap();
-        match self.ssa.types[op] {
+        let op = *self.running_stack.last().unwrap();
+        // Lists are freed with their last copy, and arguments belong to the caller
+        let root = self.vector_root(op);
+        let shared = self.running_stack[..self.running_stack.len()-1].iter().any(|&l| self.vector_root(l) == root)
+            || (root.tier == 0 && matches!(self.ssa.instructions[&root.index], Instruction::Argument));
+        match self.ssa.types[&op] {
             crate::bytecode::VariableType::Null => unreachable!(),
-            crate::bytecode::VariableType::Float => self.bytecode.push(Command::Pop as u8),
-            crate::bytecode::VariableType::List => self.bytecode.push(Command::Drop as u8),
+            crate::bytecode::VariableType::Float | crate::bytecode::VariableType::Function(_) => self.bytecode.push(Command::Pop as u8),
+            crate::bytecode::VariableType::List | crate::bytecode::VariableType::Record(_) if shared => self.bytecode.push(Command::Pop as u8),
+            crate::bytecode::VariableType::List | crate::bytecode::VariableType::Record(_) => self.bytecode.push(Command::Drop as u8),
         };
         self.running_stack.pop();
     }
 
-    fn roll_state(&mut self, target: &[Location]) {
-        // Roll until the target is matched, building up from the back
-        for (n_chars, var) in target.iter().enumerate().rev() {
-            let stack_start = self.running_stack.len()-n_chars;
-            let pos = self.running_stack[stack_start..].iter().position(|v| v==var).unwrap();
-            let dist_from_n = self.running_stack.len() - pos - 1;
-            let shift = (dist_from_n + 1) % n_chars;
-            self.roll(n_chars, shift);
-            dbg!(self.running_stack.last().unwrap());
-            dbg!(var);
+    /// The location that allocated a list, if this function allocated it and so must free it
+    fn owned_root(&self, loc: Location) -> Option<Location> {
+        let root = self.vector_root(loc);
+        match root.tier {
+            0 if matches!(self.ssa.instructions[&root.index], Instruction::Argument) => None,
+            0 => Some(root),
+            _ => self.outer_roots.get(&root).copied().flatten(),
         }
     }
-}
\ No newline at end of file
+
+    /// The location that allocated the memory behind a list, since stores and records share it
+    fn vector_root(&self, mut loc: Location) -> Location {
+        while loc.tier == 0 {
+            match self.ssa.instructions[&loc.index] {
+                Instruction::Stb(adr, _) | Instruction::St(adr, _, _) | Instruction::Record(_, adr) => loc = adr,
+                _ => break,
+            }
+        }
+        loc
+    }
+}
+
+fn is_vector(typ: VariableType) -> bool {
+    matches!(typ, VariableType::List | VariableType::Record(_))
+}
@@ -1,11 +1,23 @@
 // Functions to perform the full compilation process
 mod ssa;
 mod implementer;
+mod dump;
+mod cache;
+mod object;
+mod debug;
+
+use std::{collections::BTreeMap, rc::Rc};
 
 use lazy_static::lazy_static;
-use rustc_hash::FxHashMap;
-use crate::{bytecode::VariableType, compiler::implementer::Bytecode, parser::SyntaxNode};
-use ssa::Ssa;
+use rustc_hash::{FxHashMap, FxHashSet};
+use crate::{Command, GlobalFunction, bytecode::{FUNCTION_MAP_LOWER, InterruptKind, VariableType, encode_format, encode_str}, compiler::implementer::{Bytecode, FunctionCode}, parser::SyntaxNode};
+use ssa::{Ssa, TypeChecker};
+use dump::Dumps;
+pub use dump::Stage;
+pub use cache::CompileCache;
+pub use object::{Object, link};
+pub use debug::DebugInfo;
+use object::ObjectFunction;
 
 lazy_static! {
     static ref CONSTANT_PRECURSOR: SyntaxNode = {
@@ -18,15 +30,197 @@ lazy_static! {
     };
 }
 
-struct Function {
-    name: String,
-    node: SyntaxNode,
-    return_value: VariableType,
-    arguments: Vec<(String, VariableType)>,
+/// Split the arguments of a host call into data written into the call before them, and the values.
+/// `print` takes a string literal as its format, with a `{}` for each value, and the signal functions take one
+/// naming their channel.
+pub(crate) fn host_arguments<'a>(func: GlobalFunction, node: &SyntaxNode, arguments: &'a [SyntaxNode]) -> Result<(Vec<f64>, &'a [SyntaxNode]), String> {
+    match func {
+        GlobalFunction::Print => {
+            let Some((SyntaxNode::Str(format), values)) = arguments.split_first() else {
+                return node.raise("The first argument of print must be a string");
+            };
+            let placeholders = format.get_inner().matches("{}").count();
+            if placeholders != values.len() {
+                return node.raise(&format!("The format of print has {} `{{}}`, but {} value{} given", placeholders,
+                    values.len(), if values.len() == 1 { " was" } else { "s were" }));
+            }
+            Ok((encode_format(format.get_inner()), values))
+        },
+        GlobalFunction::Send | GlobalFunction::Broadcast | GlobalFunction::Pending | GlobalFunction::Recv => {
+            let Some((SyntaxNode::Str(channel), values)) = arguments.split_first() else {
+                return node.raise(&format!("The first argument of {} must be a channel name in quotes", func.to_string().to_lowercase()));
+            };
+            Ok((encode_str(channel.get_inner()), values))
+        },
+        _ => Ok((Vec::new(), arguments)),
+    }
 }
 
-impl Function {
+/// A record type declared as `struct Vec3 { x, y, z }`. Its values are vectors holding a float per field, in order.
+#[derive(Clone, Debug)]
+pub(crate) struct Record {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+impl Record {
     fn new(header: &SyntaxNode, body: &SyntaxNode) -> Result<Self, String> {
+        let name = match header {
+            SyntaxNode::Adjacent(nodes) => match &nodes[..] {
+                [_, SyntaxNode::Unclassified(name)] => name.get_inner().to_owned(),
+                _ => return header.raise("Record declarations must be `struct Name { fields }`"),
+            },
+            _ => return header.raise("Record declarations must be `struct Name { fields }`"),
+        };
+        // Fields are names separated by commas, which are not grouped into a list inside braces
+        let items: &[SyntaxNode] = match body {
+            SyntaxNode::Adjacent(items) => items,
+            other => std::slice::from_ref(other),
+        };
+        let mut fields: Vec<String> = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            match item {
+                SyntaxNode::Unclassified(t) if t == "," && i % 2 == 1 => (),
+                SyntaxNode::Unclassified(field) if i % 2 == 0 && field != "," => {
+                    if fields.contains(field.get_inner()) {
+                        return item.raise(&format!("Field {} is declared twice", field.get_inner()));
+                    }
+                    fields.push(field.get_inner().to_owned());
+                },
+                _ => return item.raise("Record fields must be names separated by commas"),
+            }
+        }
+        if fields.is_empty() {
+            return header.raise(&format!("Record {} has no fields", name));
+        }
+        Ok(Self { name, fields })
+    }
+
+    pub fn field(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|f| f == name)
+    }
+}
+
+/// The types of the arguments of a function, which is the type of the function as a value
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Signature {
+    pub arguments: Vec<VariableType>,
+}
+
+/// Types declared by a program: its records, and the signatures of its functions
+#[derive(Debug, Default)]
+pub(crate) struct Declarations {
+    pub records: Vec<Record>,
+    pub signatures: Vec<Signature>,
+}
+
+impl Declarations {
+    /// Find a record by name
+    pub fn record(&self, name: &str) -> Option<u32> {
+        self.records.iter().position(|r| r.name == name).map(|i| i as u32)
+    }
+
+    /// The index of a signature, adding it if it is new. Functions with the same arguments have the same type.
+    fn signature(&mut self, arguments: &[(String, VariableType)]) -> u32 {
+        let signature = Signature { arguments: arguments.iter().map(|(_, t)| *t).collect() };
+        match self.signatures.iter().position(|s| *s == signature) {
+            Some(i) => i as u32,
+            None => {
+                self.signatures.push(signature);
+                self.signatures.len() as u32 - 1
+            },
+        }
+    }
+
+    /// The name of a type as it is written in scripts
+    pub fn type_name(&self, typ: VariableType) -> String {
+        self.write_type(typ, false)
+    }
+
+    /// The type of a function with these arguments, with the fields of records written out. Modules compiled
+    /// separately agree on a function if they give it the same text.
+    pub fn link_signature(&self, arguments: &[(String, VariableType)]) -> String {
+        let arguments: Vec<String> = arguments.iter().map(|(_, t)| self.write_type(*t, true)).collect();
+        format!("fn({})", arguments.join(", "))
+    }
+
+    fn write_type(&self, typ: VariableType, fields: bool) -> String {
+        match typ {
+            VariableType::Null => "nothing".to_owned(),
+            VariableType::Float => "float".to_owned(),
+            VariableType::List => "list".to_owned(),
+            VariableType::Record(record) => {
+                let record = &self.records[record as usize];
+                match fields {
+                    true => format!("{} {{ {} }}", record.name, record.fields.join(", ")),
+                    false => record.name.clone(),
+                }
+            },
+            VariableType::Function(signature) => {
+                let arguments: Vec<String> = self.signatures[signature as usize].arguments.iter()
+                    .map(|t| self.write_type(*t, fields))
+                    .collect();
+                format!("fn({})", arguments.join(", "))
+            },
+        }
+    }
+}
+
+/// Parse the inside of the parentheses of an argument list. Arguments are floats unless declared as `list[]`,
+/// `v: Record` or `f: fn(arguments)`.
+fn parse_arguments(header: &SyntaxNode, arg: &SyntaxNode, declarations: &mut Declarations) -> Result<Vec<(String, VariableType)>, String> {
+    let list: &[SyntaxNode] = match arg {
+        SyntaxNode::List(",", items) => match &**items {
+            SyntaxNode::Adjacent(items) => items,
+            _ => return header.raise("Invalid syntax 4"),
+        },
+        SyntaxNode::Adjacent(items) if items.is_empty() => &[],
+        // A single argument
+        other => std::slice::from_ref(other),
+    };
+    let mut arguments = Vec::new();
+    for item in list {
+        match item {
+            SyntaxNode::Unclassified(token) => arguments.push((token.get_inner().clone(), VariableType::Float)),
+            SyntaxNode::Adjacent(nodes) => match &nodes[..] {
+                [SyntaxNode::Unclassified(token), SyntaxNode::Parenthesis(c, _)] if *c == "[" => {
+                    arguments.push((token.get_inner().clone(), VariableType::List))
+                },
+                [_, SyntaxNode::Parenthesis(_, syntax_node)] => {
+                    return syntax_node.raise("Only brackets can appear in variable definitions");
+                },
+                [SyntaxNode::Unclassified(token), SyntaxNode::Unclassified(colon), SyntaxNode::Unclassified(typ)] if colon == ":" => {
+                    match declarations.record(typ.get_inner()) {
+                        Some(record) => arguments.push((token.get_inner().clone(), VariableType::Record(record))),
+                        None => return typ.raise(&format!("Unrecognized record {}", typ.get_inner())),
+                    }
+                },
+                [SyntaxNode::Unclassified(token), SyntaxNode::Unclassified(colon), SyntaxNode::Unclassified(f),
+                    SyntaxNode::Parenthesis("(", inner)] if colon == ":" && f == "fn" => {
+                    let inner = parse_arguments(item, inner, declarations)?;
+                    arguments.push((token.get_inner().clone(), VariableType::Function(declarations.signature(&inner))));
+                },
+                _ => return item.raise("Invalid symbol in argument list"),
+            },
+            _ => return item.raise("Invalid symbol in argument list"),
+        };
+    }
+    Ok(arguments)
+}
+
+pub(crate) struct Function {
+    pub name: String,
+    pub node: SyntaxNode,
+    pub return_value: VariableType,
+    pub arguments: Vec<(String, VariableType)>,
+    /// Type of the function as a value
+    pub signature: u32,
+    /// Declared with `extern fn`, and defined by another module. The node is the declaration.
+    pub external: bool,
+}
+
+impl Function {
+    fn new(header: &SyntaxNode, body: &SyntaxNode, declarations: &mut Declarations) -> Result<Self, String> {
         let mut return_value = VariableType::Float;
         let mut node_iter = match header {
             SyntaxNode::Adjacent(v) => v.iter(),
@@ -55,81 +249,151 @@ impl Function {
         }
 
         // Handle arguments
-        let mut arguments = Vec::new();
-        match next {
+        let arguments = match next {
             Some(SyntaxNode::Parenthesis(c, arg)) => {
                 if *c != "(" { return header.raise("Function declaractions must have parentheses"); }
-                let mut last_argument : Option<String> = None;
-                match &**arg {
-                    SyntaxNode::Adjacent(list) => {
-                        for item in list {
-                            match item {
-                                SyntaxNode::Unclassified(token) => {
-                                    // Commit float
-                                    if let Some(item) = &last_argument {
-                                        arguments.push((item.to_owned(), VariableType::Float))
-                                    }
-                                    last_argument = Some(token.get_inner().clone());
-                                },
-                                SyntaxNode::Parenthesis(c, syntax_node) => {
-                                    if *c != "[" { return syntax_node.raise("Only brackets can appear in variable definitions"); }
-                                    // Commit list
-                                    if let Some(item) = &last_argument {
-                                        arguments.push((item.to_owned(), VariableType::List))
-                                    } else {
-                                        return syntax_node.raise("Brackets must follow variable name");
-                                    }
-                                },
-                                _ => {return header.raise("Invalid symbol in argument list");}
-                            };
-                        }
-                        if let Some(item) = &last_argument {
-                            arguments.push((item.to_owned(), VariableType::Float))
-                        }
-                    },
-                    _ => return header.raise("Invalid syntax 4"),
-                };
+                parse_arguments(header, arg, declarations)?
             }
             _ => return header.raise("Function declaractions must have parentheses")
-        }
+        };
 
         Ok(Self {
             name: function_name.to_owned(),
             node: body.clone(),
             return_value,
+            signature: declarations.signature(&arguments),
             arguments,
+            external: false,
         })
     }
 
-    fn compile(&self, available_functions: &FxHashMap<String, Function>) -> Result<Ssa, String> {
-        let mut arguments = FxHashMap::default();
-        for (name, typ) in &self.arguments {
-            arguments.insert(name.to_owned(), *typ);
+    /// Parse `extern fn name(arguments)`, a function defined in another module
+    fn new_extern(declaration: &SyntaxNode, declarations: &mut Declarations) -> Result<Self, String> {
+        let SyntaxNode::Adjacent(nodes) = declaration else { unreachable!() };
+        let header = SyntaxNode::Adjacent(nodes[1..].to_vec());
+        let mut function = Self::new(&header, &SyntaxNode::Adjacent(Vec::new()), declarations)?;
+        function.node = declaration.clone();
+        function.external = true;
+        Ok(function)
+    }
+
+    /// Parse an interrupt handler such as `on interact { }` or `on forward(x) { }`
+    fn new_handler(header: &SyntaxNode, body: &SyntaxNode, declarations: &mut Declarations) -> Result<(InterruptKind, Self), String> {
+        let nodes = match header {
+            SyntaxNode::Adjacent(v) => v,
+            _ => return header.raise("Interrupt handlers must name an interrupt"),
+        };
+        let kind = match nodes.get(1) {
+            Some(SyntaxNode::Unclassified(t)) => match InterruptKind::from_string(t.get_inner()) {
+                Some(k) => k,
+                None => return t.raise(&format!("Unrecognized interrupt {}", t.get_inner())),
+            },
+            _ => return header.raise("Interrupt handlers must name an interrupt"),
+        };
+
+        let mut arguments = Vec::new();
+        match nodes.get(2) {
+            Some(SyntaxNode::Parenthesis("(", arg)) => match &**arg {
+                SyntaxNode::Unclassified(t) => arguments.push((t.get_inner().to_owned(), VariableType::Float)),
+                SyntaxNode::Adjacent(list) if list.is_empty() => (),
+                _ => return arg.raise("Interrupt handlers take at most one argument"),
+            },
+            Some(n) => return n.raise("Invalid syntax in interrupt handler"),
+            None => (),
+        };
+        if nodes.len() > 3 {
+            return nodes[3].raise("Invalid syntax in interrupt handler");
+        }
+        if kind.takes_arg() == arguments.is_empty() {
+            let message = match kind.takes_arg() {
+                true => format!("Handler for {} must take one argument", kind.to_string().to_lowercase()),
+                false => format!("Handler for {} takes no arguments", kind.to_string().to_lowercase()),
+            };
+            return header.raise(&message);
         }
-        let mut ssa = Ssa::new(&self.node, &arguments, available_functions)?;
+
+        Ok((kind, Self {
+            name: format!("on {}", kind.to_string().to_lowercase()),
+            node: body.clone(),
+            return_value: VariableType::Null,
+            signature: declarations.signature(&arguments),
+            arguments,
+            external: false,
+        }))
+    }
+
+    fn compile(&self, available_functions: &FxHashMap<String, Function>, declarations: &Rc<Declarations>) -> Result<Ssa, String> {
+        let mut ssa = Ssa::new(&self.node, &self.arguments, available_functions, declarations)?;
+        TypeChecker::new(available_functions).check(&mut ssa)?;
         ssa.order();
         Ok(ssa)
     }
 }
 
-struct Compiler {
-    functions: FxHashMap<String, Function>,
+pub(crate) struct Compiler {
+    pub functions: FxHashMap<String, Function>,
+    /// Interrupt handlers, and the name of the function implementing each
+    pub handlers: Vec<(InterruptKind, String)>,
+    pub declarations: Rc<Declarations>,
 }
 
 impl Compiler {
-    fn new(tree: &SyntaxNode) -> Result<Self, String> {
+    pub fn new(tree: &SyntaxNode) -> Result<Self, String> {
         let mut constants = Vec::new();
         let mut functions = FxHashMap::default();
+        let mut handlers = Vec::new();
 
         let main_list: &[SyntaxNode] = match tree {
             SyntaxNode::Adjacent(nodes) => nodes,
             _ => std::slice::from_ref(&tree),
         };
 
+        // Records are found first, so that functions may take them in any order
+        let mut declarations = Declarations::default();
+        for entry in main_list {
+            if let SyntaxNode::Block(header, body) = entry && is_record(header) {
+                let record = Record::new(header, body)?;
+                if declarations.record(&record.name).is_some() {
+                    return header.raise(&format!("Record {} is declared twice", record.name));
+                }
+                if FUNCTION_MAP_LOWER.contains_key(&record.name) {
+                    return header.raise(&format!("Record {} has the name of a built in function", record.name));
+                }
+                declarations.records.push(record);
+            }
+        }
+
         for entry in main_list {
             match entry {
+                SyntaxNode::Block(header, _) if is_record(header) => (),
                 SyntaxNode::Block(header, node_end) => {
-                    let function = Function::new(&header, &node_end)?;
+                    let is_handler = match &**header {
+                        SyntaxNode::Adjacent(v) => matches!(v.first(), Some(SyntaxNode::Unclassified(t)) if t == "on"),
+                        _ => false,
+                    };
+                    if is_handler {
+                        let (kind, function) = Function::new_handler(&header, &node_end, &mut declarations)?;
+                        if handlers.iter().any(|(k, _)| *k == kind) {
+                            return header.raise(&format!("Interrupt {} is handled twice", kind.to_string().to_lowercase()));
+                        }
+                        handlers.push((kind, function.name.to_owned()));
+                        functions.insert(function.name.to_owned(), function);
+                    } else {
+                        let function = Function::new(&header, &node_end, &mut declarations)?;
+                        if declarations.record(&function.name).is_some() {
+                            return header.raise(&format!("Function {} has the name of a record", function.name));
+                        }
+                        if functions.get(&function.name).is_some_and(|f: &Function| f.external) {
+                            return header.raise(&format!("Extern function {} is also declared in this module", function.name));
+                        }
+                        functions.insert(function.name.to_owned(), function);
+                    }
+                },
+                SyntaxNode::Adjacent(nodes) if matches!(nodes.first(), Some(SyntaxNode::Unclassified(t)) if t == "extern") => {
+                    let function = Function::new_extern(entry, &mut declarations)?;
+                    if functions.contains_key(&function.name) || declarations.record(&function.name).is_some() {
+                        return entry.raise(&format!("Extern function {} is also declared in this module", function.name));
+                    }
                     functions.insert(function.name.to_owned(), function);
                 },
                 SyntaxNode::List(_, syntax_node) => {
@@ -138,71 +402,173 @@ impl Compiler {
                         _ => return syntax_node.raise("Invalid syntax 7"),
                     }
                 },
-                _ => return entry.raise("All code outside functions must be pragmas or function definitions"),
+                _ => return entry.raise("All code outside functions must be pragmas, records, function definitions or extern declarations"),
             }
         }
         
-        let constants = Function::new(&CONSTANT_PRECURSOR, &SyntaxNode::Adjacent(constants))?;
+        let constants = Function::new(&CONSTANT_PRECURSOR, &SyntaxNode::Adjacent(constants), &mut declarations)?;
         functions.insert(constants.name.to_owned(), constants);
 
         Ok(Self {
             functions,
+            handlers,
+            declarations: Rc::new(declarations),
         })
     }
 
-    fn compile(&self, name: &str) -> Result<FxHashMap<String, Ssa>, String> {
-        let mut compiled = FxHashMap::default();
-        let mut queue = vec![name.to_owned()];
-        while !queue.is_empty() {
-            let name = queue.pop().unwrap();
-            let ssa = self.functions[&name].compile(&self.functions)?;
-            for f in &ssa.get_used_functions() {
-                queue.push(f.clone());
+    /// Check every function for errors, without writing any code
+    pub fn check(&self) -> Result<(), String> {
+        self.compile(&[]).map(|_| ())
+    }
+
+    /// The functions that are run directly: main and the interrupt handlers
+    fn roots(&self, tree: &SyntaxNode) -> Result<Vec<String>, String> {
+        if !self.functions.contains_key("main") {
+            return tree.raise("No function named main");
+        }
+        let mut roots = vec!["main".to_owned()];
+        roots.extend(self.handlers.iter().map(|(_, name)| name.to_owned()));
+        Ok(roots)
+    }
+
+    /// Write the bytecode of a function. Main waits for interrupts once it finishes, and handlers return to the
+    /// interrupted code.
+    fn write(&self, name: &str, ssa: &Ssa) -> FunctionCode {
+        let ending = match name {
+            "main" => Command::Wait,
+            _ if self.handlers.iter().any(|(_, n)| n == name) => Command::Reti,
+            // Local functions are called with the return address below their arguments
+            _ => Command::Ret,
+        };
+        let mut bytecode = Bytecode::new(ssa, ending == Command::Ret);
+        bytecode.push_command(ending);
+        bytecode.into_code()
+    }
+
+    /// Package written functions as an object. The extern functions they use become its imports.
+    fn object(&self, module: &str, functions: BTreeMap<String, FunctionCode>) -> Object {
+        let mut imports: Vec<(String, String)> = functions.values()
+            .flat_map(|code| code.uses())
+            .filter(|name| self.functions[*name].external)
+            .map(|name| (name.to_owned(), self.declarations.link_signature(&self.functions[name].arguments)))
+            .collect();
+        imports.sort();
+        imports.dedup();
+        let functions = functions.into_iter()
+            .map(|(name, code)| ObjectFunction {
+                signature: self.declarations.link_signature(&self.functions[&name].arguments),
+                name,
+                code,
+            })
+            .collect();
+        Object {
+            module: module.to_owned(),
+            functions,
+            handlers: self.handlers.clone(),
+            imports,
+        }
+    }
+
+    /// Compile the named functions and those they use, each once. The order of compilation, and so the first
+    /// error found, depends only on the source.
+    fn compile(&self, names: &[String]) -> Result<BTreeMap<String, Ssa>, String> {
+        let mut compiled = BTreeMap::new();
+        let mut seen: FxHashSet<&str> = names.iter().map(|n| n.as_str()).collect();
+        let mut queue: Vec<&str> = names.iter().rev().map(|n| n.as_str()).collect();
+        while let Some(name) = queue.pop() {
+            // Extern functions are compiled with the module defining them
+            if self.functions[name].external { continue; }
+            let ssa = self.functions[name].compile(&self.functions, &self.declarations)?;
+            for f in ssa.get_used_functions().iter().rev() {
+                if let Some((f, _)) = self.functions.get_key_value(f) && seen.insert(f) {
+                    queue.push(f);
+                }
             }
-            compiled.insert(name, ssa);
+            compiled.insert(name.to_owned(), ssa);
+        }
+
+        // Functions which are never called are not written, but are still checked
+        let mut unused: Vec<&String> = self.functions.keys()
+            .filter(|name| !compiled.contains_key(*name) && *name != "const" && !self.functions[*name].external)
+            .collect();
+        unused.sort();
+        for name in unused {
+            self.functions[name].compile(&self.functions, &self.declarations)?;
         }
         Ok(compiled)
     }
 }
 
-fn compile_tree(tree: &SyntaxNode) -> Result<Vec<u8>, String> {
+fn is_record(header: &SyntaxNode) -> bool {
+    matches!(header, SyntaxNode::Adjacent(v) if matches!(v.first(), Some(SyntaxNode::Unclassified(t)) if t == "struct"))
+}
+
+fn compile_tree(tree: &SyntaxNode, filename: &str, dumps: &mut Dumps) -> Result<(Vec<u8>, DebugInfo), String> {
     let compiler = Compiler::new(tree)?;
-    if !compiler.functions.contains_key("main") {
-        return tree.raise("No function named main");
-    }
-    let ssa = compiler.compile("main")?;
+    let roots = compiler.roots(tree)?;
+    let ssa = compiler.compile(&roots)?;
+    dumps.add(Stage::Ssa, || dump::dump_ssa(&ssa));
     // let const_ssa = compiler.compile("const")?; // TODO implement constants
     
     // Optimize IR
 
-    // Write to bytecode
-    let mut names = vec!["main".to_owned()];
-    let mut compiled_functions = vec![Bytecode::new(&ssa["main"])];
-    for (name, ssa) in &ssa {
-        if name == "main" { continue; }
-        names.push(name.to_owned());
-        compiled_functions.push(Bytecode::new(ssa));
-    }
+    let functions = ssa.iter().map(|(name, ssa)| (name.to_owned(), compiler.write(name, ssa))).collect();
+    let (code, notes, debug) = object::link_noted(&[compiler.object(filename, functions)])?;
+    dumps.add(Stage::Asm, || dump::dump_asm(&code, &notes));
+    Ok((code, debug))
+}
 
-    // Get all the function locations
-    let mut locations = FxHashMap::default();
-    let mut net_loc = 0;
-    for (name, bytecode) in names.iter().zip(&compiled_functions) {
-        locations.insert(name.to_owned(), net_loc);
-        net_loc += bytecode.len();
-    }
+/// Compile a string of Biscuit code to an object, to be linked with others. Every function is written, and
+/// main is not required.
+pub fn compile_object_str(s: &str, filename: &str) -> Result<Object, String> {
+    let tokens = crate::parser::load_str(s, filename)?;
+    let tree = SyntaxNode::tree(tokens)?;
+    let compiler = Compiler::new(&tree)?;
+    let mut names: Vec<String> = compiler.functions.iter()
+        .filter(|(name, function)| *name != "const" && !function.external)
+        .map(|(name, _)| name.to_owned())
+        .collect();
+    names.sort();
+    let ssa = compiler.compile(&names)?;
+    let functions = ssa.iter().map(|(name, ssa)| (name.to_owned(), compiler.write(name, ssa))).collect();
+    Ok(compiler.object(filename, functions))
+}
 
-    let mut code = Vec::new();
-    for mut f in compiled_functions {
-        f.replace_calls(&locations);
-        code.extend(f.code().into_iter());
-    }
-    Ok(code)
+/// The result of a compilation, with text dumps of its stages
+pub struct Explanation {
+    /// Dumps of the requested stages. Stages reached before an error are still dumped.
+    pub dumps: Vec<(Stage, String)>,
+    pub result: Result<Vec<u8>, String>,
 }
 
 /// Compile a string (usually read from a file) of Biscuit code to binary
 pub fn compile_str(s: &str, filename: &str) -> Result<Vec<u8>, String> {
+    explain_str(s, filename, &[]).result
+}
+
+/// Compile a string of Biscuit code to binary, with the debug info needed to profile it
+pub fn compile_debug_str(s: &str, filename: &str) -> Result<(Vec<u8>, DebugInfo), String> {
+    compile_dumped(s, filename, &mut Dumps::new(&[]))
+}
+
+/// Compile a string of Biscuit code, and dump the listed stages as text
+pub fn explain_str(s: &str, filename: &str, stages: &[Stage]) -> Explanation {
+    let mut dumps = Dumps::new(stages);
+    let result = compile_dumped(s, filename, &mut dumps).map(|(code, _)| code);
+    Explanation {
+        dumps: dumps.into_inner(),
+        result,
+    }
+}
+
+fn compile_dumped(s: &str, filename: &str, dumps: &mut Dumps) -> Result<(Vec<u8>, DebugInfo), String> {
     let tokens = crate::parser::load_str(s, filename)?;
+    dumps.add(Stage::Tokens, || crate::parser::dump_tokens(&tokens));
     let tree = crate::parser::SyntaxNode::tree(tokens)?;
-    compile_tree(&tree)
-}
\ No newline at end of file
+    dumps.add(Stage::Ast, || {
+        let mut out = String::new();
+        tree.dump(0, &mut out);
+        out
+    });
+    compile_tree(&tree, filename, dumps)
+}
@@ -0,0 +1,239 @@
+// Object files: modules compiled on their own, which are linked into a binary
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{Command, InterruptKind, compiler::{DebugInfo, implementer::FunctionCode}, util::bytes::{Reader, write_len, write_str}};
+
+const MAGIC: &[u8; 4] = b"BISO";
+const VERSION: u8 = 1;
+
+/// Descriptions of the code at offsets in a binary, for compiler dumps
+pub(crate) type Notes = Vec<(usize, String)>;
+
+/// A function defined by a module
+#[derive(Clone, Debug)]
+pub(crate) struct ObjectFunction {
+    pub name: String,
+    /// Types of the arguments, written out so that modules can agree on them without sharing declarations
+    pub signature: String,
+    pub code: FunctionCode,
+}
+
+/// A compiled module which has not been linked. Its functions are placed and their calls filled in by `link`.
+#[derive(Clone, Debug)]
+pub struct Object {
+    /// Name of the module, for errors
+    pub(crate) module: String,
+    /// Functions defined by the module, by name
+    pub(crate) functions: Vec<ObjectFunction>,
+    pub(crate) handlers: Vec<(InterruptKind, String)>,
+    /// Extern functions the module uses, with the signature it expects
+    pub(crate) imports: Vec<(String, String)>,
+}
+
+impl Object {
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    /// Names of the functions defined by the module
+    pub fn exports(&self) -> impl Iterator<Item = &str> {
+        self.functions.iter().map(|f| f.name.as_str()).filter(|n| !self.handlers.iter().any(|(_, h)| h == n))
+    }
+
+    /// Names of the extern functions the module uses
+    pub fn imports(&self) -> impl Iterator<Item = &str> {
+        self.imports.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Write the object file. The notes used by compiler dumps and the source lines are not kept.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        write_str(&mut out, &self.module);
+        write_len(&mut out, self.functions.len());
+        for function in &self.functions {
+            write_str(&mut out, &function.name);
+            write_str(&mut out, &function.signature);
+            write_len(&mut out, function.code.code.len());
+            out.extend(&function.code.code);
+            for symbols in [&function.code.calls, &function.code.function_values] {
+                write_len(&mut out, symbols.len());
+                for (pos, name) in symbols {
+                    write_len(&mut out, *pos);
+                    write_str(&mut out, name);
+                }
+            }
+            write_len(&mut out, function.code.jumps.len());
+            for pos in &function.code.jumps {
+                write_len(&mut out, *pos);
+            }
+        }
+        write_len(&mut out, self.handlers.len());
+        for (kind, name) in &self.handlers {
+            out.push(*kind as u8);
+            write_str(&mut out, name);
+        }
+        write_len(&mut out, self.imports.len());
+        for (name, signature) in &self.imports {
+            write_str(&mut out, name);
+            write_str(&mut out, signature);
+        }
+        out
+    }
+
+    /// Read an object file written by `to_bytes`
+    pub fn from_bytes(bytes: &[u8], filename: &str) -> Result<Self, String> {
+        let invalid = || format!("{} is not a valid object file", filename);
+        let mut reader = Reader::new(bytes);
+        if reader.take(4) != Some(MAGIC) {
+            return Err(invalid());
+        }
+        if reader.take(1) != Some(&[VERSION]) {
+            return Err(format!("{} was written by another version of the compiler", filename));
+        }
+        let object = read_object(&mut reader).ok_or_else(invalid)?;
+        match reader.is_finished() {
+            true => Ok(object),
+            false => Err(invalid()),
+        }
+    }
+}
+
+fn read_symbols(reader: &mut Reader, code_len: usize) -> Option<Vec<(usize, String)>> {
+    (0..reader.length()?).map(|_| {
+        let pos = reader.length().filter(|p| p + 8 <= code_len)?;
+        Some((pos, reader.str()?))
+    }).collect()
+}
+
+fn read_object(reader: &mut Reader) -> Option<Object> {
+    let module = reader.str()?;
+    let mut functions = Vec::new();
+    for _ in 0..reader.length()? {
+        let name = reader.str()?;
+        let signature = reader.str()?;
+        let len = reader.length()?;
+        let code = reader.take(len)?.to_vec();
+        let calls = read_symbols(reader, code.len())?;
+        let function_values = read_symbols(reader, code.len())?;
+        let jumps = (0..reader.length()?).map(|_| reader.length().filter(|p| p + 8 <= code.len())).collect::<Option<_>>()?;
+        let code = FunctionCode { code, calls, function_values, jumps, layout: Vec::new(), lines: Vec::new() };
+        functions.push(ObjectFunction { name, signature, code });
+    }
+    let mut handlers = Vec::new();
+    for _ in 0..reader.length()? {
+        let kind = InterruptKind::try_from(reader.take(1)?[0]).ok()?;
+        handlers.push((kind, reader.str()?));
+    }
+    let mut imports = Vec::new();
+    for _ in 0..reader.length()? {
+        imports.push((reader.str()?, reader.str()?));
+    }
+    Some(Object { module, functions, handlers, imports })
+}
+
+/// Link objects into a binary. Only functions reachable from main and the interrupt handlers are kept.
+pub fn link(objects: &[Object]) -> Result<Vec<u8>, String> {
+    link_noted(objects).map(|(code, _, _)| code)
+}
+
+/// Link objects, also returning notes on the code at each offset and its debug info. Main comes first, then the other functions by
+/// name, and handlers are registered in order of their interrupts, so the binary does not depend on the order of
+/// the objects.
+pub(crate) fn link_noted(objects: &[Object]) -> Result<(Vec<u8>, Notes, DebugInfo), String> {
+    let mut handlers: Vec<(&(InterruptKind, String), &Object)> = objects.iter()
+        .flat_map(|o| o.handlers.iter().map(move |h| (h, o)))
+        .collect();
+    handlers.sort_by_key(|((kind, _), _)| *kind as u8);
+    for pair in handlers.windows(2) {
+        if pair[0].0.0 == pair[1].0.0 {
+            return Err(format!("Interrupt {} is handled in both {} and {}", pair[0].0.0.to_string().to_lowercase(),
+                pair[0].1.module, pair[1].1.module));
+        }
+    }
+    let handlers: Vec<&(InterruptKind, String)> = handlers.into_iter().map(|(h, _)| h).collect();
+
+    let mut defined: FxHashMap<&str, (&Object, &ObjectFunction)> = FxHashMap::default();
+    for object in objects {
+        for function in &object.functions {
+            if let Some((other, _)) = defined.insert(&function.name, (object, function)) {
+                return Err(format!("Function {} is defined in both {} and {}", function.name, other.module, object.module));
+            }
+        }
+    }
+    for object in objects {
+        for (name, signature) in &object.imports {
+            match defined.get(name.as_str()) {
+                None => return Err(format!("Unresolved function {}, used in {}", name, object.module)),
+                Some((other, function)) if function.signature != *signature => {
+                    return Err(format!("Function {} is declared as {} in {}, but defined as {} in {}", name, signature,
+                        object.module, function.signature, other.module));
+                },
+                Some(_) => (),
+            }
+        }
+    }
+    if !defined.contains_key("main") {
+        return Err("No function named main".to_owned());
+    }
+    if let Some((_, name)) = handlers.iter().find(|(_, name)| !defined.contains_key(name.as_str())) {
+        return Err(format!("Unresolved function {}, used as an interrupt handler", name));
+    }
+
+    // Keep the functions reachable from main and the handlers
+    let mut names = vec!["main"];
+    let mut queue: Vec<&str> = handlers.iter().map(|(_, name)| name.as_str()).chain(names.iter().copied()).collect();
+    let mut reached = FxHashSet::default();
+    while let Some(name) = queue.pop() {
+        let (object, function) = defined[name];
+        if name != "main" {
+            reached.insert(name);
+        }
+        for used in function.code.uses() {
+            if !defined.contains_key(used.as_str()) {
+                return Err(format!("Unresolved function {}, used in {}", used, object.module));
+            }
+            if used != "main" && !reached.contains(used.as_str()) && !queue.contains(&used.as_str()) {
+                queue.push(used);
+            }
+        }
+    }
+    let mut reached: Vec<&str> = reached.into_iter().collect();
+    reached.sort();
+    names.extend(reached);
+
+    // The prologue registers the handlers: push address, push interrupt, hnd
+    const REGISTRATION_LEN: usize = 9 + 9 + 1;
+    let prologue_len = handlers.len() * REGISTRATION_LEN;
+
+    // Get all the function locations
+    let mut locations = FxHashMap::default();
+    let mut net_loc = prologue_len;
+    for name in &names {
+        locations.insert(name.to_string(), net_loc);
+        net_loc += defined[name].1.code.code.len();
+    }
+
+    let mut code = Vec::new();
+    let mut notes = Vec::new();
+    let mut debug = DebugInfo::default();
+    for (kind, name) in handlers {
+        notes.push((code.len(), format!("register {}", name)));
+        code.push(Command::Push as u8);
+        code.extend((locations[name] as f64).to_le_bytes());
+        code.push(Command::Push as u8);
+        code.extend((*kind as u8 as f64).to_le_bytes());
+        code.push(Command::Hnd as u8);
+    }
+    for name in &names {
+        let mut f = defined[name].1.code.clone();
+        f.relocate(locations[*name]);
+        f.replace_calls(&locations);
+        notes.push((code.len(), format!("fn {}", name)));
+        notes.extend(f.layout.iter().map(|(pos, text)| (pos + code.len(), text.to_owned())));
+        debug.functions.push((code.len(), name.to_string(), defined[name].0.module.clone()));
+        debug.lines.extend(f.lines.iter().map(|(pos, line)| (pos + code.len(), *line)));
+        code.extend(f.code);
+    }
+    Ok((code, notes, debug))
+}
@@ -1,20 +1,26 @@
 mod ssa_data;
 mod ordering;
+mod typecheck;
 
 use crate::bytecode::VariableType;
 pub(crate) use ordering::Ssa;
+pub(crate) use typecheck::TypeChecker;
 
 #[derive(Clone, Debug)]
 pub enum Instruction {
     Argument,
     LiteralVector(Vec<f64>),
     LiteralFloat(f64),
+    LiteralFunction(String),
 
     Call(u8, Location),
-    LocalCall(String, Location),
+    LocalCall(String, Vec<Location>),
+    DynamicCall(String, Location, Vec<Location>), // Call the function in B, held by variable A
     Theta(usize, String),
     Action(usize),
 
+    Record(u32, Location), // Vector A viewed as a record of the type
+
     Ld(Location, Location), // Get index B f vector A
     St(Location, Location, Location), // Store C in index B of vector A 
     Stb(Location, Location), // Store C in back of vector A 
@@ -41,29 +47,37 @@ impl Instruction {
     pub fn is_action(&self) -> bool {
         match &self {
             Instruction::Action(_) | Instruction::Call(_, _) | Instruction::LocalCall(_, _) | Instruction::Stb(_, _) |
-            Instruction::St(_, _, _) => true,
+            Instruction::St(_, _, _) | Instruction::DynamicCall(_, _, _) => true,
             _ => false,
         }
     }
     pub fn typ(&self) -> VariableType {
         match &self {
             Instruction::LiteralVector(_) | Instruction::St(_, _, _) | Instruction::Stb(_, _) => VariableType::List,
+            Instruction::Record(record, _) => VariableType::Record(*record),
             Instruction::Ld(_, _) | Instruction::LiteralFloat(_) | Instruction::Lt(_, _) | Instruction::Gt(_, _) | 
             Instruction::Le(_, _) |  Instruction::Ge(_, _) | Instruction::Eq(_, _) | Instruction::And(_, _) |
             Instruction::Or(_, _) | Instruction::Xor(_, _) | Instruction::Not(_) | Instruction::Add(_, _) | 
             Instruction::Sub(_, _) | Instruction::Mul(_, _) | Instruction::Div(_, _) | Instruction::Neg(_) | 
             Instruction::Pow(_, _) => VariableType::Float,
-            Instruction::LocalCall(_, _) | Instruction::Action(_) => VariableType::Null,
-            Instruction::Argument | Instruction::Call(_, _) | Instruction::Theta(_, _) => unreachable!(),
+            Instruction::LocalCall(_, _) | Instruction::DynamicCall(_, _, _) | Instruction::Action(_) => VariableType::Null,
+            Instruction::Argument | Instruction::Call(_, _) | Instruction::Theta(_, _) | Instruction::LiteralFunction(_) => unreachable!(),
         }
     }
     fn get_var_dependencies(&self) -> Vec<Location> {
         match &self {
-            Instruction::Argument | Instruction::LiteralVector(_) | Instruction::LiteralFloat(_) => vec![],
+            Instruction::Argument | Instruction::LiteralVector(_) | Instruction::LiteralFloat(_) |
+            Instruction::LiteralFunction(_) => vec![],
             Instruction::Theta(_, _) | Instruction::Action(_) => unreachable!(),
-            Instruction::Call(_, a) | Instruction::LocalCall(_, a) | Instruction::Not(a) | Instruction::Neg(a) => {
+            Instruction::Call(_, a) | Instruction::Not(a) | Instruction::Neg(a) | Instruction::Record(_, a) => {
                 vec![*a]
             },
+            Instruction::LocalCall(_, args) => args.clone(),
+            Instruction::DynamicCall(_, callee, args) => {
+                let mut dependencies = vec![*callee];
+                dependencies.extend(args);
+                dependencies
+            },
             Instruction::Ld(a, b) | Instruction::Stb(a, b) | Instruction::Lt(a, b) | Instruction::Gt(a, b) |
             Instruction::Le(a, b) | Instruction::Ge(a, b) | Instruction::Eq(a, b) | Instruction::And(a, b) |
             Instruction::Or(a, b) | Instruction::Xor(a, b) | Instruction::Add(a, b) | Instruction::Sub(a, b) |
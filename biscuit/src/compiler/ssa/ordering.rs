@@ -1,8 +1,8 @@
-use std::{ops::{Deref, DerefMut}, slice::Iter};
+use std::{ops::{Deref, DerefMut}, rc::Rc, slice::Iter};
 
 use rustc_hash::FxHashMap;
 
-use crate::{bytecode::VariableType, compiler::{Function, ssa::{Instruction, Location, ssa_data::SsaData}}, parser::SyntaxNode};
+use crate::{bytecode::VariableType, compiler::{Declarations, Function, ssa::{Instruction, Location, ssa_data::SsaData}}, parser::SyntaxNode};
 
 
 #[derive(Debug, Clone)]
@@ -12,50 +12,67 @@ pub(crate) enum Ssa {
 }
 
 impl Ssa {
-    pub fn new(node: &SyntaxNode, arguments: &FxHashMap<String, VariableType>, available_functions: &FxHashMap<String, Function>) -> Result<Self, String> {
-        Ok(Ssa::Unordered{data: SsaData::new(node, arguments, available_functions)?})
+    pub fn new(node: &SyntaxNode, arguments: &[(String, VariableType)], available_functions: &FxHashMap<String, Function>, declarations: &Rc<Declarations>) -> Result<Self, String> {
+        Ok(Ssa::Unordered{data: SsaData::new(node, arguments, available_functions, declarations)?})
     }
 
     /// Get the instruction order of this branch and return those used by previous tiers (if there are any)
     pub fn order(&mut self) -> Vec<Location> {
         let mut last_used = FxHashMap::default();
+        let mut previously_used = Vec::new();
 
         // Add all the return variables
         for v in &self.return_variables {
             if v.tier == 0 { 
                 last_used.insert(v.index, u32::MAX);
+            } else {
+                previously_used.push(Location { tier: v.tier - 1, index: v.index });
             }
         }
 
         let mut reverse_instruction_order = Vec::new();
-        let mut previously_used = Vec::new();
         for instruction_index in (0..self.instructions.len() as u32).rev() {
             let instruction = self.instructions[&instruction_index].clone();
-            if !last_used.contains_key(&instruction_index) && !instruction.is_action() { continue; }
+            // Arguments are always on the stack, so they must be tracked even if unused
+            if !last_used.contains_key(&instruction_index) && !instruction.is_action() && !matches!(instruction, Instruction::Argument) { continue; }
 
             reverse_instruction_order.push(instruction_index);
             let dependencies = match instruction {
+                // Loop thetas are set from the value before the loop, and the loop writes back to them
+                Instruction::Theta(b, _) if matches!(self.branches[b], super::Branch::Loop(_)) => {
+                    vec![self.theta_origins[&instruction_index]]
+                },
                 Instruction::Action(b) | Instruction::Theta(b, _) => {
+                    // All the thetas of a branch are written with it. Loops write back to their thetas, and
+                    // if statements start from the values before the branch where a path leaves them unchanged.
+                    let is_loop = matches!(self.branches[b], super::Branch::Loop(_));
+                    let mut thetas: Vec<u32> = self.instructions.iter()
+                        .filter(|(_, other)| matches!(other, Instruction::Theta(ob, _) if *ob == b))
+                        .map(|(index, _)| *index)
+                        .collect();
+                    thetas.sort();
+                    let mut dependencies: Vec<Location> = thetas.into_iter().map(|index| match is_loop {
+                        true => Location::internal(index),
+                        false => self.theta_origins[&index],
+                    }).collect();
                     // Get the branch dependencies
-                    match &mut self.branches[b] {
+                    dependencies.extend(match &mut self.branches[b] {
                         super::Branch::If(items, ssa) => {
                             let mut output = Vec::new();
                             for (body, condition) in items {
                                 output.append(&mut body.order());
-                                dbg!(&output);
                                 output.append(&mut condition.order());
-                                dbg!(&output);
                             }
                             if let Some(ssa) = ssa {
                                 output.append(&mut ssa.order());
-                                dbg!(&output);
                             }
                             output
                         },
                         super::Branch::Loop(ssa) => {
                             ssa.order()
                         },
-                    }
+                    });
+                    dependencies
                 },
                 _ => {
                     instruction.get_var_dependencies()
@@ -1,7 +1,9 @@
+use std::rc::Rc;
+
 use rustc_hash::FxHashMap;
 use sorted_vec::SortedSet;
 
-use crate::{bytecode::{FUNCTION_MAP_LOWER, VariableType}, compiler::{Function, ssa::{Branch, Instruction, Location, Ssa}}, parser::SyntaxNode};
+use crate::{bytecode::{FUNCTION_MAP_LOWER, VariableType}, compiler::{Declarations, Function, host_arguments, ssa::{Branch, Instruction, Location, Ssa}}, parser::{Span, SyntaxNode}};
 
 
 #[derive(Clone)]
@@ -12,9 +14,21 @@ pub(crate) struct SsaData {
     instruction_counter: u32,
     pub return_variables: Vec<Location>,
     pub branches: Vec<Branch>, // The hash map maps from the local block variable to the main block variable
+    /// Source position of each instruction, for diagnostics
+    pub spans: FxHashMap<u32, Span>,
+    /// Location each theta's variable had before its branch
+    pub theta_origins: FxHashMap<u32, Location>,
+    /// Position of the node being processed
+    pub span: Option<Span>,
+    /// Records and function signatures declared in the program
+    pub declarations: Rc<Declarations>,
+    /// Whether the node being processed is the last thing its function does
+    tail: bool,
+    /// Call which is the last thing the function does, so may take over its frame
+    pub tail_call: Option<u32>,
 }
 impl SsaData {
-    pub fn new(node: &SyntaxNode, arguments: &FxHashMap<String, VariableType>, available_functions: &FxHashMap<String, Function>) -> Result<Self, String> {
+    pub fn new(node: &SyntaxNode, arguments: &[(String, VariableType)], available_functions: &FxHashMap<String, Function>, declarations: &Rc<Declarations>) -> Result<Self, String> {
         let mut data = Self {
             instructions: FxHashMap::default(),
             types: FxHashMap::default(),
@@ -22,7 +36,14 @@ impl SsaData {
             instruction_counter: 0,
             return_variables: Vec::new(),
             branches: Vec::new(),
+            spans: FxHashMap::default(),
+            theta_origins: FxHashMap::default(),
+            span: node.span(),
+            declarations: declarations.clone(),
+            tail: true,
+            tail_call: None,
         };
+        // Arguments are on the stack in order, the last at the top
         for (name, typ) in arguments.iter() {
             let var = data.push_instruction_typ(Instruction::Argument, *typ);
             data.declared_variables.insert(name.to_owned(), var);
@@ -33,6 +54,18 @@ impl SsaData {
 
     /// Add a node to the SSA
     fn process_node(&mut self, node: &SyntaxNode, available_functions: &FxHashMap<String, Function>) -> Result<Location, String> {
+        let outer_span = self.span.clone();
+        if let Some(span) = node.span() {
+            self.span = Some(span);
+        }
+        // Only statements pass on their position, so the parts of a node are never in tail position
+        let tail = std::mem::take(&mut self.tail);
+        let output = self.process_node_inner(node, available_functions, tail);
+        self.span = outer_span;
+        output
+    }
+
+    fn process_node_inner(&mut self, node: &SyntaxNode, available_functions: &FxHashMap<String, Function>, tail: bool) -> Result<Location, String> {
         Ok(match node {
             // Non-if statement block
             SyntaxNode::Block(header, body) => {
@@ -41,15 +74,23 @@ impl SsaData {
                         // loop
                         match token.get_inner().as_str() {
                             "loop" => {
-                                let (ssa, variables) = self.compile_branch_ssa(body, available_functions)?;
-                                if ssa.is_action() {
-                                    self.push_instruction(Instruction::Action(self.branches.len()));
+                                // Variables assigned in the body are carried between iterations by thetas, which the
+                                // body reads and writes back to at the end of each iteration
+                                let branch = self.branches.len();
+                                let mut assigned = SortedSet::new();
+                                assigned_variables(body, &mut assigned);
+                                let carried: Vec<String> = assigned.iter()
+                                    .filter(|name| self.declared_variables.contains_key(*name))
+                                    .cloned()
+                                    .collect();
+                                for variable in &carried {
+                                    self.push_theta(branch, variable.clone());
                                 }
+                                let (mut ssa, _, _) = self.compile_branch_ssa(body, available_functions, false)?;
+                                ssa.return_variables = carried.iter().map(|name| ssa.declared_variables[name]).collect();
                                 self.branches.push(Branch::Loop(ssa));
-                                for variable in variables {
-                                    *self.declared_variables.get_mut(&variable).unwrap() = Location::internal(self.instruction_counter);
-                                    self.push_instruction(Instruction::Theta(self.branches.len()-1, variable));
-                                }
+                                // Loops never finish, so they are kept even without effects
+                                self.push_instruction(Instruction::Action(branch));
                             },
                             _ => {return node.raise(&format!("Invalid keyword `{}`", token.get_inner()));}
                         }
@@ -90,7 +131,7 @@ impl SsaData {
                                 _ => unreachable!()
                             };
 
-                            let (body_ssa, variables) = self.compile_branch_ssa(body, available_functions)?;
+                            let (body_ssa, variables, _) = self.compile_branch_ssa(body, available_functions, tail)?;
                             let condition_ssa = match condition {
                                 Some(c) => Some(self.compile_condition_ssa(&c, available_functions)?.0),
                                 None => None 
@@ -107,15 +148,22 @@ impl SsaData {
                         _ => unreachable!()
                     }
                 }
+                // Each path leaves the final values of the variables it may change
+                for (body, _) in ifs.iter_mut() {
+                    body.return_variables = thetas.iter().map(|name| body.declared_variables[name]).collect();
+                }
+                if let Some(els) = &mut els {
+                    els.return_variables = thetas.iter().map(|name| els.declared_variables[name]).collect();
+                }
                 self.branches.push(Branch::If(ifs, els));
                 if is_action {
                     self.push_instruction(Instruction::Action(self.branches.len()-1));
                 }
                 for variable in thetas {
-                    *self.declared_variables.get_mut(&variable).unwrap() = Location::internal(self.instruction_counter);
-                    self.push_instruction(Instruction::Theta(self.branches.len()-1, variable));
+                    self.push_theta(self.branches.len()-1, variable);
                 }
-                Location::internal(self.instruction_counter-1)
+                // If statements have no value, and may write no instructions
+                Location::internal(self.instruction_counter.saturating_sub(1))
             },
             // Keyword phrase, function call, or just a bunch of commands
             SyntaxNode::Adjacent(nodes) => {
@@ -138,32 +186,59 @@ impl SsaData {
                                         _ => {return node.raise("Invalid function call 4");}
                                     }
                                 },
-                                SyntaxNode::Adjacent(tokens) => if tokens.is_empty() {
+                                SyntaxNode::Adjacent(tokens) if tokens.is_empty() => {
                                     // There were no arguments
                                     &[]
-                                } else {
-                                    return node.raise("Invalid function call 2");
                                 },
                                 _ => {
                                     // There was a single argument
                                     std::slice::from_ref(arguments)
                                 }
                             };
-                            let mut arg_v = self.push_instruction(Instruction::LiteralVector(Vec::new()));
+                            let first = text.get_inner();
+                            let (data, arguments) = match FUNCTION_MAP_LOWER.get(first) {
+                                Some(f) => host_arguments(*f, node, arguments)?,
+                                None => (Vec::new(), arguments),
+                            };
+                            let mut values = Vec::new();
                             for argument in arguments {
-                                let node = self.process_node(argument, available_functions)?;
-                                arg_v = self.push_instruction(Instruction::Stb(arg_v, node));
+                                values.push(self.process_node(argument, available_functions)?);
                             }
-                            
-                            let first = text.get_inner();
+
                             match FUNCTION_MAP_LOWER.get(first) {
                                 Some(f) => {
+                                    // Host functions take their arguments in a vector
+                                    let mut arg_v = self.push_instruction(Instruction::LiteralVector(data));
+                                    for value in values {
+                                        arg_v = self.push_instruction(Instruction::Stb(arg_v, value));
+                                    }
                                     let typ = f.return_type();
                                     self.push_instruction_typ(Instruction::Call(*f as u8, arg_v), typ)
                                 },
+                                None if let Some(record) = self.declarations.record(first) => {
+                                    // Records are built like the arguments of host functions
+                                    let fields = self.declarations.records[record as usize].fields.len();
+                                    if values.len() != fields {
+                                        return node.raise(&format!("Record {} has {} field{}, but {} {} given", first, fields,
+                                            if fields == 1 { "" } else { "s" }, values.len(), if values.len() == 1 { "was" } else { "were" }));
+                                    }
+                                    let mut vector = self.push_instruction(Instruction::LiteralVector(Vec::new()));
+                                    for value in values {
+                                        vector = self.push_instruction(Instruction::Stb(vector, value));
+                                    }
+                                    self.push_instruction(Instruction::Record(record, vector))
+                                },
+                                // Variables holding functions are called like the functions
+                                None if let Some(callee) = self.declared_variables.get(first).copied() => {
+                                    let call = self.push_instruction(Instruction::DynamicCall(first.to_owned(), callee, values));
+                                    if tail { self.tail_call = Some(call.index); }
+                                    call
+                                },
                                 None => match available_functions.get(first) {
                                     Some(f) => {
-                                        self.push_instruction_typ(Instruction::LocalCall(f.name.clone(), arg_v), f.return_value)
+                                        let call = self.push_instruction_typ(Instruction::LocalCall(f.name.clone(), values), f.return_value);
+                                        if tail { self.tail_call = Some(call.index); }
+                                        call
                                     },
                                     None => {return node.raise(&format!("Unrecognized function {}", first));},
                                 }
@@ -172,16 +247,25 @@ impl SsaData {
                     },
                     _ => {
                         let mut value = Location::internal(0);
-                        for node in nodes {
+                        for (i, node) in nodes.iter().enumerate() {
+                            self.tail = tail && i + 1 == nodes.len();
                             value = self.process_node(node, available_functions)?;
                         }
                         value
                     }
                 }
             },
+            SyntaxNode::Unclassified(token) if token.get_inner().contains('.') => {
+                let (record, _, field) = self.field(node, token.get_inner())?;
+                self.load_field(record, field)
+            },
             SyntaxNode::Unclassified(token) => {
                 match self.declared_variables.get(token.get_inner()) {
                     Some(n) => *n,
+                    // A function used as a value
+                    None if let Some(f) = available_functions.get(token.get_inner()) && f.name != "const" => {
+                        self.push_instruction_typ(Instruction::LiteralFunction(f.name.clone()), VariableType::Function(f.signature))
+                    },
                     None => {return node.raise(&format!("Undeclared variable {}", token.get_inner()));},
                 }
             }
@@ -193,6 +277,37 @@ impl SsaData {
                     _ => None
                 };
                 match *op {
+                    // Assign to a field of a record
+                    "=" | "+=" | "-=" | "*=" | "/=" if a_name.is_some_and(|n| n.contains('.')) => {
+                        let a_name = a_name.unwrap();
+                        let (record, typ, field) = self.field(a, a_name)?;
+                        let value = match *op {
+                            "=" => b_var,
+                            _ => {
+                                let old = self.load_field(record, field);
+                                match *op {
+                                    "+=" => self.push_instruction(Instruction::Add(old, b_var)),
+                                    "-=" => self.push_instruction(Instruction::Sub(old, b_var)),
+                                    "*=" => self.push_instruction(Instruction::Mul(old, b_var)),
+                                    "/=" => self.push_instruction(Instruction::Div(old, b_var)),
+                                    _ => unreachable!()
+                                }
+                            },
+                        };
+                        // Records are values, so the record is copied with the field replaced
+                        let mut vector = self.push_instruction(Instruction::LiteralVector(Vec::new()));
+                        for i in 0..self.declarations.records[typ as usize].fields.len() {
+                            let item = match i == field {
+                                true => value,
+                                false => self.load_field(record, i),
+                            };
+                            vector = self.push_instruction(Instruction::Stb(vector, item));
+                        }
+                        let updated = self.push_instruction(Instruction::Record(typ, vector));
+                        let name = a_name.split('.').next().unwrap();
+                        *self.declared_variables.get_mut(name).unwrap() = updated;
+                        value
+                    },
                     // Handle equal sign
                     "=" => {
                         let a_name = a_name.ok_or(a.raise_str("Invalid syntax 19"))?;
@@ -202,12 +317,15 @@ impl SsaData {
                     // Handle assignment operators
                     "+=" | "-=" | "*=" | "/=" => {
                         let a_name = a_name.ok_or(a.raise_str("Invalid syntax 20"))?;
-                        let a_var = self.process_node(b, available_functions)?;
+                        let a_var = match self.declared_variables.get(a_name) {
+                            Some(v) => *v,
+                            None => return a.raise(&format!("Undeclared variable {}", a_name)),
+                        };
                         let result = match *op {
                             "+=" => self.push_instruction(Instruction::Add(a_var, b_var)),
-                            "-=" => self.push_instruction(Instruction::Add(a_var, b_var)),
-                            "*=" => self.push_instruction(Instruction::Add(a_var, b_var)),
-                            "/=" => self.push_instruction(Instruction::Add(a_var, b_var)), 
+                            "-=" => self.push_instruction(Instruction::Sub(a_var, b_var)),
+                            "*=" => self.push_instruction(Instruction::Mul(a_var, b_var)),
+                            "/=" => self.push_instruction(Instruction::Div(a_var, b_var)),
                             _ => unreachable!()
                         };
                         *self.declared_variables.get_mut(a_name).unwrap() = result;
@@ -222,6 +340,10 @@ impl SsaData {
                             ">=" => self.push_instruction(Instruction::Ge(a_var, b_var)),
                             "<=" => self.push_instruction(Instruction::Le(a_var, b_var)),
                             "==" => self.push_instruction(Instruction::Eq(a_var, b_var)),
+                            "!=" => {
+                                let equal = self.push_instruction(Instruction::Eq(a_var, b_var));
+                                self.push_instruction(Instruction::Not(equal))
+                            },
                             "&&" => self.push_instruction(Instruction::And(a_var, b_var)),
                             "||" => self.push_instruction(Instruction::Or(a_var, b_var)),
                             "^" => self.push_instruction(Instruction::Xor(a_var, b_var)),
@@ -248,17 +370,59 @@ impl SsaData {
             SyntaxNode::Number(t) => {
                 self.push_instruction(Instruction::LiteralFloat(*t.get_inner()))
             },
+            // Strings are lists of character codes
+            SyntaxNode::Str(t) => {
+                self.push_instruction(Instruction::LiteralVector(t.get_inner().bytes().map(|b| b as f64).collect()))
+            },
+            // Grouping
+            SyntaxNode::Parenthesis("(", inner) => self.process_node(inner, available_functions)?,
             SyntaxNode::Parenthesis(_, _) => { return node.raise("Lines cannot start with a parenthesis"); },
             SyntaxNode::List(_, _) => { return node.raise("Lines cannot start with a list"); },
         })
     }
 
+    /// Find the record and field named by `variable.field`, returning the record's location and type, and the
+    /// field's index
+    fn field(&self, node: &SyntaxNode, path: &str) -> Result<(Location, u32, usize), String> {
+        let mut parts = path.split('.');
+        let (Some(name), Some(field), None) = (parts.next(), parts.next(), parts.next()) else {
+            return node.raise(&format!("Invalid field access {}", path));
+        };
+        let Some(record) = self.declared_variables.get(name).copied() else {
+            return node.raise(&format!("Undeclared variable {}", name));
+        };
+        let VariableType::Record(typ) = self.types[&record] else {
+            return node.raise(&format!("Variable {} is not a record, so it has no field {}", name, field));
+        };
+        match self.declarations.records[typ as usize].field(field) {
+            Some(index) => Ok((record, typ, index)),
+            None => node.raise(&format!("Record {} has no field {}", self.declarations.records[typ as usize].name, field)),
+        }
+    }
+
+    fn load_field(&mut self, record: Location, field: usize) -> Location {
+        let index = self.push_instruction(Instruction::LiteralFloat(field as f64));
+        self.push_instruction(Instruction::Ld(record, index))
+    }
+
     fn push_instruction(&mut self, instruction: Instruction) -> Location {
         let typ = instruction.typ();
         self.push_instruction_typ(instruction, typ)
     }
 
+    /// Declare the value of a variable after a branch. Its type is assumed unchanged until the type checker runs.
+    fn push_theta(&mut self, branch: usize, variable: String) {
+        let before = self.declared_variables[&variable];
+        let typ = self.types[&before];
+        self.theta_origins.insert(self.instruction_counter, before);
+        *self.declared_variables.get_mut(&variable).unwrap() = Location::internal(self.instruction_counter);
+        self.push_instruction_typ(Instruction::Theta(branch, variable), typ);
+    }
+
     fn push_instruction_typ(&mut self, instruction: Instruction, typ: VariableType) -> Location {
+        if let Some(span) = &self.span {
+            self.spans.insert(self.instruction_counter, span.clone());
+        }
         self.instructions.insert(self.instruction_counter, instruction);
         self.types.insert(Location::internal(self.instruction_counter), typ);
         self.instruction_counter += 1;
@@ -267,14 +431,14 @@ impl SsaData {
 
     /// Compile an SSA from code in a branch, returning code and the theta variables
     fn compile_condition_ssa(&self, node: &SyntaxNode, available_functions: &FxHashMap<String, Function>) -> Result<(Ssa, SortedSet<String>), String> {
-        let (mut ssa, thetas) = self.compile_branch_ssa(node, available_functions)?;
-        let last_variable = Location::internal(ssa.instruction_counter-1);
-        ssa.return_variables.push(last_variable);
+        let (mut ssa, thetas, value) = self.compile_branch_ssa(node, available_functions, false)?;
+        ssa.return_variables.push(value);
         Ok((ssa, thetas))
     }
 
-    /// Compile an SSA from code in a branch, returning code and the theta variables
-    fn compile_branch_ssa(&self, node: &SyntaxNode, available_functions: &FxHashMap<String, Function>) -> Result<(Ssa, SortedSet<String>), String> {
+    /// Compile an SSA from code in a branch, returning code, the theta variables and the value of the code.
+    /// `tail` is whether the branch is the last thing its function does.
+    fn compile_branch_ssa(&self, node: &SyntaxNode, available_functions: &FxHashMap<String, Function>, tail: bool) -> Result<(Ssa, SortedSet<String>, Location), String> {
         let declared_variables = FxHashMap::from_iter(self.declared_variables.iter().map(|(k, v)| (k.to_owned(), v.graduate())));
         let types = FxHashMap::from_iter(self.types.iter().map(|(k, v)| (k.graduate(), *v)));
         let mut data = Self {
@@ -284,8 +448,14 @@ impl SsaData {
             instruction_counter: 0,
             return_variables: Vec::new(),
             branches: Vec::new(),
+            spans: FxHashMap::default(),
+            theta_origins: FxHashMap::default(),
+            span: node.span(),
+            declarations: self.declarations.clone(),
+            tail,
+            tail_call: None,
         };
-        data.process_node(node, available_functions)?;
+        let value = data.process_node(node, available_functions)?;
 
         // Find all the external variables written to
         let mut variables = SortedSet::new();
@@ -296,14 +466,14 @@ impl SsaData {
             }
         }
     
-        Ok((Ssa::Unordered { data }, variables))
+        Ok((Ssa::Unordered { data }, variables, value))
     }
 
     pub fn get_used_functions(&self) -> Vec<String> {
         let mut funcs = SortedSet::new();
         for command in self.instructions.values() {
             match &command {
-                Instruction::LocalCall(name, _) => {funcs.push(name.clone());},
+                Instruction::LocalCall(name, _) | Instruction::LiteralFunction(name) => {funcs.push(name.clone());},
                 _ => ()
             };
         }
@@ -312,8 +482,9 @@ impl SsaData {
         for branch in &self.branches {
             match branch {
                 Branch::If(items, ssa) => {
-                    for (ssa, _) in items {
+                    for (ssa, condition) in items {
                         funcs.append(&mut ssa.get_used_functions());
+                        funcs.append(&mut condition.get_used_functions());
                     }
                     if let Some(ssa) = ssa {
                         funcs.append(&mut ssa.get_used_functions());
@@ -373,3 +544,26 @@ impl std::fmt::Debug for SsaData {
         write!(f, ")")
     }
 }
+
+/// Find the names of all variables assigned to in the code
+fn assigned_variables(node: &SyntaxNode, names: &mut SortedSet<String>) {
+    match node {
+        SyntaxNode::Binop(op, a, b) => {
+            if let ("=" | "+=" | "-=" | "*=" | "/=", SyntaxNode::Unclassified(token)) = (*op, &**a) {
+                // Assigning to a field replaces the whole record
+                names.push(token.get_inner().split('.').next().unwrap().to_owned());
+            }
+            assigned_variables(a, names);
+            assigned_variables(b, names);
+        },
+        SyntaxNode::Adjacent(nodes) | SyntaxNode::IfChain(nodes) => for node in nodes {
+            assigned_variables(node, names);
+        },
+        SyntaxNode::Block(a, b) => {
+            assigned_variables(a, names);
+            assigned_variables(b, names);
+        },
+        SyntaxNode::Parenthesis(_, n) | SyntaxNode::List(_, n) | SyntaxNode::Unop(_, n) => assigned_variables(n, names),
+        SyntaxNode::Unclassified(_) | SyntaxNode::Number(_) | SyntaxNode::Str(_) => (),
+    }
+}
@@ -0,0 +1,272 @@
+use rustc_hash::FxHashMap;
+
+use crate::{bytecode::{GlobalFunction, VariableType}, compiler::{Function, ssa::{Branch, Instruction, Location, ssa_data::SsaData}}, parser::Span};
+
+fn describe(data: &SsaData, typ: VariableType) -> String {
+    match typ {
+        VariableType::Null => "nothing".to_owned(),
+        _ => format!("a {}", data.declarations.type_name(typ)),
+    }
+}
+
+/// Infers the types of an SSA through its branches, and checks the operands of every instruction and the
+/// arguments of every call. All mismatches are collected, so that they are reported together.
+pub(crate) struct TypeChecker<'a> {
+    functions: &'a FxHashMap<String, Function>,
+    diagnostics: Vec<String>,
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new(functions: &'a FxHashMap<String, Function>) -> Self {
+        Self {
+            functions,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn check(mut self, data: &mut SsaData) -> Result<(), String> {
+        self.check_data(data);
+        match self.diagnostics.is_empty() {
+            true => Ok(()),
+            false => Err(self.diagnostics.join("\n\n")),
+        }
+    }
+
+    fn raise(&mut self, data: &SsaData, index: u32, message: &str) {
+        self.raise_at(data.spans.get(&index), message);
+    }
+
+    fn raise_at(&mut self, span: Option<&Span>, message: &str) {
+        let diagnostic = match span {
+            Some(span) => span.raise_str(message),
+            None => format!("No file\n{}", message),
+        };
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn check_data(&mut self, data: &mut SsaData) {
+        let mut checked_branches = vec![false; data.branches.len()];
+        let mut indices: Vec<u32> = data.instructions.keys().copied().collect();
+        indices.sort();
+        for index in indices {
+            let instruction = data.instructions[&index].clone();
+            let typ = match &instruction {
+                Instruction::Argument | Instruction::LiteralFunction(_) => data.types[&Location::internal(index)],
+                Instruction::LiteralVector(_) => VariableType::List,
+                Instruction::LiteralFloat(_) => VariableType::Float,
+                Instruction::Call(func, args) => {
+                    let func = GlobalFunction::try_from(*func).unwrap();
+                    let name = func.to_string().to_lowercase();
+                    match func.arguments() {
+                        Some(expected) => if let Some(args) = call_arguments(data, *args) {
+                            self.check_arguments(data, index, &name, expected, &args);
+                        },
+                        None => if let Some(args) = call_arguments(data, *args) {
+                            for arg in args {
+                                self.expect(data, index, arg, VariableType::Float, &format!("Arguments of {}", name));
+                            }
+                        },
+                    }
+                    func.return_type()
+                },
+                Instruction::LocalCall(name, args) => {
+                    let function = &self.functions[name];
+                    let expected: Vec<VariableType> = function.arguments.iter().map(|(_, t)| *t).collect();
+                    self.check_arguments(data, index, name, &expected, args);
+                    // Functions do not return values yet
+                    VariableType::Null
+                },
+                Instruction::DynamicCall(name, callee, args) => {
+                    match data.types.get(callee).copied() {
+                        Some(VariableType::Function(signature)) => {
+                            let expected = data.declarations.signatures[signature as usize].arguments.clone();
+                            self.check_arguments(data, index, name, &expected, args);
+                        },
+                        Some(typ) => {
+                            let message = format!("Variable {} is {}, so it cannot be called", name, describe(data, typ));
+                            self.raise(data, index, &message);
+                        },
+                        None => (),
+                    }
+                    VariableType::Null
+                },
+                Instruction::Theta(branch, name) => {
+                    if !checked_branches[*branch] {
+                        self.check_branch(data, *branch);
+                        checked_branches[*branch] = true;
+                    }
+                    self.theta_type(data, index, *branch, name)
+                },
+                Instruction::Action(branch) => {
+                    if !checked_branches[*branch] {
+                        self.check_branch(data, *branch);
+                        checked_branches[*branch] = true;
+                    }
+                    VariableType::Null
+                },
+                Instruction::Record(record, vector) => {
+                    self.expect(data, index, *vector, VariableType::List, "Record");
+                    VariableType::Record(*record)
+                },
+                Instruction::Ld(vector, i) => {
+                    // Fields of records are loaded by index
+                    if !matches!(data.types.get(vector), Some(VariableType::Record(_))) {
+                        self.expect(data, index, *vector, VariableType::List, "Indexed value");
+                    }
+                    self.expect(data, index, *i, VariableType::Float, "Index");
+                    VariableType::Float
+                },
+                Instruction::St(vector, i, value) => {
+                    self.expect(data, index, *vector, VariableType::List, "Indexed value");
+                    self.expect(data, index, *i, VariableType::Float, "Index");
+                    self.expect(data, index, *value, VariableType::Float, "List item");
+                    VariableType::List
+                },
+                Instruction::Stb(vector, value) => {
+                    self.expect(data, index, *vector, VariableType::List, "Appended value");
+                    self.expect(data, index, *value, VariableType::Float, "List item");
+                    VariableType::List
+                },
+                Instruction::Lt(a, b) | Instruction::Gt(a, b) | Instruction::Le(a, b) | Instruction::Ge(a, b) |
+                Instruction::Eq(a, b) | Instruction::And(a, b) | Instruction::Or(a, b) | Instruction::Xor(a, b) |
+                Instruction::Add(a, b) | Instruction::Sub(a, b) | Instruction::Mul(a, b) | Instruction::Div(a, b) |
+                Instruction::Pow(a, b) => {
+                    self.expect(data, index, *a, VariableType::Float, "Operand");
+                    self.expect(data, index, *b, VariableType::Float, "Operand");
+                    VariableType::Float
+                },
+                Instruction::Not(a) | Instruction::Neg(a) => {
+                    self.expect(data, index, *a, VariableType::Float, "Operand");
+                    VariableType::Float
+                },
+            };
+            data.types.insert(Location::internal(index), typ);
+        }
+
+        // Branches without effects are never written, but their mistakes are still reported
+        for (branch, checked) in checked_branches.into_iter().enumerate() {
+            if !checked {
+                self.check_branch(data, branch);
+            }
+        }
+    }
+
+    /// Report if the value at `loc` is not of the expected type
+    fn expect(&mut self, data: &SsaData, index: u32, loc: Location, expected: VariableType, what: &str) {
+        if let Some(message) = mismatch(data, loc, expected, what) {
+            self.raise(data, index, &message);
+        }
+    }
+
+    fn check_arguments(&mut self, data: &SsaData, index: u32, name: &str, expected: &[VariableType], args: &[Location]) {
+        if args.len() != expected.len() {
+            let message = format!("Function {} takes {} argument{}, but {} {} given", name, expected.len(),
+                if expected.len() == 1 { "" } else { "s" }, args.len(), if args.len() == 1 { "was" } else { "were" });
+            self.raise(data, index, &message);
+            return;
+        }
+        for (i, (arg, typ)) in args.iter().zip(expected).enumerate() {
+            self.expect(data, index, *arg, *typ, &format!("Argument {} of {}", i + 1, name));
+        }
+    }
+
+    /// Check the code of a branch, using the types of the enclosing code as they are now known
+    fn check_branch(&mut self, data: &mut SsaData, branch: usize) {
+        let SsaData { types, branches, .. } = data;
+        match &mut branches[branch] {
+            Branch::If(items, els) => {
+                for (body, condition) in items {
+                    self.check_child(types, condition);
+                    if let Some(result) = condition.return_variables.first().copied()
+                        && let Some(message) = mismatch(condition, result, VariableType::Float, "Condition") {
+                        // Conditions which only name a variable have no instructions to point at
+                        let span = match result.tier {
+                            0 => condition.spans.get(&result.index),
+                            _ => None,
+                        };
+                        self.raise_at(span.or(condition.span.as_ref()), &message);
+                    }
+                    self.check_child(types, body);
+                }
+                if let Some(els) = els {
+                    self.check_child(types, els);
+                }
+            },
+            Branch::Loop(body) => self.check_child(types, body),
+        }
+    }
+
+    fn check_child(&mut self, parent_types: &FxHashMap<Location, VariableType>, child: &mut SsaData) {
+        child.types.retain(|l, _| l.tier == 0);
+        child.types.extend(parent_types.iter().map(|(l, t)| (l.graduate(), *t)));
+        self.check_data(child);
+    }
+
+    /// The type of a variable after a branch, which must be the same on every path through it
+    fn theta_type(&mut self, data: &SsaData, index: u32, branch: usize, name: &str) -> VariableType {
+        let before = data.types[&data.theta_origins[&index]];
+        let final_type = |ssa: &SsaData| ssa.types[&ssa.declared_variables[name]];
+        match &data.branches[branch] {
+            Branch::If(items, els) => {
+                let mut paths: Vec<VariableType> = items.iter().map(|(body, _)| final_type(body)).collect();
+                match els {
+                    Some(els) => paths.push(final_type(els)),
+                    // The variable is unchanged if no condition holds
+                    None => paths.push(before),
+                }
+                if let Some(other) = paths.iter().find(|t| **t != paths[0]) {
+                    let message = format!("Variable {} is {} on one path through the if statement, but {} on another",
+                        name, describe(data, paths[0]), describe(data, *other));
+                    self.raise(data, index, &message);
+                }
+                paths[0]
+            },
+            Branch::Loop(body) => {
+                let after = final_type(body);
+                if after != before {
+                    let message = format!("Variable {} is {} before the loop, but {} after an iteration",
+                        name, describe(data, before), describe(data, after));
+                    self.raise(data, index, &message);
+                }
+                before
+            },
+        }
+    }
+}
+
+/// Describe how the value at `loc` differs from the expected type, if it does
+fn mismatch(data: &SsaData, loc: Location, expected: VariableType, what: &str) -> Option<String> {
+    let actual = data.types.get(&loc).copied()?;
+    if actual == expected { return None; }
+    let mut message = match actual {
+        VariableType::Null => format!("{} has no value. Expected {}", what, describe(data, expected)),
+        _ => format!("{} should be {}, but is {}", what, describe(data, expected), describe(data, actual)),
+    };
+    // Suggest declaring list arguments
+    if expected == VariableType::List && loc.tier == 0
+        && let Some(Instruction::Argument) = data.instructions.get(&loc.index)
+        && let Some((name, _)) = data.declared_variables.iter().find(|(_, l)| **l == loc) {
+        message = format!("{}. Declare the argument as `{}[]` to pass a list", message, name);
+    }
+    Some(message)
+}
+
+/// Follow the vector built for a call back to its literal, returning the arguments in order.
+/// Returns None if the vector was not built in place.
+fn call_arguments(data: &SsaData, mut loc: Location) -> Option<Vec<Location>> {
+    let mut args = Vec::new();
+    loop {
+        if loc.tier != 0 { return None; }
+        match data.instructions.get(&loc.index)? {
+            Instruction::Stb(vector, value) => {
+                args.push(*value);
+                loc = *vector;
+            },
+            // Data written by the compiler, such as the format of print, is not checked
+            Instruction::LiteralVector(_) => break,
+            _ => return None,
+        }
+    }
+    args.reverse();
+    Some(args)
+}
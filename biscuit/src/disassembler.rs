@@ -42,7 +42,7 @@ pub fn disassemble_bytes(s: &[u8], _filename: &str) -> Result<String, String> {
                 line = format!("{} {}", line, arg);
             },
             
-            Command::Jmp | Command::Jnz=> {
+            Command::Jmp | Command::Jnz | Command::Jsr => {
                 let label_pos = u64::from_le_bytes([
                     *iter.next().ok_or("Corrupted file")?.1,
                     *iter.next().ok_or("Corrupted file")?.1,
@@ -1,4 +1,4 @@
-use biscuit::{Instructions, Machine, MachineOutput};
+use biscuit::{GlobalFunction, Instructions, Log, Machine, MachineOutput, bytecode::VariableType};
 use ratatui::{
     Frame, crossterm::event::KeyCode, layout::{Constraint, Direction, Layout, Rect}, style::{Color, Style}, widgets::{Block, Borders, Paragraph},
 };
@@ -10,7 +10,7 @@ pub struct App {
     machine: Machine,
     ip_map: FxHashMap<usize, usize>,
     err_state: bool,
-    output_text: String,
+    log: Log,
 }
 
 impl App {
@@ -35,7 +35,7 @@ impl App {
             machine,
             ip_map,
             err_state: false,
-            output_text: "".to_owned(),
+            log: Log::new(256),
         }
     }
 }
@@ -60,8 +60,12 @@ impl App {
             KeyCode::Char(' ') => {
                 let copy = self.machine.clone();
                 let result = match self.machine.run_to_call() {
+                    Ok(MachineOutput::Call{func: func @ (GlobalFunction::Dbg | GlobalFunction::Print), args}) => self.log.write(func, args),
                     Ok(MachineOutput::Call{func, args}) => {
-                        self.output_text = format!("{}{} {:?}\n", self.output_text, func, args);
+                        self.log.push(format!("{} {:?}", func, args));
+                        if func.return_type() != VariableType::Null {
+                            self.machine.return_value(0.);
+                        }
                         Ok(())
                     },
                     Ok(MachineOutput::None) => Ok(()),
@@ -69,7 +73,7 @@ impl App {
                 };
                 if let Err(e) = result {
                     self.machine = copy;
-                    self.output_text = format!("{}{:?} Error", self.output_text, e);
+                    self.log.push(format!("{:?} Error", e));
                     self.err_state = true;
                 };
             },
@@ -134,7 +138,10 @@ impl App {
 
         let color = if self.err_state { Color::DarkGray } else { Color::White };
 
-        let mut para = Paragraph::new((&self.output_text).as_str());
+        // Show the newest lines which fit
+        let lines: Vec<&str> = self.log.lines().rev().take(inner.height as usize).collect();
+        let text = lines.into_iter().rev().collect::<Vec<_>>().join("\n");
+        let mut para = Paragraph::new(text);
         let style = Style::default().fg(color);
         para = para.style(style);
         f.render_widget(para, inner);
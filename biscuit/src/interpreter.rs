@@ -0,0 +1,785 @@
+// Runs Biscuit code directly from its syntax tree, with the same host interface as `Machine`. Scripts start
+// running as soon as they parse, which makes iterating on them quicker, and the interpreter defines what the
+// compiled code of a script should do.
+use std::rc::Rc;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{GlobalFunction, InterruptKind, bytecode::FUNCTION_MAP_LOWER, compiler::{Compiler, Declarations, host_arguments}, machine::{Executor, InterruptQueue, MAX_CALL_DEPTH, MachineError, MachineOutput, MachineState}, parser::SyntaxNode};
+
+type NodeId = usize;
+type Name = usize;
+
+#[derive(Clone, Copy, Debug)]
+enum Binary {
+    Add, Sub, Mul, Div, Pow,
+    Lt, Gt, Le, Ge, Eq, Ne,
+    And, Or, Xor,
+}
+
+impl Binary {
+    fn from_str(op: &str) -> Option<Self> {
+        Some(match op {
+            "+" | "+=" => Binary::Add,
+            "-" | "-=" => Binary::Sub,
+            "*" | "*=" => Binary::Mul,
+            "/" | "/=" => Binary::Div,
+            "**" => Binary::Pow,
+            "<" => Binary::Lt,
+            ">" => Binary::Gt,
+            "<=" => Binary::Le,
+            ">=" => Binary::Ge,
+            "==" => Binary::Eq,
+            "!=" => Binary::Ne,
+            "&&" => Binary::And,
+            "||" => Binary::Or,
+            "^" => Binary::Xor,
+            _ => return None,
+        })
+    }
+
+    /// Apply the operation the same way the machine does
+    fn apply(self, a: f64, b: f64) -> f64 {
+        let truth = |t: bool| t as i64 as f64;
+        match self {
+            Binary::Add => a + b,
+            Binary::Sub => a - b,
+            Binary::Mul => a * b,
+            Binary::Div => a / b,
+            Binary::Pow => a.powf(b),
+            Binary::Lt => truth(a < b),
+            Binary::Gt => truth(a > b),
+            Binary::Le => truth(a <= b),
+            Binary::Ge => truth(a >= b),
+            Binary::Eq => truth(a == b),
+            Binary::Ne => truth(a != b),
+            Binary::And => truth((a != 0.) && (b != 0.)),
+            Binary::Or => truth((a != 0.) || (b != 0.)),
+            Binary::Xor => truth((a != 0.) ^ (b != 0.)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Unary {
+    Not,
+    Neg,
+}
+
+/// A value on the stack or in a variable. Lists are shared, since they are never changed in place.
+#[derive(Clone, Debug)]
+enum Value {
+    Float(f64),
+    // Scripts cannot index lists yet
+    #[allow(dead_code)]
+    List(Rc<[f64]>),
+    /// A record, with the index of its type
+    Record(u32, Rc<[f64]>),
+    /// A function, by its procedure
+    Function(usize),
+}
+
+impl Value {
+    /// The value as a float. The type checker makes sure that floats are found where they are used.
+    fn float(&self) -> Result<f64, MachineError> {
+        match self {
+            Value::Float(value) => Ok(*value),
+            _ => Err(MachineError::Stack),
+        }
+    }
+}
+
+/// A node of the syntax tree, with names and operators resolved
+#[derive(Debug)]
+enum Node {
+    Number(f64),
+    /// A string, as a list of character codes
+    Str(Rc<[f64]>),
+    Variable(Name),
+    /// Field of a record variable
+    Field(Name, String),
+    /// Assign to a field of a record variable, applying an operation to its old value first for compound assignments
+    SetField(Name, String, Option<Binary>, NodeId),
+    /// Build a record from its fields
+    Record(u32, Vec<NodeId>),
+    /// Assign to a variable, applying an operation to its old value first for compound assignments
+    Assign(Name, Option<Binary>, NodeId),
+    Binary(Binary, NodeId, NodeId),
+    Unary(Unary, NodeId),
+    Sequence(Vec<NodeId>),
+    /// Conditions and bodies, and the else body
+    If(Vec<(NodeId, NodeId)>, Option<NodeId>),
+    Loop(NodeId),
+    /// Host function, the data passed before the arguments, and the arguments
+    HostCall(GlobalFunction, Vec<f64>, Vec<NodeId>),
+    /// Call a function, with whether the call is the last thing its caller does
+    Call(usize, Vec<NodeId>, bool),
+    /// A function as a value
+    Function(usize),
+    /// Call the function held by a variable, which is evaluated after the arguments
+    DynamicCall(NodeId, Vec<NodeId>, bool),
+}
+
+#[derive(Debug)]
+struct Procedure {
+    arguments: Vec<Name>,
+    body: NodeId,
+}
+
+#[derive(Debug)]
+struct Program {
+    nodes: Vec<Node>,
+    declarations: Rc<Declarations>,
+    procedures: Vec<Procedure>,
+    main: usize,
+    handlers: Vec<(InterruptKind, usize)>,
+}
+
+/// Builds a `Program` from a syntax tree, reporting the same errors as the compiler would
+struct Lowering {
+    nodes: Vec<Node>,
+    declarations: Rc<Declarations>,
+    names: FxHashMap<String, Name>,
+    /// Index and number of arguments of each function
+    procedures: FxHashMap<String, (usize, usize)>,
+    /// Whether the node being lowered is the last thing its function does
+    tail: bool,
+}
+
+impl Lowering {
+    fn name(&mut self, name: &str) -> Name {
+        let next = self.names.len();
+        *self.names.entry(name.to_owned()).or_insert(next)
+    }
+
+    /// Split `variable.field`. The type checker has made sure that the variable is a record with the field.
+    fn field(&mut self, node: &SyntaxNode, path: &str, scope: &FxHashSet<Name>) -> Result<(Name, String), String> {
+        let Some((name, field)) = path.split_once('.') else { unreachable!() };
+        let name = self.name(name);
+        if !scope.contains(&name) {
+            return node.raise(&format!("Undeclared variable {}", path));
+        }
+        Ok((name, field.to_owned()))
+    }
+
+    fn push(&mut self, node: Node) -> NodeId {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    /// Lower the body of a branch. Variables declared inside it are not visible after it.
+    fn lower_branch(&mut self, node: &SyntaxNode, scope: &FxHashSet<Name>) -> Result<NodeId, String> {
+        self.lower(node, &mut scope.clone())
+    }
+
+    fn lower(&mut self, node: &SyntaxNode, scope: &mut FxHashSet<Name>) -> Result<NodeId, String> {
+        // Only statements pass on their position, as in the compiler
+        let tail = std::mem::take(&mut self.tail);
+        let lowered = match node {
+            SyntaxNode::Block(header, body) => match &**header {
+                SyntaxNode::Unclassified(token) if token == "loop" => {
+                    let body = self.lower_branch(body, scope)?;
+                    Node::Loop(body)
+                },
+                SyntaxNode::Unclassified(token) => return node.raise(&format!("Invalid keyword `{}`", token.get_inner())),
+                _ => return node.raise("Invalid syntax 16"),
+            },
+            SyntaxNode::IfChain(nodes) => {
+                let mut arms = Vec::new();
+                let mut els = None;
+                for node in nodes {
+                    let SyntaxNode::Block(predicate, body) = node else { unreachable!() };
+                    match &**predicate {
+                        SyntaxNode::Adjacent(nodes) => {
+                            let condition: Vec<&SyntaxNode> = nodes.iter()
+                                .filter(|n| !matches!(n, SyntaxNode::Unclassified(t) if t == "else" || t == "if"))
+                                .collect();
+                            let [condition] = condition[..] else {
+                                return node.raise("If statements must contain exactly one condition");
+                            };
+                            // Assignments in conditions are not kept
+                            let condition = self.lower_branch(condition, scope)?;
+                            self.tail = tail;
+                            arms.push((condition, self.lower_branch(body, scope)?));
+                        },
+                        SyntaxNode::Unclassified(t) if t == "else" => {
+                            self.tail = tail;
+                            els = Some(self.lower_branch(body, scope)?);
+                        },
+                        _ => return node.raise("Else statements must contain no conditions"),
+                    }
+                }
+                Node::If(arms, els)
+            },
+            SyntaxNode::Adjacent(nodes) => match nodes.first() {
+                Some(SyntaxNode::Unclassified(name)) => {
+                    let arguments = match nodes.get(1) {
+                        Some(SyntaxNode::Parenthesis("(", arguments)) if nodes.len() == 2 => arguments,
+                        _ => return node.raise("Invalid function call 1"),
+                    };
+                    let arguments: &[SyntaxNode] = match &**arguments {
+                        SyntaxNode::List(",", items) => match &**items {
+                            SyntaxNode::Adjacent(items) => items,
+                            _ => return node.raise("Invalid function call 4"),
+                        },
+                        SyntaxNode::Adjacent(items) if items.is_empty() => &[],
+                        other => std::slice::from_ref(other),
+                    };
+                    let name = name.get_inner();
+                    let (data, arguments) = match FUNCTION_MAP_LOWER.get(name) {
+                        Some(func) => host_arguments(*func, node, arguments)?,
+                        None => (Vec::new(), arguments),
+                    };
+                    let mut values = Vec::new();
+                    for argument in arguments {
+                        values.push(self.lower(argument, scope)?);
+                    }
+                    let variable = self.name(name);
+                    match (FUNCTION_MAP_LOWER.get(name), self.procedures.get(name)) {
+                        (Some(func), _) => Node::HostCall(*func, data, values),
+                        (None, _) if let Some(record) = self.declarations.record(name) => Node::Record(record, values),
+                        (None, _) if scope.contains(&variable) => {
+                            let callee = self.push(Node::Variable(variable));
+                            Node::DynamicCall(callee, values, tail)
+                        },
+                        (None, Some((index, count))) => {
+                            if *count != values.len() {
+                                return node.raise(&format!("Function {} takes {} argument{}, but {} {} given", name, count,
+                                    if *count == 1 { "" } else { "s" }, values.len(), if values.len() == 1 { "was" } else { "were" }));
+                            }
+                            Node::Call(*index, values, tail)
+                        },
+                        (None, None) => return node.raise(&format!("Unrecognized function {}", name)),
+                    }
+                },
+                _ => {
+                    let mut statements = Vec::new();
+                    for (i, node) in nodes.iter().enumerate() {
+                        self.tail = tail && i + 1 == nodes.len();
+                        statements.push(self.lower(node, scope)?);
+                    }
+                    Node::Sequence(statements)
+                },
+            },
+            SyntaxNode::Unclassified(token) if token.get_inner().contains('.') => {
+                let (name, field) = self.field(node, token.get_inner(), scope)?;
+                Node::Field(name, field)
+            },
+            SyntaxNode::Unclassified(token) => {
+                let name = self.name(token.get_inner());
+                match self.procedures.get(token.get_inner()) {
+                    _ if scope.contains(&name) => Node::Variable(name),
+                    Some((index, _)) => Node::Function(*index),
+                    None => return node.raise(&format!("Undeclared variable {}", token.get_inner())),
+                }
+            },
+            SyntaxNode::Binop(op, a, b) => match *op {
+                "=" | "+=" | "-=" | "*=" | "/=" => {
+                    let value = self.lower(b, scope)?;
+                    let SyntaxNode::Unclassified(token) = &**a else {
+                        return a.raise("Invalid syntax 19");
+                    };
+                    let operation = Binary::from_str(op);
+                    if token.get_inner().contains('.') {
+                        let (name, field) = self.field(a, token.get_inner(), scope)?;
+                        return Ok(self.push(Node::SetField(name, field, operation, value)));
+                    }
+                    let name = self.name(token.get_inner());
+                    if operation.is_some() && !scope.contains(&name) {
+                        return a.raise(&format!("Undeclared variable {}", token.get_inner()));
+                    }
+                    scope.insert(name);
+                    Node::Assign(name, operation, value)
+                },
+                _ => {
+                    let Some(operation) = Binary::from_str(op) else {
+                        return node.raise(&format!("Unrecognized binary operation {}", op));
+                    };
+                    let a = self.lower(a, scope)?;
+                    let b = self.lower(b, scope)?;
+                    Node::Binary(operation, a, b)
+                },
+            },
+            SyntaxNode::Unop(op, a) => {
+                let operation = match *op {
+                    "!" => Unary::Not,
+                    "-" => Unary::Neg,
+                    _ => return a.raise(&format!("Unrecognized unary operation {}", op)),
+                };
+                Node::Unary(operation, self.lower(a, scope)?)
+            },
+            SyntaxNode::Number(t) => Node::Number(*t.get_inner()),
+            SyntaxNode::Str(t) => Node::Str(t.get_inner().bytes().map(|b| b as f64).collect()),
+            SyntaxNode::Parenthesis("(", inner) => return self.lower(inner, scope),
+            SyntaxNode::Parenthesis(_, _) => return node.raise("Lines cannot start with a parenthesis"),
+            SyntaxNode::List(_, _) => return node.raise("Lines cannot start with a list"),
+        };
+        Ok(self.push(lowered))
+    }
+}
+
+impl Program {
+    fn new(source: &str, filename: &str) -> Result<Self, String> {
+        let tokens = crate::parser::load_str(source, filename)?;
+        let tree = SyntaxNode::tree(tokens)?;
+        let compiler = Compiler::new(&tree)?;
+        compiler.check()?;
+        // Without linking there is nothing to run for extern functions
+        if let Some(function) = compiler.functions.values().filter(|f| f.external).min_by_key(|f| &f.name) {
+            return function.node.raise(&format!("Extern function {} cannot be interpreted, as it is defined in another module", function.name));
+        }
+
+        let mut names: Vec<&String> = compiler.functions.keys().filter(|n| *n != "const").collect();
+        names.sort();
+        let mut lowering = Lowering {
+            nodes: Vec::new(),
+            declarations: compiler.declarations.clone(),
+            names: FxHashMap::default(),
+            procedures: names.iter().enumerate().map(|(i, n)| ((*n).to_owned(), (i, compiler.functions[*n].arguments.len()))).collect(),
+            tail: false,
+        };
+        let mut procedures = Vec::new();
+        for name in &names {
+            let function = &compiler.functions[*name];
+            let arguments: Vec<Name> = function.arguments.iter().map(|(a, _)| lowering.name(a)).collect();
+            let mut scope = arguments.iter().copied().collect();
+            lowering.tail = true;
+            let body = lowering.lower(&function.node, &mut scope)?;
+            procedures.push(Procedure { arguments, body });
+        }
+
+        let Some(main) = names.iter().position(|n| *n == "main") else {
+            return tree.raise("No function named main");
+        };
+        let handlers = compiler.handlers.iter()
+            .map(|(kind, name)| (*kind, names.iter().position(|n| *n == name).unwrap()))
+            .collect();
+        Ok(Self {
+            nodes: lowering.nodes,
+            declarations: compiler.declarations.clone(),
+            procedures,
+            main,
+            handlers,
+        })
+    }
+}
+
+/// A step of running the tree. Each counts as one instruction.
+#[derive(Clone, Copy, Debug)]
+enum Task {
+    Eval(NodeId),
+    /// Push a value
+    Push(f64),
+    /// Pop a value which is not used
+    Discard,
+    Binary(Binary),
+    Unary(Unary),
+    Assign(Name, Option<Binary>),
+    /// Assign to the field of a record, as in a `SetField` node
+    SetField(NodeId),
+    /// Build the record of a node from the fields on the stack
+    Record(NodeId),
+    /// Take the arm of an if statement if its condition, on the stack, holds. Otherwise try the next.
+    Branch(NodeId, usize),
+    Loop(NodeId),
+    /// Enter a copy of the current scope
+    EnterScope,
+    /// Leave a scope, keeping the values of the variables declared outside it
+    ExitScope,
+    /// Call the host function of a node, with its arguments on the stack
+    HostCall(NodeId),
+    /// Call a function, taking over the frame of the caller if the call is the last thing it does
+    Call(usize, bool),
+    /// Call the function on the stack, with its arguments below it
+    DynamicCall(bool),
+    Return,
+    Reti,
+}
+
+/// Runs Biscuit code from its syntax tree. Host functions are called through `MachineOutput`, as with a `Machine`.
+#[derive(Clone)]
+pub struct Interpreter {
+    program: Rc<Program>,
+    /// Steps left to run, the next last
+    tasks: Vec<Task>,
+    values: Vec<Value>,
+    /// Variables of each scope, innermost last
+    scopes: Vec<FxHashMap<Name, Value>>,
+    /// Arguments of the last host call
+    args: Vec<f64>,
+    /// Calls which have not returned, not counting tail calls
+    depth: usize,
+    max_lines_per_tick: usize,
+    /// Raised interrupts, and the function handling each
+    interrupts: InterruptQueue<usize>,
+    executed: u64,
+}
+
+impl Interpreter {
+    pub fn new(source: &str, filename: &str, max_lines_per_tick: usize) -> Result<Self, String> {
+        let mut interpreter = Self {
+            program: Rc::new(Program::new(source, filename)?),
+            tasks: Vec::new(),
+            values: Vec::new(),
+            scopes: Vec::new(),
+            args: Vec::new(),
+            depth: 0,
+            max_lines_per_tick,
+            interrupts: InterruptQueue::new(),
+            executed: 0,
+        };
+        interpreter.reset();
+        Ok(interpreter)
+    }
+
+    /// Replace the running script, and start it again. If the new script does not parse, the old one keeps running.
+    pub fn reload(&mut self, source: &str, filename: &str) -> Result<(), String> {
+        self.program = Rc::new(Program::new(source, filename)?);
+        self.reset();
+        Ok(())
+    }
+
+    pub fn instructions_executed(&self) -> u64 {
+        self.executed
+    }
+
+    /// Whether `main` has finished, and no interrupt is waiting to be dispatched
+    pub fn is_waiting(&self) -> bool {
+        !self.interrupts.has_pending() && self.tasks.is_empty()
+    }
+
+    /// Raise an interrupt. If the same interrupt is already pending, its argument is replaced.
+    /// Masked interrupts are discarded.
+    pub fn interrupt(&mut self, kind: InterruptKind, arg: f64) {
+        self.interrupts.raise(kind, arg);
+    }
+
+    /// Give the value returned by the host function just called, in place of the zero it was left with
+    pub fn return_value(&mut self, value: f64) {
+        if let Some(last) = self.values.last_mut() {
+            *last = Value::Float(value);
+        }
+    }
+
+    pub fn mask(&mut self, kind: InterruptKind) {
+        self.interrupts.mask(kind);
+    }
+
+    pub fn unmask(&mut self, kind: InterruptKind) {
+        self.interrupts.unmask(kind);
+    }
+
+    pub fn set_priority(&mut self, kind: InterruptKind, priority: u8) {
+        self.interrupts.set_priority(kind, priority);
+    }
+
+    /// Run until a call is encountered, or tick
+    pub fn run_to_call(&mut self) -> Result<MachineOutput<'_>, MachineError> {
+        self.run_for(self.max_lines_per_tick)
+    }
+
+    /// Run until a call is encountered, or at most `budget` steps have been run
+    pub fn run_for(&mut self, budget: usize) -> Result<MachineOutput<'_>, MachineError> {
+        for _ in 0..budget {
+            if self.interrupts.has_pending() {
+                self.dispatch_interrupt();
+            }
+            let Some(task) = self.tasks.pop() else {
+                return Ok(MachineOutput::None);
+            };
+            self.executed += 1;
+            if let Some(func) = self.step(task)? {
+                return Ok(MachineOutput::Call { func, args: &self.args });
+            }
+        }
+        Ok(MachineOutput::None)
+    }
+
+    /// Start the script again from `main`
+    pub fn reset(&mut self) {
+        self.tasks.clear();
+        self.values.clear();
+        self.scopes.clear();
+        self.depth = 0;
+        self.interrupts.clear();
+        for (kind, procedure) in &self.program.handlers {
+            self.interrupts.register(*kind, *procedure);
+        }
+        self.scopes.push(FxHashMap::default());
+        self.schedule(&[Task::Eval(self.program.procedures[self.program.main].body), Task::Discard]);
+    }
+
+    /// Add tasks to run next, in order
+    fn schedule(&mut self, tasks: &[Task]) {
+        self.tasks.extend(tasks.iter().rev());
+    }
+
+    fn pop(&mut self) -> Result<Value, MachineError> {
+        self.values.pop().ok_or(MachineError::Stack)
+    }
+
+    fn pop_float(&mut self) -> Result<f64, MachineError> {
+        self.pop()?.float()
+    }
+
+    /// Pop the last `count` values, which must be floats
+    fn pop_floats(&mut self, count: usize) -> Result<Vec<f64>, MachineError> {
+        let start = self.values.len().checked_sub(count).ok_or(MachineError::Stack)?;
+        self.values.drain(start..).map(|v| v.float()).collect()
+    }
+
+    fn scope(&mut self) -> Result<&mut FxHashMap<Name, Value>, MachineError> {
+        self.scopes.last_mut().ok_or(MachineError::Stack)
+    }
+
+    /// Drop the frame of the running function for a call which is the last thing it does, as compiled code does.
+    /// Returns false if the frame is kept: when the arguments include lists, which compiled code frees with the
+    /// frame that allocated them, or when the function does not return to a caller.
+    fn take_frame(&mut self, arguments: usize) -> Result<bool, MachineError> {
+        let start = self.values.len().checked_sub(arguments).ok_or(MachineError::Stack)?;
+        if self.values[start..].iter().any(|v| matches!(v, Value::List(_) | Value::Record(..))) {
+            return Ok(false);
+        }
+        // Only the end of the function is left to run
+        let Some(end) = self.tasks.iter().rev().position(|t| !matches!(t, Task::Discard | Task::ExitScope | Task::Push(_))) else {
+            return Ok(false);
+        };
+        let end = self.tasks.len() - 1 - end;
+        if !matches!(self.tasks[end], Task::Return) {
+            return Ok(false);
+        }
+        let scopes = self.tasks[end + 1..].iter().filter(|t| matches!(t, Task::ExitScope)).count() + 1;
+        self.tasks.truncate(end);
+        self.scopes.truncate(self.scopes.len().checked_sub(scopes).ok_or(MachineError::Stack)?);
+        Ok(true)
+    }
+
+    fn dispatch_interrupt(&mut self) {
+        if let Some((p, procedure)) = self.interrupts.dispatch() {
+            let procedure = &self.program.procedures[procedure];
+            let mut scope = FxHashMap::default();
+            if let Some(argument) = procedure.arguments.first() {
+                scope.insert(*argument, Value::Float(p.arg));
+            }
+            self.scopes.push(scope);
+            let body = procedure.body;
+            self.schedule(&[Task::Eval(body), Task::Discard, Task::Reti]);
+        }
+    }
+
+    /// Run one task, returning the host function to call if it is a call
+    fn step(&mut self, task: Task) -> Result<Option<GlobalFunction>, MachineError> {
+        match task {
+            Task::Eval(node) => self.eval(node)?,
+            Task::Push(value) => self.values.push(Value::Float(value)),
+            Task::Discard => { self.pop()?; },
+            Task::Binary(operation) => {
+                let b = self.pop_float()?;
+                let a = self.pop_float()?;
+                self.values.push(Value::Float(operation.apply(a, b)));
+            },
+            Task::Unary(operation) => {
+                let a = self.pop_float()?;
+                self.values.push(Value::Float(match operation {
+                    Unary::Not => (a == 0.) as i64 as f64,
+                    Unary::Neg => -a,
+                }));
+            },
+            Task::Assign(name, operation) => {
+                let b = self.pop()?;
+                let scope = self.scope()?;
+                let value = match operation {
+                    Some(operation) => Value::Float(operation.apply(scope.get(&name).ok_or(MachineError::Stack)?.float()?, b.float()?)),
+                    None => b,
+                };
+                scope.insert(name, value.clone());
+                self.values.push(value);
+            },
+            Task::SetField(node) => {
+                let program = self.program.clone();
+                let Node::SetField(name, field, operation, _) = &program.nodes[node] else { unreachable!() };
+                let b = self.pop_float()?;
+                let scope = self.scope()?;
+                let Some(Value::Record(record, fields)) = scope.get(name) else { return Err(MachineError::Stack) };
+                let index = program.declarations.records[*record as usize].field(field).ok_or(MachineError::Memory)?;
+                // Records are values, so the record is copied with the field replaced
+                let mut fields = fields.to_vec();
+                fields[index] = match operation {
+                    Some(operation) => operation.apply(fields[index], b),
+                    None => b,
+                };
+                let value = fields[index];
+                scope.insert(*name, Value::Record(*record, fields.into()));
+                self.values.push(Value::Float(value));
+            },
+            Task::Record(node) => {
+                let program = self.program.clone();
+                let Node::Record(record, fields) = &program.nodes[node] else { unreachable!() };
+                let fields = self.pop_floats(fields.len())?;
+                self.values.push(Value::Record(*record, fields.into()));
+            },
+            Task::Branch(node, arm) => {
+                let holds = self.pop_float()? != 0.;
+                self.scopes.pop();
+                let program = self.program.clone();
+                let Node::If(arms, els) = &program.nodes[node] else { unreachable!() };
+                match (holds, arms.get(arm + 1), els) {
+                    (true, _, _) => self.schedule(&[Task::EnterScope, Task::Eval(arms[arm].1), Task::Discard, Task::ExitScope, Task::Push(0.)]),
+                    (false, Some((condition, _)), _) => self.schedule(&[Task::EnterScope, Task::Eval(*condition), Task::Branch(node, arm + 1)]),
+                    (false, None, Some(els)) => self.schedule(&[Task::EnterScope, Task::Eval(*els), Task::Discard, Task::ExitScope, Task::Push(0.)]),
+                    (false, None, None) => self.values.push(Value::Float(0.)),
+                }
+            },
+            Task::Loop(body) => self.schedule(&[Task::EnterScope, Task::Eval(body), Task::Discard, Task::ExitScope, Task::Loop(body)]),
+            Task::EnterScope => {
+                let scope = self.scope()?.clone();
+                self.scopes.push(scope);
+            },
+            Task::ExitScope => {
+                let inner = self.scopes.pop().ok_or(MachineError::Stack)?;
+                for (name, value) in self.scope()?.iter_mut() {
+                    *value = inner[name].clone();
+                }
+            },
+            Task::HostCall(node) => {
+                let program = self.program.clone();
+                let Node::HostCall(func, data, arguments) = &program.nodes[node] else { unreachable!() };
+                let values = self.pop_floats(arguments.len())?;
+                self.args.clear();
+                self.args.extend(data);
+                self.args.extend(values);
+                // Replaced by `return_value` for functions which return something
+                self.values.push(Value::Float(0.));
+                return Ok(Some(*func));
+            },
+            Task::Call(procedure, tail) => {
+                let program = self.program.clone();
+                let procedure = &program.procedures[procedure];
+                if !(tail && self.take_frame(procedure.arguments.len())?) {
+                    if self.depth == MAX_CALL_DEPTH {
+                        return Err(MachineError::CallDepth);
+                    }
+                    self.depth += 1;
+                }
+                let start = self.values.len().checked_sub(procedure.arguments.len()).ok_or(MachineError::Stack)?;
+                let scope = procedure.arguments.iter().copied().zip(self.values.drain(start..)).collect();
+                self.scopes.push(scope);
+                self.schedule(&[Task::Eval(procedure.body), Task::Discard, Task::Return]);
+            },
+            Task::DynamicCall(tail) => {
+                let Value::Function(procedure) = self.pop()? else { return Err(MachineError::Stack) };
+                self.schedule(&[Task::Call(procedure, tail)]);
+            },
+            Task::Return => {
+                self.depth = self.depth.checked_sub(1).ok_or(MachineError::Stack)?;
+                self.scopes.pop().ok_or(MachineError::Stack)?;
+                // Functions return nothing
+                self.values.push(Value::Float(0.));
+            },
+            Task::Reti => {
+                self.scopes.pop().ok_or(MachineError::Stack)?;
+                self.interrupts.finish().ok_or(MachineError::Stack)?;
+            },
+        }
+        Ok(None)
+    }
+
+    /// Schedule the evaluation of a node, which leaves its value on the stack
+    fn eval(&mut self, node: NodeId) -> Result<(), MachineError> {
+        let program = self.program.clone();
+        match &program.nodes[node] {
+            Node::Number(value) => self.values.push(Value::Float(*value)),
+            Node::Str(chars) => self.values.push(Value::List(chars.clone())),
+            Node::Variable(name) => {
+                let value = self.scope()?.get(name).ok_or(MachineError::Stack)?.clone();
+                self.values.push(value);
+            },
+            Node::Field(name, field) => {
+                let Some(Value::Record(record, fields)) = self.scope()?.get(name) else { return Err(MachineError::Stack) };
+                let index = program.declarations.records[*record as usize].field(field).ok_or(MachineError::Memory)?;
+                let value = fields[index];
+                self.values.push(Value::Float(value));
+            },
+            Node::SetField(_, _, _, value) => self.schedule(&[Task::Eval(*value), Task::SetField(node)]),
+            Node::Record(_, fields) => {
+                self.schedule(&[Task::Record(node)]);
+                for field in fields.iter().rev() {
+                    self.schedule(&[Task::Eval(*field)]);
+                }
+            },
+            Node::Assign(name, operation, value) => self.schedule(&[Task::Eval(*value), Task::Assign(*name, *operation)]),
+            Node::Binary(operation, a, b) => self.schedule(&[Task::Eval(*a), Task::Eval(*b), Task::Binary(*operation)]),
+            Node::Unary(operation, a) => self.schedule(&[Task::Eval(*a), Task::Unary(*operation)]),
+            Node::Sequence(statements) => match statements.split_last() {
+                Some((last, rest)) => {
+                    self.schedule(&[Task::Eval(*last)]);
+                    for statement in rest.iter().rev() {
+                        self.schedule(&[Task::Eval(*statement), Task::Discard]);
+                    }
+                },
+                None => self.values.push(Value::Float(0.)),
+            },
+            Node::If(arms, els) => match (arms.first(), els) {
+                (Some((condition, _)), _) => self.schedule(&[Task::EnterScope, Task::Eval(*condition), Task::Branch(node, 0)]),
+                (None, Some(els)) => self.schedule(&[Task::EnterScope, Task::Eval(*els), Task::Discard, Task::ExitScope, Task::Push(0.)]),
+                (None, None) => self.values.push(Value::Float(0.)),
+            },
+            Node::Loop(body) => self.schedule(&[Task::Loop(*body)]),
+            Node::HostCall(_, _, arguments) => {
+                self.schedule(&[Task::HostCall(node)]);
+                for argument in arguments.iter().rev() {
+                    self.schedule(&[Task::Eval(*argument)]);
+                }
+            },
+            Node::Call(procedure, arguments, tail) => {
+                self.schedule(&[Task::Call(*procedure, *tail)]);
+                for argument in arguments.iter().rev() {
+                    self.schedule(&[Task::Eval(*argument)]);
+                }
+            },
+            Node::Function(procedure) => self.values.push(Value::Function(*procedure)),
+            Node::DynamicCall(callee, arguments, tail) => {
+                self.schedule(&[Task::Eval(*callee), Task::DynamicCall(*tail)]);
+                for argument in arguments.iter().rev() {
+                    self.schedule(&[Task::Eval(*argument)]);
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+impl Executor for Interpreter {
+    fn run_for(&mut self, budget: usize) -> Result<MachineOutput<'_>, MachineError> {
+        Interpreter::run_for(self, budget)
+    }
+
+    fn instructions_executed(&self) -> u64 {
+        Interpreter::instructions_executed(self)
+    }
+
+    fn is_waiting(&self) -> bool {
+        Interpreter::is_waiting(self)
+    }
+
+    fn interrupt(&mut self, kind: InterruptKind, arg: f64) {
+        Interpreter::interrupt(self, kind, arg)
+    }
+
+    fn return_value(&mut self, value: f64) {
+        Interpreter::return_value(self, value)
+    }
+
+    fn reset(&mut self) {
+        Interpreter::reset(self)
+    }
+
+    /// The interpreter walks the syntax tree, so it has no state which could be saved
+    fn save_state(&self) -> Option<MachineState> {
+        None
+    }
+
+    fn restore_state(&mut self, _state: &MachineState) -> Result<(), MachineError> {
+        Err(MachineError::Unsupported)
+    }
+}
@@ -5,13 +5,15 @@ pub mod bytecode;
 mod assembler;
 mod disassembler;
 pub mod machine;
+pub mod interpreter;
 pub mod util;
 
 use std::{fs::File, io::Read};
 
-pub use bytecode::{Command, GlobalFunction};
-pub use machine::{Instructions, Machine, MachineError, MachineOutput};
-pub use {compiler::compile_str, assembler::assemble_str, disassembler::disassemble_bytes};
+pub use bytecode::{Command, GlobalFunction, InterruptKind};
+pub use interpreter::Interpreter;
+pub use machine::{Executor, Instructions, Log, Machine, MachineError, MachineId, MachineOutput, MachineState, Profile, Scheduler, TaskState};
+pub use {compiler::{CompileCache, DebugInfo, Explanation, Object, Stage, compile_debug_str, compile_object_str, compile_str, explain_str, link}, assembler::assemble_str, disassembler::disassemble_bytes};
 
 /// Compile a file of Biscuit code to binary
 pub fn compile_file(filename: &str) -> Result<Vec<u8>, String> {
@@ -22,6 +24,46 @@ pub fn compile_file(filename: &str) -> Result<Vec<u8>, String> {
     compile_str(&text, filename)
 }
 
+/// Compile a file of Biscuit code to binary, with the debug info needed to profile it
+pub fn compile_debug_file(filename: &str) -> Result<(Vec<u8>, DebugInfo), String> {
+    let mut file = File::open(filename).map_err(|_| format!("Could not find file {}", filename))?;
+    let mut text = "".to_owned();
+    file.read_to_string(&mut text).map_err(|_| format!("Could not read file {}", filename))?;
+
+    compile_debug_str(&text, filename)
+}
+
+/// Compile a file of Biscuit code to an object, to be linked with others
+pub fn compile_object_file(filename: &str) -> Result<Object, String> {
+    let mut file = File::open(filename).map_err(|_| format!("Could not find file {}", filename))?;
+    let mut text = "".to_owned();
+    file.read_to_string(&mut text).map_err(|_| format!("Could not read file {}", filename))?;
+
+    compile_object_str(&text, filename)
+}
+
+/// Link object files into a binary
+pub fn link_files(filenames: &[&str]) -> Result<Vec<u8>, String> {
+    let objects = filenames.iter()
+        .map(|filename| {
+            let bytes = std::fs::read(filename).map_err(|_| format!("Could not find file {}", filename))?;
+            Object::from_bytes(&bytes, filename)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    link(&objects)
+}
+
+/// Compile a file of Biscuit code, and dump the listed stages as text
+pub fn explain_file(filename: &str, stages: &[Stage]) -> Explanation {
+    let mut text = "".to_owned();
+    let read = File::open(filename).map_err(|_| format!("Could not find file {}", filename))
+        .and_then(|mut file| file.read_to_string(&mut text).map_err(|_| format!("Could not read file {}", filename)));
+    match read {
+        Ok(_) => explain_str(&text, filename, stages),
+        Err(e) => Explanation { dumps: Vec::new(), result: Err(e) },
+    }
+}
+
 // Compile a file of Biscuit assembly to binary
 pub fn assemble_file(filename: &str) -> Result<Vec<u8>, String> {
     let mut file = File::open(filename).map_err(|_| format!("Could not find file {}", filename))?;
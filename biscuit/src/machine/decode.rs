@@ -0,0 +1,266 @@
+use crate::{Command, bytecode::GlobalFunction};
+
+/// An instruction with its operand decoded
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Op {
+    /// A command without an operand
+    Bare(Command),
+    Push(f64),
+    Jmp(usize),
+    Jnz(usize),
+    Jsr(usize),
+    Call(GlobalFunction),
+
+    // Superinstructions, fused from the sequences `implementer::roll_state` emits
+    /// `push n; pick`
+    PickN(usize),
+    /// `push n; roll`
+    RollN(usize),
+    /// `push n; rolr`
+    RolrN(usize),
+    /// `alc` then `push x; stb` for each item, as list literals such as strings are built. Indexes the program's
+    /// literals.
+    Vector(u32),
+
+    /// Not the start of a valid instruction
+    Invalid,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Decoded {
+    pub op: Op,
+    /// Length in bytes
+    pub len: u32,
+    /// Number of bytecode instructions the op stands for
+    pub count: u32,
+}
+
+impl Decoded {
+    const INVALID: Self = Self { op: Op::Invalid, len: 1, count: 1 };
+
+    /// Decode the single instruction starting at `ip`
+    pub fn decode(bytes: &[u8], ip: usize) -> Self {
+        let Some(command) = bytes.get(ip).and_then(|b| Command::try_from(*b).ok()) else {
+            return Self::INVALID;
+        };
+        let (op, len) = match command {
+            Command::Push => match read_u64(bytes, ip + 1) {
+                Some(v) => (Op::Push(f64::from_bits(v)), 9),
+                None => return Self::INVALID,
+            },
+            Command::Jmp => match read_u64(bytes, ip + 1) {
+                Some(v) => (Op::Jmp(v as usize), 9),
+                None => return Self::INVALID,
+            },
+            Command::Jnz => match read_u64(bytes, ip + 1) {
+                Some(v) => (Op::Jnz(v as usize), 9),
+                None => return Self::INVALID,
+            },
+            Command::Jsr => match read_u64(bytes, ip + 1) {
+                Some(v) => (Op::Jsr(v as usize), 9),
+                None => return Self::INVALID,
+            },
+            Command::Call => match bytes.get(ip + 1).and_then(|b| GlobalFunction::try_from(*b).ok()) {
+                Some(func) => (Op::Call(func), 2),
+                None => return Self::INVALID,
+            },
+            _ => (Op::Bare(command), 1),
+        };
+        Self { op, len, count: 1 }
+    }
+
+    /// Decode the instruction starting at `ip`, fusing it with those after it if they form a superinstruction.
+    /// The items of list literals are added to `literals`.
+    pub fn decode_fused(bytes: &[u8], ip: usize, literals: &mut Vec<Box<[f64]>>) -> Self {
+        let first = Self::decode(bytes, ip);
+        match first.op {
+            Op::Push(n) => Self::fuse_stack_op(bytes, ip, first, n),
+            Op::Bare(Command::Alc) => Self::fuse_vector(bytes, ip, first, literals),
+            _ => first,
+        }
+    }
+
+    /// `push n` followed by an instruction taking `n` as a stack distance
+    fn fuse_stack_op(bytes: &[u8], ip: usize, first: Self, n: f64) -> Self {
+        // Only small non-negative integers are stack distances
+        if n < 0. || n.fract() != 0. || n > u32::MAX as f64 {
+            return first;
+        }
+        let n = n as usize;
+        let op = match Self::decode(bytes, ip + first.len as usize).op {
+            Op::Bare(Command::Pick) => Op::PickN(n),
+            Op::Bare(Command::Roll) => Op::RollN(n),
+            Op::Bare(Command::Rolr) => Op::RolrN(n),
+            _ => return first,
+        };
+        Self { op, len: first.len + 1, count: 2 }
+    }
+
+    /// `alc` followed by the `push x; stb` pairs which fill in a literal
+    fn fuse_vector(bytes: &[u8], ip: usize, first: Self, literals: &mut Vec<Box<[f64]>>) -> Self {
+        let mut items = Vec::new();
+        let mut next = ip + 1;
+        while let Op::Push(item) = Self::decode(bytes, next).op
+            && Self::decode(bytes, next + 9).op == Op::Bare(Command::Stb) {
+            items.push(item);
+            next += 10;
+        }
+        if items.is_empty() {
+            return first;
+        }
+        let count = 1 + 2 * items.len() as u32;
+        literals.push(items.into());
+        Self { op: Op::Vector(literals.len() as u32 - 1), len: (next - ip) as u32, count }
+    }
+}
+
+fn read_u64(bytes: &[u8], start: usize) -> Option<u64> {
+    let operand = bytes.get(start..start + 8)?;
+    Some(u64::from_le_bytes(operand.try_into().unwrap()))
+}
+
+/// A program decoded once, ahead of time, and shared by every machine running it
+pub(crate) struct Program {
+    /// The instruction starting at each byte
+    pub code: Box<[Decoded]>,
+    /// The items of each `Op::Vector`
+    pub literals: Box<[Box<[f64]>]>,
+}
+
+/// Decode a program. Every byte address gets an entry, so that jumps and returns may land anywhere, including
+/// inside a superinstruction. This costs a `Decoded` (24 bytes on 64-bit targets) for each byte of bytecode, so a
+/// 4 KB script takes 96 KB.
+pub(crate) fn decode_program(bytes: &[u8]) -> Program {
+    let mut literals = Vec::new();
+    let code = (0..bytes.len()).map(|ip| Decoded::decode_fused(bytes, ip, &mut literals)).collect();
+    Program { code, literals: literals.into() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InterruptKind, Machine, MachineOutput, assemble_str, bytecode::VariableType, compile_str, machine::InstructionData, util::Vendor};
+
+    /// Alternates between jumping to the `push` of a `push 0; pick` pair and straight to its `pick`
+    const JUMPS: &str = "
+        push 0
+        push 0
+    loop:
+        not
+        dup
+        jnz direct
+        push 0
+        jmp mid
+    direct:
+        push 0
+    mid:
+        pick
+        pop
+        push 2
+        rolr
+        push 1
+        add
+        push 2
+        roll
+        jmp loop
+    ";
+
+    /// Run slices of `budget` instructions with both interpreters, and check they stop in the same state each time
+    fn compare(bytes: &[u8], budget: usize) {
+        let mut vendor = Vendor::new();
+        let instructions = vendor.insert(InstructionData::from_compiled(bytes));
+        let mut decoded = Machine::new(instructions.clone(), 1000);
+        let mut undecoded = Machine::new(instructions, 1000);
+        for slice in 0..300 {
+            let a = step(&mut decoded, |m| m.run_for(budget));
+            let b = step(&mut undecoded, |m| m.run_for_bytes(budget));
+            assert_eq!(a, b, "Slice {} of {} instructions", slice, budget);
+            assert_eq!(
+                (decoded.ip, &decoded.stack, decoded.instructions_executed()),
+                (undecoded.ip, &undecoded.stack, undecoded.instructions_executed()),
+                "Slice {} of {} instructions", slice, budget,
+            );
+            if a.starts_with("Err") { break; }
+        }
+    }
+
+    /// Run one slice, answering host calls with zero and raising interrupts when the machine waits
+    fn step(machine: &mut Machine, run: impl FnOnce(&mut Machine) -> Result<MachineOutput<'_>, crate::MachineError>) -> String {
+        let (text, returns) = match run(machine) {
+            Ok(MachineOutput::Call { func, args }) => (format!("{} {:?}", func, args), func.return_type() != VariableType::Null),
+            Ok(MachineOutput::None) => ("None".to_owned(), false),
+            Err(e) => (format!("Err {:?}", e), false),
+        };
+        if returns {
+            machine.return_value(0.);
+        }
+        if machine.is_waiting() {
+            machine.interrupt(InterruptKind::Interact, 0.);
+            machine.interrupt(InterruptKind::Forward, 1.);
+        }
+        text
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn decoded_programs_take_24_bytes_per_byte() {
+        assert_eq!(std::mem::size_of::<Decoded>(), 24);
+    }
+
+    #[test]
+    fn superinstructions_are_fused_at_each_entry() {
+        let bytes = assemble_str(JUMPS, "test").unwrap();
+        let code = decode_program(&bytes).code;
+        let fused: Vec<usize> = (0..bytes.len()).filter(|ip| code[*ip].count == 2).collect();
+        // The pick, roll and rolr pairs are fused where they start
+        assert_eq!(fused.len(), 3);
+        for ip in fused {
+            let pick = ip + 9;
+            // Their second halves decode on their own, for jumps which land there
+            assert_eq!(code[pick].op, Decoded::decode(&bytes, pick).op);
+            assert_eq!(code[pick].count, 1);
+        }
+
+        // The loop keeps a counter and a flag on the stack
+        let mut vendor = Vendor::new();
+        let mut machine = Machine::new(vendor.insert(InstructionData::from_compiled(&bytes)), 1000);
+        assert!(machine.run_for(1000).is_ok());
+        assert_eq!(machine.stack.len(), 2);
+        assert!(machine.stack[0] > 50., "{:?}", machine.stack);
+    }
+
+    #[test]
+    fn list_literals_are_built_in_one_op() {
+        let bytes = compile_str("fn main() {\n    print(\"ab\");\n}\n", "test").unwrap();
+        let program = decode_program(&bytes);
+        let vectors: Vec<&Decoded> = program.code.iter().filter(|d| matches!(d.op, Op::Vector(_))).collect();
+        assert_eq!(vectors.len(), 1);
+        // The alc, then a push and a stb for the length and for each character
+        assert_eq!(vectors[0].count, 7);
+        assert_eq!(&program.literals[..], [vec![2., 97., 98.].into_boxed_slice()]);
+    }
+
+    #[test]
+    fn decoding_runs_like_the_bytecode() {
+        let jumps = assemble_str(JUMPS, "test").unwrap();
+        let scripts = [
+            include_str!("../../tests/addition.bisc"),
+            include_str!("../../tests/branch.bisc"),
+            include_str!("../../tests/loop.bisc"),
+            include_str!("../../tests/interrupts.bisc"),
+            include_str!("../../tests/strings.bisc"),
+            include_str!("../../tests/records.bisc"),
+            include_str!("../../tests/functions.bisc"),
+            include_str!("../../../assets/scripts/chair.txt"),
+            include_str!("../../../assets/scripts/engine.txt"),
+        ];
+        let mut programs = vec![jumps];
+        programs.extend(scripts.iter().map(|s| compile_str(s, "test").unwrap_or_else(|e| panic!("{}", e))));
+        for bytes in &programs {
+            // Odd budgets end slices in the middle of superinstructions, which are then split
+            for budget in [1, 2, 3, 5, 7, 1000] {
+                compare(bytes, budget);
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use rustc_hash::FxHashMap;
+use strum::IntoEnumIterator;
+
+use crate::{bytecode::InterruptKind, util::bytes::{Reader, write_f64, write_len}};
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PendingInterrupt {
+    pub kind: InterruptKind,
+    pub arg: f64,
+}
+
+/// Raised interrupts, and the handlers, priorities and masks which decide when they are dispatched.
+/// `H` is how a handler is found, such as the address of its code.
+#[derive(Clone)]
+pub(crate) struct InterruptQueue<H> {
+    /// Handler of each interrupt, registered by the program
+    handlers: FxHashMap<InterruptKind, H>,
+    priorities: FxHashMap<InterruptKind, u8>,
+    masked: Vec<InterruptKind>,
+    /// Raised interrupts which have not been dispatched yet. At most one per kind.
+    pending: Vec<PendingInterrupt>,
+    /// Priorities of the handlers currently running, innermost last
+    active: Vec<u8>,
+}
+
+impl<H: Copy> InterruptQueue<H> {
+    pub fn new() -> Self {
+        Self {
+            handlers: FxHashMap::default(),
+            priorities: InterruptKind::iter().map(|k| (k, k.default_priority())).collect(),
+            masked: Vec::new(),
+            pending: Vec::new(),
+            active: Vec::new(),
+        }
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn register(&mut self, kind: InterruptKind, handler: H) {
+        self.handlers.insert(kind, handler);
+    }
+
+    /// Raise an interrupt. If the same interrupt is already pending, its argument is replaced.
+    /// Masked interrupts are discarded.
+    pub fn raise(&mut self, kind: InterruptKind, arg: f64) {
+        if self.masked.contains(&kind) { return; }
+        match self.pending.iter_mut().find(|p| p.kind == kind) {
+            Some(p) => p.arg = arg,
+            None => self.pending.push(PendingInterrupt { kind, arg }),
+        }
+    }
+
+    pub fn mask(&mut self, kind: InterruptKind) {
+        if !self.masked.contains(&kind) {
+            self.masked.push(kind);
+        }
+        self.pending.retain(|p| p.kind != kind);
+    }
+
+    pub fn unmask(&mut self, kind: InterruptKind) {
+        self.masked.retain(|k| *k != kind);
+    }
+
+    pub fn set_priority(&mut self, kind: InterruptKind, priority: u8) {
+        self.priorities.insert(kind, priority);
+    }
+
+    /// Take the highest priority pending interrupt if it may preempt the running code, and mark its handler as
+    /// running. Pending interrupts without a handler are discarded.
+    pub fn dispatch(&mut self) -> Option<(PendingInterrupt, H)> {
+        self.pending.retain(|p| self.handlers.contains_key(&p.kind));
+        let current = self.active.last().copied();
+        let mut best: Option<(usize, u8)> = None;
+        for (index, p) in self.pending.iter().enumerate() {
+            let priority = self.priorities[&p.kind];
+            if current.is_some_and(|c| priority <= c) { continue; }
+            // Earlier interrupts win ties
+            if best.is_some_and(|(_, b)| priority <= b) { continue; }
+            best = Some((index, priority));
+        }
+        let (index, priority) = best?;
+        let p = self.pending.remove(index);
+        self.active.push(priority);
+        Some((p, self.handlers[&p.kind]))
+    }
+
+    /// Mark the innermost running handler as finished. Returns None if no handler was running.
+    pub fn finish(&mut self) -> Option<()> {
+        self.active.pop().map(|_| ())
+    }
+
+    /// Forget the handlers and raised interrupts, keeping the priorities and masks
+    pub fn clear(&mut self) {
+        self.handlers.clear();
+        self.pending.clear();
+        self.active.clear();
+    }
+}
+
+impl InterruptQueue<usize> {
+    /// Write the queue, with the handlers and priorities in order of interrupt so that equal queues write equal bytes
+    pub fn write(&self, out: &mut Vec<u8>) {
+        let mut handlers: Vec<(InterruptKind, usize)> = self.handlers.iter().map(|(k, h)| (*k, *h)).collect();
+        handlers.sort_by_key(|(k, _)| *k as u8);
+        write_len(out, handlers.len());
+        for (kind, handler) in handlers {
+            out.push(kind as u8);
+            write_len(out, handler);
+        }
+        for kind in InterruptKind::iter() {
+            out.push(self.priorities[&kind]);
+        }
+        write_len(out, self.masked.len());
+        out.extend(self.masked.iter().map(|k| *k as u8));
+        write_len(out, self.pending.len());
+        for p in &self.pending {
+            out.push(p.kind as u8);
+            write_f64(out, p.arg);
+        }
+        write_len(out, self.active.len());
+        out.extend(&self.active);
+    }
+
+    pub fn read(reader: &mut Reader) -> Option<Self> {
+        fn kind(reader: &mut Reader) -> Option<InterruptKind> {
+            InterruptKind::try_from(reader.u8()?).ok()
+        }
+        let mut handlers = FxHashMap::default();
+        for _ in 0..reader.length()? {
+            handlers.insert(kind(reader)?, reader.length()?);
+        }
+        let priorities = InterruptKind::iter().map(|k| Some((k, reader.u8()?))).collect::<Option<_>>()?;
+        let masked = (0..reader.length()?).map(|_| kind(reader)).collect::<Option<_>>()?;
+        let pending = (0..reader.length()?).map(|_| Some(PendingInterrupt { kind: kind(reader)?, arg: reader.f64()? }))
+            .collect::<Option<_>>()?;
+        let len = reader.length()?;
+        let active = reader.take(len)?.to_vec();
+        Some(Self { handlers, priorities, masked, pending, active })
+    }
+
+    /// Handler addresses, to check them against the code they will run in
+    pub fn handlers(&self) -> impl Iterator<Item = usize> + '_ {
+        self.handlers.values().copied()
+    }
+}
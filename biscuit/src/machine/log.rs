@@ -0,0 +1,48 @@
+use std::collections::VecDeque;
+
+use crate::{GlobalFunction, bytecode::format_print, machine::MachineError};
+
+/// Output of a machine's `dbg` and `print` calls, keeping only the most recent lines
+#[derive(Clone, Debug)]
+pub struct Log {
+    lines: VecDeque<String>,
+    capacity: usize,
+}
+
+impl Log {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, line: String) {
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        if self.capacity > 0 {
+            self.lines.push_back(line);
+        }
+    }
+
+    /// Write the output of a `dbg` or `print` call. Other functions are invalid.
+    pub fn write(&mut self, func: GlobalFunction, args: &[f64]) -> Result<(), MachineError> {
+        let line = match func {
+            GlobalFunction::Dbg => args.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", "),
+            GlobalFunction::Print => format_print(args).ok_or(MachineError::Func)?,
+            _ => return Err(MachineError::Func),
+        };
+        self.push(line);
+        Ok(())
+    }
+
+    /// Lines from oldest to newest
+    pub fn lines(&self) -> impl DoubleEndedIterator<Item = &str> {
+        self.lines.iter().map(|l| l.as_str())
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+}
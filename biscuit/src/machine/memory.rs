@@ -1,5 +1,7 @@
 use rustc_hash::FxHashMap;
 
+use crate::util::bytes::{Reader, write_f64s, write_len};
+
 #[derive(Clone)]
 pub(crate) struct Memory {
     vector_map: FxHashMap<u32, Vec<f64>>,
@@ -14,9 +16,14 @@ impl Memory {
     }
 
     pub fn allocate(&mut self) -> u32 {
+        self.allocate_from(&[])
+    }
+
+    /// Allocate an array holding a copy of `items`
+    pub fn allocate_from(&mut self, items: &[f64]) -> u32 {
         let address = self.next_address;
         self.next_address += 1;
-        self.vector_map.insert(address, Vec::new());
+        self.vector_map.insert(address, items.to_vec());
         address
     }
 
@@ -66,4 +73,28 @@ impl Memory {
             None => None,
         }
     }
-}
\ No newline at end of file
+}
+impl Memory {
+    /// Write the arrays in order of address, so that equal memories write equal bytes
+    pub fn write(&self, out: &mut Vec<u8>) {
+        write_len(out, self.next_address as usize);
+        let mut addresses: Vec<&u32> = self.vector_map.keys().collect();
+        addresses.sort();
+        write_len(out, addresses.len());
+        for address in addresses {
+            write_len(out, *address as usize);
+            write_f64s(out, &self.vector_map[address]);
+        }
+    }
+
+    pub fn read(reader: &mut Reader) -> Option<Self> {
+        let next_address = reader.length()? as u32;
+        let mut vector_map = FxHashMap::default();
+        for _ in 0..reader.length()? {
+            let address = reader.length()? as u32;
+            if address >= next_address { return None; }
+            vector_map.insert(address, reader.f64s()?);
+        }
+        Some(Self { vector_map, next_address })
+    }
+}
@@ -1,19 +1,38 @@
+pub(crate) mod decode;
+mod interrupts;
+mod log;
 mod memory;
-use std::borrow::Cow;
+mod profile;
+mod scheduler;
+mod state;
+use std::{borrow::Cow, rc::Rc};
 
-use crate::{Command, bytecode::GlobalFunction, machine::memory::Memory, util::Tagged};
+use crate::{Command, bytecode::{GlobalFunction, InterruptKind}, machine::{decode::{Decoded, Op, Program, decode_program}, memory::Memory}, util::Tagged};
+pub(crate) use interrupts::InterruptQueue;
+
+pub use log::Log;
+pub use profile::Profile;
+pub use scheduler::{MachineId, Scheduler, TaskState};
+pub use state::MachineState;
 
 pub type Instructions = Tagged<InstructionData>;
 
+/// Calls a machine may be nested in before it fails with `MachineError::CallDepth`. Tail calls do not count.
+pub const MAX_CALL_DEPTH: usize = 256;
+
 pub struct InstructionData {
     instructions: Cow<'static, [u8]>,
+    /// The instructions, decoded ahead of time
+    decoded: Rc<Program>,
 }
 impl InstructionData {
     pub fn from_compiled(instructions: &[u8]) -> Self {
+        let decoded = Rc::new(decode_program(instructions));
         let instructions = Cow::Owned(instructions.to_vec());
         // Process the script
         Self {
             instructions,
+            decoded,
         }
     }
 }
@@ -25,6 +44,10 @@ pub enum MachineError {
     Ip,
     Func,
     OpCode,
+    /// Calls were nested deeper than `MAX_CALL_DEPTH`
+    CallDepth,
+    /// The executor cannot do this, such as an `Interpreter` restoring a saved state
+    Unsupported,
 }
 
 pub enum MachineOutput<'a> {
@@ -32,88 +55,250 @@ pub enum MachineOutput<'a> {
     Call { func: GlobalFunction, args: &'a [f64]},
 }
 
+/// Something which runs Biscuit code, and calls host functions through `MachineOutput`
+pub trait Executor {
+    /// Run until a call is encountered, or at most `budget` instructions have been run
+    fn run_for(&mut self, budget: usize) -> Result<MachineOutput<'_>, MachineError>;
+    fn instructions_executed(&self) -> u64;
+    /// Whether the code is blocked until an interrupt is raised
+    fn is_waiting(&self) -> bool;
+    fn interrupt(&mut self, kind: InterruptKind, arg: f64);
+    /// Give the value returned by the host function just called. Must be called before running on, for functions
+    /// with a return type only.
+    fn return_value(&mut self, value: f64);
+    fn reset(&mut self);
+    /// Snapshot the running code, if this executor supports it
+    fn save_state(&self) -> Option<MachineState>;
+    /// Carry on from a saved state. Executors which cannot save one fail with `MachineError::Unsupported`.
+    fn restore_state(&mut self, state: &MachineState) -> Result<(), MachineError>;
+}
+
+/// What the run loop does after an instruction
+enum Flow {
+    Next,
+    Wait,
+    /// Call a function with the arguments in the vector at the address
+    Call(GlobalFunction, u32),
+}
+
 #[derive(Clone)]
 pub struct Machine {
     pub stack: Vec<f64>,
     memory: Memory,
     pub ip: usize,
     instructions: Instructions,
-    max_lines_per_tick: usize
+    /// Shared with `instructions`, so that running does not look up the tagged data
+    program: Rc<Program>,
+    max_lines_per_tick: usize,
+
+    /// Raised interrupts, and the handler address of each, registered by the program with `hnd`
+    interrupts: InterruptQueue<usize>,
+    /// Number of instructions run since the machine was created
+    executed: u64,
+    /// Calls made with `jsr` or `jsrp` which have not returned. Their return addresses are on the stack.
+    depth: usize,
+    /// Instructions run at each address, if profiling
+    profile: Option<Box<Profile>>,
 }
 
 impl Machine {
     pub fn new(instructions: Instructions, max_lines_per_tick: usize) -> Self {
         let memory = Memory::new();
+        let program = instructions.decoded.clone();
         Self {
             stack: Vec::new(),
             ip: 0,
             memory,
             instructions,
+            program,
             max_lines_per_tick,
+            interrupts: InterruptQueue::new(),
+            executed: 0,
+            depth: 0,
+            profile: None,
+        }
+    }
+
+    pub fn instructions_executed(&self) -> u64 {
+        self.executed
+    }
+
+    /// Count the instructions run at each address from now on. Superinstructions are run one instruction at a
+    /// time while profiling, so every address is counted.
+    pub fn start_profiling(&mut self) {
+        self.profile.get_or_insert_default();
+    }
+
+    /// Stop profiling, returning the counts
+    pub fn stop_profiling(&mut self) -> Option<Profile> {
+        self.profile.take().map(|p| *p)
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
+    }
+
+    /// Whether the machine is blocked on a `wait` with no interrupt to dispatch
+    pub fn is_waiting(&self) -> bool {
+        !self.interrupts.has_pending() && self.program.code.get(self.ip).is_some_and(|d| d.op == Op::Bare(Command::Wait))
+    }
+
+    /// Raise an interrupt. If the same interrupt is already pending, its argument is replaced.
+    /// Masked interrupts are discarded.
+    pub fn interrupt(&mut self, kind: InterruptKind, arg: f64) {
+        self.interrupts.raise(kind, arg);
+    }
+
+    /// Push the value returned by the host function just called
+    pub fn return_value(&mut self, value: f64) {
+        self.stack.push(value);
+    }
+
+    pub fn mask(&mut self, kind: InterruptKind) {
+        self.interrupts.mask(kind);
+    }
+
+    pub fn unmask(&mut self, kind: InterruptKind) {
+        self.interrupts.unmask(kind);
+    }
+
+    pub fn set_priority(&mut self, kind: InterruptKind, priority: u8) {
+        self.interrupts.set_priority(kind, priority);
+    }
+
+    /// Jump to the handler of the highest priority pending interrupt, if it may preempt the running code
+    fn dispatch_interrupt(&mut self) {
+        if let Some((p, address)) = self.interrupts.dispatch() {
+            if let Some(profile) = &mut self.profile {
+                profile.enter(self.ip);
+            }
+            self.stack.push(self.ip as f64);
+            if p.kind.takes_arg() {
+                self.stack.push(p.arg);
+            }
+            self.ip = address;
         }
     }
 
     /// Run until a call is encountered, or tick. Run the function call.
     pub fn run_to_call<'a> (&'a mut self) -> Result<MachineOutput<'a>,MachineError> {
-        for _ in 0..self.max_lines_per_tick {
-            if self.ip > self.instructions.instructions.len() {
-                return Err(MachineError::Ip)
+        self.run_for(self.max_lines_per_tick)
+    }
+
+    /// Run until a call is encountered, or at most `budget` instructions have been run.
+    pub fn run_for<'a> (&'a mut self, budget: usize) -> Result<MachineOutput<'a>,MachineError> {
+        let mut left = budget;
+        while left > 0 {
+            if self.interrupts.has_pending() {
+                self.dispatch_interrupt();
+            }
+            let mut decoded = *self.program.code.get(self.ip).ok_or(MachineError::Ip)?;
+            if decoded.count as usize > left || self.profile.is_some() {
+                // Split superinstructions which do not fit in the budget, or which would hide an address from the
+                // profile
+                decoded = Decoded::decode(&self.instructions.instructions, self.ip);
+            }
+            match self.execute(decoded)? {
+                Flow::Next => (),
+                Flow::Wait => return Ok(MachineOutput::None),
+                Flow::Call(func, address) => {
+                    let args = self.memory.access(address).ok_or(MachineError::Memory)?;
+                    return Ok(MachineOutput::Call{func, args});
+                },
+            }
+            left -= decoded.count as usize;
+        }
+        Ok(MachineOutput::None)
+    }
+
+    /// Same as `run_for`, but decodes each instruction from the bytecode as it is run, as the machine did before
+    /// the code was decoded ahead of time. It is kept as the baseline `run_for` is benchmarked and tested against,
+    /// so it shares none of `execute`, and it does not record a profile. Only built for tests and the `bench`
+    /// feature.
+    #[cfg(any(test, feature = "bench"))]
+    pub fn run_for_bytes<'a> (&'a mut self, budget: usize) -> Result<MachineOutput<'a>,MachineError> {
+        for _ in 0..budget {
+            if self.interrupts.has_pending() {
+                self.dispatch_interrupt();
             }
-            if self.ip >= self.instructions.instructions.len() {
+            let bytes = &self.instructions.instructions;
+            if self.ip >= bytes.len() {
                 return Err(MachineError::Ip);
             }
-            let command = match Command::try_from(self.instructions.instructions[self.ip]) {
+            let command = match Command::try_from(bytes[self.ip]) {
                 Ok(c) => c,
                 _ => return Err(MachineError::OpCode)
             };
+            let operand = |ip: usize| bytes.get(ip + 1..ip + 9)
+                .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+                .ok_or(MachineError::OpCode);
+            if command != Command::Wait {
+                self.executed += 1;
+            }
             match command {
                 Command::Nop => (),
                 Command::Push => {
-                    self.stack.push(f64::from_le_bytes([
-                        self.instructions.instructions[self.ip+1],
-                        self.instructions.instructions[self.ip+2],
-                        self.instructions.instructions[self.ip+3],
-                        self.instructions.instructions[self.ip+4],
-                        self.instructions.instructions[self.ip+5],
-                        self.instructions.instructions[self.ip+6],
-                        self.instructions.instructions[self.ip+7],
-                        self.instructions.instructions[self.ip+8]
-                    ]));
+                    let value = f64::from_bits(operand(self.ip)?);
+                    self.stack.push(value);
                     self.ip += 8;
                 },
                 Command::Pop => { self.stack.pop().ok_or(MachineError::Stack)?; },
                 Command::Dup => { self.stack.push(*self.stack.last().ok_or(MachineError::Stack)?); }
                 Command::Pip => { self.stack.push(self.ip as f64); },
-                Command::Jpop => { self.ip = self.stack.pop().ok_or(MachineError::Stack)?.round() as usize; },
-                
+                Command::Jpop => {
+                    self.ip = self.stack.pop().ok_or(MachineError::Stack)?.round() as usize;
+                    continue;
+                },
+                Command::Hnd => {
+                    let kind = self.stack.pop().ok_or(MachineError::Stack)?.round() as u8;
+                    let kind = InterruptKind::try_from(kind).map_err(|_| MachineError::OpCode)?;
+                    let address = self.stack.pop().ok_or(MachineError::Stack)?.round() as usize;
+                    self.interrupts.register(kind, address);
+                },
+                Command::Wait => {
+                    // Stay on this instruction until an interrupt is dispatched
+                    return Ok(MachineOutput::None);
+                },
+                Command::Reti => {
+                    self.interrupts.finish().ok_or(MachineError::Stack)?;
+                    self.ip = self.stack.pop().ok_or(MachineError::Stack)?.round() as usize;
+                    continue;
+                },
                 Command::Jmp => {
-                    self.ip = u64::from_le_bytes([
-                        self.instructions.instructions[self.ip+1],
-                        self.instructions.instructions[self.ip+2],
-                        self.instructions.instructions[self.ip+3],
-                        self.instructions.instructions[self.ip+4],
-                        self.instructions.instructions[self.ip+5],
-                        self.instructions.instructions[self.ip+6],
-                        self.instructions.instructions[self.ip+7],
-                        self.instructions.instructions[self.ip+8]
-                    ]) as usize - 1;
+                    self.ip = operand(self.ip)? as usize;
+                    continue;
                 },
                 Command::Jnz => {
+                    let target = operand(self.ip)? as usize;
                     if self.stack.pop().ok_or(MachineError::Stack)? != 0. {
-                        self.ip = u64::from_le_bytes([
-                            self.instructions.instructions[self.ip+1],
-                            self.instructions.instructions[self.ip+2],
-                            self.instructions.instructions[self.ip+3],
-                            self.instructions.instructions[self.ip+4],
-                            self.instructions.instructions[self.ip+5],
-                            self.instructions.instructions[self.ip+6],
-                            self.instructions.instructions[self.ip+7],
-                            self.instructions.instructions[self.ip+8]
-                        ]) as usize - 1;
+                        self.ip = target;
+                        continue;
                     } else {
                         self.ip += 8;
                     }
                 },
+                Command::Jsr => {
+                    let target = operand(self.ip)? as usize;
+                    if self.depth == MAX_CALL_DEPTH {
+                        return Err(MachineError::CallDepth);
+                    }
+                    self.depth += 1;
+                    self.ip = target;
+                    continue;
+                },
+                Command::Jsrp => {
+                    if self.depth == MAX_CALL_DEPTH {
+                        return Err(MachineError::CallDepth);
+                    }
+                    self.depth += 1;
+                    self.ip = self.stack.pop().ok_or(MachineError::Stack)?.round() as usize;
+                    continue;
+                },
+                Command::Ret => {
+                    self.depth = self.depth.checked_sub(1).ok_or(MachineError::Stack)?;
+                    self.ip = self.stack.pop().ok_or(MachineError::Stack)?.round() as usize;
+                    continue;
+                },
                 Command::Lt => {
                     let a = self.stack.pop().ok_or(MachineError::Stack)?;
                     let b = self.stack.pop().ok_or(MachineError::Stack)?;
@@ -123,7 +308,7 @@ impl Machine {
                     let a = self.stack.pop().ok_or(MachineError::Stack)?;
                     let b = self.stack.pop().ok_or(MachineError::Stack)?;
                     self.stack.push((a > b) as i64 as f64)
-                }, 
+                },
                 Command::Le => {
                     let a = self.stack.pop().ok_or(MachineError::Stack)?;
                     let b = self.stack.pop().ok_or(MachineError::Stack)?;
@@ -188,13 +373,11 @@ impl Machine {
                     self.stack.push((!(a != 0.)) as i64 as f64);
                 },
                 Command::Call => {
-                    let func = GlobalFunction::try_from(u8::from_le_bytes([
-                        self.instructions.instructions[self.ip+1],
-                    ])).map_err(|_| MachineError::OpCode)?;
+                    let func = bytes.get(self.ip + 1).and_then(|b| GlobalFunction::try_from(*b).ok())
+                        .ok_or(MachineError::OpCode)?;
                     let arg_index = self.stack.pop().ok_or(MachineError::Stack)?.round() as u32;
+                    self.ip += 2;
                     let args = self.memory.access(arg_index).ok_or(MachineError::Memory)?;
-                    self.ip += 1;
-                    self.ip += 1;
                     return Ok(MachineOutput::Call{func, args});
                 },
                 Command::Swp => {
@@ -206,8 +389,8 @@ impl Machine {
                 Command::Pick => {
                     let index = self.stack.pop().ok_or(MachineError::Stack)?;
                     let index = (index.round() as u32) as usize;
-                    let b = self.stack.iter().nth(self.stack.len() - index - 1).ok_or(MachineError::Stack)?;
-                    self.stack.push(*b);
+                    let position = self.stack.len().checked_sub(index + 1).ok_or(MachineError::Stack)?;
+                    self.stack.push(self.stack[position]);
                 },
                 Command::Alc => {
                     self.stack.push(self.memory.allocate() as f64)
@@ -235,21 +418,285 @@ impl Machine {
                 Command::Roll => {
                     let dist = self.stack.pop().ok_or(MachineError::Stack)?.round() as usize;
                     let len = self.stack.len();
-                    self.stack[(len - dist)..len].rotate_left(1);
+                    let start = len.checked_sub(dist).ok_or(MachineError::Stack)?;
+                    self.stack[start..len].rotate_left(1);
                 }
                 Command::Rolr => {
                     let dist = self.stack.pop().ok_or(MachineError::Stack)?.round() as usize;
                     let len = self.stack.len();
-                    self.stack[(len - dist)..len].rotate_right(1);
+                    let start = len.checked_sub(dist).ok_or(MachineError::Stack)?;
+                    self.stack[start..len].rotate_right(1);
                 },
             }
             self.ip += 1;
         }
         Ok(MachineOutput::None)
     }
+
+    /// Run one decoded instruction, and move the IP past it unless it jumped
+    fn execute(&mut self, decoded: Decoded) -> Result<Flow, MachineError> {
+        if decoded.op == Op::Bare(Command::Wait) {
+            // Stay on this instruction until an interrupt is dispatched
+            return Ok(Flow::Wait);
+        }
+        self.executed += decoded.count as u64;
+        if let Some(profile) = &mut self.profile {
+            profile.record(self.ip, decoded.count as usize);
+        }
+        let next = self.ip + decoded.len as usize;
+        match decoded.op {
+            Op::Bare(command) => self.execute_bare(command)?,
+            Op::Push(value) => self.stack.push(value),
+            Op::Jmp(target) => {
+                self.ip = target;
+                return Ok(Flow::Next);
+            },
+            Op::Jnz(target) => {
+                if self.pop()? != 0. {
+                    self.ip = target;
+                    return Ok(Flow::Next);
+                }
+            },
+            Op::Jsr(target) => {
+                self.enter()?;
+                self.ip = target;
+                return Ok(Flow::Next);
+            },
+            Op::Call(func) => {
+                let address = self.pop()?.round() as u32;
+                self.ip = next;
+                return Ok(Flow::Call(func, address));
+            },
+            Op::PickN(index) => self.pick(index)?,
+            Op::RollN(dist) => self.roll(dist, false)?,
+            Op::RolrN(dist) => self.roll(dist, true)?,
+            Op::Vector(literal) => {
+                let address = self.memory.allocate_from(&self.program.literals[literal as usize]);
+                self.stack.push(address as f64);
+            },
+            Op::Invalid => return Err(MachineError::OpCode),
+        }
+        if let Op::Bare(Command::Jpop | Command::Jsrp | Command::Ret | Command::Reti) = decoded.op {
+            // The IP was popped from the stack
+            return Ok(Flow::Next);
+        }
+        self.ip = next;
+        Ok(Flow::Next)
+    }
+
+    fn execute_bare(&mut self, command: Command) -> Result<(), MachineError> {
+        match command {
+            Command::Nop => (),
+            Command::Pop => { self.pop()?; },
+            Command::Dup => { self.stack.push(*self.stack.last().ok_or(MachineError::Stack)?); }
+            Command::Pip => { self.stack.push(self.ip as f64); },
+            Command::Jpop => { self.ip = self.pop()?.round() as usize; },
+            Command::Jsrp => {
+                self.enter()?;
+                self.ip = self.pop()?.round() as usize;
+            },
+            Command::Ret => {
+                self.depth = self.depth.checked_sub(1).ok_or(MachineError::Stack)?;
+                self.ip = self.pop()?.round() as usize;
+                if let Some(profile) = &mut self.profile {
+                    profile.leave();
+                }
+            },
+            Command::Hnd => {
+                let kind = self.pop()?.round() as u8;
+                let kind = InterruptKind::try_from(kind).map_err(|_| MachineError::OpCode)?;
+                let address = self.pop()?.round() as usize;
+                self.interrupts.register(kind, address);
+            },
+            Command::Reti => {
+                self.interrupts.finish().ok_or(MachineError::Stack)?;
+                self.ip = self.pop()?.round() as usize;
+                if let Some(profile) = &mut self.profile {
+                    profile.leave();
+                }
+            },
+            Command::Lt => { let (a, b) = self.pop2()?; self.stack.push((a < b) as i64 as f64) },
+            Command::Gt => { let (a, b) = self.pop2()?; self.stack.push((a > b) as i64 as f64) },
+            Command::Le => { let (a, b) = self.pop2()?; self.stack.push((a <= b) as i64 as f64) },
+            Command::Ge => { let (a, b) = self.pop2()?; self.stack.push((a >= b) as i64 as f64) },
+            Command::Eq => { let (a, b) = self.pop2()?; self.stack.push((a == b) as i64 as f64) },
+            Command::Add => { let (a, b) = self.pop2()?; self.stack.push(a + b) },
+            Command::Sub => { let (a, b) = self.pop2()?; self.stack.push(a - b) },
+            Command::Mul => { let (a, b) = self.pop2()?; self.stack.push(a * b) },
+            Command::Div => { let (a, b) = self.pop2()?; self.stack.push(a / b) },
+            Command::Pow => { let (a, b) = self.pop2()?; self.stack.push(a.powf(b)) },
+            Command::Neg => {
+                let a = self.pop()?;
+                self.stack.push(-a)
+            },
+            Command::And => { let (a, b) = self.pop2()?; self.stack.push(((a != 0.) && (b != 0.)) as i64 as f64); },
+            Command::Or => { let (a, b) = self.pop2()?; self.stack.push(((a != 0.) || (b != 0.)) as i64 as f64); },
+            Command::Xor => { let (a, b) = self.pop2()?; self.stack.push(((a != 0.) ^ (b != 0.)) as i64 as f64); },
+            Command::Not => {
+                let a = self.pop()?;
+                self.stack.push((!(a != 0.)) as i64 as f64);
+            },
+            Command::Swp => {
+                let (a, b) = self.pop2()?;
+                self.stack.push(a);
+                self.stack.push(b);
+            },
+            Command::Pick => {
+                let index = self.pop()?.round() as u32 as usize;
+                self.pick(index)?;
+            },
+            Command::Alc => {
+                self.stack.push(self.memory.allocate() as f64)
+            },
+            Command::Drop => {
+                let address = self.pop()?.round() as u32;
+                self.memory.drop(address);
+            },
+            Command::Ld => {
+                let index = self.pop()?.round() as u32;
+                let address = self.stack.last().ok_or(MachineError::Stack)?.round() as u32;
+                self.stack.push(self.memory.load(address, index).ok_or(MachineError::Memory)?);
+            },
+            Command::St => {
+                let index = self.pop()?.round() as u32;
+                let item = self.pop()?;
+                let address = self.stack.last().ok_or(MachineError::Stack)?.round() as u32;
+                self.memory.store(address, index, item).ok_or(MachineError::Memory)?;
+            },
+            Command::Stb => {
+                let item = self.pop()?;
+                let address = self.stack.last().ok_or(MachineError::Stack)?.round() as u32;
+                self.memory.store_back(address, item).ok_or(MachineError::Memory)?;
+            },
+            Command::Roll => {
+                let dist = self.pop()?.round() as usize;
+                self.roll(dist, false)?;
+            },
+            Command::Rolr => {
+                let dist = self.pop()?.round() as usize;
+                self.roll(dist, true)?;
+            },
+            // These take operands, so are never bare
+            Command::Push | Command::Jmp | Command::Jnz | Command::Jsr | Command::Call => return Err(MachineError::OpCode),
+            Command::Wait => unreachable!(),
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<f64, MachineError> {
+        self.stack.pop().ok_or(MachineError::Stack)
+    }
+
+    /// Add a frame to the call stack
+    fn enter(&mut self) -> Result<(), MachineError> {
+        if self.depth == MAX_CALL_DEPTH {
+            return Err(MachineError::CallDepth);
+        }
+        self.depth += 1;
+        if let Some(profile) = &mut self.profile {
+            profile.enter(self.ip);
+        }
+        Ok(())
+    }
+
+    /// Pop T, then N
+    fn pop2(&mut self) -> Result<(f64, f64), MachineError> {
+        let a = self.pop()?;
+        let b = self.pop()?;
+        Ok((a, b))
+    }
+
+    /// Push a copy of the item `index` down from the top
+    fn pick(&mut self, index: usize) -> Result<(), MachineError> {
+        let position = self.stack.len().checked_sub(index + 1).ok_or(MachineError::Stack)?;
+        self.stack.push(self.stack[position]);
+        Ok(())
+    }
+
+    /// Rotate the top `dist` items, moving the deepest to the top (or the top to the deepest if `right`)
+    fn roll(&mut self, dist: usize, right: bool) -> Result<(), MachineError> {
+        let len = self.stack.len();
+        let start = len.checked_sub(dist).ok_or(MachineError::Stack)?;
+        match right {
+            false => self.stack[start..len].rotate_left(1),
+            true => self.stack[start..len].rotate_right(1),
+        }
+        Ok(())
+    }
     
     pub fn reset(&mut self) {
         self.stack.clear();
         self.ip = 0;
+        self.depth = 0;
+        self.interrupts.clear();
+        if let Some(profile) = &mut self.profile {
+            profile.reset();
+        }
+    }
+
+    /// Snapshot everything the machine needs to carry on from here
+    pub fn save_state(&self) -> MachineState {
+        MachineState {
+            stack: self.stack.clone(),
+            ip: self.ip,
+            depth: self.depth,
+            executed: self.executed,
+            memory: self.memory.clone(),
+            interrupts: self.interrupts.clone(),
+            code_hash: state::hash_code(&self.instructions.instructions),
+        }
+    }
+
+    /// Carry on from a saved state. Fails with `MachineError::Ip` if the state was saved from other code.
+    pub fn restore_state(&mut self, state: &MachineState) -> Result<(), MachineError> {
+        let len = self.instructions.instructions.len();
+        if state.code_hash != state::hash_code(&self.instructions.instructions) || state.ip >= len
+            || state.interrupts.handlers().any(|h| h >= len) {
+            return Err(MachineError::Ip);
+        }
+        self.stack = state.stack.clone();
+        self.ip = state.ip;
+        self.depth = state.depth;
+        self.executed = state.executed;
+        self.memory = state.memory.clone();
+        self.interrupts = state.interrupts.clone();
+        if let Some(profile) = &mut self.profile {
+            profile.reset();
+        }
+        Ok(())
+    }
+}
+
+impl Executor for Machine {
+    fn run_for(&mut self, budget: usize) -> Result<MachineOutput<'_>, MachineError> {
+        Machine::run_for(self, budget)
+    }
+
+    fn instructions_executed(&self) -> u64 {
+        Machine::instructions_executed(self)
     }
-}
\ No newline at end of file
+
+    fn is_waiting(&self) -> bool {
+        Machine::is_waiting(self)
+    }
+
+    fn interrupt(&mut self, kind: InterruptKind, arg: f64) {
+        Machine::interrupt(self, kind, arg)
+    }
+
+    fn return_value(&mut self, value: f64) {
+        Machine::return_value(self, value)
+    }
+
+    fn reset(&mut self) {
+        Machine::reset(self)
+    }
+
+    fn save_state(&self) -> Option<MachineState> {
+        Some(Machine::save_state(self))
+    }
+
+    fn restore_state(&mut self, state: &MachineState) -> Result<(), MachineError> {
+        Machine::restore_state(self, state)
+    }
+}
@@ -0,0 +1,142 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use rustc_hash::FxHashMap;
+
+use crate::DebugInfo;
+
+/// Instructions run by a machine, counted by address and by the call stack they were run in
+#[derive(Clone, Debug)]
+pub struct Profile {
+    /// Each call stack, as the stack it was entered from and the address of the call or interrupted
+    /// instruction. Stack 0 is the one the program starts in.
+    stacks: Vec<(u32, usize)>,
+    children: FxHashMap<(u32, usize), u32>,
+    current: u32,
+    /// Instructions run, by stack and address
+    samples: FxHashMap<(u32, usize), u64>,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self {
+            stacks: vec![(0, 0)],
+            children: FxHashMap::default(),
+            current: 0,
+            samples: FxHashMap::default(),
+        }
+    }
+
+    /// Count `count` instructions run at the address
+    pub(crate) fn record(&mut self, ip: usize, count: usize) {
+        *self.samples.entry((self.current, ip)).or_default() += count as u64;
+    }
+
+    /// Enter a function called, or an interrupt handler dispatched, at the address
+    pub(crate) fn enter(&mut self, ip: usize) {
+        let key = (self.current, ip);
+        self.current = match self.children.get(&key) {
+            Some(stack) => *stack,
+            None => {
+                self.stacks.push(key);
+                let stack = self.stacks.len() as u32 - 1;
+                self.children.insert(key, stack);
+                stack
+            },
+        };
+    }
+
+    pub(crate) fn leave(&mut self) {
+        self.current = self.stacks[self.current as usize].0;
+    }
+
+    /// Go back to the stack the program starts in, keeping the counts
+    pub(crate) fn reset(&mut self) {
+        self.current = 0;
+    }
+
+    /// Total number of instructions run
+    pub fn total(&self) -> u64 {
+        self.samples.values().sum()
+    }
+
+    /// Instructions run at each address
+    pub fn counts(&self) -> BTreeMap<usize, u64> {
+        let mut counts = BTreeMap::new();
+        for ((_, ip), count) in &self.samples {
+            *counts.entry(*ip).or_default() += count;
+        }
+        counts
+    }
+
+    /// Names of the functions in a stack, outermost first, ending with the function holding `ip`
+    fn frames<'a>(&self, mut stack: u32, ip: usize, debug: &'a DebugInfo) -> Vec<&'a str> {
+        let mut frames = vec![debug.function(ip).unwrap_or("?")];
+        while stack != 0 {
+            let (parent, site) = self.stacks[stack as usize];
+            frames.push(debug.function(site).unwrap_or("?"));
+            stack = parent;
+        }
+        frames.reverse();
+        frames
+    }
+
+    /// A flat profile: the instructions run in each function, by itself and with the functions it called, then
+    /// in each source line. Each list is sorted with the most run first.
+    pub fn flat(&self, debug: &DebugInfo) -> String {
+        let total = self.total().max(1) as f64;
+        let mut own: BTreeMap<&str, u64> = BTreeMap::new();
+        let mut inclusive: BTreeMap<&str, u64> = BTreeMap::new();
+        let mut lines: BTreeMap<(&str, Option<u32>, &str), u64> = BTreeMap::new();
+        for ((stack, ip), count) in &self.samples {
+            let mut frames = self.frames(*stack, *ip, debug);
+            *own.entry(frames.last().unwrap()).or_default() += count;
+            // Recursive functions count once towards their total
+            frames.sort();
+            frames.dedup();
+            for frame in frames {
+                *inclusive.entry(frame).or_default() += count;
+            }
+            let module = debug.module(*ip).unwrap_or("?");
+            *lines.entry((module, debug.line(*ip), debug.function(*ip).unwrap_or("?"))).or_default() += count;
+        }
+
+        let mut out = String::new();
+        writeln!(out, "{} instructions", self.total()).unwrap();
+        writeln!(out, "\n{:>12} {:>6} {:>12} {:>6}  function", "self", "%", "total", "%").unwrap();
+        let mut functions: Vec<(&str, u64)> = own.into_iter().collect();
+        functions.sort_by_key(|(name, count)| (std::cmp::Reverse(*count), *name));
+        for (name, count) in functions {
+            let all = inclusive[name];
+            writeln!(out, "{:>12} {:>5.1}% {:>12} {:>5.1}%  {}", count, 100. * count as f64 / total, all,
+                100. * all as f64 / total, name).unwrap();
+        }
+        writeln!(out, "\n{:>12} {:>6}  line", "count", "%").unwrap();
+        let mut lines: Vec<_> = lines.into_iter().collect();
+        lines.sort_by_key(|(key, count)| (std::cmp::Reverse(*count), *key));
+        for ((module, line, function), count) in lines {
+            let line = line.map_or("?".to_owned(), |l| l.to_string());
+            writeln!(out, "{:>12} {:>5.1}%  {}:{} ({})", count, 100. * count as f64 / total, module, line, function).unwrap();
+        }
+        out
+    }
+
+    /// The profile in the folded stack format read by flamegraph tools: a line per call stack, with its
+    /// functions separated by `;` and followed by the instructions run in it
+    pub fn folded(&self, debug: &DebugInfo) -> String {
+        let mut stacks: BTreeMap<String, u64> = BTreeMap::new();
+        for ((stack, ip), count) in &self.samples {
+            *stacks.entry(self.frames(*stack, *ip, debug).join(";")).or_default() += count;
+        }
+        let mut out = String::new();
+        for (stack, count) in stacks {
+            writeln!(out, "{} {}", stack, count).unwrap();
+        }
+        out
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,245 @@
+use crate::{bytecode::{GlobalFunction, InterruptKind, VariableType}, machine::{Executor, Log, MachineError, MachineOutput}};
+
+/// Handle to a machine owned by a `Scheduler`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MachineId(usize);
+
+/// Lines of output kept for each machine
+const LOG_LINES: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TaskState {
+    Ready,
+    /// Called `tick`, and runs again next frame
+    Yielded,
+    /// Called `sleep`, and runs again after this many frames
+    Sleeping(u64),
+    /// Called `wait_for_signal`, and runs again once the host signals it
+    WaitingForSignal,
+    /// Blocked on `wait` until an interrupt is raised
+    WaitingForInterrupt,
+}
+
+struct Task {
+    machine: Box<dyn Executor>,
+    state: TaskState,
+    /// Share of the budget relative to the other machines
+    weight: u32,
+    /// Instructions run in the current or last frame
+    used: u64,
+    log: Log,
+}
+
+/// Runs many machines cooperatively. Each frame, a global instruction budget is split between the
+/// runnable machines in proportion to their weights. Budget left over by machines which yield early
+/// is shared out again between the rest.
+pub struct Scheduler {
+    tasks: Vec<Option<Task>>,
+    budget: u64,
+    /// Task the next frame starts from, so that the same machine is not always served last
+    cursor: usize,
+}
+
+impl Scheduler {
+    pub fn new(budget: u64) -> Self {
+        Self {
+            tasks: Vec::new(),
+            budget,
+            cursor: 0,
+        }
+    }
+
+    pub fn set_budget(&mut self, budget: u64) {
+        self.budget = budget;
+    }
+
+    /// Add a machine, or anything else which runs Biscuit code such as an `Interpreter`
+    pub fn insert(&mut self, machine: impl Executor + 'static) -> MachineId {
+        let task = Task {
+            machine: Box::new(machine),
+            state: TaskState::Ready,
+            weight: 1,
+            used: 0,
+            log: Log::new(LOG_LINES),
+        };
+        match self.tasks.iter().position(|t| t.is_none()) {
+            Some(index) => {
+                self.tasks[index] = Some(task);
+                MachineId(index)
+            },
+            None => {
+                self.tasks.push(Some(task));
+                MachineId(self.tasks.len() - 1)
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: MachineId) -> Option<Box<dyn Executor>> {
+        self.tasks.get_mut(id.0)?.take().map(|t| t.machine)
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = MachineId> + '_ {
+        self.tasks.iter().enumerate().filter(|(_, t)| t.is_some()).map(|(i, _)| MachineId(i))
+    }
+
+    fn task(&self, id: MachineId) -> Option<&Task> {
+        self.tasks.get(id.0)?.as_ref()
+    }
+
+    fn task_mut(&mut self, id: MachineId) -> Option<&mut Task> {
+        self.tasks.get_mut(id.0)?.as_mut()
+    }
+
+    pub fn machine(&self, id: MachineId) -> Option<&dyn Executor> {
+        self.task(id).map(|t| &*t.machine)
+    }
+
+    pub fn machine_mut(&mut self, id: MachineId) -> Option<&mut dyn Executor> {
+        self.task_mut(id).map(|t| &mut *t.machine as &mut dyn Executor)
+    }
+
+    /// Output of the machine's `dbg` and `print` calls, and its faults
+    pub fn log(&self, id: MachineId) -> Option<&Log> {
+        self.task(id).map(|t| &t.log)
+    }
+
+    pub fn state(&self, id: MachineId) -> Option<TaskState> {
+        self.task(id).map(|t| t.state)
+    }
+
+    /// Machines with a higher weight get a proportionally larger share of the budget. The default is 1.
+    pub fn set_weight(&mut self, id: MachineId, weight: u32) {
+        if let Some(t) = self.task_mut(id) {
+            t.weight = weight.max(1);
+        }
+    }
+
+    /// Wake a machine blocked on `wait_for_signal`
+    pub fn signal(&mut self, id: MachineId) {
+        if let Some(t) = self.task_mut(id) && t.state == TaskState::WaitingForSignal {
+            t.state = TaskState::Ready;
+        }
+    }
+
+    /// Raise an interrupt on a machine, waking it if it is waiting for one
+    pub fn interrupt(&mut self, id: MachineId, kind: InterruptKind, arg: f64) {
+        if let Some(t) = self.task_mut(id) {
+            t.machine.interrupt(kind, arg);
+            if t.state == TaskState::WaitingForInterrupt && !t.machine.is_waiting() {
+                t.state = TaskState::Ready;
+            }
+        }
+    }
+
+    /// Number of instructions the machine ran last frame
+    pub fn instructions_last_frame(&self, id: MachineId) -> u64 {
+        self.task(id).map_or(0, |t| t.used)
+    }
+
+    /// Fraction of last frame's budget used by the machine
+    pub fn cpu_usage(&self, id: MachineId) -> f64 {
+        if self.budget == 0 { return 0.; }
+        self.instructions_last_frame(id) as f64 / self.budget as f64
+    }
+
+    /// Run one frame. `dbg` and `print` are written to the machine's log, and function calls other than those
+    /// and the scheduling ones are passed to `host`, which returns the value of functions with a return type.
+    /// Machines which fault are reset, and returned with their error.
+    pub fn run_frame<F>(&mut self, mut host: F) -> Vec<(MachineId, MachineError)>
+    where F: FnMut(MachineId, GlobalFunction, &[f64]) -> Result<f64, MachineError> {
+        for task in self.tasks.iter_mut().flatten() {
+            task.used = 0;
+            task.state = match task.state {
+                TaskState::Yielded => TaskState::Ready,
+                TaskState::Sleeping(n) if n <= 1 => TaskState::Ready,
+                TaskState::Sleeping(n) => TaskState::Sleeping(n - 1),
+                TaskState::WaitingForInterrupt if !task.machine.is_waiting() => TaskState::Ready,
+                state => state,
+            };
+        }
+
+        let mut faults = Vec::new();
+        let mut remaining = self.budget;
+        let n_tasks = self.tasks.len();
+        while remaining > 0 {
+            let runnable: Vec<usize> = (0..n_tasks)
+                .map(|i| (i + self.cursor) % n_tasks)
+                .filter(|i| self.tasks[*i].as_ref().is_some_and(|t| t.state == TaskState::Ready))
+                .collect();
+            if runnable.is_empty() { break; }
+
+            let total_weight: u64 = runnable.iter().map(|i| self.tasks[*i].as_ref().unwrap().weight as u64).sum();
+            let round_budget = remaining;
+            let mut progressed = false;
+            for index in runnable {
+                if remaining == 0 { break; }
+                let weight = self.tasks[index].as_ref().unwrap().weight as u64;
+                let share = (round_budget * weight / total_weight).clamp(1, remaining);
+                let used = self.run_task(index, share, &mut host, &mut faults);
+                remaining -= used;
+                progressed |= used > 0;
+            }
+            if !progressed { break; }
+        }
+
+        if n_tasks > 0 {
+            self.cursor = (self.cursor + 1) % n_tasks;
+        }
+        faults
+    }
+
+    /// Run a task for at most `slice` instructions. Returns the number of instructions run.
+    fn run_task<F>(&mut self, index: usize, slice: u64, host: &mut F, faults: &mut Vec<(MachineId, MachineError)>) -> u64
+    where F: FnMut(MachineId, GlobalFunction, &[f64]) -> Result<f64, MachineError> {
+        let id = MachineId(index);
+        let task = self.tasks[index].as_mut().unwrap();
+        let start = task.machine.instructions_executed();
+        loop {
+            let left = slice - (task.machine.instructions_executed() - start);
+            if left == 0 { break; }
+            let result = match task.machine.run_for(left as usize) {
+                Ok(MachineOutput::Call { func, args }) => match func {
+                    GlobalFunction::Tick => Ok(Some(TaskState::Yielded)),
+                    GlobalFunction::Sleep => {
+                        let ticks = args.first().copied().unwrap_or(1.).round().max(1.);
+                        Ok(Some(TaskState::Sleeping(ticks as u64)))
+                    },
+                    GlobalFunction::WaitForSignal => Ok(Some(TaskState::WaitingForSignal)),
+                    GlobalFunction::Dbg | GlobalFunction::Print => task.log.write(func, args).map(|_| None),
+                    _ => host(id, func, args).map(|value| {
+                        if func.return_type() != VariableType::Null {
+                            task.machine.return_value(value);
+                        }
+                        None
+                    }),
+                },
+                Ok(MachineOutput::None) => Ok(Some(TaskState::Ready)),
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(None) => (),
+                Ok(Some(TaskState::Ready)) => {
+                    // Either the slice ran out, or the machine is blocked
+                    if task.machine.is_waiting() {
+                        task.state = TaskState::WaitingForInterrupt;
+                    }
+                    break;
+                },
+                Ok(Some(state)) => {
+                    task.state = state;
+                    break;
+                },
+                Err(e) => {
+                    task.log.push(format!("{:?} fault, restarting", e));
+                    faults.push((id, e));
+                    task.machine.reset();
+                    task.state = TaskState::Yielded;
+                    break;
+                },
+            }
+        }
+        let used = task.machine.instructions_executed() - start;
+        task.used += used;
+        used
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{machine::{InterruptQueue, memory::Memory}, util::bytes::{Reader, write_f64s, write_len}};
+
+const MAGIC: &[u8; 4] = b"BSMS";
+/// Bumped whenever the layout of a state changes. States of other versions are refused.
+const VERSION: u8 = 1;
+
+/// Everything a machine needs to carry on running where it stopped, apart from its code. States can be written to
+/// bytes, so that machines survive a save game.
+#[derive(Clone)]
+pub struct MachineState {
+    pub(crate) stack: Vec<f64>,
+    pub(crate) ip: usize,
+    pub(crate) depth: usize,
+    pub(crate) executed: u64,
+    pub(crate) memory: Memory,
+    pub(crate) interrupts: InterruptQueue<usize>,
+    /// Hash of the code the state was saved from. A state only makes sense with the same code.
+    pub(crate) code_hash: u64,
+}
+
+impl MachineState {
+    /// Number of instructions the machine had run when the state was saved
+    pub fn instructions_executed(&self) -> u64 {
+        self.executed
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(MAGIC);
+        out.push(VERSION);
+        out.extend(self.code_hash.to_le_bytes());
+        out.extend(self.executed.to_le_bytes());
+        write_len(&mut out, self.ip);
+        write_len(&mut out, self.depth);
+        write_f64s(&mut out, &self.stack);
+        self.memory.write(&mut out);
+        self.interrupts.write(&mut out);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let invalid = || "Machine state is corrupt".to_owned();
+        let mut reader = Reader::new(bytes);
+        if reader.take(4) != Some(MAGIC) {
+            return Err(invalid());
+        }
+        if reader.take(1) != Some(&[VERSION]) {
+            return Err("Machine state was written by another version of the machine".to_owned());
+        }
+        let state = read_state(&mut reader).ok_or_else(invalid)?;
+        match reader.is_finished() {
+            true => Ok(state),
+            false => Err(invalid()),
+        }
+    }
+}
+
+fn read_state(reader: &mut Reader) -> Option<MachineState> {
+    let code_hash = u64::from_le_bytes(reader.take(8)?.try_into().ok()?);
+    let executed = u64::from_le_bytes(reader.take(8)?.try_into().ok()?);
+    let ip = reader.length()?;
+    let depth = reader.length()?;
+    let stack = reader.f64s()?;
+    let memory = Memory::read(reader)?;
+    let interrupts = InterruptQueue::read(reader)?;
+    Some(MachineState { stack, ip, depth, executed, memory, interrupts, code_hash })
+}
+
+/// FNV-1a, which unlike the standard hasher is fixed, so saved hashes stay valid
+pub(crate) fn hash_code(code: &[u8]) -> u64 {
+    code.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
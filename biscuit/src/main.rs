@@ -1,8 +1,9 @@
 use std::path::{Path, PathBuf};
-use biscuit::util::Vendor;
+use biscuit::{Stage, util::Vendor};
+use strum::IntoEnumIterator;
 use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
 mod gui;
-use biscuit::machine::InstructionData;
+use biscuit::{Machine, machine::InstructionData};
 
 use clap::{Args, Parser, Subcommand};
 
@@ -17,12 +18,18 @@ struct Cli {
 enum Command {
     /// Build the project
     Build(Build),
+    /// Link object files into a binary
+    Link(Link),
     /// Run the debugger
     Run(Run),
     /// Assemble an assembly code
     Asm(Asm),
     /// Disassemble the output binary code
     Dis(Dis),
+    /// Print how the compiler sees the code, stage by stage
+    Explain(Explain),
+    /// Run the code and print where it spends its instructions
+    Profile(Profile),
 }
 
 fn main() {
@@ -30,9 +37,12 @@ fn main() {
 
     let result = match cli.command {
         Command::Build(args) => args.run(),
+        Command::Link(args) => args.run(),
         Command::Run(args) => args.run(),
         Command::Asm(args) => args.run(),
         Command::Dis(args) => args.run(),
+        Command::Explain(args) => args.run(),
+        Command::Profile(args) => args.run(),
     };
     if let Err(message) = result {
         println!("Error:\n{}", message);
@@ -47,16 +57,34 @@ struct Build {
 
     #[arg(short, long)]
     output: Option<String>,
+
+    /// Also write these stages next to the output, e.g. `--emit=tokens,ast,ssa,asm`
+    #[arg(long, value_delimiter = ',')]
+    emit: Vec<Stage>,
+
+    /// Write an object file to be linked with others, instead of a binary
+    #[arg(long)]
+    object: bool,
 }
 impl Build {
     fn run(self) -> Result<(), String> {
         let input = Path::new(&self.input);
         let output = match &self.output {
             Some(v) => PathBuf::from(v),
+            None if self.object => input.with_extension("o"),
             None => input.with_extension("b"),
         };
+        if self.object {
+            let object = biscuit::compile_object_file(input.to_string_lossy().as_ref())?;
+            return std::fs::write(output, object.to_bytes()).map_err(|_| "Could not write output file".to_owned());
+        }
 
-        let bytes = biscuit::compile_file(input.to_string_lossy().as_ref())?;
+        // Stages reached before an error are still written, to help find it
+        let explanation = biscuit::explain_file(input.to_string_lossy().as_ref(), &self.emit);
+        for (stage, text) in explanation.dumps {
+            std::fs::write(output.with_extension(stage.to_string()), text).map_err(|_| "Could not write dump file".to_owned())?;
+        }
+        let bytes = explanation.result?;
 
         std::fs::write(output, bytes).map_err(|_| "Could not write output file".to_owned())?;
 
@@ -65,6 +93,93 @@ impl Build {
 }
 
 
+#[derive(Args)]
+struct Link {
+    #[arg(required = true)]
+    inputs: Vec<String>,
+
+    #[arg(short, long)]
+    output: String,
+}
+impl Link {
+    fn run(self) -> Result<(), String> {
+        let inputs: Vec<&str> = self.inputs.iter().map(|i| i.as_str()).collect();
+        let bytes = biscuit::link_files(&inputs)?;
+
+        std::fs::write(&self.output, bytes).map_err(|_| "Could not write output file".to_owned())?;
+
+        Ok(())
+    }
+}
+
+
+#[derive(Args)]
+struct Explain {
+    #[arg()]
+    input: String,
+
+    /// Stages to print, all by default
+    #[arg(long, value_delimiter = ',')]
+    emit: Vec<Stage>,
+}
+impl Explain {
+    fn run(self) -> Result<(), String> {
+        let stages = match self.emit.is_empty() {
+            true => Stage::iter().collect(),
+            false => self.emit,
+        };
+        let explanation = biscuit::explain_file(&self.input, &stages);
+        for (stage, text) in explanation.dumps {
+            println!("== {} ==\n{}", stage, text);
+        }
+        explanation.result.map(|_| ())
+    }
+}
+
+
+#[derive(Args)]
+struct Profile {
+    #[arg()]
+    input: String,
+
+    /// Where to write the folded stacks, for flamegraph tools
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Number of instructions to run, unless the code finishes first
+    #[arg(short, long, default_value_t = 1_000_000)]
+    instructions: u64,
+}
+impl Profile {
+    fn run(self) -> Result<(), String> {
+        let input = Path::new(&self.input);
+        let output = match &self.output {
+            Some(v) => PathBuf::from(v),
+            None => input.with_extension("folded"),
+        };
+        let (bytes, debug) = biscuit::compile_debug_file(input.to_string_lossy().as_ref())?;
+
+        let mut script_vendor = Vendor::new();
+        let mut machine = Machine::new(script_vendor.insert(InstructionData::from_compiled(&bytes)), 1000);
+        machine.start_profiling();
+        // Host calls are not answered, as there is no ship
+        while !machine.is_waiting() && machine.instructions_executed() < self.instructions {
+            let budget = (self.instructions - machine.instructions_executed()).min(1000) as usize;
+            if let Err(e) = machine.run_for(budget) {
+                println!("Stopped by {:?} Error at {}", e, machine.ip);
+                break;
+            }
+        }
+
+        let profile = machine.stop_profiling().unwrap();
+        print!("{}", profile.flat(&debug));
+        std::fs::write(output, profile.folded(&debug)).map_err(|_| "Could not write output file".to_owned())?;
+
+        Ok(())
+    }
+}
+
+
 #[derive(Args)]
 struct Asm {
     #[arg()]
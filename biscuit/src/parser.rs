@@ -21,6 +21,36 @@ impl<T: std::fmt::Display + PartialEq> Token<T> {
     pub(crate) fn get_inner(&self) -> &T {
         &self.s
     }
+    pub(crate) fn span(&self) -> Span {
+        Span {
+            filename: self.filename.clone(),
+            line_no: self.line_no,
+            col_no: self.col_no,
+        }
+    }
+}
+
+/// Position of a token, kept after parsing so later passes can point at the source
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Span {
+    filename: String,
+    line_no: u32,
+    col_no: u32,
+}
+impl Span {
+    pub fn raise_str(&self, message: &str) -> String {
+        format!("{}:{}:{}\n{}", self.filename, self.line_no+1, self.col_no+1, message)
+    }
+
+    /// Line number, counting from 1
+    pub fn line(&self) -> u32 {
+        self.line_no + 1
+    }
+}
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.filename, self.line_no+1, self.col_no+1)
+    }
 }
 impl<T: std::fmt::Display + PartialEq> std::fmt::Debug for Token<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -39,10 +69,22 @@ impl PartialEq<f64> for Token<f64> {
     }
 }
 
+/// Tokens after which an operator is a prefix: those which separate two operands, and keywords
+const PREFIX_CONTEXT: &[&str] = &["**", "*", "/", "+", "-", "==", "!=", "<", ">", "<=", ">=", "&&", "^", "||", "!", "if", "else"];
+
+enum IfRole {
+    If,
+    ElseIf,
+    Else,
+    Other,
+}
+
 #[derive(Clone)]
 pub enum SyntaxNode {
     Unclassified(Token<String>),
     Number(Token<f64>),
+    /// String literal, without its quotes
+    Str(Token<String>),
     Adjacent(Vec<SyntaxNode>),
     Parenthesis(&'static str, Box<SyntaxNode>),
     List(&'static str, Box<SyntaxNode>),
@@ -51,6 +93,31 @@ pub enum SyntaxNode {
     Block(Box<SyntaxNode>, Box<SyntaxNode>),
     IfChain(Vec<SyntaxNode>),
 }
+/// Positions are not hashed, so code hashes the same wherever it is in a file
+impl std::hash::Hash for SyntaxNode {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Unclassified(token) | SyntaxNode::Str(token) => token.s.hash(state),
+            SyntaxNode::Number(token) => token.s.to_bits().hash(state),
+            SyntaxNode::Adjacent(nodes) | SyntaxNode::IfChain(nodes) => nodes.hash(state),
+            Parenthesis(op, n) | SyntaxNode::List(op, n) | SyntaxNode::Unop(op, n) => {
+                op.hash(state);
+                n.hash(state);
+            },
+            SyntaxNode::Binop(op, n1, n2) => {
+                op.hash(state);
+                n1.hash(state);
+                n2.hash(state);
+            },
+            SyntaxNode::Block(n1, n2) => {
+                n1.hash(state);
+                n2.hash(state);
+            },
+        }
+    }
+}
+
 impl SyntaxNode {
     fn reduce(&mut self) -> Result<(), String> {
         self.strip_comment()?;
@@ -62,18 +129,19 @@ impl SyntaxNode {
         self.reduce_parens("[", "]")?;
         self.reduce_list(",")?;
         self.reduce_total_binop(&["*=", "/=", "+=", "-="])?;
-        self.reduce_binop(&["==", "!="])?;
         self.reduce_total_binop(&["="])?;
+        // Tightest binding first
         self.reduce_unop(&["!", "-"])?;
-        self.reduce_binop(&["&&"])?;
-        self.reduce_binop(&["||"])?;
         self.reduce_binop(&["**"])?;
         self.reduce_binop(&["*", "/"])?;
         self.reduce_binop(&["+", "-"])?;
-        self.reduce_number();
+        self.reduce_binop(&["==", "!=", "<", ">", "<=", ">="])?;
+        self.reduce_binop(&["&&"])?;
+        self.reduce_binop(&["^"])?;
+        self.reduce_binop(&["||"])?;
+        self.reduce_literals();
         self.reduce_singletons();
         self.reduce_ifs()?;
-        dbg!(&self);
         Ok(())
     }
 
@@ -244,27 +312,32 @@ impl SyntaxNode {
     fn reduce_list(&mut self, symbol: &'static str) -> Result<(), String> {
         match self {
             SyntaxNode::Adjacent(nodes) => {
-                let mut start_index = 0;
-                let mut i = 0;
-                while i < nodes.len() {
-                    match &nodes[i] {
-                        SyntaxNode::Unclassified(t) => if t == symbol {
-                            let line = SyntaxNode::Adjacent(nodes[start_index..i].to_vec());
-                            nodes.splice(start_index..=i, [line]);
-                            start_index += 1;
-                            i = start_index;
-                        },
-                        _ => i += 1
-                    };
+                for node in nodes {
+                    node.reduce_list(symbol)?;
                 }
             }
             SyntaxNode::Parenthesis(_, n) => {
                 n.reduce_list(symbol)?;
+                // Split the contents at each separator
+                if let SyntaxNode::Adjacent(nodes) = &mut **n
+                    && nodes.iter().any(|n| matches!(n, Unclassified(t) if t == symbol)) {
+                    let mut items = Vec::new();
+                    let mut item = Vec::new();
+                    for node in nodes.drain(..) {
+                        match &node {
+                            Unclassified(t) if t == symbol => items.push(SyntaxNode::Adjacent(std::mem::take(&mut item))),
+                            _ => item.push(node),
+                        }
+                    }
+                    items.push(SyntaxNode::Adjacent(item));
+                    **n = SyntaxNode::List(symbol, Box::new(SyntaxNode::Adjacent(items)));
+                }
             },
             SyntaxNode::Block(n1, n2) => {
                 n1.reduce_list(symbol)?;
                 n2.reduce_list(symbol)?;
             },
+            SyntaxNode::Unclassified(_) => (),
             _ => unreachable!()
         };
         Ok(())
@@ -284,7 +357,8 @@ impl SyntaxNode {
                                 } else if i == nodes.len()-1 {
                                     return t.raise("Binary operation encountered with no right side");
                                 }
-                                let suffix = SyntaxNode::Adjacent(nodes.drain(i+1..).collect::<Vec<_>>());
+                                let mut suffix = SyntaxNode::Adjacent(nodes.drain(i+1..).collect::<Vec<_>>());
+                                suffix.reduce_total_binop(symbols)?;
                                 let prefix = SyntaxNode::Adjacent(nodes.drain(..i).collect::<Vec<_>>());
                                 let new_node = SyntaxNode::Binop(op, Box::new(prefix), Box::new(suffix));
                                 nodes[0] = new_node;
@@ -314,31 +388,30 @@ impl SyntaxNode {
     fn reduce_binop(&mut self, symbols: &[&'static str]) -> Result<(), String> {
         match self {
             SyntaxNode::Adjacent(nodes) => {
+                // Operands are reduced before they are grouped
+                for node in nodes.iter_mut() {
+                    if !matches!(node, Unclassified(_)) {
+                        node.reduce_binop(symbols)?;
+                    }
+                }
                 let mut i = 0;
                 while i < nodes.len() {
-                    if let Unclassified(t) = &nodes[i] {
-                        match symbols.iter().position(|x| t == *x) {
-                            Some(index) => {
-                                let op = symbols[index];
-                                if i == 0 {
-                                    return t.raise("Binary operation encountered with no left side");
-                                } else if i == nodes.len()-1 {
-                                    return t.raise("Binary operation encountered with no right side");
-                                }
-                                let new_node = SyntaxNode::Binop(op, Box::new(nodes[i-1].clone()), Box::new(nodes[i+1].clone()));
-                                nodes.drain(i..=i+1);
-                                nodes[i-1] = new_node;
-                                continue;
-                            },
-                            None => (),
+                    if let Unclassified(t) = &nodes[i]
+                        && let Some(op) = symbols.iter().find(|x| t == **x) {
+                        if i == 0 {
+                            return t.raise("Binary operation encountered with no left side");
+                        } else if i == nodes.len()-1 {
+                            return t.raise("Binary operation encountered with no right side");
                         }
-                    } else {
-                        nodes[i].reduce_binop(symbols)?;
+                        let new_node = SyntaxNode::Binop(op, Box::new(nodes[i-1].clone()), Box::new(nodes[i+1].clone()));
+                        nodes.drain(i..=i+1);
+                        nodes[i-1] = new_node;
+                        continue;
                     }
                     i += 1;
                 }
             }
-            SyntaxNode::Parenthesis(_, n) | SyntaxNode::List(_, n) => {
+            SyntaxNode::Parenthesis(_, n) | SyntaxNode::List(_, n) | SyntaxNode::Unop(_, n) => {
                 n.reduce_binop(symbols)?;
             },
             SyntaxNode::Binop(_, n1, n2) | SyntaxNode::Block(n1, n2) => {
@@ -354,26 +427,28 @@ impl SyntaxNode {
     fn reduce_unop(&mut self, symbols: &[&'static str]) -> Result<(), String> {
         match self {
             SyntaxNode::Adjacent(nodes) => {
-                let mut i = 0;
-                while i < nodes.len() {
-                    if let Unclassified(t) = &nodes[i] {
-                        match symbols.iter().position(|x| t == *x) {
-                            Some(index) => {
-                                let op = symbols[index];
-                                if i == 0 {
-                                    return t.raise("Unary operation encountered with no left side");
-                                }
-                                let new_node = SyntaxNode::Unop(op, Box::new(nodes[i-1].clone()));
-                                nodes.remove(i);
-                                nodes[i-1] = new_node;
-                                continue;
-                            },
-                            None => (),
-                        }
-                    } else {
-                        nodes[i].reduce_unop(symbols)?;
+                // Right to left, so that repeated operators nest
+                let mut i = nodes.len();
+                while i > 0 {
+                    i -= 1;
+                    let op = match &nodes[i] {
+                        Unclassified(t) => match symbols.iter().find(|x| t == **x) {
+                            Some(op) => *op,
+                            None => continue,
+                        },
+                        _ => {
+                            nodes[i].reduce_unop(symbols)?;
+                            continue;
+                        },
+                    };
+                    // An operator following an operand is binary
+                    let prefix = i == 0 || matches!(&nodes[i-1], Unclassified(t) if PREFIX_CONTEXT.iter().any(|x| t == *x));
+                    if !prefix { continue; }
+                    if i == nodes.len()-1 {
+                        return nodes[i].raise("Unary operation encountered with no right side");
                     }
-                    i += 1;
+                    let operand = nodes.remove(i+1);
+                    nodes[i] = SyntaxNode::Unop(op, Box::new(operand));
                 }
             }
             SyntaxNode::Parenthesis(_, n) | SyntaxNode::List(_, n) | SyntaxNode::Unop(_, n) => {
@@ -389,22 +464,31 @@ impl SyntaxNode {
         Ok(())
     }
 
-    fn reduce_number(&mut self) {
+    /// Classify numbers and strings
+    fn reduce_literals(&mut self) {
         match self {
             SyntaxNode::Adjacent(nodes) => {
                 for node in nodes {
-                    node.reduce_number();
+                    node.reduce_literals();
                 }
             }
             SyntaxNode::Parenthesis(_, n) | SyntaxNode::List(_, n) | SyntaxNode::Unop(_, n) => {
-                n.reduce_number();
+                n.reduce_literals();
             },
             SyntaxNode::Binop(_, n1, n2) | SyntaxNode::Block(n1, n2) => {
-                n1.reduce_number();
-                n2.reduce_number();
+                n1.reduce_literals();
+                n2.reduce_literals();
             },
             SyntaxNode::Unclassified(v) => {
-                if let Ok(n) = v.s.parse::<f64>() {
+                if v.s.len() >= 2 && v.s.starts_with(['"', '\'']) {
+                    let new_token = Token {
+                        s: v.s[1..v.s.len()-1].to_owned(),
+                        filename: v.filename.clone(),
+                        line_no: v.line_no,
+                        col_no: v.col_no,
+                    };
+                    *self = SyntaxNode::Str(new_token);
+                } else if let Ok(n) = v.s.parse::<f64>() {
                     let new_token = Token {
                         s: n,
                         filename: v.filename.clone(),
@@ -414,7 +498,7 @@ impl SyntaxNode {
                     *self = SyntaxNode::Number(new_token);
                 }
             },
-            SyntaxNode::Number(_) => (),
+            SyntaxNode::Number(_) | SyntaxNode::Str(_) => (),
             _ => unreachable!(),
         };
     }
@@ -438,7 +522,7 @@ impl SyntaxNode {
                 n1.reduce_singletons();
                 n2.reduce_singletons();
             },
-            SyntaxNode::Unclassified(_) | SyntaxNode::Number(_) => (),
+            SyntaxNode::Unclassified(_) | SyntaxNode::Number(_) | SyntaxNode::Str(_) => (),
             _ => unimplemented!()
         };
     }
@@ -446,68 +530,61 @@ impl SyntaxNode {
     fn reduce_ifs(&mut self) -> Result<(), String> {
         match self {
             SyntaxNode::Adjacent(nodes) => {
-                let mut start_index = None;
-                let mut i = 0;
-                while i < nodes.len() {
-                    let end_i = match &nodes[i] {
-                        SyntaxNode::Block(predicate, _) => {
-                            match &**predicate {
-                                SyntaxNode::Adjacent(n) => {
-                                    let first = n.first().ok_or(nodes[i].raise_str("You cannot have empty braces"))?;
-                                    match first {
-                                        Unclassified(t) => {
-                                            if t == "if" {
-                                                // It's an if statement
-                                                match start_index {
-                                                    None => { start_index = Some(i); None },
-                                                    Some(i) => Some(i-1)
-                                                }
-                                            } else if t == "else" {
-                                                // It's an else if statement
-                                                if let None = start_index { return first.raise("`else if` must follow an `if` statement"); }
-                                                None
-                                            } else {
-                                                // It's something else
-                                                Some(i-1)
-                                            }
-                                        },
-                                        _ => { return first.raise("Code blocks must start with a keyword"); }
-                                    }
-                                },
-                                SyntaxNode::Unclassified(t) => {
-                                    if t == "else" {
-                                        // It's an else statement
-                                        if let None = start_index { return nodes[i].raise("`else` must follow an `if` statement"); }
-                                        Some(i)
-                                    } else {
-                                        // It's something else
-                                        Some(i-1)
-                                    }
-                                },
-                                _ => { return nodes[i].raise("Invalid syntax"); }
-                            }
+                for node in nodes.iter_mut() {
+                    match node {
+                        // Blocks are grouped below, so only their bodies are reduced here
+                        SyntaxNode::Block(_, body) => body.reduce_ifs()?,
+                        _ => node.reduce_ifs()?,
+                    }
+                }
+                let mut roles = Vec::with_capacity(nodes.len());
+                for node in nodes.iter() {
+                    roles.push(node.if_role()?);
+                }
+
+                // Group each if statement with the else ifs and else that follow it
+                let mut output = Vec::with_capacity(nodes.len());
+                let mut chain = Vec::new();
+                for (node, role) in nodes.drain(..).zip(roles) {
+                    match role {
+                        IfRole::If => {
+                            if !chain.is_empty() { output.push(SyntaxNode::IfChain(std::mem::take(&mut chain))); }
+                            chain.push(node);
                         },
-                        _ => Some(i-1)
-                    };
-                    match (end_i, start_index) {
-                        (Some(j), Some(start)) => {
-                            let if_nodes = nodes[start..=j].to_vec();
-                            nodes.splice(start..=j, [SyntaxNode::IfChain(if_nodes)]);
-                            i = j + 1;
-                            start_index = None;
-                        }
-                        _ => ()
-                    };
-                    i += 1;
+                        IfRole::ElseIf => {
+                            if chain.is_empty() { return node.raise("`else if` must follow an `if` statement"); }
+                            chain.push(node);
+                        },
+                        IfRole::Else => {
+                            if chain.is_empty() { return node.raise("`else` must follow an `if` statement"); }
+                            chain.push(node);
+                            output.push(SyntaxNode::IfChain(std::mem::take(&mut chain)));
+                        },
+                        IfRole::Other => {
+                            if !chain.is_empty() { output.push(SyntaxNode::IfChain(std::mem::take(&mut chain))); }
+                            output.push(node);
+                        },
+                    }
                 }
+                if !chain.is_empty() { output.push(SyntaxNode::IfChain(chain)); }
+                *nodes = output;
             },
             Unclassified(_) => (),
-            SyntaxNode::Number(_) => (),
+            SyntaxNode::Number(_) | SyntaxNode::Str(_) => (),
             Parenthesis(_, syntax_node) => syntax_node.reduce_ifs()?,
             SyntaxNode::List(_, _) => (),
             SyntaxNode::Binop(_, _, _) => (),
             SyntaxNode::Unop(_, _) => (),
-            SyntaxNode::Block(_, syntax_node1) => syntax_node1.reduce_ifs()?,
+            SyntaxNode::Block(_, syntax_node1) => {
+                syntax_node1.reduce_ifs()?;
+                // A lone if statement is not wrapped in a list
+                match self.if_role()? {
+                    IfRole::If => *self = SyntaxNode::IfChain(vec![self.clone()]),
+                    IfRole::ElseIf => return self.raise("`else if` must follow an `if` statement"),
+                    IfRole::Else => return self.raise("`else` must follow an `if` statement"),
+                    IfRole::Other => (),
+                }
+            },
             SyntaxNode::IfChain(nodes) => {
                 for node in nodes {
                     node.reduce_ifs()?
@@ -517,10 +594,36 @@ impl SyntaxNode {
         Ok(())
     }
 
+    /// Get the role this node plays in an if chain
+    fn if_role(&self) -> Result<IfRole, String> {
+        let predicate = match self {
+            SyntaxNode::Block(predicate, _) => predicate,
+            _ => return Ok(IfRole::Other),
+        };
+        match &**predicate {
+            SyntaxNode::Adjacent(n) => {
+                let first = n.first().ok_or(self.raise_str("You cannot have empty braces"))?;
+                match first {
+                    Unclassified(t) => Ok(if t == "if" {
+                        IfRole::If
+                    } else if t == "else" {
+                        IfRole::ElseIf
+                    } else {
+                        IfRole::Other
+                    }),
+                    _ => first.raise("Code blocks must start with a keyword"),
+                }
+            },
+            SyntaxNode::Unclassified(t) => Ok(if t == "else" { IfRole::Else } else { IfRole::Other }),
+            _ => self.raise("Invalid syntax"),
+        }
+    }
+
     fn internal_raise<T>(&self, message: &str) -> Option<Result<T, String>> {
         match self {
             Unclassified(token) => Some(token.raise(message)),
             SyntaxNode::Number(token) => Some(token.raise(message)),
+            SyntaxNode::Str(token) => Some(token.raise(message)),
             SyntaxNode::Adjacent(syntax_nodes) => match syntax_nodes.first() {
                 Some(n) => n.internal_raise(message),
                 None => None,
@@ -539,6 +642,43 @@ impl SyntaxNode {
         }
     }
 
+    /// Position of the first token in the node
+    pub(crate) fn span(&self) -> Option<Span> {
+        match self {
+            Unclassified(token) => Some(token.span()),
+            SyntaxNode::Number(token) => Some(token.span()),
+            SyntaxNode::Str(token) => Some(token.span()),
+            SyntaxNode::Adjacent(syntax_nodes) | SyntaxNode::IfChain(syntax_nodes) => syntax_nodes.first()?.span(),
+            SyntaxNode::Parenthesis(_, n) | SyntaxNode::List(_, n) | SyntaxNode::Unop(_, n) => n.span(),
+            SyntaxNode::Binop(_, n1, n2) | SyntaxNode::Block(n1, n2) => n1.span().or_else(|| n2.span()),
+        }
+    }
+
+    /// Write the tree as indented text, one node per line
+    pub(crate) fn dump(&self, depth: usize, out: &mut String) {
+        let indent = "  ".repeat(depth);
+        let position = match self.span() {
+            Some(span) => format!("{}:{}", span.line_no+1, span.col_no+1),
+            None => "-".to_owned(),
+        };
+        let (label, children): (String, Vec<&SyntaxNode>) = match self {
+            Unclassified(token) => (format!("Word {:?}", token.s), vec![]),
+            SyntaxNode::Number(token) => (format!("Number {}", token.s), vec![]),
+            SyntaxNode::Str(token) => (format!("String {:?}", token.s), vec![]),
+            SyntaxNode::Adjacent(nodes) => ("Adjacent".to_owned(), nodes.iter().collect()),
+            SyntaxNode::IfChain(nodes) => ("IfChain".to_owned(), nodes.iter().collect()),
+            Parenthesis(op, n) => (format!("Parenthesis {}", op), vec![n]),
+            SyntaxNode::List(op, n) => (format!("List {}", op), vec![n]),
+            SyntaxNode::Binop(op, n1, n2) => (format!("Binop {}", op), vec![n1, n2]),
+            SyntaxNode::Unop(op, n) => (format!("Unop {}", op), vec![n]),
+            SyntaxNode::Block(n1, n2) => ("Block".to_owned(), vec![n1, n2]),
+        };
+        out.push_str(&format!("{}{} @ {}\n", indent, label, position));
+        for child in children {
+            child.dump(depth + 1, out);
+        }
+    }
+
     pub fn raise<T>(&self, message: &str) -> Result<T, String> {
         match self.internal_raise(message) {
             Some(r) => r,
@@ -562,6 +702,7 @@ impl std::fmt::Debug for SyntaxNode {
         match self {
             Self::Unclassified(arg0) => write!(f, "`{:?}`", arg0),
             Self::Number(arg0) => write!(f, "Number({:?})", arg0),
+            Self::Str(arg0) => write!(f, "Str({:?})", arg0),
             Self::Adjacent(arg0) => write!(f, "{:?}", arg0),
             Self::IfChain(arg0) => write!(f, "{:?}", arg0),
             Self::Parenthesis(op, arg1) => write!(f, "Parens `{}` ({:?})", op, arg1),
@@ -573,6 +714,15 @@ impl std::fmt::Debug for SyntaxNode {
     }
 }
 
+/// Write tokens as text, one per line with its position
+pub(crate) fn dump_tokens(tokens: &[Token<String>]) -> String {
+    let mut out = String::new();
+    for token in tokens {
+        out.push_str(&format!("{}:{}:{} {:?}\n", token.filename, token.line_no+1, token.col_no+1, token.s));
+    }
+    out
+}
+
 /// Load file, handling import statements
 pub fn load_file(filename: &str) -> Result<Vec<Token<String>>, String> {
     let mut file = File::open(filename).map_err(|_| format!("Could not find file {}", filename))?;
@@ -597,11 +747,44 @@ fn get_stream(text: &str, filename: &str) -> Result<Vec<Token<String>>, String>
     let mut token_is_special = false;
     let mut line_no = 0;
     let mut col_no = 0;
+    // Quote character and contents of the string literal being read, and its position
+    let mut string: Option<(char, String, u32, u32)> = None;
+    let mut escaped = false;
     for c in text.chars() {
         if !c.is_ascii(){
             return Err(format!("{}:{}:{}\nNon-ascii characters are not allowed", filename, line_no, col_no));
         }
 
+        if let Some((quote, contents, start_line, start_col)) = &mut string {
+            col_no += 1;
+            if c == '\n' {
+                return Err(format!("{}:{}:{}\nUnterminated string", filename, *start_line+1, *start_col+1));
+            }
+            if escaped {
+                contents.push(match c {
+                    'n' => '\n',
+                    't' => '\t',
+                    '\\' | '"' | '\'' => c,
+                    _ => return Err(format!("{}:{}:{}\nUnknown escape sequence \\{}", filename, line_no+1, col_no, c)),
+                });
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == *quote {
+                // Strings are kept in one token, quotes included, with their escapes replaced
+                tokens.push(Token {
+                    s: format!("{}{}{}", quote, contents, quote),
+                    filename: filename.to_owned(),
+                    line_no: *start_line,
+                    col_no: *start_col,
+                });
+                string = None;
+            } else {
+                contents.push(c);
+            }
+            continue;
+        }
+
         let c_is_special = specials.contains(&c);
         let c_is_singleton = singletons.contains(&c);
         // Push the existing token if necessary
@@ -623,7 +806,9 @@ fn get_stream(text: &str, filename: &str) -> Result<Vec<Token<String>>, String>
                 });
             }
         }
-        if c != ' ' && c != '\t' {
+        if c == '"' || c == '\'' {
+            string = Some((c, String::new(), line_no, col_no));
+        } else if c != ' ' && c != '\t' {
             if c_is_singleton {
                 tokens.push(Token {
                     s: format!("{}", c),
@@ -644,6 +829,9 @@ fn get_stream(text: &str, filename: &str) -> Result<Vec<Token<String>>, String>
             col_no = 0;
         }
     }
+    if let Some((_, _, start_line, start_col)) = string {
+        return Err(format!("{}:{}:{}\nUnterminated string", filename, start_line+1, start_col+1));
+    }
     if !token.is_empty() {
         // Push the token immediately
         tokens.push(Token {
@@ -678,11 +866,9 @@ fn reduce_pragmas(tokens: &mut Vec<Token<String>>) -> Result<(), String> {
                             _ => tokens[i].raise(&format!("Could not find library {}", lib_name)),
                         }
                     },
-                    "\"" => {
+                    path if path.len() >= 2 && path.starts_with(['"', '\'']) => {
                         // File import
-                        let mut end_index = i+3;
-                        while &tokens[end_index] != "\"" { end_index += 1; }
-                        let lib_name: String = tokens[i+3..end_index].iter().map(|t| &t.s as &str).collect();
+                        let lib_name = path[1..path.len()-1].to_owned();
 
                         // Load the file
                         let mut file = File::open(&lib_name).map_err(|_| format!("Could not find file {}", lib_name))?;
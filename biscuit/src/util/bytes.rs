@@ -0,0 +1,79 @@
+// Little endian encoding shared by the binary formats: object files, machine states and the game's saves
+
+pub fn write_len(out: &mut Vec<u8>, len: usize) {
+    out.extend((len as u32).to_le_bytes());
+}
+
+pub fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_len(out, s.len());
+    out.extend(s.as_bytes());
+}
+
+pub fn write_f64(out: &mut Vec<u8>, value: f64) {
+    out.extend(value.to_le_bytes());
+}
+
+/// Write a length followed by the values
+pub fn write_f64s(out: &mut Vec<u8>, values: &[f64]) {
+    write_len(out, values.len());
+    for value in values {
+        write_f64(out, *value);
+    }
+}
+
+/// Reads what the `write_` functions wrote. Every read returns None once the bytes run out.
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// Whether every byte has been read
+    pub fn is_finished(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    pub fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let slice = self.bytes.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(slice)
+    }
+
+    /// Everything not read yet
+    pub fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.bytes[self.pos..];
+        self.pos = self.bytes.len();
+        rest
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    /// Read a length or address written by `write_len`
+    pub fn length(&mut self) -> Option<usize> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?) as usize)
+    }
+
+    pub fn str(&mut self) -> Option<String> {
+        let len = self.length()?;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    pub fn f64(&mut self) -> Option<f64> {
+        Some(f64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    pub fn f64s(&mut self) -> Option<Vec<f64>> {
+        let len = self.length()?;
+        // Each value takes 8 bytes, so a corrupt length cannot allocate more than is there
+        if len > (self.bytes.len() - self.pos) / 8 {
+            return None;
+        }
+        (0..len).map(|_| self.f64()).collect()
+    }
+}
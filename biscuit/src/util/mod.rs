@@ -1,2 +1,3 @@
 pub mod vendor;
-pub use vendor::{Vendor, Tagged};
\ No newline at end of file
+pub mod bytes;
+pub use vendor::{Vendor, Tagged};
@@ -0,0 +1,77 @@
+//! Compiling with a `CompileCache`, which must give the same binaries as compiling from scratch.
+
+use biscuit::{CompileCache, compile_str};
+
+const LIBRARY: &str = "struct Vec3 { x, y, z }
+
+fn scale(v: Vec3, k) {
+    dbg(v.x * k, v.y * k, v.z * k);
+}
+
+fn each(v: Vec3, f: fn(axis, value)) {
+    f(0, v.x);
+    f(1, v.y);
+    f(2, v.z);
+}
+
+fn report(axis, value) {
+    print(\"{}: {}\", axis, value);
+}
+";
+
+fn script(main: &str) -> String {
+    format!("{}\nfn main() {{\n{}\n}}\n", LIBRARY, main)
+}
+
+/// Compile with the cache, checking the binary against a full compilation. Returns the hits and misses.
+fn compile(cache: &mut CompileCache, source: &str) -> (u64, u64) {
+    let (hits, misses) = (cache.hits(), cache.misses());
+    let cached = cache.compile_str(source, "test").unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(cached, compile_str(source, "test").unwrap());
+    (cache.hits() - hits, cache.misses() - misses)
+}
+
+#[test]
+fn unchanged_functions_are_reused() {
+    let mut cache = CompileCache::new();
+    let source = script("    v = Vec3(1, 2, 3);\n    scale(v, 2);\n    each(v, report);");
+    assert_eq!(compile(&mut cache, &source), (0, 4));
+    assert_eq!(compile(&mut cache, &source), (4, 0));
+
+    // Only the edited function is compiled again
+    let edited = source.replace("scale(v, 2)", "scale(v, 3)");
+    assert_eq!(compile(&mut cache, &edited), (3, 1));
+    let edited = edited.replace("v.y * k", "v.y * k * 2");
+    assert_eq!(compile(&mut cache, &edited), (3, 1));
+}
+
+#[test]
+fn libraries_are_shared_between_scripts() {
+    let mut cache = CompileCache::new();
+    compile(&mut cache, &script("    v = Vec3(1, 2, 3);\n    each(v, report);"));
+    // Moving functions down a line does not change them
+    let other = script("    v = Vec3(0, 0, 1);\n    scale(v, 5);").replace("fn scale", "\nfn scale");
+    assert_eq!(compile(&mut cache, &other), (3, 1));
+}
+
+#[test]
+fn changed_declarations_are_compiled_again() {
+    let mut cache = CompileCache::new();
+    let source = script("    v = Vec3(1, 2, 3);\n    scale(v, 2);\n    each(v, report);");
+    compile(&mut cache, &source);
+    // Reordering the fields changes what `v.x` loads, in every function using the record
+    let reordered = source.replace("struct Vec3 { x, y, z }", "struct Vec3 { z, y, x }");
+    assert_eq!(compile(&mut cache, &reordered), (1, 3));
+    // Functions calling one whose arguments change are checked again
+    let changed = source.replace("fn report(axis, value)", "fn report(axis, values[])").replace("print(\"{}: {}\", axis, value);", "dbg(axis);");
+    assert!(cache.compile_str(&changed, "test").unwrap_err().contains("Argument 2 of each should be a fn(float, float), but is a fn(float, list)"));
+}
+
+#[test]
+fn errors_are_not_cached() {
+    let mut cache = CompileCache::new();
+    let source = script("    scale(1, 2);");
+    let first = cache.compile_str(&source, "test").unwrap_err();
+    assert_eq!(first, cache.compile_str(&source, "test").unwrap_err());
+    assert_eq!(first, compile_str(&source, "test").unwrap_err());
+}
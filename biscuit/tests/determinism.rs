@@ -0,0 +1,64 @@
+//! Binaries are saved and compared between players, so a source must always compile to the same bytes: in any
+//! run, on any platform, and whether or not the cache or linker is involved.
+
+use biscuit::{CompileCache, compile_object_str, compile_str, link};
+
+/// FNV-1a, which unlike the standard hasher is fixed across Rust versions and platforms
+fn fingerprint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Each sample script, with the fingerprint of its binary. A change to the compiler which changes these must be
+/// deliberate, as it changes saved binaries.
+const EXPECTED: &[(&str, u64)] = &[
+    ("addition.bisc", 0xa58b7f6ce8d8a9b1),
+    ("branch.bisc", 0xf47d20ef24a6f5d9),
+    ("loop.bisc", 0x75e9de034da2c449),
+    ("interrupts.bisc", 0xe0272f95380571f4),
+    ("strings.bisc", 0x95d8bc9e9d36a094),
+    ("records.bisc", 0x168204a1c06327a3),
+    ("functions.bisc", 0x4b78bb78e746773d),
+];
+
+fn read(name: &str) -> String {
+    std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/").to_owned() + name).unwrap()
+}
+
+#[test]
+fn binaries_are_fixed() {
+    let mut mismatches = Vec::new();
+    for (name, expected) in EXPECTED {
+        let actual = fingerprint(&compile_str(&read(name), name).unwrap());
+        if actual != *expected {
+            mismatches.push(format!("(\"{}\", {:#018x}),", name, actual));
+        }
+    }
+    assert!(mismatches.is_empty(), "Binaries changed:\n{}", mismatches.join("\n"));
+}
+
+#[test]
+fn repeated_compilation() {
+    for (name, _) in EXPECTED {
+        let source = read(name);
+        let first = compile_str(&source, name).unwrap();
+        let mut cache = CompileCache::new();
+        for _ in 0..20 {
+            assert_eq!(compile_str(&source, name).unwrap(), first, "{}", name);
+            assert_eq!(cache.compile_str(&source, name).unwrap(), first, "{}", name);
+        }
+        let object = compile_object_str(&source, name).unwrap();
+        assert_eq!(link(std::slice::from_ref(&object)).unwrap(), first, "{}", name);
+        assert_eq!(compile_object_str(&source, name).unwrap().to_bytes(), object.to_bytes(), "{}", name);
+    }
+}
+
+#[test]
+fn errors_are_repeatable() {
+    // Several functions have errors, and the same one must always be reported
+    let source = "fn b() {\n    x = [1];\n    dbg(x + 1);\n}\nfn a() {\n    y = [2];\n    dbg(y * 2);\n}\nfn main() {\n    a();\n    b();\n}\n";
+    let first = compile_str(source, "test").unwrap_err();
+    assert!(first.starts_with("test:6:"), "{}", first);
+    for _ in 0..20 {
+        assert_eq!(compile_str(source, "test").unwrap_err(), first);
+    }
+}
@@ -0,0 +1,252 @@
+//! Generates random well-typed Biscuit programs, and checks that the compiled bytecode run on `Machine` makes the
+//! same host calls as the `Interpreter`.
+
+use biscuit::{Executor, GlobalFunction, InterruptKind, Interpreter, Machine, MachineOutput, compile_str, machine::InstructionData, util::Vendor};
+
+const PROGRAMS: u64 = 500;
+const MAX_CALLS: usize = 40;
+const MAX_INSTRUCTIONS: u64 = 1_000_000;
+/// Raised in order whenever the code is waiting
+const INTERRUPTS: &[(InterruptKind, f64)] = &[(InterruptKind::Interact, 0.), (InterruptKind::Forward, 1.5), (InterruptKind::Forward, -2.)];
+
+/// A small xorshift generator, so that failures can be reproduced from the seed
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9E3779B97F4A7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+}
+
+struct Generator {
+    rng: Rng,
+    out: String,
+    /// Functions which may be called, with their number of arguments
+    functions: Vec<(String, usize)>,
+    /// Variables in scope
+    variables: Vec<String>,
+    next_variable: usize,
+    /// Whether loops may be written here
+    loops: bool,
+}
+
+const BINARY: &[&str] = &["+", "-", "*", "/", "**", "<", ">", "<=", ">=", "==", "!=", "&&", "||", "^"];
+
+impl Generator {
+    fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            out: String::new(),
+            functions: Vec::new(),
+            variables: Vec::new(),
+            next_variable: 0,
+            loops: false,
+        }
+    }
+
+    fn program(mut self) -> String {
+        for i in 0..self.rng.below(3) {
+            let name = format!("f{}", i);
+            let arguments: Vec<String> = (0..self.rng.below(4)).map(|j| format!("a{}", j)).collect();
+            self.out += &format!("fn {}({}) {{\n", name, arguments.join(", "));
+            self.variables = arguments.clone();
+            self.block(1, 2);
+            self.out += "}\n\n";
+            // Functions may only call those defined before them
+            self.functions.push((name, arguments.len()));
+        }
+
+        self.out += "fn main() {\n";
+        self.variables.clear();
+        self.loops = true;
+        self.block(1, 3);
+        self.out += "}\n";
+
+        self.loops = false;
+        if self.rng.chance(50) {
+            self.out += "\non interact {\n";
+            self.variables.clear();
+            self.block(1, 2);
+            self.out += "}\n";
+        }
+        if self.rng.chance(50) {
+            self.out += "\non forward(throttle) {\n";
+            self.variables = vec!["throttle".to_owned()];
+            self.block(1, 2);
+            self.out += "}\n";
+        }
+        self.out
+    }
+
+    fn line(&mut self, depth: usize, text: &str) {
+        self.out += &"    ".repeat(depth);
+        self.out += text;
+        self.out += "\n";
+    }
+
+    fn block(&mut self, depth: usize, nesting: usize) {
+        for _ in 0..1 + self.rng.below(5) {
+            self.statement(depth, nesting);
+        }
+    }
+
+    /// Write a nested block. Variables declared inside it go out of scope at its end.
+    fn nested(&mut self, depth: usize, nesting: usize) {
+        let variables = self.variables.clone();
+        self.block(depth, nesting);
+        self.variables = variables;
+    }
+
+    fn statement(&mut self, depth: usize, nesting: usize) {
+        match self.rng.below(10) {
+            0..=2 => {
+                let value = self.expression(3);
+                let name = match self.variables.is_empty() || self.rng.chance(40) {
+                    true => {
+                        self.next_variable += 1;
+                        let name = format!("v{}", self.next_variable);
+                        self.variables.push(name.clone());
+                        name
+                    },
+                    false => self.variables[self.rng.below(self.variables.len())].clone(),
+                };
+                self.line(depth, &format!("{} = {};", name, value));
+            },
+            3 if !self.variables.is_empty() => {
+                let name = self.variables[self.rng.below(self.variables.len())].clone();
+                let op = ["+=", "-=", "*=", "/="][self.rng.below(4)];
+                let value = self.expression(2);
+                self.line(depth, &format!("{} {} {};", name, op, value));
+            },
+            4 | 5 => {
+                let arguments: Vec<String> = (0..1 + self.rng.below(3)).map(|_| self.expression(2)).collect();
+                self.line(depth, &format!("dbg({});", arguments.join(", ")));
+            },
+            6 if !self.functions.is_empty() => {
+                let (name, count) = self.functions[self.rng.below(self.functions.len())].clone();
+                let arguments: Vec<String> = (0..count).map(|_| self.expression(2)).collect();
+                self.line(depth, &format!("{}({});", name, arguments.join(", ")));
+            },
+            7 | 8 if nesting > 0 => {
+                let condition = self.expression(3);
+                self.line(depth, &format!("if {} {{", condition));
+                self.nested(depth + 1, nesting - 1);
+                while self.rng.chance(30) {
+                    let condition = self.expression(2);
+                    self.line(depth, &format!("}} else if {} {{", condition));
+                    self.nested(depth + 1, nesting - 1);
+                }
+                if self.rng.chance(50) {
+                    self.line(depth, "} else {");
+                    self.nested(depth + 1, nesting - 1);
+                }
+                self.line(depth, "}");
+            },
+            9 if nesting > 0 && self.loops => {
+                // Loops never finish, so every iteration makes a call to bound the comparison
+                self.line(depth, "loop {");
+                self.nested(depth + 1, nesting - 1);
+                self.line(depth + 1, "tick();");
+                self.line(depth, "}");
+            },
+            _ => {
+                let value = self.expression(1);
+                self.line(depth, &format!("dbg({});", value));
+            },
+        }
+    }
+
+    fn expression(&mut self, depth: usize) -> String {
+        if depth == 0 || self.rng.chance(30) {
+            return match self.variables.is_empty() || self.rng.chance(40) {
+                true => format!("{}", self.rng.below(9) as f64 / 2.),
+                false => self.variables[self.rng.below(self.variables.len())].clone(),
+            };
+        }
+        match self.rng.below(8) {
+            // Runs of symbols are read as one token, so prefix operators are spaced
+            0 => format!("- {}", self.expression(depth - 1)),
+            1 => format!("! {}", self.expression(depth - 1)),
+            2 => format!("({})", self.expression(depth - 1)),
+            _ => {
+                let op = BINARY[self.rng.below(BINARY.len())];
+                format!("{} {} {}", self.expression(depth - 1), op, self.expression(depth - 1))
+            },
+        }
+    }
+}
+
+/// Run code until it makes `max_calls` host calls, or waits with no interrupts left to raise
+fn run(executor: &mut dyn Executor, max_calls: usize) -> Result<Vec<(GlobalFunction, Vec<f64>)>, String> {
+    let mut calls = Vec::new();
+    let mut interrupts = INTERRUPTS.iter();
+    while calls.len() < max_calls {
+        if executor.is_waiting() {
+            match interrupts.next() {
+                Some((kind, arg)) => executor.interrupt(*kind, *arg),
+                None => break,
+            }
+        }
+        if executor.instructions_executed() > MAX_INSTRUCTIONS {
+            return Err("Ran too long".to_owned());
+        }
+        match executor.run_for(1000).map_err(|e| format!("{:?}", e))? {
+            MachineOutput::Call { func, args } => calls.push((func, args.to_vec())),
+            MachineOutput::None => (),
+        }
+    }
+    Ok(calls)
+}
+
+/// Compare calls, treating NaNs as equal
+fn same_calls(a: &[(GlobalFunction, Vec<f64>)], b: &[(GlobalFunction, Vec<f64>)]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|((f, x), (g, y))| {
+        f == g && x.len() == y.len() && x.iter().zip(y).all(|(x, y)| x == y || (x.is_nan() && y.is_nan()))
+    })
+}
+
+fn check(source: &str, label: &str) {
+    let mut interpreter = Interpreter::new(source, label, 1000)
+        .unwrap_or_else(|e| panic!("{} failed to parse:\n{}\n{}", label, e, source));
+    let expected = run(&mut interpreter, MAX_CALLS)
+        .unwrap_or_else(|e| panic!("{} failed to interpret: {}\n{}", label, e, source));
+    let bytes = compile_str(source, label)
+        .unwrap_or_else(|e| panic!("{} failed to compile:\n{}\n{}", label, e, source));
+    let mut vendor = Vendor::new();
+    let mut machine = Machine::new(vendor.insert(InstructionData::from_compiled(&bytes)), 1000);
+    let actual = run(&mut machine, MAX_CALLS)
+        .unwrap_or_else(|e| panic!("{} failed to run: {}\n{}", label, e, source));
+    assert!(same_calls(&expected, &actual), "{} differs\n{}\nexpected {:?}\nactual   {:?}", label, source, expected, actual);
+}
+
+#[test]
+fn random_programs() {
+    for seed in 0..PROGRAMS {
+        let source = Generator::new(seed).program();
+        check(&source, &format!("seed {}", seed));
+    }
+}
+
+#[test]
+fn sample_scripts() {
+    for name in ["addition.bisc", "branch.bisc", "loop.bisc", "interrupts.bisc", "strings.bisc", "records.bisc", "functions.bisc"] {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/").to_owned() + name;
+        let source = std::fs::read_to_string(&path).unwrap();
+        check(&source, name);
+    }
+}
@@ -0,0 +1,61 @@
+//! The text dumps of each stage of compilation, compared with the snapshots in `tests/dumps/`. These are what
+//! `biscuit build --emit` writes next to the binary, so a changed dump can be checked in by rebuilding
+//! `small.bisc` in that directory.
+
+use std::{path::{Path, PathBuf}, process::Command};
+
+use biscuit::{Stage, explain_str};
+use strum::IntoEnumIterator;
+
+const DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/dumps");
+
+fn snapshot(stage: Stage) -> String {
+    std::fs::read_to_string(Path::new(DIR).join(format!("small.{}", stage))).unwrap()
+}
+
+/// Run the command line tool from the snapshot directory, so that positions name the file as the snapshots do
+fn biscuit(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_biscuit")).args(args).current_dir(DIR).output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn stages_match_the_snapshots() {
+    let source = std::fs::read_to_string(Path::new(DIR).join("small.bisc")).unwrap();
+    let stages: Vec<Stage> = Stage::iter().collect();
+    let explanation = explain_str(&source, "small.bisc", &stages);
+    assert!(explanation.result.is_ok());
+    let dumped: Vec<Stage> = explanation.dumps.iter().map(|(stage, _)| *stage).collect();
+    assert_eq!(dumped, stages);
+    for (stage, text) in explanation.dumps {
+        assert!(text == snapshot(stage), "Dump {} changed:\n{}", stage, text);
+    }
+
+    // Only the requested stages are dumped
+    let explanation = explain_str(&source, "small.bisc", &[Stage::Asm, Stage::Tokens]);
+    let dumped: Vec<Stage> = explanation.dumps.iter().map(|(stage, _)| *stage).collect();
+    assert_eq!(dumped, [Stage::Tokens, Stage::Asm]);
+}
+
+#[test]
+fn explain_prints_the_snapshots() {
+    let expected: String = Stage::iter().map(|stage| format!("== {} ==\n{}\n", stage, snapshot(stage))).collect();
+    assert_eq!(biscuit(&["explain", "small.bisc"]), expected);
+    let expected = format!("== ssa ==\n{}\n", snapshot(Stage::Ssa));
+    assert_eq!(biscuit(&["explain", "small.bisc", "--emit=ssa"]), expected);
+}
+
+#[test]
+fn build_writes_the_snapshots() {
+    let out = std::env::temp_dir().join(format!("biscuit-dump-{}", std::process::id()));
+    std::fs::create_dir_all(&out).unwrap();
+    let binary: PathBuf = out.join("small.b");
+    biscuit(&["build", "small.bisc", "-o", binary.to_str().unwrap(), "--emit=tokens,ast,ssa,asm"]);
+    assert!(binary.exists());
+    for stage in Stage::iter() {
+        let text = std::fs::read_to_string(binary.with_extension(stage.to_string())).unwrap();
+        assert!(text == snapshot(stage), "Dump {} changed:\n{}", stage, text);
+    }
+    std::fs::remove_dir_all(&out).unwrap();
+}
@@ -0,0 +1,50 @@
+; fn main
+; 0000 = LiteralFloat(1.0) [0000]
+000000 push 1
+; 0001 = Action(0) [0000]
+;   0000 = LiteralFloat(0.0) [1_0000 0000]
+000009 push 0
+;   0001 = Gt(1_0000, 0000) [1_0000 0000 0001]
+000018 dup
+000019 push 2
+000028 pick
+000029 gt
+000030 swp
+000031 pop
+000032 not
+000033 jnz 000081
+;   0000 = LocalCall("twice", [1_0000]) [1_0000]
+000042 pip
+000043 push 30
+000052 add
+000053 push 1
+000062 pick
+000063 jsr 000083
+000072 jmp 000081
+000081 pop
+000082 wait
+; fn twice
+; 0000 = Argument [0000]
+; 0001 = LiteralFloat(2.0) [0000 0001]
+000083 push 2
+; 0002 = Mul(0000, 0001) [0000 0001 0002]
+000092 dup
+000093 push 2
+000102 pick
+000103 mul
+; 0003 = LiteralVector([]) [0000 0001 0002 0003]
+000104 alc
+; 0004 = Stb(0003, 0002) [0000 0001 0002 0003 0004]
+000105 dup
+000106 push 2
+000115 pick
+000116 stb
+; 0005 = Call(0, 0004) [0000 0001 0002 0003 0004]
+000117 dup
+000118 call DBG
+000120 pop
+000121 drop
+000122 pop
+000123 pop
+000124 pop
+000125 ret
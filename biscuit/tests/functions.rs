@@ -0,0 +1,33 @@
+//! Functions used as values, and the errors for calling them wrongly.
+
+use biscuit::compile_str;
+
+fn error_of(source: &str) -> String {
+    compile_str(source, "test").unwrap_err()
+}
+
+#[test]
+fn signatures_are_checked() {
+    let e = error_of("fn one(a) {\n    dbg(a);\n}\nfn apply(f: fn(a, b)) {\n    f(1, 2);\n}\nfn main() {\n    g = one;\n    apply(g);\n}\n");
+    assert!(e.contains("Argument 1 of apply should be a fn(float, float), but is a fn(float)"), "{}", e);
+    let e = error_of("fn apply(f: fn(v[])) {\n    f(1);\n}\nfn main() {\n    dbg(1);\n}\n");
+    assert!(e.contains("Argument 1 of f should be a list, but is a float"), "{}", e);
+}
+
+#[test]
+fn argument_count() {
+    let e = error_of("fn two(a, b) {\n}\nfn main() {\n    f = two;\n    f(1);\n}\n");
+    assert!(e.contains("Function f takes 2 arguments, but 1 was given"), "{}", e);
+}
+
+#[test]
+fn only_functions_are_called() {
+    let e = error_of("fn main() {\n    f = 1;\n    f(2);\n}\n");
+    assert!(e.contains("Variable f is a float, so it cannot be called"), "{}", e);
+}
+
+#[test]
+fn same_type_on_every_path() {
+    let e = error_of("fn one(a) {\n    dbg(a);\n}\nfn main() {\n    f = one;\n    if 1 {\n        f = 2;\n    }\n}\n");
+    assert!(e.contains("Variable f is a float on one path through the if statement, but a fn(float) on another"), "{}", e);
+}
@@ -0,0 +1,94 @@
+//! Host functions which return values to the script.
+
+use biscuit::{Executor, GlobalFunction, Interpreter, Machine, MachineOutput, Scheduler, bytecode::decode_str, compile_str, machine::InstructionData, util::Vendor};
+
+/// Fuel in the tanks, and what `draw_fuel` takes out of them
+struct Tanks(f64);
+
+impl Tanks {
+    fn call(&mut self, func: GlobalFunction, args: &[f64]) -> f64 {
+        match func {
+            GlobalFunction::Fuel => self.0,
+            GlobalFunction::FuelCapacity => 10.,
+            GlobalFunction::DrawFuel => {
+                let taken = args[0].min(self.0);
+                self.0 -= taken;
+                taken
+            },
+            _ => panic!("Unexpected call to {}", func),
+        }
+    }
+}
+
+const SOURCE: &str = "fn main() {
+    draw_fuel(1);
+    left = fuel();
+    taken = draw_fuel(left / 2);
+    dbg(taken, fuel(), fuel_capacity());
+    dbg(fuel());
+    loop {
+        taken = draw_fuel(2);
+        if taken < 2 {
+            print(\"empty with {} left\", fuel());
+            wait_for_signal();
+        }
+    }
+}
+";
+
+#[test]
+fn scripts_use_returned_values() {
+    let bytes = compile_str(SOURCE, "test").unwrap_or_else(|e| panic!("{}", e));
+    let mut vendor = Vendor::new();
+    let mut scheduler = Scheduler::new(10_000);
+    let id = scheduler.insert(Machine::new(vendor.insert(InstructionData::from_compiled(&bytes)), 1000));
+    let mut tanks = Tanks(9.);
+    let faults = scheduler.run_frame(|_, func, args| Ok(tanks.call(func, args)));
+    assert!(faults.is_empty(), "{:?}", faults);
+    let log: Vec<&str> = scheduler.log(id).unwrap().lines().collect();
+    assert_eq!(log, ["4, 4, 10", "4", "empty with 0 left"]);
+}
+
+#[test]
+fn interpreter_returns_the_same_values() {
+    let mut vendor = Vendor::new();
+    let mut machine = Machine::new(vendor.insert(InstructionData::from_compiled(&compile_str(SOURCE, "test").unwrap())), 1000);
+    let mut interpreter = Interpreter::new(SOURCE, "test", 1000).unwrap();
+    let calls = |executor: &mut dyn Executor| {
+        let mut tanks = Tanks(9.);
+        let mut calls = Vec::new();
+        while calls.len() < 20 {
+            let (func, args) = match executor.run_for(1000).unwrap() {
+                MachineOutput::Call { func, args } => (func, args.to_vec()),
+                MachineOutput::None => break,
+            };
+            if matches!(func, GlobalFunction::Dbg | GlobalFunction::Print | GlobalFunction::WaitForSignal) {
+                calls.push((func, args));
+                continue;
+            }
+            let value = tanks.call(func, &args);
+            executor.return_value(value);
+            calls.push((func, vec![value]));
+        }
+        calls
+    };
+    assert_eq!(calls(&mut machine), calls(&mut interpreter));
+}
+
+#[test]
+fn channels_are_passed_by_name() {
+    let source = "fn main() {\n    sent = send(\"throttle\", 4, 0.5);\n    n = pending(\"throttle\");\n    dbg(sent, n);\n}\n";
+    let mut vendor = Vendor::new();
+    let mut machine = Machine::new(vendor.insert(InstructionData::from_compiled(&compile_str(source, "test").unwrap())), 1000);
+    let MachineOutput::Call { func, args } = machine.run_for(1000).unwrap() else { panic!("Expected a call") };
+    assert_eq!(func, GlobalFunction::Send);
+    assert_eq!(decode_str(args), Some(("throttle".to_owned(), &[4., 0.5][..])));
+    machine.return_value(1.);
+    let MachineOutput::Call { func, args } = machine.run_for(1000).unwrap() else { panic!("Expected a call") };
+    assert_eq!(func, GlobalFunction::Pending);
+    assert_eq!(decode_str(args), Some(("throttle".to_owned(), &[][..])));
+
+    let error = compile_str("fn main() {\n    send(1, 2, 3);\n}\n", "test").err().unwrap();
+    assert!(error.contains("The first argument of send must be a channel name in quotes"), "{}", error);
+    assert!(compile_str("fn main() {\n    x = recv(\"a\", 1);\n}\n", "test").is_err());
+}
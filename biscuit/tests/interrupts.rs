@@ -0,0 +1,154 @@
+//! Interrupt handlers: which one runs when several are raised, preempting a running handler, masking, and returning
+//! with `reti` to the interrupted code.
+
+use biscuit::{GlobalFunction, InterruptKind, Machine, MachineError, MachineOutput, assemble_str, compile_str, machine::InstructionData, util::Vendor};
+
+const SOURCE: &str = "fn main() {
+    a = 10;
+    tick();
+    dbg(a);
+}
+
+on interact {
+    dbg(1);
+    tick();
+    dbg(2);
+}
+
+on forward(x) {
+    dbg(x);
+    tick();
+    y = x + 1;
+    dbg(y);
+}
+";
+
+fn machine(vendor: &mut Vendor<InstructionData>) -> Machine {
+    let bytes = compile_str(SOURCE, "test").unwrap_or_else(|e| panic!("{}", e));
+    Machine::new(vendor.insert(InstructionData::from_compiled(&bytes)), 1000)
+}
+
+/// Run until the next `tick` or until the machine waits, returning the arguments of each `dbg` call
+fn run(machine: &mut Machine) -> Vec<Vec<f64>> {
+    let mut calls = Vec::new();
+    for _ in 0..100 {
+        if machine.is_waiting() { return calls; }
+        match machine.run_for(7).unwrap() {
+            MachineOutput::Call { func: GlobalFunction::Dbg, args } => calls.push(args.to_vec()),
+            MachineOutput::Call { func: GlobalFunction::Tick, .. } => return calls,
+            _ => (),
+        }
+    }
+    panic!("The machine never ticked or waited");
+}
+
+/// Run `main` to the end, so that the machine waits for interrupts
+fn waiting(vendor: &mut Vendor<InstructionData>) -> Machine {
+    let mut machine = machine(vendor);
+    assert_eq!(run(&mut machine), Vec::<Vec<f64>>::new());
+    assert_eq!(run(&mut machine), [[10.]]);
+    assert!(machine.is_waiting());
+    machine
+}
+
+#[test]
+fn reti_resumes_the_interrupted_code() {
+    let mut vendor = Vendor::new();
+    let mut machine = machine(&mut vendor);
+    assert_eq!(run(&mut machine), Vec::<Vec<f64>>::new());
+    machine.interrupt(InterruptKind::Interact, 0.);
+    assert_eq!(run(&mut machine), [[1.]]);
+    // The handler returns into main, which still has its variables
+    assert_eq!(run(&mut machine), [[2.], [10.]]);
+    assert!(machine.is_waiting());
+}
+
+#[test]
+fn higher_priorities_run_first() {
+    let mut vendor = Vendor::new();
+    let mut machine = waiting(&mut vendor);
+    machine.interrupt(InterruptKind::Forward, 5.);
+    machine.interrupt(InterruptKind::Interact, 0.);
+    assert_eq!(run(&mut machine), [[1.]]);
+    assert_eq!(run(&mut machine), [[2.], [5.]]);
+    assert_eq!(run(&mut machine), [[6.]]);
+
+    machine.set_priority(InterruptKind::Forward, 2);
+    machine.interrupt(InterruptKind::Interact, 0.);
+    machine.interrupt(InterruptKind::Forward, 5.);
+    assert_eq!(run(&mut machine), [[5.]]);
+    assert_eq!(run(&mut machine), [[6.], [1.]]);
+    assert_eq!(run(&mut machine), [[2.]]);
+    assert!(machine.is_waiting());
+}
+
+#[test]
+fn handlers_are_preempted_by_higher_priorities_only() {
+    let mut vendor = Vendor::new();
+    let mut machine = waiting(&mut vendor);
+    machine.interrupt(InterruptKind::Interact, 0.);
+    assert_eq!(run(&mut machine), [[1.]]);
+    // Forward has a lower priority, so it waits for the running handler to return
+    machine.interrupt(InterruptKind::Forward, 5.);
+    assert_eq!(run(&mut machine), [[2.], [5.]]);
+    assert_eq!(run(&mut machine), [[6.]]);
+
+    machine.set_priority(InterruptKind::Forward, 1);
+    machine.interrupt(InterruptKind::Interact, 0.);
+    assert_eq!(run(&mut machine), [[1.]]);
+    // An equal priority does not preempt either
+    machine.interrupt(InterruptKind::Forward, 5.);
+    assert_eq!(run(&mut machine), [[2.], [5.]]);
+    assert_eq!(run(&mut machine), [[6.]]);
+
+    machine.set_priority(InterruptKind::Forward, 2);
+    machine.interrupt(InterruptKind::Interact, 0.);
+    assert_eq!(run(&mut machine), [[1.]]);
+    machine.interrupt(InterruptKind::Forward, 5.);
+    assert_eq!(run(&mut machine), [[5.]]);
+    // Each reti returns to the handler it interrupted
+    assert_eq!(run(&mut machine), [[6.], [2.]]);
+    assert!(machine.is_waiting());
+}
+
+#[test]
+fn masked_interrupts_are_discarded() {
+    let mut vendor = Vendor::new();
+    let mut machine = waiting(&mut vendor);
+    machine.mask(InterruptKind::Forward);
+    machine.interrupt(InterruptKind::Forward, 5.);
+    assert!(machine.is_waiting());
+
+    // Masking also discards the interrupt if it is already pending
+    machine.unmask(InterruptKind::Forward);
+    machine.interrupt(InterruptKind::Forward, 5.);
+    machine.mask(InterruptKind::Forward);
+    assert!(machine.is_waiting());
+
+    // Interrupts raised while masked are not kept for later
+    machine.unmask(InterruptKind::Forward);
+    assert!(machine.is_waiting());
+    machine.interrupt(InterruptKind::Forward, 7.);
+    assert_eq!(run(&mut machine), [[7.]]);
+    assert_eq!(run(&mut machine), [[8.]]);
+    assert!(machine.is_waiting());
+}
+
+#[test]
+fn raising_a_pending_interrupt_replaces_its_argument() {
+    let mut vendor = Vendor::new();
+    let mut machine = waiting(&mut vendor);
+    machine.interrupt(InterruptKind::Forward, 5.);
+    machine.interrupt(InterruptKind::Forward, 7.);
+    assert_eq!(run(&mut machine), [[7.]]);
+    assert_eq!(run(&mut machine), [[8.]]);
+    assert!(machine.is_waiting());
+}
+
+#[test]
+fn unknown_interrupts_are_invalid() {
+    let bytes = assemble_str("push 0\npush 99\nhnd\n", "test").unwrap();
+    let mut vendor = Vendor::new();
+    let mut machine = Machine::new(vendor.insert(InstructionData::from_compiled(&bytes)), 1000);
+    assert!(matches!(machine.run_for(10), Err(MachineError::OpCode)));
+}
@@ -0,0 +1,99 @@
+//! Compiling modules to objects and linking them, which must give the same binaries as compiling one file.
+
+use biscuit::{Object, compile_object_str, compile_str, link};
+
+const LIBRARY: &str = "struct Vec3 { x, y, z }
+
+fn scale(v: Vec3, k) {
+    dbg(v.x * k, v.y * k, v.z * k);
+}
+
+fn report(axis, value) {
+    print(\"{}: {}\", axis, value);
+}
+
+fn unused(a) {
+    dbg(a);
+}
+";
+
+const PROGRAM: &str = "struct Vec3 { x, y, z }
+
+extern fn scale(v: Vec3, k);
+extern fn report(axis, value);
+
+fn main() {
+    v = Vec3(1, 2, 3);
+    scale(v, 2);
+    f = report;
+    f(0, v.x);
+}
+
+on interact {
+    report(1, 1);
+}
+";
+
+fn object(source: &str, module: &str) -> Object {
+    compile_object_str(source, module).unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[test]
+fn linking_matches_one_file() {
+    let program = object(PROGRAM, "program");
+    let library = object(LIBRARY, "library");
+    assert_eq!(program.imports().collect::<Vec<_>>(), ["report", "scale"]);
+    assert_eq!(library.exports().collect::<Vec<_>>(), ["report", "scale", "unused"]);
+
+    let whole = format!("{}\n{}", LIBRARY, PROGRAM.replace("struct Vec3 { x, y, z }", "").replace("extern fn scale(v: Vec3, k);", "").replace("extern fn report(axis, value);", ""));
+    let expected = compile_str(&whole, "test").unwrap();
+    // The order of the objects does not matter, and `unused` is left out
+    assert_eq!(link(&[program.clone(), library.clone()]).unwrap(), expected);
+    assert_eq!(link(&[library, program]).unwrap(), expected);
+}
+
+#[test]
+fn objects_round_trip() {
+    let library = object(LIBRARY, "library");
+    let program = object(PROGRAM, "program");
+    let bytes = library.to_bytes();
+    let read = Object::from_bytes(&bytes, "library.o").unwrap();
+    assert_eq!(read.to_bytes(), bytes);
+    assert_eq!(link(&[program, read]).unwrap(), link(&[object(PROGRAM, "program"), library]).unwrap());
+
+    let e = Object::from_bytes(&bytes[..bytes.len() - 1], "library.o").unwrap_err();
+    assert_eq!(e, "library.o is not a valid object file");
+    let e = Object::from_bytes(b"nothing", "other.o").unwrap_err();
+    assert_eq!(e, "other.o is not a valid object file");
+}
+
+#[test]
+fn unresolved_functions() {
+    let e = link(&[object(PROGRAM, "program")]).unwrap_err();
+    assert_eq!(e, "Unresolved function report, used in program");
+    let e = link(&[object(LIBRARY, "library")]).unwrap_err();
+    assert_eq!(e, "No function named main");
+}
+
+#[test]
+fn signatures_must_match() {
+    let library = LIBRARY.replace("struct Vec3 { x, y, z }", "struct Vec3 { z, y, x }");
+    let e = link(&[object(PROGRAM, "program"), object(&library, "library")]).unwrap_err();
+    assert_eq!(e, "Function scale is declared as fn(Vec3 { x, y, z }, float) in program, but defined as fn(Vec3 { z, y, x }, float) in library");
+}
+
+#[test]
+fn symbols_are_defined_once() {
+    let other = "fn report(axis, value) {\n    dbg(axis);\n}\n";
+    let e = link(&[object(PROGRAM, "program"), object(LIBRARY, "library"), object(other, "other")]).unwrap_err();
+    assert_eq!(e, "Function report is defined in both library and other");
+    let handler = "on interact {\n    dbg(2);\n}\n";
+    let e = link(&[object(PROGRAM, "program"), object(LIBRARY, "library"), object(handler, "handler")]).unwrap_err();
+    assert_eq!(e, "Interrupt interact is handled in both program and handler");
+}
+
+#[test]
+fn externs_are_declared_once() {
+    let e = compile_object_str("extern fn report(a);\nfn report(a) {\n    dbg(a);\n}\n", "test").unwrap_err();
+    assert!(e.contains("Extern function report is also declared in this module"), "{}", e);
+}
@@ -0,0 +1,96 @@
+//! Profiling a machine: counts by function, line and call stack, which must not change what the code does.
+
+use biscuit::{DebugInfo, GlobalFunction, Machine, MachineOutput, compile_debug_str, compile_str, machine::InstructionData, util::Vendor};
+
+const SOURCE: &str = "fn hot(n) {
+    x = n * 2 + 1;
+    if n > 0 {
+        hot(n - 1);
+    }
+}
+
+fn report(n) {
+    dbg(n);
+}
+
+fn outer() {
+    hot(50);
+    report(0);
+}
+
+fn main() {
+    outer();
+    report(1);
+}
+";
+
+/// Run `main` to the end, returning the arguments of each `dbg` call and the machine. The vendor must outlive it.
+fn run(vendor: &mut Vendor<InstructionData>, bytes: &[u8], profile: bool) -> (Vec<Vec<f64>>, Machine) {
+    let mut machine = Machine::new(vendor.insert(InstructionData::from_compiled(bytes)), 1000);
+    if profile {
+        machine.start_profiling();
+    }
+    let mut calls = Vec::new();
+    while !machine.is_waiting() {
+        if let MachineOutput::Call { func: GlobalFunction::Dbg, args } = machine.run_for(7).unwrap() {
+            calls.push(args.to_vec());
+        }
+    }
+    (calls, machine)
+}
+
+fn profiled(vendor: &mut Vendor<InstructionData>) -> (Machine, DebugInfo) {
+    let (bytes, debug) = compile_debug_str(SOURCE, "test").unwrap_or_else(|e| panic!("{}", e));
+    (run(vendor, &bytes, true).1, debug)
+}
+
+#[test]
+fn profiling_does_not_change_the_code() {
+    let (bytes, _) = compile_debug_str(SOURCE, "test").unwrap();
+    assert_eq!(bytes, compile_str(SOURCE, "test").unwrap());
+    let mut vendor = Vendor::new();
+    let (plain_calls, plain) = run(&mut vendor, &bytes, false);
+    let (profiled_calls, profiled) = run(&mut vendor, &bytes, true);
+    assert_eq!(plain_calls, profiled_calls);
+    assert_eq!(plain.instructions_executed(), profiled.instructions_executed());
+    assert_eq!(profiled.profile().unwrap().total(), profiled.instructions_executed());
+    assert!(plain.profile().is_none());
+}
+
+#[test]
+fn debug_info_finds_functions_and_lines() {
+    let (bytes, debug) = compile_debug_str(SOURCE, "test").unwrap();
+    assert_eq!(debug.function(0), Some("main"));
+    assert_eq!(debug.module(0), Some("test"));
+    assert_eq!(debug.line(0), Some(18));
+    // Functions are laid out with main first, then the rest by name
+    let starts: Vec<&str> = (0..bytes.len()).filter_map(|ip| debug.function(ip)).collect::<Vec<_>>()
+        .chunk_by(|a, b| a == b).map(|c| c[0]).collect();
+    assert_eq!(starts, ["main", "hot", "outer", "report"]);
+}
+
+#[test]
+fn counts_by_function_and_line() {
+    let mut vendor = Vendor::new();
+    let (machine, debug) = profiled(&mut vendor);
+    let profile = machine.profile().unwrap();
+    let flat = profile.flat(&debug);
+    let functions: Vec<&str> = flat.lines().skip(3).take(4).map(|l| l.split_whitespace().last().unwrap()).collect();
+    assert_eq!(functions, ["hot", "report", "main", "outer"], "{}", flat);
+    assert!(flat.contains(" test:4 (hot)"), "{}", flat);
+    // main runs everything, so its total is all the instructions
+    let main = flat.lines().find(|l| l.ends_with("  main")).unwrap();
+    assert_eq!(main.split_whitespace().nth(2).unwrap(), profile.total().to_string(), "{}", flat);
+}
+
+#[test]
+fn folded_stacks() {
+    let mut vendor = Vendor::new();
+    let (machine, debug) = profiled(&mut vendor);
+    let folded = machine.profile().unwrap().folded(&debug);
+    let stacks: Vec<&str> = folded.lines().map(|l| l.rsplit_once(' ').unwrap().0).collect();
+    // Tail calls replace their caller, so hot appears once, and report is called in place of outer
+    assert_eq!(stacks, ["main", "main;outer", "main;outer;hot", "main;report"], "{}", folded);
+    let sum: u64 = folded.lines().map(|l| l.rsplit_once(' ').unwrap().1.parse::<u64>().unwrap()).sum();
+    assert_eq!(sum, machine.instructions_executed());
+}
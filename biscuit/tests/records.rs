@@ -0,0 +1,39 @@
+//! Record declarations and the errors for misusing them.
+
+use biscuit::compile_str;
+
+fn error_of(source: &str) -> String {
+    compile_str(source, "test").unwrap_err()
+}
+
+#[test]
+fn unknown_field() {
+    let e = error_of("struct Vec3 { x, y, z }\nfn main() {\n    v = Vec3(1, 2, 3);\n    dbg(v.w);\n}\n");
+    assert!(e.contains("Record Vec3 has no field w"), "{}", e);
+    let e = error_of("fn main() {\n    v = 1;\n    v.x = 2;\n}\n");
+    assert!(e.contains("Variable v is not a record, so it has no field x"), "{}", e);
+}
+
+#[test]
+fn constructor_arity() {
+    let e = error_of("struct Pair { a, b }\nfn main() {\n    p = Pair(1);\n}\n");
+    assert!(e.contains("Record Pair has 2 fields, but 1 was given"), "{}", e);
+}
+
+#[test]
+fn record_types_are_checked() {
+    let e = error_of("struct Pair { a, b }\nstruct Vec3 { x, y, z }\nfn f(v: Vec3) {\n}\nfn main() {\n    p = Pair(1, 2);\n    f(p);\n}\n");
+    assert!(e.contains("should be a Vec3, but is a Pair"), "{}", e);
+    let e = error_of("fn f(v: Quat) {\n}\nfn main() {\n}\n");
+    assert!(e.contains("Unrecognized record Quat"), "{}", e);
+}
+
+#[test]
+fn declarations() {
+    let e = error_of("struct Pair { a, a }\nfn main() {\n}\n");
+    assert!(e.contains("Field a is declared twice"), "{}", e);
+    let e = error_of("struct Pair { a, b }\nstruct Pair { c }\nfn main() {\n}\n");
+    assert!(e.contains("Record Pair is declared twice"), "{}", e);
+    let e = error_of("struct Pair { a, b }\nfn Pair() {\n}\nfn main() {\n}\n");
+    assert!(e.contains("Function Pair has the name of a record"), "{}", e);
+}
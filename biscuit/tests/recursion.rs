@@ -0,0 +1,67 @@
+//! Recursion on both executors: calls nested too deeply fail, and tail calls do not nest.
+
+use biscuit::{Executor, GlobalFunction, Interpreter, Machine, MachineError, MachineOutput, compile_str, machine::{InstructionData, MAX_CALL_DEPTH}, util::Vendor};
+
+/// Run `main` on the machine and the interpreter, returning the arguments of each `dbg` call
+fn run(source: &str) -> [Result<Vec<Vec<f64>>, MachineError>; 2] {
+    let bytes = compile_str(source, "test").unwrap_or_else(|e| panic!("{}", e));
+    let mut vendor = Vendor::new();
+    let mut machine = Machine::new(vendor.insert(InstructionData::from_compiled(&bytes)), 1000);
+    let mut interpreter = Interpreter::new(source, "test", 1000).unwrap_or_else(|e| panic!("{}", e));
+    [run_on(&mut machine), run_on(&mut interpreter)]
+}
+
+fn run_on(executor: &mut impl Executor) -> Result<Vec<Vec<f64>>, MachineError> {
+    let mut calls = Vec::new();
+    while !executor.is_waiting() {
+        assert!(executor.instructions_executed() < 10_000_000, "Ran too long");
+        if let MachineOutput::Call { func: GlobalFunction::Dbg, args } = executor.run_for(1000)? {
+            calls.push(args.to_vec());
+        }
+    }
+    Ok(calls)
+}
+
+#[test]
+fn tail_calls_do_not_nest() {
+    let source = "fn count(n) {\n    if n > 0 {\n        count(n - 1);\n    } else {\n        dbg(n);\n    }\n}\nfn main() {\n    count(20000);\n}\n";
+    for result in run(source) {
+        assert_eq!(result.unwrap(), [[0.]]);
+    }
+}
+
+#[test]
+fn call_depth_is_limited() {
+    let source = |n: usize| format!("fn down(n) {{\n    if n > 0 {{\n        down(n - 1);\n        dbg(n);\n    }}\n}}\nfn main() {{\n    down({});\n}}\n", n);
+    // main is not counted
+    for result in run(&source(MAX_CALL_DEPTH - 1)) {
+        assert_eq!(result.unwrap().len(), MAX_CALL_DEPTH - 1);
+    }
+    for result in run(&source(MAX_CALL_DEPTH)) {
+        assert!(matches!(result, Err(MachineError::CallDepth)));
+    }
+}
+
+#[test]
+fn lists_in_tail_recursive_frames() {
+    let source = "fn spin(n) {\n    label = \"spinning\";\n    print(\"{}\", n);\n    if n > 0 {\n        spin(n - 1);\n    } else {\n        dbg(n);\n    }\n}\nfn main() {\n    spin(5000);\n}\n";
+    for result in run(source) {
+        assert_eq!(result.unwrap(), [[0.]]);
+    }
+}
+
+#[test]
+fn dynamic_tail_calls() {
+    let source = "fn count(n) {\n    f = count;\n    if n > 0 {\n        f(n - 1);\n    } else {\n        dbg(n);\n    }\n}\nfn main() {\n    count(20000);\n}\n";
+    for result in run(source) {
+        assert_eq!(result.unwrap(), [[0.]]);
+    }
+}
+
+#[test]
+fn list_arguments_are_not_tail_calls() {
+    let source = "fn walk(n, label[]) {\n    if n > 0 {\n        walk(n - 1, label);\n    }\n}\nfn main() {\n    walk(1000, \"walking\");\n}\n";
+    for result in run(source) {
+        assert!(matches!(result, Err(MachineError::CallDepth)));
+    }
+}
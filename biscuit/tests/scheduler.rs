@@ -0,0 +1,111 @@
+//! Sharing a frame's budget between several machines, and machines which sleep or wait.
+
+use biscuit::{InterruptKind, Machine, MachineId, Scheduler, TaskState, compile_str, machine::InstructionData, util::Vendor};
+
+const BUSY: &str = "fn main() {\n    a = 0;\n    loop {\n        a = a + 1;\n    }\n}\n";
+
+fn insert(scheduler: &mut Scheduler, vendor: &mut Vendor<InstructionData>, source: &str) -> MachineId {
+    let bytes = compile_str(source, "test").unwrap_or_else(|e| panic!("{}", e));
+    scheduler.insert(Machine::new(vendor.insert(InstructionData::from_compiled(&bytes)), 1000))
+}
+
+fn frame(scheduler: &mut Scheduler) {
+    let faults = scheduler.run_frame(|_, func, _| panic!("Unexpected call to {}", func));
+    assert!(faults.is_empty(), "{:?}", faults);
+}
+
+fn log_len(scheduler: &Scheduler, id: MachineId) -> usize {
+    scheduler.log(id).unwrap().lines().count()
+}
+
+#[test]
+fn busy_machines_share_the_budget_by_weight() {
+    let mut vendor = Vendor::new();
+    let mut scheduler = Scheduler::new(4000);
+    let ids: Vec<MachineId> = (0..3).map(|_| insert(&mut scheduler, &mut vendor, BUSY)).collect();
+    scheduler.set_weight(ids[2], 2);
+    for _ in 0..3 {
+        frame(&mut scheduler);
+        let used: Vec<u64> = ids.iter().map(|id| scheduler.instructions_last_frame(*id)).collect();
+        assert_eq!(used, [1000, 1000, 2000]);
+        let usage: Vec<f64> = ids.iter().map(|id| scheduler.cpu_usage(*id)).collect();
+        assert_eq!(usage, [0.25, 0.25, 0.5]);
+    }
+}
+
+#[test]
+fn budget_left_by_yielding_machines_goes_to_the_others() {
+    let mut vendor = Vendor::new();
+    let mut scheduler = Scheduler::new(3000);
+    let busy = [insert(&mut scheduler, &mut vendor, BUSY), insert(&mut scheduler, &mut vendor, BUSY)];
+    let ticking = insert(&mut scheduler, &mut vendor, "fn main() {\n    loop {\n        tick();\n    }\n}\n");
+    for _ in 0..3 {
+        frame(&mut scheduler);
+        let short = scheduler.instructions_last_frame(ticking);
+        assert!(short > 0 && short < 100, "{}", short);
+        assert_eq!(scheduler.state(ticking), Some(TaskState::Yielded));
+        // The whole budget is used, and split evenly between the busy machines
+        let used = busy.map(|id| scheduler.instructions_last_frame(id));
+        assert_eq!(used[0] + used[1] + short, 3000);
+        assert!(used[0].abs_diff(used[1]) <= 1, "{:?}", used);
+    }
+}
+
+#[test]
+fn sleeping_machines_wake_after_their_ticks() {
+    let mut vendor = Vendor::new();
+    let mut scheduler = Scheduler::new(1000);
+    let id = insert(&mut scheduler, &mut vendor, "fn main() {\n    loop {\n        dbg(1);\n        sleep(3);\n    }\n}\n");
+    let busy = insert(&mut scheduler, &mut vendor, BUSY);
+    let mut woken = Vec::new();
+    for frame_index in 0..10 {
+        let before = log_len(&scheduler, id);
+        frame(&mut scheduler);
+        if log_len(&scheduler, id) > before {
+            woken.push(frame_index);
+        } else {
+            assert_eq!(scheduler.instructions_last_frame(id), 0);
+            // A sleeping machine leaves its share to the others
+            assert_eq!(scheduler.instructions_last_frame(busy), 1000);
+        }
+    }
+    assert_eq!(woken, [0, 3, 6, 9]);
+}
+
+#[test]
+fn waiting_machines_run_once_signalled() {
+    let mut vendor = Vendor::new();
+    let mut scheduler = Scheduler::new(1000);
+    let id = insert(&mut scheduler, &mut vendor, "fn main() {\n    loop {\n        dbg(1);\n        wait_for_signal();\n    }\n}\n");
+    frame(&mut scheduler);
+    assert_eq!(scheduler.state(id), Some(TaskState::WaitingForSignal));
+    for _ in 0..3 {
+        frame(&mut scheduler);
+        assert_eq!(scheduler.instructions_last_frame(id), 0);
+    }
+    assert_eq!(log_len(&scheduler, id), 1);
+
+    scheduler.signal(id);
+    assert_eq!(scheduler.state(id), Some(TaskState::Ready));
+    frame(&mut scheduler);
+    assert_eq!(log_len(&scheduler, id), 2);
+    assert_eq!(scheduler.state(id), Some(TaskState::WaitingForSignal));
+}
+
+#[test]
+fn machines_waiting_for_interrupts_wake_when_raised() {
+    let mut vendor = Vendor::new();
+    let mut scheduler = Scheduler::new(1000);
+    let id = insert(&mut scheduler, &mut vendor, "fn main() {\n    dbg(0);\n}\n\non forward(x) {\n    dbg(x);\n}\n");
+    frame(&mut scheduler);
+    assert_eq!(scheduler.state(id), Some(TaskState::WaitingForInterrupt));
+    frame(&mut scheduler);
+    assert_eq!(scheduler.instructions_last_frame(id), 0);
+
+    scheduler.interrupt(id, InterruptKind::Forward, 4.);
+    assert_eq!(scheduler.state(id), Some(TaskState::Ready));
+    frame(&mut scheduler);
+    let log: Vec<&str> = scheduler.log(id).unwrap().lines().collect();
+    assert_eq!(log, ["0", "4"]);
+    assert_eq!(scheduler.state(id), Some(TaskState::WaitingForInterrupt));
+}
@@ -0,0 +1,87 @@
+//! Saving a machine's state and restoring it, possibly in another machine: it must carry on exactly as if it had never stopped.
+
+use biscuit::{Executor, GlobalFunction, InterruptKind, Interpreter, Machine, MachineError, MachineOutput, MachineState, compile_str, machine::InstructionData, util::Vendor};
+
+fn read(name: &str) -> String {
+    std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/").to_owned() + name).unwrap()
+}
+
+fn machine(vendor: &mut Vendor<InstructionData>, source: &str) -> Machine {
+    let bytes = compile_str(source, "test").unwrap_or_else(|e| panic!("{}", e));
+    Machine::new(vendor.insert(InstructionData::from_compiled(&bytes)), 1000)
+}
+
+/// Run in small steps, returning the arguments of each `dbg` call
+fn run(machine: &mut Machine, steps: usize) -> Vec<Vec<f64>> {
+    let mut calls = Vec::new();
+    for _ in 0..steps {
+        if machine.is_waiting() { break; }
+        if let MachineOutput::Call { func: GlobalFunction::Dbg, args } = machine.run_for(7).unwrap() {
+            calls.push(args.to_vec());
+        }
+    }
+    calls
+}
+
+#[test]
+fn resumes_where_it_stopped() {
+    // Records live in the machine's memory, and the loop never ends
+    let source = read("records.bisc");
+    let mut vendor = Vendor::new();
+    let mut original = machine(&mut vendor, &source);
+    run(&mut original, 50);
+    let bytes = original.save_state().to_bytes();
+
+    let mut restored = machine(&mut vendor, &source);
+    restored.restore_state(&MachineState::from_bytes(&bytes).unwrap()).unwrap();
+    assert_eq!(restored.instructions_executed(), original.instructions_executed());
+    assert_eq!(run(&mut restored, 200), run(&mut original, 200));
+    assert_eq!(restored.instructions_executed(), original.instructions_executed());
+    // Equal states write equal bytes
+    assert_eq!(restored.save_state().to_bytes(), original.save_state().to_bytes());
+}
+
+#[test]
+fn interrupts_are_kept() {
+    let source = read("interrupts.bisc");
+    let mut vendor = Vendor::new();
+    let mut original = machine(&mut vendor, &source);
+    assert_eq!(run(&mut original, 100), [[3.]]);
+    original.set_priority(InterruptKind::Forward, 4);
+    original.mask(InterruptKind::Interact);
+    original.interrupt(InterruptKind::Forward, 2.5);
+    let state = MachineState::from_bytes(&original.save_state().to_bytes()).unwrap();
+
+    let mut restored = machine(&mut vendor, &source);
+    restored.restore_state(&state).unwrap();
+    // The pending interrupt is dispatched, and the masked one is still discarded
+    restored.interrupt(InterruptKind::Interact, 0.);
+    assert_eq!(run(&mut restored, 100), [[2.5]]);
+    original.interrupt(InterruptKind::Interact, 0.);
+    assert_eq!(run(&mut original, 100), [[2.5]]);
+}
+
+#[test]
+fn other_code_is_refused() {
+    let mut vendor = Vendor::new();
+    let mut original = machine(&mut vendor, &read("records.bisc"));
+    run(&mut original, 10);
+    let state = original.save_state();
+    let mut other = machine(&mut vendor, &read("loop.bisc"));
+    assert!(matches!(other.restore_state(&state), Err(MachineError::Ip)));
+
+    let bytes = state.to_bytes();
+    assert!(MachineState::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    let mut newer = bytes.clone();
+    newer[4] += 1;
+    assert!(MachineState::from_bytes(&newer).err().unwrap().contains("another version"));
+}
+
+#[test]
+fn interpreters_have_no_state() {
+    let mut vendor = Vendor::new();
+    let state = machine(&mut vendor, &read("records.bisc")).save_state();
+    let mut interpreter = Interpreter::new(&read("records.bisc"), "test", 1000).unwrap();
+    assert!(interpreter.save_state().is_none());
+    assert!(matches!(interpreter.restore_state(&state), Err(MachineError::Unsupported)));
+}
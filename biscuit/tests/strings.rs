@@ -0,0 +1,39 @@
+//! String literals and the output of `print` and `dbg`.
+
+use biscuit::{Machine, Scheduler, compile_str, machine::InstructionData, util::Vendor};
+
+fn log_of(source: &str) -> Vec<String> {
+    let bytes = compile_str(source, "test").unwrap_or_else(|e| panic!("{}", e));
+    let mut vendor = Vendor::new();
+    let mut scheduler = Scheduler::new(10_000);
+    let id = scheduler.insert(Machine::new(vendor.insert(InstructionData::from_compiled(&bytes)), 1000));
+    let faults = scheduler.run_frame(|_, func, _| panic!("Unexpected call to {}", func));
+    assert!(faults.is_empty(), "{:?}", faults);
+    scheduler.log(id).unwrap().lines().map(|l| l.to_owned()).collect()
+}
+
+#[test]
+fn print_formats_values() {
+    let log = log_of("fn main() {\n    x = 1.5;\n    print(\"thrust {} of {}\", x, 3);\n    dbg(x, 2);\n}\n");
+    assert_eq!(log, ["thrust 1.5 of 3", "1.5, 2"]);
+}
+
+#[test]
+fn escapes() {
+    let log = log_of("fn main() {\n    print(\"a \\\"b\\\" \\\\ 'c'\");\n}\n");
+    assert_eq!(log, ["a \"b\" \\ 'c'"]);
+}
+
+#[test]
+fn print_checks_its_format() {
+    let e = compile_str("fn main() {\n    print(\"{} {}\", 1);\n}\n", "test").unwrap_err();
+    assert!(e.contains("The format of print has 2 `{}`, but 1 value was given"), "{}", e);
+    let e = compile_str("fn main() {\n    x = 1;\n    print(x);\n}\n", "test").unwrap_err();
+    assert!(e.contains("The first argument of print must be a string"), "{}", e);
+}
+
+#[test]
+fn unterminated_string() {
+    let e = compile_str("fn main() {\n    print(\"abc);\n}\n", "test").unwrap_err();
+    assert!(e.contains("test:2:11\nUnterminated string"), "{}", e);
+}
@@ -0,0 +1,64 @@
+//! Type checking: the arguments of calls, and the types of variables through if statements and loops.
+
+use biscuit::compile_str;
+
+fn error_of(source: &str) -> String {
+    compile_str(source, "test").unwrap_err()
+}
+
+#[test]
+fn local_calls() {
+    let e = error_of("fn f(a, b) {\n    dbg(a, b);\n}\nfn main() {\n    f(1);\n}\n");
+    assert!(e.contains("Function f takes 2 arguments, but 1 was given"), "{}", e);
+    let e = error_of("fn f(a) {\n    dbg(a);\n}\nfn main() {\n    f(1, 2);\n}\n");
+    assert!(e.contains("Function f takes 1 argument, but 2 were given"), "{}", e);
+    let e = error_of("fn f(v[]) {\n    dbg(v[0]);\n}\nfn main() {\n    f(1);\n}\n");
+    assert!(e.contains("Argument 1 of f should be a list, but is a float"), "{}", e);
+    let e = error_of("fn f(a) {\n    dbg(a);\n}\nfn main() {\n    x = \"a\";\n    f(x);\n}\n");
+    assert!(e.contains("Argument 1 of f should be a float, but is a list"), "{}", e);
+}
+
+#[test]
+fn host_calls() {
+    let e = error_of("fn main() {\n    add_force(1);\n}\n");
+    assert!(e.contains("Function add_force takes 3 arguments, but 1 was given"), "{}", e);
+    let e = error_of("fn main() {\n    x = \"a\";\n    add_force(1, x, 2);\n}\n");
+    assert!(e.contains("Argument 2 of add_force should be a float, but is a list"), "{}", e);
+    // Functions with any number of arguments still take floats
+    let e = error_of("fn main() {\n    x = \"a\";\n    dbg(1, x);\n}\n");
+    assert!(e.contains("Arguments of dbg should be a float, but is a list"), "{}", e);
+}
+
+#[test]
+fn all_mismatches_are_reported() {
+    let e = error_of("fn f(a, b) {\n    dbg(a, b);\n}\nfn main() {\n    f(1);\n    add_force(1);\n}\n");
+    assert!(e.contains("Function f takes 2 arguments"), "{}", e);
+    assert!(e.contains("Function add_force takes 3 arguments"), "{}", e);
+}
+
+#[test]
+fn types_pass_through_if_statements() {
+    // The list assigned on every path is still a list afterwards
+    let e = error_of("fn main() {\n    x = \"a\";\n    if 1 {\n        x = \"b\";\n    } else {\n        x = \"c\";\n    }\n    dbg(x);\n}\n");
+    assert!(e.contains("Arguments of dbg should be a float, but is a list"), "{}", e);
+    // Code in a branch sees the types from before it
+    let e = error_of("fn main() {\n    x = \"a\";\n    if 1 {\n        dbg(x);\n    }\n}\n");
+    assert!(e.contains("Arguments of dbg should be a float, but is a list"), "{}", e);
+    let e = error_of("fn main() {\n    x = \"a\";\n    if x {\n        dbg(1);\n    }\n}\n");
+    assert!(e.contains("Condition should be a float, but is a list"), "{}", e);
+    // Without an else, the variable may keep its type from before
+    let e = error_of("fn main() {\n    x = 1;\n    if 1 {\n        x = \"b\";\n    }\n}\n");
+    assert!(e.contains("Variable x is a list on one path through the if statement, but a float on another"), "{}", e);
+    assert!(compile_str("fn main() {\n    x = 1;\n    if 1 {\n        x = 2;\n    }\n    dbg(x);\n}\n", "test").is_ok());
+}
+
+#[test]
+fn types_pass_through_loops() {
+    let e = error_of("fn main() {\n    x = 1;\n    loop {\n        x = \"a\";\n    }\n}\n");
+    assert!(e.contains("Variable x is a float before the loop, but a list after an iteration"), "{}", e);
+    let e = error_of("fn main() {\n    x = \"a\";\n    loop {\n        x = \"b\";\n        dbg(x);\n    }\n}\n");
+    assert!(e.contains("Arguments of dbg should be a float, but is a list"), "{}", e);
+    // Branches in loops see the types from outside both
+    let e = error_of("fn main() {\n    x = \"a\";\n    loop {\n        if 1 {\n            add_force(x, 0, 0);\n        }\n    }\n}\n");
+    assert!(e.contains("Argument 1 of add_force should be a float, but is a list"), "{}", e);
+}